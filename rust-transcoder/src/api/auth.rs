@@ -0,0 +1,167 @@
+//! Идентификация вызывающего по API ключу
+//!
+//! Ключ передаётся в `X-API-Key` или в `Authorization: Bearer <key>`.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+use crate::config::{ApiKeyScope, Config};
+use crate::error::AppError;
+use crate::AppState;
+
+/// Извлекает API ключ из заголовков запроса
+pub fn extract_api_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get("X-API-Key").and_then(|v| v.to_str().ok()) {
+        return Some(key.trim());
+    }
+
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Проверяет, что ключ из заголовков обладает указанным scope
+pub fn caller_has_scope(config: &Config, headers: &HeaderMap, scope: ApiKeyScope) -> bool {
+    extract_api_key(headers).is_some_and(|key| config.has_scope(key, scope))
+}
+
+/// Middleware `/api/v1/*`: при заданном `Config::api_key` без верного ключа - 401
+pub async fn require_api_key(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.config.accepts_api_key(extract_api_key(request.headers())) {
+        warn!(path = %request.uri().path(), "Rejected request without a valid API key");
+        return AppError::Unauthorized.into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{HeaderValue, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    fn protected_router() -> axum::Router {
+        let config = Config {
+            api_key: Some("secret".to_string()),
+            ..Config::default()
+        };
+        crate::build_router(Arc::new(AppState::with_config(1, config).unwrap()))
+    }
+
+    async fn get(uri: &str, headers: &[(&str, &str)]) -> Response {
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        protected_router()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_api_requires_key() {
+        let response = get("/api/v1/formats", &[]).await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "UNAUTHORIZED");
+    }
+
+    #[tokio::test]
+    async fn test_api_rejects_wrong_key() {
+        let response = get("/api/v1/formats", &[("X-API-Key", "guess")]).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = get("/api/v1/formats", &[("Authorization", "Bearer guess")]).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_api_accepts_correct_key() {
+        let response = get("/api/v1/formats", &[("X-API-Key", "secret")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get("/api/v1/formats", &[("Authorization", "Bearer secret")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_health_and_metrics_stay_open() {
+        for uri in ["/health", "/health/live", "/metrics"] {
+            let response = get(uri, &[]).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn test_extract_api_key_from_x_api_key() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-API-Key", HeaderValue::from_static("secret"));
+        assert_eq!(extract_api_key(&headers), Some("secret"));
+    }
+
+    #[test]
+    fn test_extract_api_key_from_bearer() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret"),
+        );
+        assert_eq!(extract_api_key(&headers), Some("secret"));
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(extract_api_key(&headers), None);
+    }
+
+    #[test]
+    fn test_caller_has_scope() {
+        let mut config = Config::default();
+        config
+            .api_key_scopes
+            .insert("trusted".to_string(), vec![ApiKeyScope::DurationOverride]);
+
+        let mut headers = HeaderMap::new();
+        assert!(!caller_has_scope(
+            &config,
+            &headers,
+            ApiKeyScope::DurationOverride
+        ));
+
+        headers.insert("X-API-Key", HeaderValue::from_static("untrusted"));
+        assert!(!caller_has_scope(
+            &config,
+            &headers,
+            ApiKeyScope::DurationOverride
+        ));
+
+        headers.insert("X-API-Key", HeaderValue::from_static("trusted"));
+        assert!(caller_has_scope(
+            &config,
+            &headers,
+            ApiKeyScope::DurationOverride
+        ));
+    }
+}
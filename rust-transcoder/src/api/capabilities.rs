@@ -0,0 +1,30 @@
+//! Capability endpoints
+//!
+//! GET /api/v1/formats - поддерживаемые форматы
+//! GET /api/v1/codecs - поддерживаемые кодеки и совместимые с ними форматы
+
+use std::sync::Arc;
+
+use axum::{routing::get, Json, Router};
+
+use crate::{
+    models::{AudioCodec, AudioFormat, CodecInfo, FormatInfo},
+    AppState,
+};
+
+/// Создаёт routes для capability API
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/formats", get(formats_handler))
+        .route("/codecs", get(codecs_handler))
+}
+
+/// GET /api/v1/formats
+pub async fn formats_handler() -> Json<Vec<FormatInfo>> {
+    Json(AudioFormat::ALL.into_iter().map(FormatInfo::from).collect())
+}
+
+/// GET /api/v1/codecs
+pub async fn codecs_handler() -> Json<Vec<CodecInfo>> {
+    Json(AudioCodec::ALL.into_iter().map(CodecInfo::from).collect())
+}
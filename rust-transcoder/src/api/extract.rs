@@ -0,0 +1,206 @@
+//! Extractors запросов
+//!
+//! Обёртки над стандартными extractors axum с дополнительными ограничениями.
+
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use tracing::warn;
+
+use crate::{error::AppError, AppState};
+
+/// JSON body с ограничением времени чтения
+///
+/// Если клиент не передал body целиком за `Config::body_read_timeout_ms`,
+/// запрос завершается с 408 (защита от slowloris-подобных соединений).
+/// Некорректный JSON и несовпадение типов полей - 400 `VALIDATION_ERROR` с
+/// путём к полю; остальные отказы (content-type, размер body) - как у `axum::Json`.
+pub struct TimedJson<T>(pub T);
+
+#[async_trait]
+impl<T> FromRequest<Arc<AppState>> for TimedJson<T>
+where
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let window = state.config.body_read_timeout();
+        let headers = req.headers().clone();
+
+        let bytes = match tokio::time::timeout(window, Bytes::from_request(req, state)).await {
+            Ok(result) => result.map_err(IntoResponse::into_response)?,
+            Err(_) => {
+                warn!(
+                    timeout_ms = window.as_millis() as u64,
+                    "Request body read timed out"
+                );
+                return Err(AppError::RequestTimeout(format!(
+                    "Request body was not received within {} ms",
+                    window.as_millis()
+                ))
+                .into_response());
+            }
+        };
+
+        let mut buffered = Request::new(Body::from(bytes));
+        *buffered.headers_mut() = headers;
+
+        let Json(value) = Json::<T>::from_request(buffered, state)
+            .await
+            .map_err(json_rejection)?;
+
+        Ok(Self(value))
+    }
+}
+
+/// Ошибки разбора body в формате `ErrorResponse`
+///
+/// Текст axum содержит путь к полю (`eq_preset: unknown variant ...`).
+fn json_rejection(rejection: JsonRejection) -> Response {
+    match rejection {
+        JsonRejection::JsonDataError(err) => {
+            AppError::Validation(format!("Invalid request body: {}", err.body_text()))
+                .into_response()
+        }
+        JsonRejection::JsonSyntaxError(err) => {
+            AppError::Validation(format!("Malformed JSON: {}", err.body_text())).into_response()
+        }
+        other => other.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+
+    use axum::{
+        http::{Request, StatusCode},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::Config;
+
+    async fn echo(TimedJson(value): TimedJson<serde_json::Value>) -> Json<serde_json::Value> {
+        Json(value)
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Typed {
+        name: String,
+        count: u32,
+    }
+
+    async fn typed(TimedJson(_): TimedJson<Typed>) {}
+
+    fn app(body_read_timeout_ms: u64) -> Router {
+        let config = Config {
+            body_read_timeout_ms,
+            ..Config::default()
+        };
+        Router::new()
+            .route("/", post(echo))
+            .route("/typed", post(typed))
+            .with_state(Arc::new(AppState::with_config(10, config).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_slow_body_returns_408() {
+        // Body приходит позже окна чтения
+        let slow = futures::stream::once(async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            Ok::<_, io::Error>(Bytes::from_static(b"{}"))
+        });
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("content-type", "application/json")
+            .body(Body::from_stream(slow))
+            .unwrap();
+
+        let response = app(50).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "REQUEST_TIMEOUT");
+    }
+
+    #[tokio::test]
+    async fn test_json_rejections_are_preserved() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .body(Body::from("{}"))
+            .unwrap();
+
+        // Без content-type - как у axum::Json
+        let response = app(1000).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"ok": true}"#))
+            .unwrap();
+
+        let response = app(1000).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    async fn post_body(body: &'static str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/typed")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = app(1000).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_is_validation_error() {
+        let (status, json) = post_body("not valid json").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+        assert!(
+            json["message"].as_str().unwrap().contains("Malformed JSON"),
+            "{}",
+            json
+        );
+
+        // Ошибка типа поля называет поле
+        let (status, json) = post_body(r#"{"name": "a", "count": "many"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+        assert!(
+            json["message"].as_str().unwrap().contains("count"),
+            "{}",
+            json
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Generate API endpoint
+//!
+//! POST /api/v1/generate - тестовый сигнал (синус или тишина) через обычный
+//! путь транскодирования, без внешнего источника. Для CI и нагрузочных тестов.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use tracing::instrument;
+
+use super::{
+    extract::TimedJson,
+    transcode::{self, Input},
+};
+use crate::{error::AppError, models::GenerateRequest, AppState};
+
+/// Создаёт routes для generate API
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/generate", post(generate_handler))
+}
+
+/// POST /api/v1/generate
+///
+/// Источник - `-f lavfi -i sine=...` или `anullsrc=...`; параметры результата,
+/// ответ и ошибки - как у `POST /api/v1/transcode`. Поля внешнего источника
+/// (`source_url`, `preroll_url` и т.п.) отклоняются.
+#[instrument(skip(state, request_headers, request), fields(kind = ?request.signal.kind))]
+pub async fn generate_handler(
+    State(state): State<Arc<AppState>>,
+    request_headers: HeaderMap,
+    TimedJson(request): TimedJson<GenerateRequest>,
+) -> Response {
+    let validated = request
+        .validate_with_allowlist(&state.config.source_host_allowlist)
+        .map_err(AppError::Validation)
+        .and_then(|()| {
+            request
+                .output
+                .check_sample_format()
+                .map_err(AppError::UnsupportedFormat)
+        });
+    if let Err(err) = validated {
+        return err.into_response();
+    }
+
+    let GenerateRequest { signal, output } = request;
+    transcode::run_transcode(state, request_headers, output, Input::Signal(signal)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    fn generate_request(body: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/generate")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn app(ffmpeg_script: &str) -> Router {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(ffmpeg_script),
+            ..Config::default()
+        };
+        routes().with_state(Arc::new(AppState::with_config(10, config).unwrap()))
+    }
+
+    async fn json_body(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_uses_lavfi_source() {
+        let response = app("exit 1")
+            .oneshot(generate_request(
+                r#"{"kind": "sine", "frequency": 1000, "duration": 2, "fade_out": 0.5, "dry_run": true}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = json_body(response).await;
+        let args: Vec<&str> = json["ffmpeg_args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|arg| arg.as_str().unwrap())
+            .collect();
+
+        let input = args.iter().position(|arg| *arg == "-i").unwrap();
+        assert_eq!(args[input - 2..input], ["-f", "lavfi"]);
+        assert_eq!(args[input + 1], "sine=frequency=1000:duration=2");
+        assert!(args.windows(2).any(|pair| pair == ["-t", "2"]), "{:?}", args);
+        // Длительность известна без ffprobe: fade out от конца сигнала
+        let chain = json["filter_chain"].as_str().unwrap();
+        assert!(chain.contains("afade=t=out:st=1.5"), "{}", chain);
+    }
+
+    #[tokio::test]
+    async fn test_generate_streams_output() {
+        let response = app("printf 'fake-audio'")
+            .oneshot(generate_request(r#"{"kind": "silence", "duration": 1}"#))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "audio/ogg");
+        assert!(response.headers().contains_key("X-Transcode-Id"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_rejects_source_fields() {
+        let response = app("exit 1")
+            .oneshot(generate_request(
+                r#"{"kind": "sine", "duration": 1, "source_url": "https://example.com/a.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(json_body(response).await["code"], "VALIDATION_ERROR");
+    }
+}
@@ -2,55 +2,217 @@
 //!
 //! Предоставляет /health, /health/ready и /health/live эндпоинты.
 
-use axum::{http::StatusCode, response::IntoResponse, Json};
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::Serialize;
 
+use crate::error::AppResult;
+use crate::transcoder::{ffmpeg, BreakerState};
+use crate::AppState;
+
 /// Ответ health check
 #[derive(Debug, Serialize)]
 pub struct HealthResponse {
     pub status: &'static str,
     pub service: &'static str,
     pub version: &'static str,
+    /// Коммит сборки (см. `GET /version`)
+    pub git_sha: &'static str,
+    /// Время с запуска сервиса в секундах
+    pub uptime_seconds: f64,
+    /// Выполняющиеся транскодирования (сессии, а не permit'ы)
+    pub active_transcodes: usize,
+    /// Лимит одновременных потоков (`MAX_CONCURRENT_STREAMS`)
+    pub max_concurrent_streams: usize,
 }
 
-/// GET /health - базовая проверка здоровья
-pub async fn health_check() -> impl IntoResponse {
+/// GET /health - базовая проверка здоровья и текущая нагрузка
+pub async fn health_check(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     Json(HealthResponse {
         status: "healthy",
         service: "rust-transcoder",
         version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("GIT_SHA"),
+        uptime_seconds: state.start_time.elapsed().as_secs_f64(),
+        active_transcodes: state.sessions.active_count(),
+        max_concurrent_streams: state.max_concurrent_streams,
     })
 }
 
+/// Ответ readiness check
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    /// `ready`, `degraded` или `draining` (идёт остановка)
+    pub status: &'static str,
+    /// Первая строка `ffmpeg -version`
+    pub ffmpeg_version: String,
+    /// Состояние circuit breaker'а запусков FFmpeg
+    pub circuit_breaker: BreakerState,
+}
+
 /// GET /health/ready - проверка готовности к приёму трафика
-pub async fn readiness_check() -> impl IntoResponse {
-    // TODO: Проверить доступность FFmpeg
-    (StatusCode::OK, "ready")
+///
+/// FFmpeg не найден или не запускается - 503 `FFMPEG_UNAVAILABLE`. Успешная
+/// проверка кэшируется в `AppState::ffmpeg_version`, неудачная повторяется
+/// на следующем запросе. Разомкнутый circuit breaker - 503: экземпляр всё
+/// равно отклонит транскоды.
+pub async fn readiness_check(
+    State(state): State<Arc<AppState>>,
+) -> AppResult<(StatusCode, Json<ReadinessResponse>)> {
+    let ffmpeg_version = state
+        .ffmpeg_version
+        .get_or_try_init(|| ffmpeg::check_ffmpeg_available_with_binary(&state.config.ffmpeg_path))
+        .await?
+        .clone();
+
+    let circuit_breaker = state.breaker.state();
+    let (status_code, status) = match circuit_breaker {
+        // Балансировщик должен перестать слать запросы на останавливающийся инстанс
+        _ if state.is_shutting_down() => (StatusCode::SERVICE_UNAVAILABLE, "draining"),
+        BreakerState::Open => (StatusCode::SERVICE_UNAVAILABLE, "degraded"),
+        BreakerState::Closed | BreakerState::HalfOpen => (StatusCode::OK, "ready"),
+    };
+
+    Ok((
+        status_code,
+        Json(ReadinessResponse {
+            status,
+            ffmpeg_version,
+            circuit_breaker,
+        }),
+    ))
 }
 
 /// GET /health/live - проверка что процесс жив
-pub async fn liveness_check() -> impl IntoResponse {
+pub async fn liveness_check() -> (StatusCode, &'static str) {
     (StatusCode::OK, "alive")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
 
     #[tokio::test]
     async fn test_health_check() {
-        let response = health_check().await;
+        let state = Arc::new(AppState::new(4).unwrap());
+        let response = health_check(State(state)).await;
         // Response should be valid JSON
         let json = response.into_response();
         assert_eq!(json.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_health_reports_uptime_and_load() {
+        let state = Arc::new(AppState::new(4).unwrap());
+        state.sessions.register(uuid::Uuid::new_v4());
+
+        let response = health_check(State(state)).await.into_response();
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert!(json["uptime_seconds"].as_f64().unwrap() >= 0.0);
+        assert_eq!(json["active_transcodes"], 1);
+        assert_eq!(json["max_concurrent_streams"], 4);
+        assert_eq!(json["git_sha"], env!("GIT_SHA"));
+    }
+
+    fn state_with_ffmpeg(ffmpeg_path: String) -> Arc<AppState> {
+        let config = Config {
+            ffmpeg_path,
+            ..Config::default()
+        };
+        Arc::new(AppState::with_config(10, config).unwrap())
+    }
+
+    fn state_with_fake_ffmpeg() -> Arc<AppState> {
+        state_with_ffmpeg(fake_ffmpeg(
+            "echo 'ffmpeg version 6.1.1 Copyright (c) 2000-2023'",
+        ))
+    }
+
     #[tokio::test]
     async fn test_readiness() {
-        let response = readiness_check().await;
-        let (status, body) = response;
+        let state = state_with_fake_ffmpeg();
+        let (status, Json(body)) = readiness_check(State(state)).await.unwrap();
         assert_eq!(status, StatusCode::OK);
-        assert_eq!(body, "ready");
+        assert_eq!(body.status, "ready");
+        assert_eq!(
+            body.ffmpeg_version,
+            "ffmpeg version 6.1.1 Copyright (c) 2000-2023"
+        );
+        assert_eq!(body.circuit_breaker, BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_missing_ffmpeg() {
+        let state = state_with_ffmpeg("/nonexistent/ffmpeg".to_string());
+
+        let response = readiness_check(State(state.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "FFMPEG_UNAVAILABLE");
+
+        // Неудача не кэшируется
+        assert!(state.ffmpeg_version.get().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_failing_ffmpeg() {
+        let state = state_with_ffmpeg(fake_ffmpeg("exit 1"));
+
+        let response = readiness_check(State(state)).await.into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_caches_ffmpeg_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = dir.path().join("calls");
+        let state = state_with_ffmpeg(fake_ffmpeg(&format!(
+            "echo call >> {}\necho 'ffmpeg version 6.1.1'",
+            calls.display()
+        )));
+
+        for _ in 0..3 {
+            let (status, _) = readiness_check(State(state.clone())).await.unwrap();
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let calls = std::fs::read_to_string(calls).unwrap();
+        assert_eq!(calls.lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_open_breaker() {
+        let state = state_with_fake_ffmpeg();
+        for _ in 0..state.config.breaker.failure_threshold {
+            state.breaker.record_failure();
+        }
+
+        let (status, Json(body)) = readiness_check(State(state)).await.unwrap();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body.status, "degraded");
+        assert_eq!(body.circuit_breaker, BreakerState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_draining_on_shutdown() {
+        let state = state_with_fake_ffmpeg();
+        state.begin_shutdown();
+
+        let (status, Json(body)) = readiness_check(State(state)).await.unwrap();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body.status, "draining");
     }
 
     #[tokio::test]
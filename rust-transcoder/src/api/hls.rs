@@ -0,0 +1,794 @@
+//! HLS API endpoints
+//!
+//! POST /api/v1/transcode/hls - транскодирование в VOD плейлист и сегменты
+//! GET /api/v1/hls/:session_id/:file - плейлист и сегменты сессии
+//!
+//! В отличие от `/transcode`, ответ не ждёт кодирования: FFmpeg пишет файлы
+//! в фоне, готовность - по статусу сессии (`completed`). При занятых слотах
+//! задача может ждать в очереди (`QUEUE_DEPTH`) в статусе `queued`.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use tracing::{info, instrument, warn, Instrument};
+use uuid::Uuid;
+
+use super::{
+    extract::TimedJson,
+    request_id::REQUEST_ID_HEADER,
+    transcode::{self, Input},
+};
+use crate::{
+    error::{AppError, AppResult},
+    metrics::{TRANSCODE_DURATION_SECONDS, TRANSCODE_REQUESTS_TOTAL},
+    models::{HlsResponse, TranscodeRequest, TranscodeStatus},
+    transcoder::{
+        ffmpeg, hls, queue::QueueTicket, redact::redact_url, FfmpegProcess, TranscodePermit,
+        TranscodeProfile,
+    },
+    AppState,
+};
+
+/// Создаёт routes для HLS API
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/transcode/hls", post(hls_handler))
+        .route("/hls/:session_id/:file", get(hls_file_handler))
+}
+
+/// POST /api/v1/transcode/hls
+///
+/// Параметры - как у `POST /api/v1/transcode` (кодеки - `HLS_CODECS`).
+/// Сегменты по `hls::SEGMENT_SECS` секунд пишутся в каталог сессии; ответ
+/// 202 с путями плейлиста и статуса. Общее время кодирования ограничено
+/// `Config::transcode_timeout`, permit занят до выхода FFmpeg. Если слот не
+/// освободился за `ACQUIRE_WAIT_MS`, задача встаёт в очередь (202, `queued`);
+/// при заполненной или выключенной очереди - 503.
+#[instrument(skip(state, request_headers, request), fields(session_id, request_id))]
+pub async fn hls_handler(
+    State(state): State<Arc<AppState>>,
+    request_headers: HeaderMap,
+    TimedJson(request): TimedJson<TranscodeRequest>,
+) -> Response {
+    let codec = request.codec.to_string();
+    let response = start_hls(state, request_headers, request)
+        .await
+        .into_response();
+
+    TRANSCODE_REQUESTS_TOTAL
+        .with_label_values(&["hls", &codec, response.status().as_str()])
+        .inc();
+
+    response
+}
+
+/// Проверяет запрос и запускает FFmpeg с выводом в каталог сессии
+async fn start_hls(
+    state: Arc<AppState>,
+    request_headers: HeaderMap,
+    mut request: TranscodeRequest,
+) -> AppResult<(StatusCode, Json<HlsResponse>)> {
+    request.validate_hls().map_err(AppError::Validation)?;
+    transcode::validate_params(&state, &request)?;
+    transcode::resolve_sources(&state, &mut request)?;
+    transcode::check_source_hosts(&state, &request).await?;
+
+    if state.is_shutting_down() {
+        return Err(AppError::ShuttingDown);
+    }
+
+    let session_id = Uuid::new_v4();
+    let span = tracing::Span::current();
+    span.record("session_id", session_id.to_string());
+    if let Some(request_id) = request_headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        span.record("request_id", request_id);
+    }
+
+    info!(
+        source_url = %redact_url(&request.source_url),
+        source_urls = request.source_urls.len(),
+        codec = %request.codec,
+        quality = %request.quality,
+        "Received HLS transcode request"
+    );
+
+    let profile = transcode::base_profile(&state, &request_headers, &request, &Input::Request);
+    let weight = state
+        .config
+        .permit_weights
+        .cost(request.quality, profile.codec)
+        .min(state.max_concurrent_streams as u32);
+    let acquired = TranscodePermit::acquire_within(
+        &state.transcode_semaphore,
+        weight,
+        state.config.acquire_wait(),
+    )
+    .await;
+    let Some(permit) = acquired else {
+        let ticket = state
+            .queue
+            .enqueue(session_id)
+            .ok_or(AppError::ConcurrencyLimitExceeded(
+                state.max_concurrent_streams,
+            ))?;
+        register(&state, session_id, &request);
+        info!(
+            queue_position = state.queue.position(session_id),
+            "All slots busy, HLS transcode queued"
+        );
+
+        let queued = run_queued(state.clone(), session_id, profile, ticket, weight);
+        tokio::spawn(queued.in_current_span());
+        return Ok(accepted(&state, session_id, TranscodeStatus::Queued));
+    };
+
+    register(&state, session_id, &request);
+    launch(&state, session_id, profile, permit).await?;
+
+    Ok(accepted(&state, session_id, TranscodeStatus::Processing))
+}
+
+/// Регистрирует сессию с настройками запроса
+fn register(state: &AppState, session_id: Uuid, request: &TranscodeRequest) {
+    state.sessions.register(session_id);
+    if request.allow_partial_on_cancel == Some(true) {
+        state.sessions.set_allow_partial(session_id);
+    }
+}
+
+/// Ответ 202 со ссылками на плейлист и статус сессии
+fn accepted(
+    state: &AppState,
+    session_id: Uuid,
+    status: TranscodeStatus,
+) -> (StatusCode, Json<HlsResponse>) {
+    let base = format!("{}/api/v1", state.config.route_prefix);
+    (
+        StatusCode::ACCEPTED,
+        Json(HlsResponse {
+            session_id,
+            status,
+            playlist_url: format!("{}/hls/{}/{}", base, session_id, hls::PLAYLIST),
+            status_url: format!("{}/transcode/{}", base, session_id),
+        }),
+    )
+}
+
+/// Ждёт очереди и слота, затем запускает FFmpeg
+///
+/// Отмена сессии убирает задачу из очереди; слот, полученный во время
+/// остановки сервиса, не используется.
+async fn run_queued(
+    state: Arc<AppState>,
+    session_id: Uuid,
+    profile: TranscodeProfile,
+    ticket: QueueTicket,
+    weight: u32,
+) {
+    let cancel = state.sessions.cancel_signal(session_id);
+    let cancelled = async {
+        match cancel {
+            Some(cancel) => cancel.notified().await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let permit = tokio::select! {
+        permit = ticket.acquire(&state.transcode_semaphore, weight) => permit,
+        _ = cancelled => {
+            info!("Queued HLS transcode cancelled");
+            return;
+        }
+    };
+    if state.is_shutting_down() {
+        state
+            .sessions
+            .fail(session_id, AppError::ShuttingDown.to_string());
+        return;
+    }
+
+    info!("Slot acquired, starting queued HLS transcode");
+    if let Err(err) = launch(&state, session_id, profile, permit).await {
+        warn!(error = %err, "Queued HLS transcode failed to start");
+    }
+}
+
+/// Запускает FFmpeg с выводом в каталог сессии и задачу его ожидания
+///
+/// Ошибка запуска завершает сессию (`failed`) и удаляет каталог.
+async fn launch(
+    state: &Arc<AppState>,
+    session_id: Uuid,
+    profile: TranscodeProfile,
+    permit: TranscodePermit,
+) -> AppResult<()> {
+    // Каталоги истёкших сессий удаляются при создании новых
+    state.hls.prune(&state.sessions).await;
+
+    let started = async {
+        let breaker_pass = state.breaker.admit()?;
+        // Ошибка каталога - не сбой FFmpeg: пропуск освобождается при drop
+        let dir = state.hls.create(session_id).await?;
+        let profile = TranscodeProfile {
+            hls_dir: Some(dir),
+            ..profile
+        };
+        match FfmpegProcess::spawn_with_binary(&state.config.ffmpeg_path, profile).await {
+            Ok(process) => {
+                // Итог сообщает `finish_hls`
+                breaker_pass.hand_off();
+                Ok(process)
+            }
+            Err(err) => {
+                breaker_pass.failure();
+                Err(err)
+            }
+        }
+    };
+    let process = match started.await {
+        Ok(process) => process,
+        Err(err) => {
+            state.sessions.fail(session_id, err.to_string());
+            state.hls.remove(session_id).await;
+            return Err(err);
+        }
+    };
+    state
+        .sessions
+        .set_status(session_id, TranscodeStatus::Processing);
+    info!("FFmpeg spawned, writing HLS segments");
+
+    tokio::spawn(finish_hls(state.clone(), session_id, process, permit).in_current_span());
+    Ok(())
+}
+
+/// Чем закончилось ожидание FFmpeg
+enum HlsEnd {
+    Exited(AppResult<std::process::ExitStatus>),
+    Cancelled,
+    TimedOut,
+}
+
+/// Дожидается выхода FFmpeg и переводит сессию в конечный статус
+///
+/// Отмена сессии или `transcode_timeout` убивают процесс. Каталог отменённой
+/// сессии удаляется (с `allow_partial_on_cancel` - только если не записано ни
+/// одного сегмента), каталог по таймауту - вместе с истёкшей сессией.
+async fn finish_hls(
+    state: Arc<AppState>,
+    session_id: Uuid,
+    mut process: FfmpegProcess,
+    permit: TranscodePermit,
+) {
+    let started_at = Instant::now();
+    let sessions = state.sessions.clone();
+    let stderr = process.take_stderr().map(|stderr| {
+        let sessions = sessions.clone();
+        ffmpeg::collect_stderr_with_progress(stderr, move |progress| {
+            sessions.set_progress(session_id, progress)
+        })
+    });
+
+    let cancel = sessions.cancel_signal(session_id);
+    let cancelled = async {
+        match cancel {
+            Some(cancel) => cancel.notified().await,
+            None => std::future::pending().await,
+        }
+    };
+    let timeout = state.config.transcode_timeout();
+
+    let end = tokio::select! {
+        exit = process.wait() => HlsEnd::Exited(exit),
+        _ = cancelled => HlsEnd::Cancelled,
+        _ = tokio::time::sleep(timeout) => HlsEnd::TimedOut,
+    };
+    let exit = match end {
+        HlsEnd::Exited(exit) => exit,
+        HlsEnd::Cancelled => {
+            let _ = process.kill().await;
+            drop(permit);
+            state.breaker.record_cancelled();
+            keep_or_remove_partial(&state, session_id).await;
+            return;
+        }
+        HlsEnd::TimedOut => {
+            warn!(timeout_secs = timeout.as_secs(), "HLS transcode timed out");
+            let _ = process.kill().await;
+            drop(permit);
+            state.breaker.record_failure();
+            sessions.fail(
+                session_id,
+                format!(
+                    "Transcode did not finish within {} seconds",
+                    timeout.as_secs()
+                ),
+            );
+            return;
+        }
+    };
+
+    TRANSCODE_DURATION_SECONDS.observe(started_at.elapsed().as_secs_f64());
+    drop(permit);
+    let stderr = match stderr {
+        Some(task) => task.await.unwrap_or_default(),
+        None => String::new(),
+    };
+
+    match exit {
+        Ok(status) if status.success() => {
+            state.breaker.record_success();
+            sessions.set_status(session_id, TranscodeStatus::Completed);
+            info!("HLS transcode completed");
+        }
+        Ok(status) => {
+            let message = ffmpeg::exit_error(status, &stderr);
+            warn!(error = %message, "HLS transcode failed");
+            state.breaker.record_failure();
+            sessions.fail(session_id, message);
+        }
+        Err(err) => {
+            state.breaker.record_failure();
+            sessions.fail(session_id, err.to_string());
+        }
+    }
+}
+
+/// Сохраняет записанные сегменты отменённой сессии или удаляет каталог
+async fn keep_or_remove_partial(state: &AppState, session_id: Uuid) {
+    let allow_partial = state
+        .sessions
+        .get(session_id)
+        .is_some_and(|session| session.allow_partial);
+    if allow_partial {
+        match state.hls.finalize_partial(session_id).await {
+            Ok(true) => {
+                state.sessions.set_partial(session_id);
+                info!("HLS transcode cancelled, partial output kept");
+                return;
+            }
+            Ok(false) => {}
+            Err(err) => warn!(error = %err, "Failed to finalize partial HLS playlist"),
+        }
+    }
+    state.hls.remove(session_id).await;
+}
+
+/// GET /api/v1/hls/:session_id/:file
+///
+/// `playlist.m3u8`, `init.mp4` или `segment_NNN.ts`/`.m4s` сессии. 404 -
+/// сессии нет или файл ещё не записан, 400 - имя не файла HLS.
+pub async fn hls_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path((session_id, file)): Path<(Uuid, String)>,
+) -> AppResult<Response> {
+    let (bytes, content_type) = state.hls.read(session_id, &file).await?;
+
+    // Плейлист дописывается до завершения кодирования
+    let cache_control = if file == hls::PLAYLIST {
+        "no-cache"
+    } else {
+        "max-age=3600"
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+    use axum::{body::Body, http::Request};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// Fake FFmpeg: плейлист - последний аргумент, сегмент - рядом с ним
+    const FAKE_HLS: &str = r#"for arg; do out="$arg"; done
+printf 'ts-data' > "$(dirname "$out")/segment_000.ts"
+printf '#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:4\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXTINF:4.000000,\nsegment_000.ts\n#EXT-X-ENDLIST\n' > "$out""#;
+
+    fn hls_state(script: &str) -> (Arc<AppState>, tempfile::TempDir) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(script),
+            hls_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        (Arc::new(AppState::with_config(10, config).unwrap()), dir)
+    }
+
+    fn app(state: &Arc<AppState>) -> Router {
+        routes()
+            .merge(transcode::routes())
+            .with_state(state.clone())
+    }
+
+    fn get(uri: String) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    async fn start(state: &Arc<AppState>, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/transcode/hls")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = app(state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn wait_finished(state: &AppState, session_id: Uuid) -> TranscodeStatus {
+        for _ in 0..100 {
+            let status = state.sessions.get(session_id).unwrap().status;
+            if !matches!(
+                status,
+                TranscodeStatus::Queued | TranscodeStatus::Processing
+            ) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("HLS session {} did not finish", session_id);
+    }
+
+    #[tokio::test]
+    async fn test_playlist_and_segments_are_served() {
+        let (state, _dir) = hls_state(FAKE_HLS);
+
+        let (status, json) = start(
+            &state,
+            r#"{"source_url": "https://example.com/a.mp3", "codec": "aac", "format": "aac"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let session_id: Uuid = json["session_id"].as_str().unwrap().parse().unwrap();
+        assert_eq!(
+            json["playlist_url"],
+            format!("/api/v1/hls/{}/playlist.m3u8", session_id)
+        );
+        assert_eq!(
+            wait_finished(&state, session_id).await,
+            TranscodeStatus::Completed
+        );
+
+        let response = app(&state)
+            .oneshot(get(format!("/hls/{}/playlist.m3u8", session_id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/vnd.apple.mpegurl"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let playlist = String::from_utf8(body.to_vec()).unwrap();
+        assert!(playlist.starts_with("#EXTM3U\n"), "{}", playlist);
+        assert!(playlist.contains("segment_000.ts"));
+
+        let response = app(&state)
+            .oneshot(get(format!("/hls/{}/segment_000.ts", session_id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "video/mp2t");
+
+        for (file, expected) in [
+            ("segment_001.ts", StatusCode::NOT_FOUND),
+            ("..%2Fplaylist.m3u8", StatusCode::BAD_REQUEST),
+            ("ffmpeg.log", StatusCode::BAD_REQUEST),
+        ] {
+            let response = app(&state)
+                .oneshot(get(format!("/hls/{}/{}", session_id, file)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), expected, "{}", file);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_hls_directory() {
+        let (state, dir) = hls_state(FAKE_HLS);
+
+        let (_, json) = start(&state, r#"{"source_url": "https://example.com/a.mp3"}"#).await;
+        let session_id: Uuid = json["session_id"].as_str().unwrap().parse().unwrap();
+        wait_finished(&state, session_id).await;
+        let session_dir = dir.path().join(session_id.to_string());
+        assert!(session_dir.join("playlist.m3u8").exists());
+
+        // Завершённую HLS сессию можно удалить: это освобождает диск
+        let delete = Request::builder()
+            .method("DELETE")
+            .uri(format!("/transcode/{}", session_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(&state).oneshot(delete).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!session_dir.exists());
+
+        let response = app(&state)
+            .oneshot(get(format!("/hls/{}/playlist.m3u8", session_id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Fake FFmpeg, убитый посреди кодирования: один сегмент без `#EXT-X-ENDLIST`
+    const FAKE_HLS_HANGING: &str = r#"for arg; do out="$arg"; done
+printf 'ts-data' > "$(dirname "$out")/segment_000.ts"
+printf '#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:4\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXTINF:4.000000,\nsegment_000.ts\n' > "$out"
+exec sleep 30"#;
+
+    /// Запускает HLS сессию и отменяет её после записи первого сегмента
+    async fn cancel_mid_encode(
+        state: &Arc<AppState>,
+        dir: &tempfile::TempDir,
+        body: &'static str,
+    ) -> Uuid {
+        let (status, json) = start(state, body).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let session_id: Uuid = json["session_id"].as_str().unwrap().parse().unwrap();
+        let playlist = dir.path().join(session_id.to_string()).join(hls::PLAYLIST);
+        for _ in 0..100 {
+            if playlist.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(playlist.exists());
+
+        let delete = Request::builder()
+            .method("DELETE")
+            .uri(format!("/transcode/{}", session_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state).oneshot(delete).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        session_id
+    }
+
+    async fn status_json(state: &Arc<AppState>, session_id: Uuid) -> serde_json::Value {
+        let response = app(state)
+            .oneshot(get(format!("/transcode/{}", session_id)))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cancel_keeps_partial_output_when_allowed() {
+        let (state, dir) = hls_state(FAKE_HLS_HANGING);
+
+        let session_id = cancel_mid_encode(
+            &state,
+            &dir,
+            r#"{"source_url": "https://example.com/a.mp3", "allow_partial_on_cancel": true}"#,
+        )
+        .await;
+        for _ in 0..100 {
+            if state.sessions.get(session_id).unwrap().partial {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let json = status_json(&state, session_id).await;
+        assert_eq!(json["status"], "cancelled");
+        assert_eq!(json["partial"], true);
+
+        // Плейлист закрыт: плеер не ждёт сегментов после отмены
+        let response = app(&state)
+            .oneshot(get(format!("/hls/{}/playlist.m3u8", session_id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let playlist = String::from_utf8(body.to_vec()).unwrap();
+        assert!(playlist.contains("segment_000.ts"), "{}", playlist);
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"), "{}", playlist);
+
+        let response = app(&state)
+            .oneshot(get(format!("/hls/{}/segment_000.ts", session_id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"ts-data");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_removes_partial_output_by_default() {
+        let (state, dir) = hls_state(FAKE_HLS_HANGING);
+
+        let session_id =
+            cancel_mid_encode(&state, &dir, r#"{"source_url": "https://example.com/a.mp3"}"#)
+                .await;
+        let session_dir = dir.path().join(session_id.to_string());
+        for _ in 0..100 {
+            if !session_dir.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!session_dir.exists());
+
+        let json = status_json(&state, session_id).await;
+        assert_eq!(json["status"], "cancelled");
+        assert!(json.get("partial").is_none(), "{}", json);
+    }
+
+    #[tokio::test]
+    async fn test_allow_partial_on_cancel_is_rejected_for_streams() {
+        let (state, _dir) = hls_state(FAKE_HLS);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"source_url": "https://example.com/a.mp3", "allow_partial_on_cancel": true}"#,
+            ))
+            .unwrap();
+        let response = app(&state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_hls_validation_and_ffmpeg_failure() {
+        let (state, _dir) = hls_state("echo 'Invalid data found' >&2; exit 1");
+
+        let (status, json) = start(
+            &state,
+            r#"{"source_url": "https://example.com/a.mp3", "codec": "flac", "format": "flac"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+
+        let (status, json) = start(&state, r#"{"source_url": "https://example.com/a.mp3"}"#).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        let session_id: Uuid = json["session_id"].as_str().unwrap().parse().unwrap();
+        assert_eq!(
+            wait_finished(&state, session_id).await,
+            TranscodeStatus::Failed
+        );
+        let error = state.sessions.get(session_id).unwrap().error.unwrap();
+        assert!(error.contains("Invalid data found"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_busy_slots_queue_hls_transcode() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(FAKE_HLS),
+            hls_dir: dir.path().to_path_buf(),
+            queue_depth: 1,
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(1, config).unwrap());
+        let busy = TranscodePermit::try_acquire(&state.transcode_semaphore).unwrap();
+
+        let (status, json) = start(&state, r#"{"source_url": "https://example.com/a.mp3"}"#).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(json["status"], "queued");
+        let session_id: Uuid = json["session_id"].as_str().unwrap().parse().unwrap();
+
+        let response = app(&state)
+            .oneshot(get(format!("/transcode/{}", session_id)))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let status_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status_json["status"], "queued");
+        assert_eq!(status_json["queue_position"], 1);
+
+        // Очередь на одну задачу заполнена
+        let (status, json) = start(&state, r#"{"source_url": "https://example.com/b.mp3"}"#).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["code"], "CONCURRENCY_LIMIT_EXCEEDED");
+
+        // Освободившийся слот забирает задача из очереди
+        drop(busy);
+        assert_eq!(
+            wait_finished(&state, session_id).await,
+            TranscodeStatus::Completed
+        );
+        assert!(state.queue.is_empty());
+        assert!(dir
+            .path()
+            .join(session_id.to_string())
+            .join("playlist.m3u8")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_removes_queued_hls_transcode() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(FAKE_HLS),
+            hls_dir: dir.path().to_path_buf(),
+            queue_depth: 4,
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(1, config).unwrap());
+        let _busy = TranscodePermit::try_acquire(&state.transcode_semaphore).unwrap();
+
+        let (_, json) = start(&state, r#"{"source_url": "https://example.com/a.mp3"}"#).await;
+        let session_id: Uuid = json["session_id"].as_str().unwrap().parse().unwrap();
+        assert_eq!(state.queue.position(session_id), Some(1));
+
+        state.sessions.cancel(session_id).unwrap();
+        for _ in 0..100 {
+            if state.queue.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(state.queue.is_empty());
+        assert_eq!(
+            state.sessions.get(session_id).unwrap().status,
+            TranscodeStatus::Cancelled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hls_dir_failure_does_not_hold_half_open_probe() {
+        use crate::transcoder::{breaker::BreakerSettings, BreakerState};
+
+        // Каталог HLS не создать: на его месте файл
+        let not_a_dir = tempfile::NamedTempFile::new().unwrap();
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(FAKE_HLS),
+            hls_dir: not_a_dir.path().to_path_buf(),
+            breaker: BreakerSettings {
+                cooldown: Duration::ZERO,
+                ..BreakerSettings::default()
+            },
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        for _ in 0..state.config.breaker.failure_threshold {
+            state.breaker.record_failure();
+        }
+        assert_eq!(state.breaker.state(), BreakerState::HalfOpen);
+
+        let (status, _) = start(&state, r#"{"source_url": "https://example.com/a.mp3"}"#).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        // Место пробного запроса свободно
+        assert!(state.breaker.admit().is_ok());
+    }
+}
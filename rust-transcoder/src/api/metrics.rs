@@ -1,34 +1,138 @@
 //! Metrics endpoint для Prometheus
 //!
-//! Предоставляет /metrics эндпоинт в формате Prometheus.
+//! Предоставляет /metrics эндпоинт в формате Prometheus и middleware
+//! счётчиков запросов и ошибок.
 
-use axum::response::IntoResponse;
-use prometheus::{Encoder, TextEncoder};
+use std::io::Write;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{proto::MetricFamily, Encoder, TextEncoder};
+
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::metrics::{HTTP_REQUESTS_TOTAL, TRANSCODE_ERRORS_TOTAL};
 
 /// GET /metrics - Prometheus метрики
-pub async fn metrics_handler() -> impl IntoResponse {
-    let encoder = TextEncoder::new();
-    let metric_families = prometheus::gather();
-    let mut buffer = Vec::new();
+///
+/// Ошибка кодирования возвращается как 500, а не паникой.
+pub async fn metrics_handler() -> AppResult<impl IntoResponse> {
+    crate::metrics::register();
 
-    encoder
-        .encode(&metric_families, &mut buffer)
-        .expect("Failed to encode metrics");
+    let mut buffer = Vec::new();
+    encode_metrics(&prometheus::gather(), &mut buffer)?;
 
-    (
-        [(axum::http::header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+    Ok((
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; charset=utf-8",
+        )],
         buffer,
-    )
+    ))
+}
+
+/// Кодирует метрики в текстовый формат Prometheus
+pub fn encode_metrics<W: Write>(metric_families: &[MetricFamily], writer: &mut W) -> AppResult<()> {
+    TextEncoder::new()
+        .encode(metric_families, writer)
+        .map_err(|e| AppError::Internal(format!("Failed to encode metrics: {}", e)))
+}
+
+/// Middleware `http_requests_total`: метка пути - шаблон маршрута
+/// (`/api/v1/transcode/:session_id`), чтобы ID не раздували число серий
+pub async fn count_requests(request: Request, next: Next) -> Response {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+
+    let response = next.run(request).await;
+    HTTP_REQUESTS_TOTAL
+        .with_label_values(&[&path, response.status().as_str()])
+        .inc();
+    response
+}
+
+/// Middleware `transcode_errors_total`: код ошибки берётся из `ErrorCode`,
+/// который `AppError::into_response` кладёт в ответ
+pub async fn count_transcode_errors(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if response.status().is_client_error() || response.status().is_server_error() {
+        if let Some(ErrorCode(code)) = response.extensions().get::<ErrorCode>() {
+            TRANSCODE_ERRORS_TOTAL.with_label_values(&[code]).inc();
+        }
+    }
+    response
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io;
+
     use super::*;
 
+    /// Writer, который всегда возвращает ошибку
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[tokio::test]
     async fn test_metrics_handler() {
-        let response = metrics_handler().await;
-        let (headers, body) = response;
-        assert!(!body.is_empty());
+        let response = metrics_handler().await.into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_validation_failure_counts_error_and_request() {
+        use std::sync::Arc;
+
+        use axum::{body::Body, http::StatusCode};
+        use tower::ServiceExt;
+
+        use crate::AppState;
+
+        let errors = TRANSCODE_ERRORS_TOTAL.with_label_values(&["VALIDATION_ERROR"]);
+        let requests = HTTP_REQUESTS_TOTAL.with_label_values(&["/api/v1/transcode", "400"]);
+        let (errors_before, requests_before) = (errors.get(), requests.get());
+
+        let router = crate::build_router(Arc::new(AppState::new(2).unwrap()));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"source_url": "ftp://example.com/a.mp3"}"#))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(errors.get() > errors_before);
+        assert!(requests.get() > requests_before);
+    }
+
+    #[test]
+    fn test_encode_error_is_returned_not_panicked() {
+        // Гарантируем непустой набор, чтобы encoder обратился к writer
+        crate::metrics::TRANSCODE_TTFB_SECONDS
+            .with_label_values(&["opus"])
+            .observe(0.1);
+
+        let err = encode_metrics(&prometheus::gather(), &mut FailingWriter).unwrap_err();
+        assert!(matches!(err, AppError::Internal(_)));
+
+        let response = err.into_response();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        );
     }
 }
@@ -4,17 +4,65 @@
 
 use std::sync::Arc;
 
-use axum::Router;
+use axum::{middleware, Router};
 
 use crate::AppState;
 
+pub mod auth;
+pub mod capabilities;
+pub mod extract;
+pub mod generate;
 pub mod health;
+pub mod hls;
 pub mod metrics;
+pub mod probe;
+pub mod rate_limit;
+pub mod request_id;
 pub mod transcode;
+pub mod upload;
+pub mod version;
+pub mod waveform;
 
 /// Создаёт Router для API v1
+///
+/// При заданном `Config::api_key` все маршруты требуют ключ (health и
+/// metrics монтируются отдельно и остаются открытыми). Ошибки маршрутов
+/// транскодирования, включая rate limit, считаются в `transcode_errors_total`.
 pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         // POST /api/v1/transcode - основной эндпоинт транскодирования
-        .merge(transcode::routes())
+        // POST /api/v1/generate - тестовый сигнал вместо источника
+        // POST /api/v1/transcode/hls, GET /api/v1/hls/... - вывод в HLS
+        // POST /api/v1/waveform - PNG превью источника
+        .merge(
+            transcode::routes()
+                .merge(generate::routes())
+                .merge(hls::routes())
+                .merge(waveform::routes())
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    rate_limit::limit_transcodes,
+                ))
+                .route_layer(middleware::from_fn(metrics::count_transcode_errors)),
+        )
+        // POST /api/v1/probe - параметры источника через ffprobe
+        // POST /api/v1/loudness - громкость источника без транскодирования
+        .merge(probe::routes())
+        // GET /api/v1/formats, /api/v1/codecs - поддерживаемые форматы и кодеки
+        .merge(capabilities::routes())
+        .route_layer(middleware::from_fn_with_state(state, auth::require_api_key))
+}
+
+/// Маршруты загрузки файла (`POST /api/v1/transcode/upload`)
+///
+/// Монтируются вне общего лимита body (`max_request_body_bytes`): размер
+/// файла ограничивает `max_source_bytes`. Ключ и rate limit - как у `routes`.
+pub fn upload_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    upload::routes()
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_transcodes,
+        ))
+        .route_layer(middleware::from_fn(metrics::count_transcode_errors))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_api_key))
 }
@@ -0,0 +1,248 @@
+//! Probe API endpoint
+//!
+//! POST /api/v1/probe - параметры источника (длительность, кодек, каналы,
+//! битрейт) через ffprobe, без транскодирования.
+//! POST /api/v1/loudness - громкость источника (`loudnorm`), без вывода аудио.
+
+use std::sync::Arc;
+
+use axum::{extract::State, routing::post, Json, Router};
+use tracing::{info, instrument};
+
+use super::extract::TimedJson;
+use crate::{
+    error::{AppError, AppResult},
+    models::{LoudnessRequest, LoudnessStats, ProbeRequest, ProbeResponse},
+    transcoder::{cloud, fetch, permit::TranscodePermit, probe, redact::redact_url},
+    AppState,
+};
+
+/// Создаёт routes для probe API
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/probe", post(probe_handler))
+        .route("/loudness", post(loudness_handler))
+}
+
+/// POST /api/v1/probe
+///
+/// `source_url` проходит те же проверки, что и в `/transcode` (схема, SSRF,
+/// allowlist); `s3://`/`gs://` подписываются. Ошибка ffprobe - 400
+/// `SOURCE_UNAVAILABLE`.
+#[instrument(skip(state, request), fields(source_url = %redact_url(&request.source_url)))]
+pub async fn probe_handler(
+    State(state): State<Arc<AppState>>,
+    TimedJson(request): TimedJson<ProbeRequest>,
+) -> AppResult<Json<ProbeResponse>> {
+    request
+        .validate_with_allowlist(&state.config.source_host_allowlist)
+        .map_err(AppError::Validation)?;
+    let source_url = cloud::resolve_source_url(
+        &request.source_url,
+        state.presigner.as_ref(),
+        &state.config.source_host_allowlist,
+    )?;
+    fetch::check_source_host(&source_url, &state.config.source_host_allowlist).await?;
+
+    let info = probe::probe_source_with_binary(&state.config.ffprobe_path, &source_url).await?;
+    info!(codec = ?info.codec, duration = ?info.duration, "Source probed");
+
+    Ok(Json(ProbeResponse {
+        duration: info.duration,
+        format: info.format,
+        codec: info.codec,
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        bitrate: info.bitrate,
+    }))
+}
+
+/// POST /api/v1/loudness
+///
+/// Полный проход FFmpeg по источнику: занимает один permit общего семафора,
+/// ограничен `MAX_SOURCE_DURATION` и `TRANSCODE_TIMEOUT`.
+#[instrument(skip(state, request), fields(source_url = %redact_url(&request.source_url)))]
+pub async fn loudness_handler(
+    State(state): State<Arc<AppState>>,
+    TimedJson(request): TimedJson<LoudnessRequest>,
+) -> AppResult<Json<LoudnessStats>> {
+    request
+        .validate_with_allowlist(&state.config.source_host_allowlist)
+        .map_err(AppError::Validation)?;
+    let source_url = cloud::resolve_source_url(
+        &request.source_url,
+        state.presigner.as_ref(),
+        &state.config.source_host_allowlist,
+    )?;
+    fetch::check_source_host(&source_url, &state.config.source_host_allowlist).await?;
+
+    if state.is_shutting_down() {
+        return Err(AppError::ShuttingDown);
+    }
+
+    let _permit =
+        TranscodePermit::acquire_within(&state.transcode_semaphore, 1, state.config.acquire_wait())
+            .await
+            .ok_or(AppError::ConcurrencyLimitExceeded(
+                state.max_concurrent_streams,
+            ))?;
+
+    let timeout = state.config.transcode_timeout();
+    let measured = probe::measure_source_loudness(
+        &state.config.ffmpeg_path,
+        &source_url,
+        request.target_loudness,
+        state.config.resolve_max_duration(None, false),
+    );
+    let stats = tokio::time::timeout(timeout, measured)
+        .await
+        .map_err(|_| {
+            AppError::Timeout(format!(
+                "Loudness measurement did not finish within {} seconds",
+                timeout.as_secs()
+            ))
+        })??;
+    info!(
+        integrated_lufs = stats.input_i,
+        true_peak = stats.input_tp,
+        "Source loudness measured"
+    );
+
+    Ok(Json(stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+
+    async fn probe(ffprobe_script: &str, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let config = Config {
+            ffprobe_path: fake_ffmpeg(ffprobe_script),
+            ..Config::default()
+        };
+        post_json(config, "/probe", body).await
+    }
+
+    async fn post_json(
+        config: Config,
+        uri: &str,
+        body: &'static str,
+    ) -> (StatusCode, serde_json::Value) {
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = routes().with_state(state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_probe_returns_source_parameters() {
+        let script = r#"cat <<'JSON'
+{
+  "streams": [{ "codec_name": "mp3", "sample_rate": "44100", "channels": 2,
+                "duration": "183.040000", "bit_rate": "192000" }],
+  "format": { "duration": "183.050000", "format_name": "mp3", "bit_rate": "193000" }
+}
+JSON"#;
+
+        let (status, json) = probe(script, r#"{"source_url": "https://example.com/a.mp3"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["duration"], 183.04);
+        assert_eq!(json["format"], "mp3");
+        assert_eq!(json["codec"], "mp3");
+        assert_eq!(json["sample_rate"], 44100);
+        assert_eq!(json["channels"], 2);
+        assert_eq!(json["bitrate"], 192000);
+    }
+
+    #[tokio::test]
+    async fn test_probe_rejects_private_source() {
+        let (status, json) =
+            probe("exit 0", r#"{"source_url": "http://127.0.0.1:8080/a.mp3"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+    }
+
+    #[tokio::test]
+    async fn test_probe_failure_is_source_unavailable() {
+        let script = "echo 'Server returned 404 Not Found' >&2; exit 1";
+
+        let (status, json) = probe(script, r#"{"source_url": "https://example.com/a.mp3"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "SOURCE_UNAVAILABLE");
+    }
+
+    #[tokio::test]
+    async fn test_loudness_returns_stats() {
+        let script = r#"cat >&2 <<'LOG'
+[Parsed_loudnorm_0 @ 0x5581d8]
+{
+	"input_i" : "-19.43",
+	"input_tp" : "-0.87",
+	"input_lra" : "6.20",
+	"input_thresh" : "-29.61",
+	"output_i" : "-14.02",
+	"output_tp" : "-1.50",
+	"output_lra" : "5.10",
+	"output_thresh" : "-24.18",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.02"
+}
+LOG"#;
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(script),
+            ..Config::default()
+        };
+
+        let (status, json) = post_json(
+            config,
+            "/loudness",
+            r#"{"source_url": "https://example.com/a.mp3", "target_loudness": -14}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "input_i": -19.43,
+                "input_tp": -0.87,
+                "input_lra": 6.2,
+                "input_thresh": -29.61,
+                "target_offset": 0.02
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_loudness_rejects_private_source() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("exit 0"),
+            ..Config::default()
+        };
+
+        let (status, json) = post_json(
+            config,
+            "/loudness",
+            r#"{"source_url": "http://10.0.0.1/a.mp3"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+    }
+}
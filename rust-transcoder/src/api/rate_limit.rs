@@ -0,0 +1,252 @@
+//! Ограничение частоты запросов на транскодирование
+//!
+//! Token bucket на клиента: ёмкость - `rate_limit_per_minute` запросов,
+//! токены восстанавливаются равномерно за минуту. Клиент - API ключ, если
+//! ключи настроены и переданный ключ принят, иначе IP адрес соединения.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tokio::time::Instant;
+use tracing::warn;
+
+use super::auth::extract_api_key;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::AppState;
+
+/// При стольких клиентах в таблице полные bucket'ы выбрасываются
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Разделяемый rate limiter (None в `per_minute` - без ограничений)
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_minute: Option<u32>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Limiter на `per_minute` запросов в минуту на клиента
+    pub fn new(per_minute: Option<u32>) -> Self {
+        Self {
+            per_minute: per_minute.filter(|&limit| limit > 0),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Списывает токен клиента
+    ///
+    /// `Err` - секунд до появления следующего токена (для `Retry-After`).
+    pub fn check(&self, client: &str) -> Result<(), u64> {
+        let Some(per_minute) = self.per_minute else {
+            return Ok(());
+        };
+
+        let capacity = f64::from(per_minute);
+        let refill_per_sec = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().expect("rate limiter poisoned");
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens + elapsed * refill_per_sec < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec);
+            Err(wait.as_secs_f64().ceil().max(1.0) as u64)
+        }
+    }
+}
+
+/// Ключ клиента: API ключ или IP соединения
+///
+/// Произвольный ключ (API открыт или ключ не принят) не даёт отдельный
+/// bucket: иначе новый ключ в каждом запросе обходил бы лимит.
+fn client_key(request: &Request, config: &Config) -> String {
+    if let Some(key) = extract_api_key(request.headers()) {
+        if config.api_key.is_some() && config.accepts_api_key(Some(key)) {
+            return format!("key:{}", key);
+        }
+    }
+
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+/// Middleware маршрутов транскодирования: POST (новая работа) проходит
+/// через rate limiter, статус и отмена сессий не ограничиваются
+pub async fn limit_transcodes(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() == Method::POST {
+        let client = client_key(&request, &state.config);
+        if let Err(retry_after) = state.rate_limiter.check(&client) {
+            warn!(client = %client, retry_after, "Rate limit exceeded");
+            return AppError::RateLimited(retry_after).into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+    use axum::{
+        body::Body,
+        extract::connect_info::MockConnectInfo,
+        http::{header, StatusCode},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bucket_refills_over_time() {
+        let limiter = RateLimiter::new(Some(2));
+
+        assert!(limiter.check("a").is_ok());
+        assert!(limiter.check("a").is_ok());
+        // Токен восстанавливается за 30 секунд
+        assert_eq!(limiter.check("a"), Err(30));
+        // Остальные клиенты не затронуты
+        assert!(limiter.check("b").is_ok());
+
+        tokio::time::advance(Duration::from_secs(29)).await;
+        assert_eq!(limiter.check("a"), Err(1));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(limiter.check("a").is_ok());
+    }
+
+    #[test]
+    fn test_disabled_limiter_allows_everything() {
+        for limiter in [RateLimiter::new(None), RateLimiter::new(Some(0))] {
+            for _ in 0..1000 {
+                assert!(limiter.check("a").is_ok());
+            }
+        }
+    }
+
+    fn limited_router(per_minute: u32) -> Router {
+        limited_router_with(per_minute, Config::default())
+    }
+
+    fn limited_router_with(per_minute: u32, config: Config) -> Router {
+        let config = Config {
+            rate_limit_per_minute: Some(per_minute),
+            ffmpeg_path: fake_ffmpeg("printf 'fake-audio'"),
+            ..config
+        };
+        crate::build_router(Arc::new(AppState::with_config(10, config).unwrap()))
+            .layer(MockConnectInfo(SocketAddr::from(([10, 0, 0, 1], 40000))))
+    }
+
+    fn transcode(api_key: Option<&str>) -> Request {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/transcode")
+            .header("content-type", "application/json");
+        if let Some(key) = api_key {
+            request = request.header("X-API-Key", key);
+        }
+        request
+            .body(Body::from(r#"{"source_url": "https://example.com/a.mp3"}"#))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_transcode_past_limit_returns_429() {
+        let router = limited_router(3);
+
+        for _ in 0..3 {
+            let response = router.clone().oneshot(transcode(None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = router.clone().oneshot(transcode(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        // 3 в минуту - токен раз в 20 секунд
+        let retry_after: u64 = response.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=20).contains(&retry_after), "{}", retry_after);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "RATE_LIMITED");
+
+        // Без настроенных ключей произвольный ключ не даёт новый bucket
+        for key in ["bot-1", "bot-2", "bot-3"] {
+            let response = router.clone().oneshot(transcode(Some(key))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS, "{}", key);
+        }
+
+        // Статус сессий не ограничивается
+        let status = Request::builder()
+            .uri(format!("/api/v1/transcode/{}", uuid::Uuid::new_v4()))
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(status).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_accepted_api_keys_have_own_buckets() {
+        let config = Config {
+            api_key: Some("primary".to_string()),
+            api_key_scopes: [("partner".to_string(), Vec::new())].into_iter().collect(),
+            ..Config::default()
+        };
+        let router = limited_router_with(1, config);
+
+        for key in ["primary", "partner"] {
+            let response = router.clone().oneshot(transcode(Some(key))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", key);
+        }
+
+        let response = router
+            .clone()
+            .oneshot(transcode(Some("primary")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,116 @@
+//! Correlation ID запроса
+//!
+//! Middleware берёт `X-Request-Id` вызывающей стороны (или генерирует UUID),
+//! кладёт его в tracing span запроса и возвращает в каждом ответе, включая
+//! ошибки. Внутри обработки id доступен через `current()`.
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Заголовок correlation ID
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Максимальная длина принимаемого id; более длинный заменяется сгенерированным
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Correlation ID текущего запроса (None вне middleware)
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware: проставляет `X-Request-Id` в запрос, span и ответ
+pub async fn propagate(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let header_value =
+        HeaderValue::from_str(&request_id).expect("request id is a valid header value");
+
+    // Handler'ы видят id и в заголовках, в том числе сгенерированный
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value.clone());
+
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut response = REQUEST_ID
+        .scope(request_id, next.run(request).instrument(span))
+        .await;
+
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_router, AppState};
+    use axum::{body::Body, http::StatusCode};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn send(uri: String, request_id: Option<&str>) -> Response {
+        let mut request = Request::builder().uri(uri);
+        if let Some(id) = request_id {
+            request = request.header("X-Request-Id", id);
+        }
+
+        build_router(Arc::new(AppState::new(1).unwrap()))
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_response_echoes_provided_request_id() {
+        let response = send("/health/live".to_string(), Some("bot-42")).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[&REQUEST_ID_HEADER], "bot-42");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_generated_when_absent() {
+        let response = send("/health/live".to_string(), None).await;
+
+        let id = response.headers()[&REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(Uuid::parse_str(id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_error_response_carries_request_id() {
+        let uri = format!("/api/v1/transcode/{}", Uuid::new_v4());
+        let response = send(uri, Some("bot-43")).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[&REQUEST_ID_HEADER], "bot-43");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["request_id"], "bot-43");
+    }
+
+    #[test]
+    fn test_current_outside_request() {
+        assert_eq!(current(), None);
+    }
+}
@@ -1,51 +1,153 @@
 //! Transcode API endpoint
 //!
 //! POST /api/v1/transcode - основной эндпоинт транскодирования
+//! POST /api/v1/validate - проверка запроса без запуска
+//! GET /api/v1/transcode/:session_id - статус сессии
+//! DELETE /api/v1/transcode/:session_id - отмена сессии
 
+use std::io;
 use std::sync::Arc;
+use std::time::Instant;
 
 use axum::{
-    extract::State,
-    http::{HeaderMap, HeaderValue},
-    response::IntoResponse,
-    routing::post,
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
-use tracing::{info, instrument};
+use futures::StreamExt;
+use tracing::{debug, info, instrument, warn};
 use uuid::Uuid;
 
+use super::{auth, extract::TimedJson, request_id::REQUEST_ID_HEADER};
 use crate::{
+    config::ApiKeyScope,
     error::{AppError, AppResult},
-    models::{TranscodeRequest, TranscodeResponse, TranscodeStatus},
-    transcoder::filters,
+    metrics::{TRANSCODE_BYTES_TOTAL, TRANSCODE_DURATION_SECONDS, TRANSCODE_REQUESTS_TOTAL},
+    models::{
+        DryRunResponse, TestSignal, TranscodeRequest, TranscodeStatus, TranscodeStatusResponse,
+        ValidateResponse,
+    },
+    transcoder::{
+        cloud, fetch, ffmpeg, filters, probe,
+        redact::{redact_args, redact_url},
+        FfmpegProcess, PipedSource, SessionRegistry, SourceInfo, TranscodePermit,
+        TranscodeProfile, TranscodeStream,
+    },
     AppState,
 };
 
 /// Создаёт routes для transcode API
 pub fn routes() -> Router<Arc<AppState>> {
-    Router::new().route("/transcode", post(transcode_handler))
+    Router::new()
+        .route("/transcode", post(transcode_handler))
+        .route("/validate", post(validate_handler))
+        .route(
+            "/transcode/:session_id",
+            get(status_handler).delete(cancel_handler),
+        )
 }
 
 /// POST /api/v1/transcode
 ///
-/// Запускает транскодирование аудио и возвращает streaming response.
-#[instrument(skip(state, request), fields(session_id))]
+/// Запускает транскодирование аудио и возвращает streaming response:
+/// body - выход FFmpeg, метаданные сессии - в заголовках `X-*`,
+/// статус - по `X-Transcode-Id` через `GET /api/v1/transcode/:session_id`.
+/// Ответ отправляется с первыми байтами FFmpeg; без вывода дольше
+/// `Config::transcode_timeout` - 504. С `dry_run` возвращается JSON
+/// `DryRunResponse` с аргументами FFmpeg; процесс не запускается.
+#[instrument(skip(state, request_headers, request), fields(session_id, request_id))]
 pub async fn transcode_handler(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<TranscodeRequest>,
-) -> AppResult<impl IntoResponse> {
+    request_headers: HeaderMap,
+    TimedJson(request): TimedJson<TranscodeRequest>,
+) -> Response {
+    run_transcode(state, request_headers, request, Input::Request).await
+}
+
+/// Откуда транскодирование берёт аудио
+pub(super) enum Input {
+    /// `source_url` или `source_urls` запроса
+    Request,
+    /// Тестовый сигнал (`POST /api/v1/generate`)
+    Signal(TestSignal),
+    /// Файл из multipart body (`POST /api/v1/transcode/upload`), в stdin FFmpeg
+    Upload(PipedSource),
+}
+
+/// Транскодирование с учётом в метриках
+///
+/// Сигнал и загруженный файл проверяют свои handler'ы: `TranscodeRequest`
+/// без источника здесь не валидируется.
+pub(super) async fn run_transcode(
+    state: Arc<AppState>,
+    request_headers: HeaderMap,
+    request: TranscodeRequest,
+    input: Input,
+) -> Response {
+    let format = request.format.to_string();
+    let codec = request.codec.to_string();
+
+    let expose_stderr = state.config.expose_ffmpeg_stderr;
+    let response = start_transcode(state, request_headers, request, input)
+        .await
+        .map_err(|err| if expose_stderr { err } else { err.without_stderr() })
+        .into_response();
+
+    TRANSCODE_REQUESTS_TOTAL
+        .with_label_values(&[&format, &codec, response.status().as_str()])
+        .inc();
+
+    response
+}
+
+/// Ставит информационный заголовок ответа
+///
+/// Значение с управляющими символами (например, перевод строки в цепочке
+/// фильтров) заголовок пропускает, а не роняет handler.
+fn set_header(headers: &mut HeaderMap, name: &'static str, value: &str) {
+    match HeaderValue::from_str(value) {
+        Ok(value) => {
+            headers.insert(name, value);
+        }
+        Err(_) => warn!(header = name, value, "Skipping response header with invalid value"),
+    }
+}
+
+/// Запускает транскодирование: заголовки ответа и body
+async fn start_transcode(
+    state: Arc<AppState>,
+    request_headers: HeaderMap,
+    mut request: TranscodeRequest,
+    input: Input,
+) -> AppResult<(HeaderMap, Body)> {
+    let started_at = Instant::now();
+
     // Генерируем session_id
     let session_id = Uuid::new_v4();
-    tracing::Span::current().record("session_id", session_id.to_string());
+    let span = tracing::Span::current();
+    span.record("session_id", session_id.to_string());
+
+    // Correlation с логами вызывающей стороны (middleware `request_id`
+    // проставляет заголовок и сгенерированному id)
+    if let Some(request_id) = request_headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        span.record("request_id", request_id);
+    }
 
     // Извлекаем параметры фильтров для логирования
-    let has_filters = request.audio_filters.as_ref().map_or(false, |f| f.has_filters());
+    let has_filters = request.audio_filters.as_ref().is_some_and(|f| f.has_filters());
     let eq_preset = request.audio_filters.as_ref().and_then(|f| f.eq_preset);
     let speed = request.audio_filters.as_ref().and_then(|f| f.speed);
     let volume = request.audio_filters.as_ref().and_then(|f| f.volume);
 
     info!(
-        source_url = %request.source_url,
+        source_url = %redact_url(&request.source_url),
+        source_urls = request.source_urls.len(),
         format = %request.format,
         codec = %request.codec,
         quality = %request.quality,
@@ -56,20 +158,258 @@ pub async fn transcode_handler(
         "Received transcode request"
     );
 
-    // Валидация запроса
-    request.validate().map_err(AppError::Validation)?;
+    // Валидация запроса; без URL источника запрос проверяет свой handler
+    if matches!(input, Input::Request) {
+        validate_request(&state, &request)?;
+        resolve_sources(&state, &mut request)?;
+        check_source_hosts(&state, &request).await?;
+    }
+
+    let warnings = request.warnings();
+    for warning in &warnings {
+        warn!(warning = %warning, "Transcode request warning");
+    }
+
+    // Усиление без явного limiter: включаем его, чтобы не было клиппинга
+    if let Some(filters) = request.audio_filters.as_ref() {
+        if filters.limiter.is_none() && filters.boosts_level() {
+            info!("Level boost requested, true peak limiter auto-enabled");
+        }
+    }
+
+    // Остановка сервиса: выполняющиеся транскодирования дорабатывают, новые - нет
+    if state.is_shutting_down() {
+        return Err(AppError::ShuttingDown);
+    }
+
+    // Dry run: только команда FFmpeg, без внешних проходов и запуска процесса
+    if request.dry_run == Some(true) {
+        let profile = base_profile(&state, &request_headers, &request, &input);
+        let response = DryRunResponse::new(profile.build_ffmpeg_args());
+        info!(
+            ffmpeg_args = ?redact_args(&response.ffmpeg_args),
+            "Dry run, FFmpeg not spawned"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        let body = serde_json::to_vec(&response)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize dry run: {}", e)))?;
+        return Ok((headers, Body::from(body)));
+    }
+
+    let mut profile = base_profile(&state, &request_headers, &request, &input);
+
+    // Тяжёлое транскодирование занимает несколько слотов, но не больше всех
+    let weight = state
+        .config
+        .permit_weights
+        .cost(request.quality, profile.codec)
+        .min(state.max_concurrent_streams as u32);
 
     // Проверяем доступность семафора
-    let permit = state
-        .transcode_semaphore
-        .try_acquire()
-        .map_err(|_| AppError::ConcurrencyLimitExceeded(state.max_concurrent_streams))?;
+    // При ACQUIRE_WAIT_MS > 0 пик нагрузки пережидается, а не сразу отклоняется
+    let permit = TranscodePermit::acquire_within(
+        &state.transcode_semaphore,
+        weight,
+        state.config.acquire_wait(),
+    )
+    .await
+    .ok_or(AppError::ConcurrencyLimitExceeded(state.max_concurrent_streams))?;
+
+    info!(weight, "Acquired semaphore permit");
 
-    info!("Acquired semaphore permit");
+    state.sessions.register(session_id);
+
+    // Слишком длинный (или бесконечный) источник не занимает слот часами.
+    // Результат ffprobe одного источника используется и ниже (fade out,
+    // clamping), второй запуск не нужен
+    let mut source_info = None;
+    if let Some(limit) = state.config.max_input_duration_secs {
+        if matches!(input, Input::Request) {
+            match check_input_duration(&state, &request, limit).await {
+                Ok(Some(info)) => {
+                    if let Some(duration) = info.duration {
+                        profile = profile.with_source_duration(duration);
+                    }
+                    source_info = Some(info);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    state.sessions.fail(session_id, err.to_string());
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    // Fade out отсчитывается от конца: нужна длительность источника
+    if profile.fade_out.is_some() && profile.source_duration.is_none() {
+        let duration =
+            probe::probe_duration_with_binary(&state.config.ffprobe_path, &request.source_url)
+                .await
+                .map_err(|err| match err {
+                    AppError::Validation(_) => AppError::Validation(
+                        "fade_out requires a seekable source with known duration".to_string(),
+                    ),
+                    other => other,
+                });
+        let duration = match duration {
+            Ok(duration) => duration,
+            Err(err) => {
+                state.sessions.fail(session_id, err.to_string());
+                return Err(err);
+            }
+        };
+        profile = profile.with_source_duration(duration);
+    }
+
+    // До probe точки огибающей проверялись только по max_duration_override;
+    // после конца результата они бы молча не применились
+    if let Err(message) = profile.check_volume_envelope() {
+        let err = AppError::Validation(message);
+        state.sessions.fail(session_id, err.to_string());
+        return Err(err);
+    }
+
+    // Источник читает сервис: HTTP статус и таймаут - до запуска FFmpeg.
+    // Загруженный файл читается один раз, а результат с `source_headers`
+    // доступен только их владельцу: такие запросы не объединяются
+    let coalesce = state.config.enable_coalescing
+        && !matches!(input, Input::Upload(_))
+        && request.source_headers.is_none();
+    let fetcher = state
+        .fetcher
+        .as_ref()
+        .filter(|_| fetch::can_fetch(&profile));
+
+    // Без upmix и upsample: каналы и sample rate не больше, чем в источнике.
+    // Проверяется только явно запрошенный sample rate. Copy не перекодирует,
+    // у склейки нескольких источников частоты разные. Источник, который
+    // читает сервис, ffprobe не открывает: у него нет `source_headers` и
+    // проверки адресов загрузчика
+    let clamp_channels = request.clamp_channels_to_source == Some(true);
+    let clamp_sample_rate = request.sample_rate.is_some()
+        && request.allow_upsample != Some(true)
+        && matches!(input, Input::Request)
+        && request.source_urls.is_empty()
+        && !profile.copy;
+    let probed = match source_info.take() {
+        Some(info) => Some(Ok(info)),
+        None if fetcher.is_some() => {
+            if clamp_channels {
+                warn!("clamp_channels_to_source skipped: source is fetched by the service");
+            }
+            None
+        }
+        None if clamp_channels || clamp_sample_rate => Some(
+            probe::probe_source_with_binary(&state.config.ffprobe_path, &profile.source_url)
+                .await,
+        ),
+        None => None,
+    };
+    if let Some(probed) = probed.filter(|_| clamp_channels || clamp_sample_rate) {
+        match probed {
+            Ok(info) => {
+                if clamp_channels {
+                    profile = profile.clamp_channels_to(info.channels);
+                }
+                if clamp_sample_rate {
+                    let requested = profile.sample_rate;
+                    profile = profile.clamp_sample_rate_to(info.sample_rate);
+                    if profile.sample_rate != requested {
+                        info!(
+                            requested,
+                            source = ?info.sample_rate,
+                            effective = profile.sample_rate,
+                            "Sample rate clamped to source, set allow_upsample to keep it"
+                        );
+                    }
+                }
+            }
+            Err(err) => warn!(
+                error = %err,
+                "Probe failed, channel and sample rate clamping skipped"
+            ),
+        }
+    }
+
+    // QC: громкость источника - отдельным проходом, выход не изменяется
+    let loudness = if request.measure_loudness == Some(true) {
+        match ffmpeg::measure_loudness(
+            &state.config.ffmpeg_path,
+            &request.source_url,
+            profile.max_duration,
+            state.config.transcode_timeout(),
+        )
+        .await
+        {
+            Ok(loudness) => {
+                info!(
+                    integrated_lufs = loudness.integrated_lufs,
+                    loudness_range = loudness.loudness_range,
+                    true_peak = loudness.true_peak,
+                    "Source loudness measured"
+                );
+                state.sessions.set_loudness(session_id, loudness);
+                Some(loudness)
+            }
+            Err(err) => {
+                state.sessions.fail(session_id, err.to_string());
+                return Err(err);
+            }
+        }
+    } else {
+        None
+    };
+
+    // Двухпроходная нормализация: второй проход получает измеренные значения
+    if profile.normalize && profile.normalize_mode.is_some() {
+        let timeout = state.config.transcode_timeout();
+        match probe::measure_loudness_stats(&state.config.ffmpeg_path, &profile, timeout).await {
+            Ok(stats) => {
+                info!(
+                    input_i = stats.input_i,
+                    input_tp = stats.input_tp,
+                    input_lra = stats.input_lra,
+                    input_thresh = stats.input_thresh,
+                    target_offset = stats.target_offset,
+                    "Loudness measured, running second normalization pass"
+                );
+                state.sessions.set_loudness_stats(session_id, stats);
+                profile = profile.with_loudness_stats(stats);
+            }
+            Err(err) => {
+                state.sessions.fail(session_id, err.to_string());
+                return Err(err);
+            }
+        }
+    }
+
+    let profile_sample_rate = profile.sample_rate;
+
+    // Полные URL (с токенами presigned ссылок) - только по LOG_SOURCE_URLS
+    let ffmpeg_args = profile.build_ffmpeg_args();
+    debug!(
+        max_duration = ?profile.max_duration,
+        ffmpeg_args = ?if state.config.log_source_urls {
+            ffmpeg_args
+        } else {
+            redact_args(&ffmpeg_args)
+        },
+        "Transcode profile built"
+    );
 
     // Генерируем цепочку audio filters если указаны
     let filter_chain = if has_filters {
-        let chain = filters::build_audio_filter_chain(eq_preset, speed, volume);
+        let chain = request
+            .audio_filters
+            .as_ref()
+            .map(|f| filters::build_filter_chain(f, profile_sample_rate))
+            .unwrap_or_default();
         if !chain.is_empty() {
             info!(filter_chain = %chain, "Audio filters applied");
         }
@@ -78,52 +418,510 @@ pub async fn transcode_handler(
         None
     };
 
-    // Формируем response с кастомными headers
-    let response = TranscodeResponse::new(session_id, request.format.content_type())
-        .with_message("Transcoding started");
-
     // Создаём headers
+    let content_type = match HeaderValue::from_str(&request.content_type()) {
+        Ok(value) => value,
+        Err(e) => {
+            let err = AppError::Internal(format!("Invalid content type: {}", e));
+            state.sessions.fail(session_id, err.to_string());
+            return Err(err);
+        }
+    };
     let mut headers = HeaderMap::new();
-    headers.insert(
-        "X-Transcode-Id",
-        HeaderValue::from_str(&session_id.to_string()).unwrap(),
-    );
-    headers.insert(
-        "X-Source-Format",
-        HeaderValue::from_str(&request.format.to_string()).unwrap(),
-    );
-    headers.insert(
-        "X-Target-Codec",
-        HeaderValue::from_str(&request.codec.to_string()).unwrap(),
+    headers.insert(header::CONTENT_TYPE, content_type);
+    set_header(
+        &mut headers,
+        "Content-Disposition",
+        &request.content_disposition(session_id),
     );
+    set_header(&mut headers, "X-Transcode-Id", &session_id.to_string());
+    set_header(&mut headers, "X-Source-Format", &request.format.to_string());
+    set_header(&mut headers, "X-Target-Codec", &request.codec.to_string());
 
     // Добавляем header с фильтрами если есть
     if let Some(ref chain) = filter_chain {
         if !chain.is_empty() {
-            headers.insert(
-                "X-Audio-Filters",
-                HeaderValue::from_str(chain).unwrap_or_else(|_| HeaderValue::from_static("error")),
+            set_header(&mut headers, "X-Audio-Filters", chain);
+        }
+    }
+
+    // Запрошенный sample rate не поддерживается кодеком или выше частоты источника
+    if let Some(requested) = request.sample_rate.filter(|&rate| rate != profile_sample_rate) {
+        warn!(
+            requested,
+            effective = profile_sample_rate,
+            "Sample rate adjusted for codec or source"
+        );
+        set_header(
+            &mut headers,
+            "X-Sample-Rate-Adjusted",
+            &format!("{}->{}", requested, profile_sample_rate),
+        );
+    }
+
+    if let Some(loudness) = loudness {
+        for (name, value) in [
+            ("X-Loudness-Integrated", loudness.integrated_lufs),
+            ("X-Loudness-Range", loudness.loudness_range),
+            ("X-Loudness-True-Peak", loudness.true_peak),
+        ] {
+            set_header(&mut headers, name, &format!("{:.2}", value));
+        }
+    }
+
+    // Предупреждения - по заголовку на каждое
+    for warning in &warnings {
+        if let Ok(value) = HeaderValue::from_str(warning) {
+            headers.append("X-Transcode-Warning", value);
+        }
+    }
+
+    if request.source_headers.is_some() && fetcher.is_none() {
+        let err = AppError::Validation(
+            "source_headers require FETCH_SOURCE and a single http(s) file source".to_string(),
+        );
+        state.sessions.fail(session_id, err.to_string());
+        return Err(err);
+    }
+
+    // FFmpeg стабильно падает - не запускаем заведомо неудачный процесс
+    // Пропуск без итога (ошибка источника, сброшенный handler) освобождает
+    // место пробного запроса
+    let breaker_pass = match state.breaker.admit() {
+        Ok(pass) => pass,
+        Err(err) => {
+            warn!(breaker = %state.breaker.state(), "Circuit breaker rejected transcode");
+            state.sessions.fail(session_id, err.to_string());
+            return Err(err);
+        }
+    };
+
+    let body = if coalesce {
+        state
+            .sessions
+            .set_status(session_id, TranscodeStatus::Processing);
+
+        // Одинаковые одновременные запросы получают один буферизованный
+        // результат. Permit и загрузку источника забирает только задача
+        // первого запроса; присоединившийся освобождает свой permit сразу.
+        // Отключившийся клиент оставляет сессию `Cancelled`, задача
+        // доводится до конца и освобождает permit сама
+        let _cancel_on_drop = CancelOnDrop {
+            sessions: &state.sessions,
+            session_id,
+        };
+        let ffmpeg_path = state.config.ffmpeg_path.clone();
+        let timeout = state.config.transcode_timeout();
+        let max_output_bytes = state.config.max_output_bytes;
+        let fetcher = fetcher.cloned();
+        let output = state
+            .coalescer
+            .run(profile.coalescing_key(), move || async move {
+                let _permit = permit;
+                let input = match fetcher {
+                    Some(fetcher) => Some(fetcher.fetch(&profile.source_url, None).await?),
+                    None => None,
+                };
+                ffmpeg::transcode_to_bytes_within(
+                    &ffmpeg_path,
+                    profile,
+                    input,
+                    max_output_bytes,
+                    timeout,
+                )
+                .await
+            })
+            .await;
+        TRANSCODE_DURATION_SECONDS.observe(started_at.elapsed().as_secs_f64());
+
+        match output {
+            Ok(output) => {
+                breaker_pass.success();
+                state.sessions.add_bytes(session_id, output.len() as u64);
+                TRANSCODE_BYTES_TOTAL.inc_by(output.len() as u64);
+                state
+                    .sessions
+                    .set_status(session_id, TranscodeStatus::Completed);
+                Body::from(output)
+            }
+            Err(err) => {
+                // Ошибка источника не считается сбоем FFmpeg для breaker'а
+                if !err.is_source_error() {
+                    breaker_pass.failure();
+                }
+                state.sessions.fail(session_id, err.message());
+                return Err(err.into());
+            }
+        }
+    } else {
+        // Ошибка источника не считается сбоем FFmpeg для breaker'а
+        let source_headers = request.source_headers.as_ref();
+        let input = match (input, fetcher) {
+            (Input::Upload(body), _) => Some(body),
+            (_, Some(fetcher)) => match fetcher.fetch(&profile.source_url, source_headers).await {
+                Ok(input) => Some(input),
+                Err(err) => {
+                    warn!(error = %err, "Source fetch failed");
+                    state.sessions.fail(session_id, err.to_string());
+                    return Err(err);
+                }
+            },
+            _ => None,
+        };
+
+        let ffmpeg_path = &state.config.ffmpeg_path;
+        let spawned = match input {
+            Some(input) => FfmpegProcess::spawn_with_input(ffmpeg_path, profile, input).await,
+            None => FfmpegProcess::spawn_with_binary(ffmpeg_path, profile).await,
+        };
+        let process = match spawned {
+            Ok(process) => process,
+            Err(err) => {
+                breaker_pass.failure();
+                state.sessions.fail(session_id, err.to_string());
+                return Err(err);
+            }
+        };
+        state
+            .sessions
+            .set_status(session_id, TranscodeStatus::Processing);
+        info!("FFmpeg spawned, streaming output");
+        // Дальше итог сообщает поток (`TranscodeStream`)
+        breaker_pass.hand_off();
+
+        // Permit живёт в потоке до завершения FFmpeg или отключения клиента
+        let failure_marker = request.failure_marker.unwrap_or(false);
+        let stream = TranscodeStream::new(
+            process,
+            permit,
+            state.sessions.clone(),
+            state.breaker.clone(),
+            session_id,
+            started_at,
+        );
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                state.sessions.fail(session_id, err.to_string());
+                return Err(err);
+            }
+        };
+        let mut stream = stream
+            .with_idle_timeout(state.config.transcode_timeout())
+            .with_max_output_bytes(state.config.max_output_bytes);
+
+        // Заголовки уходят вместе с первым chunk'ом: пока статус не отправлен,
+        // зависший источник можно вернуть клиенту как 504, первый chunk сверх
+        // `MAX_OUTPUT_BYTES` - как 413, а сбой FFmpeg - ошибкой со статусом.
+        // Маркер включается после: он нужен только для сбоя посреди уже
+        // начатого body
+        let first = stream.next().await;
+        match &first {
+            Some(Err(err)) if err.kind() == io::ErrorKind::TimedOut => {
+                warn!(error = %err, "FFmpeg timed out before first byte");
+                return Err(AppError::Timeout(err.to_string()));
+            }
+            Some(Err(err)) => {
+                if let Some(failure) = stream.failure().await {
+                    warn!(error = %err, "FFmpeg output failed before first byte");
+                    return Err(failure);
+                }
+            }
+            None => {
+                if let Some(err) = stream.failure().await {
+                    warn!(error = %err, "FFmpeg failed before first byte");
+                    return Err(err);
+                }
+            }
+            _ => {}
+        }
+        let stream = stream.with_failure_marker(failure_marker);
+        Body::from_stream(futures::stream::iter(first).chain(stream))
+    };
+
+    Ok((headers, body))
+}
+
+/// Проверяет длительность источников по `MAX_INPUT_DURATION` через ffprobe
+///
+/// Источник без длительности (live) отклоняется. Один источник проверяется
+/// полным ffprobe: его `SourceInfo` возвращается, чтобы fade out и clamping
+/// не запускали ffprobe повторно.
+async fn check_input_duration(
+    state: &AppState,
+    request: &TranscodeRequest,
+    limit: u64,
+) -> AppResult<Option<SourceInfo>> {
+    let unknown = || {
+        AppError::Validation(format!(
+            "Source duration is unknown, sources are limited to {} seconds",
+            limit
+        ))
+    };
+
+    let (total, info) = if request.source_urls.is_empty() {
+        let info =
+            probe::probe_source_with_binary(&state.config.ffprobe_path, &request.source_url)
+                .await?;
+        let duration = info
+            .duration
+            .filter(|duration| duration.is_finite() && *duration > 0.0)
+            .ok_or_else(unknown)?;
+        (duration, Some(info))
+    } else {
+        let mut total = 0.0;
+        for source in &request.source_urls {
+            total += probe::probe_duration_with_binary(&state.config.ffprobe_path, source)
+                .await
+                .map_err(|err| match err {
+                    AppError::Validation(_) => unknown(),
+                    other => other,
+                })?;
+        }
+        (total, None)
+    };
+
+    if total > limit as f64 {
+        warn!(duration = total, limit, "Source is longer than MAX_INPUT_DURATION");
+        return Err(AppError::Validation(format!(
+            "Source duration {:.0} seconds exceeds the limit of {} seconds",
+            total, limit
+        )));
+    }
+
+    Ok(info)
+}
+
+/// s3:// и gs:// -> presigned https URL, который FFmpeg прочитает сам
+pub(super) fn resolve_sources(state: &AppState, request: &mut TranscodeRequest) -> AppResult<()> {
+    let presigner = state.presigner.as_ref();
+    let allowlist = &state.config.source_host_allowlist;
+    if request.source_urls.is_empty() {
+        request.source_url = cloud::resolve_source_url(&request.source_url, presigner, allowlist)?;
+    } else {
+        request.source_urls = request
+            .source_urls
+            .iter()
+            .map(|url| cloud::resolve_source_url(url, presigner, allowlist))
+            .collect::<AppResult<_>>()?;
+    }
+    Ok(())
+}
+
+/// Отменяет сессию, если handler сброшен до итогового статуса (клиент
+/// отключился); конечный статус не перезаписывается
+struct CancelOnDrop<'a> {
+    sessions: &'a SessionRegistry,
+    session_id: Uuid,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        self.sessions
+            .set_status(self.session_id, TranscodeStatus::Cancelled);
+    }
+}
+
+/// Адреса хостов всех входов - до того, как их откроют ffprobe и FFmpeg
+///
+/// Без `FETCH_SOURCE` FFmpeg читает источники сам, а проверки длительности,
+/// fade out и проходы громкости - всегда, мимо загрузчика и его
+/// `PublicResolver`.
+pub(super) async fn check_source_hosts(
+    state: &AppState,
+    request: &TranscodeRequest,
+) -> AppResult<()> {
+    let sources = if request.source_urls.is_empty() {
+        std::slice::from_ref(&request.source_url)
+    } else {
+        request.source_urls.as_slice()
+    };
+    let inputs = sources
+        .iter()
+        .chain(request.preroll_url.as_ref())
+        .chain(request.background.as_ref().map(|background| &background.url));
+
+    for url in inputs {
+        fetch::check_source_host(url, &state.config.source_host_allowlist).await?;
+    }
+    Ok(())
+}
+
+/// Проверки запроса без обращения к источнику и FFmpeg: параметры,
+/// `source_url` (SSRF, allowlist), кодек в контейнере и формат сэмплов
+///
+/// `allow_partial_on_cancel` - только для HLS: поток отдаётся клиенту по
+/// мере кодирования, сохранять после отмены нечего.
+pub(super) fn validate_request(state: &AppState, request: &TranscodeRequest) -> AppResult<()> {
+    if request.allow_partial_on_cancel == Some(true) {
+        return Err(AppError::Validation(
+            "allow_partial_on_cancel is only supported for HLS transcodes".to_string(),
+        ));
+    }
+    validate_params(state, request)?;
+    request
+        .check_codec_format()
+        .map_err(AppError::UnsupportedFormat)
+}
+
+/// `validate_request` без проверки контейнера: тип сегментов HLS выбирается
+/// по кодеку, `format` там не используется
+pub(super) fn validate_params(state: &AppState, request: &TranscodeRequest) -> AppResult<()> {
+    request
+        .validate_with_allowlist(&state.config.source_host_allowlist)
+        .map_err(AppError::Validation)?;
+    request
+        .check_sample_format()
+        .map_err(AppError::UnsupportedFormat)
+}
+
+/// Профиль из запроса с серверными ограничениями
+///
+/// Лимит длительности и авто-моно; параметры, требующие ffprobe или
+/// отдельного прохода FFmpeg (fade out, громкость), добавляются позже.
+pub(super) fn base_profile(
+    state: &AppState,
+    request_headers: &HeaderMap,
+    request: &TranscodeRequest,
+    input: &Input,
+) -> TranscodeProfile {
+    // Лимит длительности: выше серверного значения - только для ключей со scope
+    let can_override =
+        auth::caller_has_scope(&state.config, request_headers, ApiKeyScope::DurationOverride);
+    let max_duration = state
+        .config
+        .resolve_max_duration(request.max_duration_override, can_override);
+
+    let mut profile = TranscodeProfile::from_request(request).with_max_duration(max_duration);
+
+    // Низкий битрейт: моно, если стерео не запрошено явно
+    if request.channels.is_none() {
+        let channels = profile.channels;
+        profile = profile.downmix_at_or_below(state.config.auto_mono_below_kbps);
+        if profile.channels != channels {
+            info!(
+                bitrate = profile.bitrate,
+                threshold = ?state.config.auto_mono_below_kbps,
+                "Low bitrate, output coerced to mono"
             );
         }
     }
 
-    // Permit будет освобождён при drop
-    drop(permit);
+    match input {
+        Input::Request => profile,
+        Input::Signal(signal) => profile.with_test_signal(signal),
+        Input::Upload(_) => TranscodeProfile {
+            source_url: fetch::PIPE_INPUT.to_string(),
+            ..profile
+        },
+    }
+}
+
+/// POST /api/v1/validate
+///
+/// Проверяет `TranscodeRequest` теми же правилами, что и `/transcode`.
+/// Permit не занимается, FFmpeg и источник не используются.
+pub async fn validate_handler(
+    State(state): State<Arc<AppState>>,
+    TimedJson(request): TimedJson<TranscodeRequest>,
+) -> AppResult<Json<ValidateResponse>> {
+    validate_request(&state, &request)?;
+
+    Ok(Json(ValidateResponse { valid: true }))
+}
+
+/// GET /api/v1/transcode/:session_id
+///
+/// Возвращает текущий статус сессии транскодирования; у задачи в очереди -
+/// ещё и позицию (`queue_position`).
+pub async fn status_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<Uuid>,
+) -> AppResult<Json<TranscodeStatusResponse>> {
+    let session = state
+        .sessions
+        .get(session_id)
+        .ok_or(AppError::SessionNotFound(session_id))?;
+
+    let mut response = session.to_response(session_id);
+    if response.status == TranscodeStatus::Queued {
+        response.queue_position = state.queue.position(session_id);
+    }
+    Ok(Json(response))
+}
+
+/// DELETE /api/v1/transcode/:session_id
+///
+/// Отменяет выполняющуюся сессию: процесс FFmpeg убивается, response body
+/// обрывается, статус - `Cancelled`. 404 - сессия неизвестна, 409 - уже
+/// завершена. Объединённое транскодирование (`enable_coalescing`) общее для
+/// нескольких клиентов и продолжает работу, отменяется только статус.
+/// У HLS сессии удаляются плейлист и сегменты, в том числе у завершённой;
+/// с `allow_partial_on_cancel` записанные сегменты остаются (`partial`).
+pub async fn cancel_handler(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    let keep_partial = state
+        .sessions
+        .get(session_id)
+        .is_some_and(|session| session.allow_partial);
+    let cancelled = state.sessions.cancel(session_id);
+    // Частичный вывод закрывает `finish_hls` после остановки FFmpeg
+    let removed_hls = if cancelled.is_ok() && keep_partial {
+        false
+    } else {
+        state.hls.remove(session_id).await
+    };
+
+    match cancelled {
+        Ok(()) => info!(session_id = %session_id, "Transcode session cancelled"),
+        Err(AppError::SessionFinished(_)) if removed_hls => {
+            info!(session_id = %session_id, "HLS output removed")
+        }
+        Err(err) => return Err(err),
+    }
 
-    Ok((headers, Json(response)))
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::{
-        body::Body,
-        http::{Request, StatusCode},
-    };
+    use axum::http::Request;
     use tower::ServiceExt;
 
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+
+    fn state_with_ffmpeg(script: &str, enable_coalescing: bool) -> Arc<AppState> {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(script),
+            enable_coalescing,
+            ..Config::default()
+        };
+        Arc::new(AppState::with_config(10, config).unwrap())
+    }
+
     fn create_test_state() -> Arc<AppState> {
-        Arc::new(AppState::new(10))
+        state_with_ffmpeg("printf 'fake-audio'", false)
+    }
+
+    fn transcode_request(body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn body_bytes(response: axum::response::Response) -> Vec<u8> {
+        http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes()
+            .to_vec()
     }
 
     #[tokio::test]
@@ -163,4 +961,1447 @@ mod tests {
         // Should return 400 Bad Request
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
+
+    #[tokio::test]
+    async fn test_unsupported_sample_fmt_returns_400() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "mp3", "codec": "libmp3lame", "sample_fmt": "s32"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "UNSUPPORTED_FORMAT");
+    }
+
+    #[tokio::test]
+    async fn test_codec_container_mismatch_returns_400_without_ffmpeg() {
+        let state = create_test_state();
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "mp3"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "UNSUPPORTED_FORMAT");
+        assert!(state.sessions.is_empty());
+        assert_eq!(state.breaker.state(), crate::transcoder::BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_lossless_with_lossy_codec_returns_warning_header() {
+        let app = routes().with_state(create_test_state());
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"source_url": "https://example.com/audio.mp3", "quality": "lossless"}"#,
+            ))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let warning = response.headers().get("X-Transcode-Warning").unwrap();
+        assert!(warning.to_str().unwrap().contains("256 kbps"));
+    }
+
+    #[tokio::test]
+    async fn test_target_loudness_without_normalize_returns_warning_header() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "target_loudness": -23.0}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["X-Transcode-Warning"],
+            "target_loudness ignored because normalize=false"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapped_sample_rate_returns_adjusted_header() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .clone()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "mp3", "codec": "libmp3lame", "sample_rate": 96000}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Sample-Rate-Adjusted"], "96000->48000");
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "opus", "sample_rate": 48000}"#,
+            ))
+            .await
+            .unwrap();
+        assert!(response.headers().get("X-Sample-Rate-Adjusted").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sample_rate_clamped_to_source_unless_upsample_allowed() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(r#"printf '%s ' "$@""#),
+            ffprobe_path: fake_ffmpeg(
+                r#"echo '{"streams": [{"codec_name": "opus", "sample_rate": "24000", "channels": 1}], "format": {}}'"#,
+            ),
+            ..Config::default()
+        };
+        let app = routes().with_state(Arc::new(AppState::with_config(10, config).unwrap()));
+
+        let response = app
+            .clone()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/voice.ogg", "sample_rate": 48000}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Sample-Rate-Adjusted"], "48000->24000");
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(args.contains("-ar 24000"), "{}", args);
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/voice.ogg", "sample_rate": 48000, "allow_upsample": true}"#,
+            ))
+            .await
+            .unwrap();
+        assert!(response.headers().get("X-Sample-Rate-Adjusted").is_none());
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(args.contains("-ar 48000"), "{}", args);
+    }
+
+    #[tokio::test]
+    async fn test_opus_with_unsupported_sample_rate_is_rejected() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "opus", "sample_rate": 44100}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_lossless_flac_has_no_warning_header() {
+        let app = routes().with_state(create_test_state());
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "flac", "codec": "flac", "quality": "lossless"}"#,
+            ))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("X-Transcode-Warning").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_content_type_override_keeps_format() {
+        let app = routes().with_state(create_test_state());
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "opus", "content_type_override": "application/octet-stream"}"#,
+            ))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Source-Format"], "opus");
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/octet-stream"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_body_is_ffmpeg_output() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "opus"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "audio/ogg");
+        assert!(response.headers().get("X-Transcode-Id").is_some());
+        assert_eq!(body_bytes(response).await, b"fake-audio");
+    }
+
+    #[tokio::test]
+    async fn test_content_disposition_matches_format() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .clone()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "flac", "codec": "flac"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            format!("attachment; filename=\"{}.flac\"", session_id(&response))
+        );
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "mp3", "codec": "libmp3lame", "filename": "../episode-12"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"episode-12.mp3\""
+        );
+    }
+
+    /// Fake FFmpeg печатает свои аргументы, политика моно - от 32 kbps
+    fn auto_mono_app() -> Router {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(r#"printf '%s ' "$@""#),
+            auto_mono_below_kbps: Some(32),
+            ..Config::default()
+        };
+        routes().with_state(Arc::new(AppState::with_config(10, config).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_low_bitrate_opus_is_coerced_to_mono() {
+        let response = auto_mono_app()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "opus", "codec": "libopus", "bitrate": 24}"#,
+            ))
+            .await
+            .unwrap();
+
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(args.contains("-ac 1 "), "{}", args);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_stereo_is_not_coerced() {
+        let response = auto_mono_app()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "opus", "codec": "libopus", "bitrate": 24, "channels": 2}"#,
+            ))
+            .await
+            .unwrap();
+
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(args.contains("-ac 2 "), "{}", args);
+    }
+
+    fn session_id(response: &axum::response::Response) -> Uuid {
+        response.headers()["X-Transcode-Id"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    /// Ждёт, пока сессия перейдёт в конечный статус
+    async fn wait_finished(state: &AppState, session_id: Uuid) -> TranscodeStatusResponse {
+        for _ in 0..200 {
+            let session = state.sessions.get(session_id).unwrap();
+            if session.is_finished() {
+                return session.to_response(session_id);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("session {} did not finish", session_id);
+    }
+
+    #[tokio::test]
+    async fn test_permit_held_until_stream_finishes() {
+        let state = create_test_state();
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let id = session_id(&response);
+
+        // Handler вернул ответ, но поток ещё не отдан
+        assert_eq!(state.transcode_semaphore.available_permits(), 9);
+
+        body_bytes(response).await;
+        wait_finished(&state, id).await;
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_lossless_transcode_holds_more_permits() {
+        let state = create_test_state();
+        let app = routes().with_state(state.clone());
+
+        let low = app
+            .clone()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "quality": "low"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(state.transcode_semaphore.available_permits(), 9);
+
+        let lossless = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "format": "flac", "codec": "flac", "quality": "lossless"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(state.transcode_semaphore.available_permits(), 5);
+
+        for response in [low, lossless] {
+            let id = session_id(&response);
+            body_bytes(response).await;
+            wait_finished(&state, id).await;
+        }
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_status_endpoint_tracks_session() {
+        let state = create_test_state();
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .clone()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let id = session_id(&response);
+        // Первый chunk уже получен вместе с заголовками
+        assert_eq!(
+            state.sessions.get(id).unwrap().status,
+            TranscodeStatus::Streaming
+        );
+
+        body_bytes(response).await;
+        wait_finished(&state, id).await;
+
+        let request = Request::builder()
+            .uri(format!("/transcode/{}", id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["session_id"], id.to_string());
+        assert_eq!(json["status"], "completed");
+        assert_eq!(json["bytes_transferred"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_ffmpeg_failure() {
+        let state = state_with_ffmpeg("printf 'part'; echo 'Conversion failed!' >&2; exit 1", false);
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let id = session_id(&response);
+        body_bytes(response).await;
+
+        let status = wait_finished(&state, id).await;
+        assert_eq!(status.status, TranscodeStatus::Failed);
+        assert!(status.error.unwrap().ends_with("Conversion failed!"));
+        assert_eq!(status.bytes_transferred, 4);
+    }
+
+    #[tokio::test]
+    async fn test_status_unknown_session_returns_404() {
+        let app = routes().with_state(create_test_state());
+
+        let request = Request::builder()
+            .uri(format!("/transcode/{}", Uuid::new_v4()))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "SESSION_NOT_FOUND");
+    }
+
+    fn delete_request(session_id: Uuid) -> Request<Body> {
+        Request::builder()
+            .method("DELETE")
+            .uri(format!("/transcode/{}", session_id))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cancel_unknown_session_returns_404() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app.oneshot(delete_request(Uuid::new_v4())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_finished_session_returns_409() {
+        let state = create_test_state();
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .clone()
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let id = session_id(&response);
+        body_bytes(response).await;
+        wait_finished(&state, id).await;
+
+        let response = app.oneshot(delete_request(id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "SESSION_ALREADY_FINISHED");
+    }
+
+    #[tokio::test]
+    async fn test_failure_marker_ends_body_on_ffmpeg_failure() {
+        let state = state_with_ffmpeg(
+            "printf 'fake-audio'\necho 'Error while decoding stream' >&2\nexit 1",
+            false,
+        );
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "failure_marker": true}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let id = session_id(&response);
+
+        let body = String::from_utf8(body_bytes(response).await).unwrap();
+        let (audio, marker) = body.split_once(crate::transcoder::stream::FAILURE_MARKER).unwrap();
+        assert_eq!(audio, "fake-audio");
+        assert!(marker.contains("Error while decoding stream"), "{}", marker);
+        assert!(marker.ends_with('\n'));
+
+        let status = wait_finished(&state, id).await;
+        assert_eq!(status.status, TranscodeStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_failure_marker_absent_on_success() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "failure_marker": true}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(body_bytes(response).await, b"fake-audio");
+    }
+
+    fn fade_out_app(ffprobe_script: &str) -> Router {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(r#"printf '%s ' "$@""#),
+            ffprobe_path: fake_ffmpeg(ffprobe_script),
+            ..Config::default()
+        };
+        routes().with_state(Arc::new(AppState::with_config(10, config).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_fade_out_starts_before_probed_end() {
+        let response = fade_out_app("echo 30.000000")
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "fade_out": 4.0}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(args.contains("afade=t=out:st=26.00:d=4.00"), "{}", args);
+    }
+
+    #[tokio::test]
+    async fn test_volume_envelope_past_probed_end_returns_400() {
+        let response = fade_out_app("echo 30.000000")
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "fade_out": 4.0,
+                    "audio_filters": {"volume_envelope": [
+                        {"time": 0.0, "gain_db": 0.0}, {"time": 45.0, "gain_db": -12.0}
+                    ]}}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+        assert!(
+            json["message"].as_str().unwrap().contains("volume_envelope"),
+            "{}",
+            json
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fade_out_with_unknown_duration_returns_400() {
+        let response = fade_out_app("echo N/A")
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/radio", "fade_out": 4.0}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+        assert!(json["message"].as_str().unwrap().contains("seekable"));
+    }
+
+    #[tokio::test]
+    async fn test_output_over_limit_kills_ffmpeg() {
+        // Бесконечный источник: FFmpeg пишет нули, пока его не убьют
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("exec cat /dev/zero"),
+            max_output_bytes: Some(256 * 1024),
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let id = session_id(&response);
+
+        let body = http_body_util::BodyExt::collect(response.into_body()).await;
+        assert!(body.is_err(), "stream must be terminated");
+
+        let status = wait_finished(&state, id).await;
+        assert_eq!(status.status, TranscodeStatus::Failed);
+        assert!(status.error.unwrap().contains("limit of 262144 bytes"));
+        assert!(status.bytes_transferred <= 256 * 1024);
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_first_chunk_over_output_limit_returns_413() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("printf 'more-than-sixteen-bytes'"),
+            max_output_bytes: Some(16),
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "failure_marker": true}"#,
+            ))
+            .await
+            .unwrap();
+
+        // Статус ещё не отправлен: вместо 200 с маркером - ошибка
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "PAYLOAD_TOO_LARGE");
+        assert!(json["message"].as_str().unwrap().contains("limit of 16 bytes"));
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    fn duration_limited_app(ffprobe_script: &str) -> Router {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(r#"printf '%s ' "$@""#),
+            ffprobe_path: fake_ffmpeg(ffprobe_script),
+            max_input_duration_secs: Some(3600),
+            ..Config::default()
+        };
+        routes().with_state(Arc::new(AppState::with_config(10, config).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_source_over_input_duration_limit_returns_400() {
+        for (ffprobe, message) in [
+            (
+                r#"echo '{"streams": [{"codec_name": "mp3"}], "format": {"duration": "7200.000000"}}'"#,
+                "exceeds the limit of 3600 seconds",
+            ),
+            (
+                r#"echo '{"streams": [{"codec_name": "mp3"}], "format": {"duration": "N/A"}}'"#,
+                "duration is unknown",
+            ),
+        ] {
+            let response = duration_limited_app(ffprobe)
+                .oneshot(transcode_request(
+                    r#"{"source_url": "https://example.com/audio.mp3"}"#,
+                ))
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let json: serde_json::Value =
+                serde_json::from_slice(&body_bytes(response).await).unwrap();
+            assert!(json["message"].as_str().unwrap().contains(message), "{}", json);
+        }
+
+        // ffprobe запускается один раз: длительность, fade out и clamping
+        // берутся из одного результата
+        let runs = tempfile::NamedTempFile::new().unwrap();
+        let script = format!(
+            r#"echo run >> {}
+echo '{{"streams": [{{"codec_name": "mp3", "sample_rate": "24000", "channels": 1}}], "format": {{"duration": "1800.000000"}}}}'"#,
+            runs.path().display()
+        );
+        let response = duration_limited_app(&script)
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "fade_out": 4.0, "sample_rate": 48000}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(args.contains("afade=t=out:st=1796.00"), "{}", args);
+        assert!(args.contains("-ar 24000"), "{}", args);
+        assert_eq!(std::fs::read_to_string(runs.path()).unwrap(), "run\n");
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_trips_and_recovers() {
+        use crate::transcoder::breaker::BreakerSettings;
+        use std::time::Duration;
+
+        // FFmpeg падает, пока существует файл-флаг
+        let flag = tempfile::NamedTempFile::new().unwrap();
+        let script = format!(
+            "if [ -e {} ]; then exit 1; fi\nprintf 'fake-audio'",
+            flag.path().display()
+        );
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(&script),
+            breaker: BreakerSettings {
+                failure_threshold: 2,
+                window: Duration::from_secs(60),
+                cooldown: Duration::from_millis(200),
+            },
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let app = routes().with_state(state.clone());
+        let request = || transcode_request(r#"{"source_url": "https://example.com/audio.mp3"}"#);
+
+        for _ in 0..2 {
+            let response = app.clone().oneshot(request()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            body_bytes(response).await;
+        }
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "SERVICE_DEGRADED");
+
+        // После cooldown пробный запрос проходит и замыкает breaker
+        flag.close().unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let id = session_id(&response);
+        assert_eq!(body_bytes(response).await, b"fake-audio");
+        assert_eq!(wait_finished(&state, id).await.status, TranscodeStatus::Completed);
+        assert_eq!(state.breaker.state(), crate::transcoder::BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_failure_returns_500() {
+        let config = Config {
+            ffmpeg_path: "/nonexistent/ffmpeg".to_string(),
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_identical_concurrent_requests_spawn_ffmpeg_once() {
+        let spawns = tempfile::NamedTempFile::new().unwrap();
+        let script = format!(
+            "echo spawn >> {}; sleep 0.5; printf 'coalesced'",
+            spawns.path().display()
+        );
+        let state = state_with_ffmpeg(&script, true);
+        let app = routes().with_state(state.clone());
+        let body = r#"{"source_url": "https://example.com/audio.mp3", "bitrate": 64}"#;
+
+        let permits = async {
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+            // Слот занимает только первый запрос
+            state.transcode_semaphore.available_permits()
+        };
+        let (first, second, permits) = tokio::join!(
+            app.clone().oneshot(transcode_request(body)),
+            app.clone().oneshot(transcode_request(body)),
+            permits,
+        );
+
+        assert_eq!(permits, 9);
+        for response in [first.unwrap(), second.unwrap()] {
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(body_bytes(response).await, b"coalesced");
+        }
+        assert_eq!(std::fs::read_to_string(spawns.path()).unwrap(), "spawn\n");
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_output_over_limit_returns_413() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("printf '0123456789'"),
+            enable_coalescing: true,
+            max_output_bytes: Some(4),
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+
+        let response = routes()
+            .with_state(state.clone())
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(state.sessions.len(), 1);
+        assert_eq!(state.sessions.active_count(), 0);
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_request_returns_buffered_output() {
+        let state = state_with_ffmpeg("printf 'coalesced'", true);
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let id = session_id(&response);
+        assert_eq!(body_bytes(response).await, b"coalesced");
+        assert_eq!(state.coalescer.in_flight(), 0);
+        assert_eq!(
+            state.sessions.get(id).unwrap().status,
+            TranscodeStatus::Completed
+        );
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_measure_loudness_reports_values_without_touching_audio() {
+        // Проход измерения пишет JSON loudnorm в stderr, транскодирование - свои аргументы
+        let state = state_with_ffmpeg(
+            r#"case "$*" in
+  *print_format=json*)
+    printf '[Parsed_loudnorm_0 @ 0x1] \n{\n"input_i" : "-23.96",\n"input_tp" : "-5.04",\n"input_lra" : "7.60"\n}\n' >&2 ;;
+  *) printf '%s ' "$@" ;;
+esac"#,
+            false,
+        );
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "measure_loudness": true}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Loudness-Integrated"], "-23.96");
+        assert_eq!(response.headers()["X-Loudness-Range"], "7.60");
+        assert_eq!(response.headers()["X-Loudness-True-Peak"], "-5.04");
+
+        let id = session_id(&response);
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(!args.contains("loudnorm"), "{}", args);
+
+        let status = wait_finished(&state, id).await;
+        assert_eq!(status.status, TranscodeStatus::Completed);
+        let loudness = status.loudness.unwrap();
+        assert_eq!(loudness.integrated_lufs, -23.96);
+        assert_eq!(loudness.loudness_range, 7.6);
+        assert_eq!(loudness.true_peak, -5.04);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_loudness_pass_returns_504_and_frees_slot() {
+        for body in [
+            r#"{"source_url": "https://example.com/audio.mp3", "measure_loudness": true}"#,
+            r#"{"source_url": "https://example.com/audio.mp3", "normalize": true, "normalize_mode": "linear"}"#,
+        ] {
+            let config = Config {
+                ffmpeg_path: fake_ffmpeg("exec sleep 30"),
+                transcode_timeout_secs: 1,
+                ..Config::default()
+            };
+            let state = Arc::new(AppState::with_config(10, config).unwrap());
+            let app = routes().with_state(state.clone());
+
+            let response = app.oneshot(transcode_request(body)).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT, "{}", body);
+            assert_eq!(state.transcode_semaphore.available_permits(), 10);
+            assert_eq!(state.sessions.active_count(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_pass_normalization_applies_measured_values() {
+        // Второй проход печатает громкость выхода после аудио
+        let state = state_with_ffmpeg(
+            r#"case "$*" in
+  *measured_I*)
+    printf '%s ' "$@"
+    printf '[Parsed_loudnorm_0 @ 0x1] \n{\n"input_i" : "-27.61",\n"input_tp" : "-4.47",\n"input_lra" : "18.06",\n"input_thresh" : "-39.20",\n"output_i" : "-16.02",\n"output_tp" : "-1.50",\n"output_lra" : "11.20",\n"output_thresh" : "-27.71",\n"normalization_type" : "dynamic",\n"target_offset" : "0.58"\n}\n' >&2 ;;
+  *print_format=json*)
+    printf '[Parsed_loudnorm_0 @ 0x1] \n{\n"input_i" : "-27.61",\n"input_tp" : "-4.47",\n"input_lra" : "18.06",\n"input_thresh" : "-39.20",\n"target_offset" : "0.58"\n}\n' >&2 ;;
+  *) printf '%s ' "$@" ;;
+esac"#,
+            false,
+        );
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "normalize": true, "normalize_mode": "dynamic"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let id = session_id(&response);
+        let args = String::from_utf8(body_bytes(response).await).unwrap();
+        assert!(args.contains("measured_I=-27.61"), "{}", args);
+        assert!(args.contains("measured_thresh=-39.20"), "{}", args);
+        assert!(args.contains("offset=0.58:linear=false"), "{}", args);
+
+        let status = wait_finished(&state, id).await;
+        let stats = status.loudness_stats.unwrap();
+        assert_eq!(stats.input_i, -27.61);
+        assert_eq!(stats.target_offset, 0.58);
+        let output = status.output_loudness.unwrap();
+        assert_eq!(output.integrated_lufs, -16.02);
+        assert_eq!(output.true_peak, -1.5);
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["output_loudness"]["loudness_range"], 11.2);
+    }
+
+    #[tokio::test]
+    async fn test_loudness_stats_absent_without_normalization() {
+        let state = state_with_ffmpeg("printf 'fake-audio'", false);
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let id = session_id(&response);
+        body_bytes(response).await;
+
+        let status = wait_finished(&state, id).await;
+        assert_eq!(status.status, TranscodeStatus::Completed);
+        let json = serde_json::to_value(&status).unwrap();
+        assert!(json.get("loudness_stats").is_none(), "{}", json);
+        assert!(json.get("output_loudness").is_none(), "{}", json);
+    }
+
+    #[tokio::test]
+    async fn test_progress_updates_status() {
+        let state = state_with_ffmpeg(
+            r#"printf 'out_time_us=2500000\ntotal_size=4096\nprogress=end\n' >&2
+printf 'fake-audio'"#,
+            false,
+        );
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let id = session_id(&response);
+        body_bytes(response).await;
+
+        let status = wait_finished(&state, id).await;
+        assert_eq!(status.status, TranscodeStatus::Completed);
+        assert_eq!(status.output_time_seconds, Some(2.5));
+        assert_eq!(status.output_size_bytes, Some(4096));
+    }
+
+    #[tokio::test]
+    async fn test_progress_lines_are_not_reported_as_error() {
+        let state = state_with_ffmpeg(
+            r#"printf 'partial'
+printf 'Invalid data found when processing input\nprogress=end\n' >&2
+exit 1"#,
+            false,
+        );
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let id = session_id(&response);
+        body_bytes(response).await;
+
+        let status = wait_finished(&state, id).await;
+        assert_eq!(status.status, TranscodeStatus::Failed);
+        let error = status.error.unwrap();
+        assert!(error.contains("Invalid data found"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_returns_ffmpeg_args_without_spawning() {
+        // FFmpeg падает при запуске: dry run не должен до него дойти
+        let state = state_with_ffmpeg("exit 1", false);
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{
+                    "source_url": "https://example.com/audio.mp3",
+                    "codec": "libmp3lame",
+                    "format": "mp3",
+                    "audio_filters": { "highpass_hz": 80, "volume": 1.5 },
+                    "dry_run": true
+                }"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        assert!(response.headers().get("X-Transcode-Id").is_none());
+
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let args: Vec<&str> = json["ffmpeg_args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|arg| arg.as_str().unwrap())
+            .collect();
+        assert!(args.windows(2).any(|pair| pair == ["-c:a", "libmp3lame"]), "{:?}", args);
+
+        let chain = json["filter_chain"].as_str().unwrap();
+        assert!(chain.contains("highpass=f=80"), "{}", chain);
+        assert!(chain.contains("volume=3.5dB"), "{}", chain);
+        assert!(args.windows(2).any(|pair| pair == ["-af", chain]));
+
+        assert!(state.sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_named_profile_and_unknown_profile() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .clone()
+            .oneshot(transcode_request(
+                r#"{
+                    "source_url": "https://example.com/audio.mp3",
+                    "profile": "telegram_voice",
+                    "dry_run": true
+                }"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        let args = json["ffmpeg_args"].as_array().unwrap();
+        assert!(args.windows(2).any(|pair| pair == ["-b:a", "64k"]), "{:?}", args);
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3", "profile": "podcast"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+    }
+
+    /// Состояние с `fetch_source` и fake FFmpeg, копирующим stdin в stdout
+    async fn fetching_state() -> (Arc<AppState>, std::net::SocketAddr) {
+        fetching_state_with(|_| {}).await
+    }
+
+    async fn fetching_state_with(
+        configure: impl FnOnce(&mut Config),
+    ) -> (Arc<AppState>, std::net::SocketAddr) {
+        let source = Router::new()
+            .route("/audio.mp3", get(|| async { "source-bytes" }))
+            .route("/missing.mp3", get(|| async { StatusCode::NOT_FOUND }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, source).await.unwrap() });
+
+        let mut config = Config {
+            ffmpeg_path: fake_ffmpeg("cat"),
+            fetch_source: true,
+            source_host_allowlist: vec!["127.0.0.1".to_string()],
+            ..Config::default()
+        };
+        configure(&mut config);
+        (Arc::new(AppState::with_config(10, config).unwrap()), addr)
+    }
+
+    fn transcode_request_for(source_url: String) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({ "source_url": source_url }).to_string(),
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_source_404_does_not_hold_half_open_probe() {
+        use crate::transcoder::BreakerState;
+
+        let (state, addr) = fetching_state_with(|config| {
+            config.breaker.cooldown = std::time::Duration::ZERO;
+        })
+        .await;
+        for _ in 0..state.config.breaker.failure_threshold {
+            state.breaker.record_failure();
+        }
+        assert_eq!(state.breaker.state(), BreakerState::HalfOpen);
+
+        let response = routes()
+            .with_state(state.clone())
+            .oneshot(transcode_request_for(format!("http://{}/missing.mp3", addr)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Пробный запрос без итога FFmpeg освободил место: следующий проходит
+        let response = routes()
+            .with_state(state.clone())
+            .oneshot(transcode_request_for(format!("http://{}/audio.mp3", addr)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let id = session_id(&response);
+        assert_eq!(body_bytes(response).await, b"source-bytes");
+        assert_eq!(wait_finished(&state, id).await.status, TranscodeStatus::Completed);
+        assert_eq!(state.breaker.state(), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_fetched_source_404_is_source_not_found() {
+        let (state, addr) = fetching_state().await;
+
+        let response = routes()
+            .with_state(state.clone())
+            .oneshot(transcode_request_for(format!("http://{}/missing.mp3", addr)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "SOURCE_NOT_FOUND");
+        assert!(json["message"].as_str().unwrap().contains("HTTP 404"));
+        // Ошибка источника - не сбой FFmpeg
+        assert_eq!(state.breaker.state().to_string(), "closed");
+    }
+
+    #[tokio::test]
+    async fn test_fetched_source_is_piped_to_ffmpeg_stdin() {
+        let (state, addr) = fetching_state().await;
+
+        let response = routes()
+            .with_state(state)
+            .oneshot(transcode_request_for(format!("http://{}/audio.mp3", addr)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&body_bytes(response).await[..], b"source-bytes");
+    }
+
+    #[tokio::test]
+    async fn test_every_input_host_is_resolved_before_ffmpeg() {
+        let state = create_test_state();
+
+        // Имя резолвится в loopback; validate() видит только литералы и
+        // `localhost`, поэтому запрос собирается без валидации
+        for input in [
+            serde_json::json!({ "preroll_url": "http://localhost:8080/jingle.mp3" }),
+            serde_json::json!({ "background": { "url": "http://localhost:8080/bed.mp3" } }),
+            serde_json::json!({ "source_urls": ["https://example.com/1.mp3", "http://localhost/2.mp3"] }),
+        ] {
+            let mut request = serde_json::json!({ "source_url": "https://example.com/a.mp3" });
+            request.as_object_mut().unwrap().extend(input.as_object().unwrap().clone());
+            let request: TranscodeRequest = serde_json::from_value(request).unwrap();
+
+            let err = check_source_hosts(&state, &request).await.unwrap_err();
+            assert!(matches!(err, AppError::Validation(_)), "{:?}", err);
+        }
+
+        let request: TranscodeRequest =
+            serde_json::from_value(serde_json::json!({ "source_url": "https://example.com/a.mp3" }))
+                .unwrap();
+        assert!(check_source_hosts(&state, &request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_source_is_fetched_by_service() {
+        let (state, addr) = fetching_state_with(|config| config.enable_coalescing = true).await;
+
+        let response = routes()
+            .with_state(state.clone())
+            .oneshot(transcode_request_for(format!("http://{}/audio.mp3", addr)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&body_bytes(response).await[..], b"source-bytes");
+
+        // Ошибка загрузки - статус источника, breaker не учитывает её как сбой
+        let response = routes()
+            .with_state(state.clone())
+            .oneshot(transcode_request_for(format!("http://{}/missing.mp3", addr)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(state.breaker.state().to_string(), "closed");
+    }
+
+    #[tokio::test]
+    async fn test_source_headers_require_fetch_mode() {
+        let app = routes().with_state(create_test_state());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/a.mp3", "source_headers": {"Authorization": "Bearer t"}}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert!(json["message"].as_str().unwrap().contains("FETCH_SOURCE"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_still_validates() {
+        let response = routes()
+            .with_state(create_test_state())
+            .oneshot(transcode_request(
+                r#"{"source_url": "file:///etc/passwd", "dry_run": true}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_transcode_rejected_while_shutting_down() {
+        let state = create_test_state();
+        state.begin_shutdown();
+
+        let response = routes()
+            .with_state(state.clone())
+            .oneshot(transcode_request(r#"{"source_url": "https://example.com/a.mp3"}"#))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let json: serde_json::Value =
+            serde_json::from_slice(&body_bytes(response).await).unwrap();
+        assert_eq!(json["code"], "SHUTTING_DOWN");
+        assert!(state.sessions.is_empty());
+    }
+
+    #[test]
+    fn test_set_header_skips_invalid_values() {
+        let mut headers = HeaderMap::new();
+
+        set_header(&mut headers, "X-Audio-Filters", "volume=1.5dB\r\nX-Injected: 1");
+        set_header(&mut headers, "X-Target-Codec", "opus\0");
+        assert!(headers.is_empty());
+
+        set_header(&mut headers, "X-Audio-Filters", "atempo=1.25");
+        assert_eq!(headers["X-Audio-Filters"], "atempo=1.25");
+    }
+
+    async fn validate(body: &'static str) -> (StatusCode, serde_json::Value) {
+        // FFmpeg падает при запуске: validate не должен до него дойти
+        let state = state_with_ffmpeg("exit 1", false);
+        let request = Request::builder()
+            .method("POST")
+            .uri("/validate")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = routes().with_state(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+        assert!(state.sessions.is_empty());
+
+        let status = response.status();
+        (status, serde_json::from_slice(&body_bytes(response).await).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_validate_accepts_valid_request() {
+        let (status, json) = validate(
+            r#"{
+                "source_url": "https://example.com/audio.mp3",
+                "format": "mp3",
+                "codec": "libmp3lame",
+                "audio_filters": { "eq_preset": "bass_boost", "highpass_hz": 80 }
+            }"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json, serde_json::json!({ "valid": true }));
+    }
+
+    #[tokio::test]
+    async fn test_validate_rejects_invalid_requests() {
+        let (status, json) =
+            validate(r#"{"source_url": "https://example.com/a.mp3", "bitrate": 4}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+        assert!(json["message"].as_str().unwrap().contains("bitrate"));
+
+        let (status, json) = validate(r#"{"source_url": "http://10.0.0.1/a.mp3"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+
+        let (status, json) =
+            validate(r#"{"source_url": "https://example.com/a.mp3", "format": "mp3"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "UNSUPPORTED_FORMAT");
+    }
+
+    async fn failing_transcode(
+        script: &str,
+        expose_ffmpeg_stderr: bool,
+    ) -> (StatusCode, serde_json::Value) {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(script),
+            expose_ffmpeg_stderr,
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let response = routes()
+            .with_state(state)
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, serde_json::from_slice(&body_bytes(response).await).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_ffmpeg_failure_exposes_stderr_tail_when_enabled() {
+        let script = "echo 'Unknown encoder libfoo' >&2; exit 1";
+
+        let (status, json) = failing_transcode(script, true).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(json["code"], "FFMPEG_ERROR");
+        assert!(json["details"].as_str().unwrap().contains("Unknown encoder libfoo"));
+
+        let (status, json) = failing_transcode(script, false).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(json.get("details").map_or(true, |d| d.is_null()), "{}", json);
+    }
+
+    #[tokio::test]
+    async fn test_source_fetch_errors_map_to_client_statuses() {
+        let cases = [
+            (
+                "https://example.com/a.mp3: Server returned 404 Not Found",
+                StatusCode::NOT_FOUND,
+                "SOURCE_NOT_FOUND",
+            ),
+            (
+                "/data/missing.mp3: No such file or directory",
+                StatusCode::NOT_FOUND,
+                "SOURCE_NOT_FOUND",
+            ),
+            (
+                "https://example.com/a.mp3: Server returned 403 Forbidden (access denied)",
+                StatusCode::BAD_REQUEST,
+                "SOURCE_UNAVAILABLE",
+            ),
+            (
+                "Connection to tcp://example.com:443 failed: Connection refused",
+                StatusCode::BAD_REQUEST,
+                "SOURCE_UNAVAILABLE",
+            ),
+            (
+                "Error opening input: Invalid data found when processing input",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "FFMPEG_ERROR",
+            ),
+        ];
+
+        for (line, expected_status, expected_code) in cases {
+            let script = format!("echo '{}' >&2; exit 1", line);
+            let (status, json) = failing_transcode(&script, false).await;
+            assert_eq!(status, expected_status, "{}", line);
+            assert_eq!(json["code"], expected_code, "{}", line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_transcode_times_out() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("exec sleep 30"),
+            enable_coalescing: true,
+            transcode_timeout_secs: 1,
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let app = routes().with_state(state.clone());
+
+        let response = app
+            .oneshot(transcode_request(
+                r#"{"source_url": "https://example.com/audio.mp3"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(state.coalescer.in_flight(), 0);
+        assert_eq!(state.transcode_semaphore.available_permits(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_coalesced_requests_release_permit() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("sleep 1\nprintf 'fake-audio'"),
+            enable_coalescing: true,
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+
+        // Оба клиента отключаются, пока FFmpeg ещё работает
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let app = routes().with_state(state.clone());
+                tokio::spawn(app.oneshot(transcode_request(
+                    r#"{"source_url": "https://example.com/audio.mp3"}"#,
+                )))
+            })
+            .collect();
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert_eq!(state.coalescer.in_flight(), 1);
+        for waiter in waiters {
+            waiter.abort();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(state.sessions.active_count(), 0);
+
+        for _ in 0..100 {
+            if state.coalescer.in_flight() == 0 && state.transcode_semaphore.available_permits() == 10
+            {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!(
+            "coalesced work still holds resources: in_flight {}, available permits {}",
+            state.coalescer.in_flight(),
+            state.transcode_semaphore.available_permits()
+        );
+    }
 }
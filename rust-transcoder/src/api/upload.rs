@@ -0,0 +1,321 @@
+//! Upload API endpoint
+//!
+//! POST /api/v1/transcode/upload - транскодирование файла из multipart body,
+//! когда у клиента нет URL (например, бот пересылает голосовое сообщение).
+
+use std::io;
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use futures::TryStreamExt;
+use multer::{Constraints, Multipart, SizeLimit};
+use tracing::{instrument, warn};
+
+use super::transcode::{self, Input};
+use crate::{
+    error::{AppError, AppResult},
+    models::TranscodeRequest,
+    transcoder::PipedSource,
+    AppState,
+};
+
+/// Поле multipart с параметрами транскодирования (JSON `TranscodeRequest`)
+pub const OPTIONS_FIELD: &str = "options";
+
+/// Поле multipart с аудио файлом
+pub const FILE_FIELD: &str = "file";
+
+/// Создаёт routes для upload API
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/transcode/upload", post(upload_handler))
+}
+
+/// POST /api/v1/transcode/upload
+///
+/// Body - `multipart/form-data`: поле `options` (JSON с параметрами как у
+/// `POST /api/v1/transcode`, без полей источника), затем поле `file`. Файл
+/// не буферизуется: он копируется в stdin FFmpeg по мере получения, поэтому
+/// `options` должно идти первым. Ответ и ошибки - как у `POST /api/v1/transcode`.
+#[instrument(skip(state, request_headers, body), fields(session_id, request_id))]
+pub async fn upload_handler(
+    State(state): State<Arc<AppState>>,
+    request_headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let (request, file) = match read_upload(&state, &request_headers, body).await {
+        Ok(upload) => upload,
+        Err(err) => return err.into_response(),
+    };
+
+    transcode::run_transcode(state, request_headers, request, Input::Upload(file)).await
+}
+
+/// Читает `options` и возвращает параметры с ещё не прочитанным файлом
+///
+/// `options` ограничено `max_request_body_bytes` и `body_read_timeout_ms`,
+/// как JSON body `/transcode`; файл - `max_source_bytes`, как источник,
+/// загружаемый сервисом, а без него - `DEFAULT_MAX_UPLOAD_BYTES`.
+async fn read_upload(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: Body,
+) -> AppResult<(TranscodeRequest, PipedSource)> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let boundary = multer::parse_boundary(content_type).map_err(|_| {
+        AppError::Validation("expected a multipart/form-data body with a boundary".to_string())
+    })?;
+
+    let max_options = state.config.max_request_body_bytes as u64;
+    let max_file = state.config.max_upload_bytes();
+
+    // Заявленный размер больше лимита - отказ до запуска FFmpeg
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if let Some(length) = content_length {
+        if length > max_file.saturating_add(max_options) {
+            return Err(AppError::PayloadTooLarge(format!(
+                "upload is {} bytes, the file limit is {}",
+                length, max_file
+            )));
+        }
+    }
+
+    let constraints = Constraints::new()
+        .allowed_fields(vec![OPTIONS_FIELD, FILE_FIELD])
+        .size_limit(SizeLimit::new().for_field(OPTIONS_FIELD, max_options));
+    let mut multipart = Multipart::with_constraints(body.into_data_stream(), boundary, constraints);
+
+    let window = state.config.body_read_timeout();
+    let fields = async {
+        let options = match multipart.next_field().await? {
+            Some(field) if field.name() == Some(OPTIONS_FIELD) => Some(field.bytes().await?),
+            _ => None,
+        };
+        let file = match options {
+            Some(_) => multipart.next_field().await?,
+            None => None,
+        };
+        Ok::<_, multer::Error>((options, file))
+    };
+    let (options, file) = tokio::time::timeout(window, fields)
+        .await
+        .map_err(|_| {
+            warn!(
+                timeout_ms = window.as_millis() as u64,
+                "Upload options read timed out"
+            );
+            AppError::RequestTimeout(format!(
+                "Upload options were not received within {} ms",
+                window.as_millis()
+            ))
+        })?
+        .map_err(multipart_error)?;
+
+    let order_error = || {
+        AppError::Validation(format!(
+            "multipart body must contain a '{}' field followed by a '{}' field",
+            OPTIONS_FIELD, FILE_FIELD
+        ))
+    };
+
+    let options = options.ok_or_else(order_error)?;
+    let request: TranscodeRequest = serde_json::from_slice(&options)
+        .map_err(|err| AppError::Validation(format!("options: {}", err)))?;
+    request.validate_upload().map_err(AppError::Validation)?;
+    request
+        .check_sample_format()
+        .map_err(AppError::UnsupportedFormat)?;
+
+    let file = file
+        .filter(|field| field.name() == Some(FILE_FIELD))
+        .ok_or_else(order_error)?;
+
+    Ok((
+        request,
+        PipedSource::new(file.map_err(io::Error::other), Some(max_file)),
+    ))
+}
+
+/// Ошибка разбора multipart body
+fn multipart_error(err: multer::Error) -> AppError {
+    match err {
+        multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+            AppError::PayloadTooLarge(err.to_string())
+        }
+        other => AppError::Validation(format!("invalid multipart body: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, DEFAULT_MAX_UPLOAD_BYTES};
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "upload-boundary";
+
+    fn multipart_body(parts: &[(&str, &str)]) -> String {
+        let mut body = String::new();
+        for (name, value) in parts {
+            let filename = if *name == FILE_FIELD {
+                "; filename=\"voice.ogg\""
+            } else {
+                ""
+            };
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"{}\r\n\r\n{}\r\n",
+                BOUNDARY, name, filename, value
+            ));
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+        body
+    }
+
+    fn upload_request(parts: &[(&str, &str)]) -> Request<Body> {
+        let body = multipart_body(parts);
+        Request::builder()
+            .method("POST")
+            .uri("/api/v1/transcode/upload")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .header("content-length", body.len())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn app(config: Config) -> axum::Router {
+        crate::build_router(Arc::new(AppState::with_config(10, config).unwrap()))
+    }
+
+    async fn error_code(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["code"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_file_is_streamed_through_ffmpeg() {
+        // fake FFmpeg копирует stdin: в ответе - байты загруженного файла
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("cat"),
+            // Файл больше общего лимита JSON body
+            max_request_body_bytes: 64,
+            ..Config::default()
+        };
+        let file = "voice-note-bytes".repeat(16);
+
+        let response = app(config)
+            .oneshot(upload_request(&[
+                (OPTIONS_FIELD, r#"{"format": "mp3", "codec": "libmp3lame"}"#),
+                (FILE_FIELD, &file),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "audio/mpeg");
+        assert!(response.headers().contains_key("X-Transcode-Id"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, file.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_upload_validation() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("cat"),
+            ..Config::default()
+        };
+        let app = app(config);
+
+        let cases = [
+            // URL источника при загрузке не нужен
+            vec![
+                (
+                    OPTIONS_FIELD,
+                    r#"{"source_url": "https://example.com/a.mp3"}"#,
+                ),
+                (FILE_FIELD, "ogg"),
+            ],
+            vec![(OPTIONS_FIELD, r#"{"bitrate": 4}"#), (FILE_FIELD, "ogg")],
+            vec![(OPTIONS_FIELD, "{not json"), (FILE_FIELD, "ogg")],
+            // Файл должен идти после параметров
+            vec![(FILE_FIELD, "ogg"), (OPTIONS_FIELD, "{}")],
+            vec![(FILE_FIELD, "ogg")],
+            vec![(OPTIONS_FIELD, "{}")],
+            vec![("extra", "x"), (FILE_FIELD, "ogg")],
+        ];
+        for parts in cases {
+            let response = app.clone().oneshot(upload_request(&parts)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{:?}", parts);
+        }
+
+        let not_multipart = Request::builder()
+            .method("POST")
+            .uri("/api/v1/transcode/upload")
+            .header("content-type", "application/json")
+            .body(Body::from("{}"))
+            .unwrap();
+        let response = app.oneshot(not_multipart).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_upload_over_source_limit_returns_413() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("cat"),
+            max_source_bytes: Some(16),
+            max_request_body_bytes: 64,
+            ..Config::default()
+        };
+
+        let response = app(config)
+            .oneshot(upload_request(&[
+                (OPTIONS_FIELD, "{}"),
+                (FILE_FIELD, &"x".repeat(1024)),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error_code(response).await, "PAYLOAD_TOO_LARGE");
+    }
+
+    #[tokio::test]
+    async fn test_upload_is_capped_without_source_limit() {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg("cat"),
+            ..Config::default()
+        };
+        assert_eq!(config.max_upload_bytes(), DEFAULT_MAX_UPLOAD_BYTES);
+
+        let mut request = upload_request(&[(OPTIONS_FIELD, "{}"), (FILE_FIELD, "ogg")]);
+        request.headers_mut().insert(
+            "content-length",
+            (DEFAULT_MAX_UPLOAD_BYTES * 2).to_string().parse().unwrap(),
+        );
+        let response = app(config).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(error_code(response).await, "PAYLOAD_TOO_LARGE");
+    }
+}
@@ -0,0 +1,111 @@
+//! Метаданные сборки
+//!
+//! `GET /version`: версия пакета, git SHA и время сборки (задаются в
+//! `build.rs`), версия rustc и FFmpeg.
+
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+
+use crate::transcoder::ffmpeg;
+use crate::AppState;
+
+/// Ответ `GET /version`
+#[derive(Debug, Serialize)]
+pub struct VersionResponse {
+    /// Версия пакета (`CARGO_PKG_VERSION`)
+    pub version: &'static str,
+    /// Коммит сборки, `unknown` вне git checkout
+    pub git_sha: &'static str,
+    /// Время сборки, RFC 3339 UTC
+    pub build_timestamp: &'static str,
+    /// `rustc --version` компилятора сборки
+    pub rustc_version: &'static str,
+    /// Первая строка `ffmpeg -version`; null, если FFmpeg недоступен
+    pub ffmpeg_version: Option<String>,
+}
+
+/// GET /version - метаданные сборки
+///
+/// Версия FFmpeg берётся из кэша readiness (`AppState::ffmpeg_version`) или
+/// проверяется и кэшируется здесь же.
+pub async fn version_handler(State(state): State<Arc<AppState>>) -> Json<VersionResponse> {
+    let ffmpeg_version = state
+        .ffmpeg_version
+        .get_or_try_init(|| ffmpeg::check_ffmpeg_available_with_binary(&state.config.ffmpeg_path))
+        .await
+        .ok()
+        .cloned();
+
+    Json(VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("GIT_SHA"),
+        build_timestamp: env!("BUILD_TIMESTAMP"),
+        rustc_version: env!("RUSTC_VERSION"),
+        ffmpeg_version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+
+    fn state_with_ffmpeg(ffmpeg_path: String) -> Arc<AppState> {
+        let config = Config {
+            ffmpeg_path,
+            ..Config::default()
+        };
+        Arc::new(AppState::with_config(1, config).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_version_shape() {
+        let state = state_with_ffmpeg(fake_ffmpeg("echo 'ffmpeg version 6.1.1'"));
+
+        let Json(response) = version_handler(State(state)).await;
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["ffmpeg_version"], "ffmpeg version 6.1.1");
+        assert!(json["rustc_version"]
+            .as_str()
+            .unwrap()
+            .starts_with("rustc "));
+        assert!(!json["git_sha"].as_str().unwrap().is_empty());
+
+        let timestamp = json["build_timestamp"].as_str().unwrap();
+        assert_eq!(
+            timestamp.len(),
+            "2024-01-01T00:00:00Z".len(),
+            "{}",
+            timestamp
+        );
+        assert!(timestamp.ends_with('Z'));
+
+        let mut keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "build_timestamp",
+                "ffmpeg_version",
+                "git_sha",
+                "rustc_version",
+                "version"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_version_without_ffmpeg() {
+        let state = state_with_ffmpeg("/nonexistent/ffmpeg".to_string());
+
+        let Json(response) = version_handler(State(state)).await;
+
+        assert_eq!(response.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(response.ffmpeg_version, None);
+    }
+}
@@ -0,0 +1,136 @@
+//! Waveform API endpoint
+//!
+//! POST /api/v1/waveform - PNG огибающей или спектрограммы источника для
+//! веб-интерфейса.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use tracing::{info, instrument};
+
+use super::extract::TimedJson;
+use crate::{
+    error::{AppError, AppResult},
+    models::WaveformRequest,
+    transcoder::{cloud, fetch, permit::TranscodePermit, preview, redact::redact_url},
+    AppState,
+};
+
+/// Создаёт routes для waveform API
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/waveform", post(waveform_handler))
+}
+
+/// POST /api/v1/waveform
+///
+/// Источник проверяется так же, как в `/transcode`; FFmpeg занимает один
+/// permit общего семафора и ограничен `MAX_SOURCE_DURATION` и
+/// `TRANSCODE_TIMEOUT`.
+#[instrument(skip(state, request), fields(source_url = %redact_url(&request.source_url)))]
+pub async fn waveform_handler(
+    State(state): State<Arc<AppState>>,
+    TimedJson(request): TimedJson<WaveformRequest>,
+) -> AppResult<Response> {
+    request
+        .validate_with_allowlist(&state.config.source_host_allowlist)
+        .map_err(AppError::Validation)?;
+    let source_url = cloud::resolve_source_url(
+        &request.source_url,
+        state.presigner.as_ref(),
+        &state.config.source_host_allowlist,
+    )?;
+    fetch::check_source_host(&source_url, &state.config.source_host_allowlist).await?;
+
+    if state.is_shutting_down() {
+        return Err(AppError::ShuttingDown);
+    }
+
+    let _permit =
+        TranscodePermit::acquire_within(&state.transcode_semaphore, 1, state.config.acquire_wait())
+            .await
+            .ok_or(AppError::ConcurrencyLimitExceeded(
+                state.max_concurrent_streams,
+            ))?;
+
+    let args = preview::preview_args(
+        &source_url,
+        request.kind,
+        request.width(),
+        request.height(),
+        state.config.resolve_max_duration(None, false),
+    );
+    let png = preview::render_preview(
+        &state.config.ffmpeg_path,
+        &source_url,
+        &args,
+        state.config.transcode_timeout(),
+    )
+    .await?;
+    info!(kind = ?request.kind, bytes = png.len(), "Preview rendered");
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use crate::config::Config;
+    use crate::transcoder::ffmpeg::testing::fake_ffmpeg;
+
+    async fn waveform(ffmpeg_script: &str, body: &'static str) -> Response {
+        let config = Config {
+            ffmpeg_path: fake_ffmpeg(ffmpeg_script),
+            ..Config::default()
+        };
+        let state = Arc::new(AppState::with_config(10, config).unwrap());
+        let request = Request::builder()
+            .method("POST")
+            .uri("/waveform")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+
+        routes().with_state(state).oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_waveform_returns_png() {
+        let response = waveform(
+            r"printf '\211PNG\r\n\032\n\0\0\0\rIHDR'",
+            r#"{"source_url": "https://example.com/a.mp3", "width": 800, "height": 200}"#,
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"), "{:?}", bytes);
+    }
+
+    #[tokio::test]
+    async fn test_waveform_rejects_oversized_image() {
+        let response = waveform(
+            "exit 0",
+            r#"{"source_url": "https://example.com/a.mp3", "width": 100000}"#,
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["code"], "VALIDATION_ERROR");
+    }
+}
@@ -0,0 +1,973 @@
+//! Конфигурация сервиса
+//!
+//! Настройки, задаваемые оператором через TOML файл (`CONFIG_PATH`) и
+//! переменные окружения.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::transcoder::breaker::BreakerSettings;
+use crate::transcoder::permit::PermitWeights;
+
+/// Лимит загружаемого файла (`/transcode/upload`), когда `MAX_SOURCE_BYTES`
+/// не задан: upload routes не закрыты лимитом body запроса
+pub const DEFAULT_MAX_UPLOAD_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Права, которые могут быть выданы API ключу
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiKeyScope {
+    /// Разрешает поднимать лимит длительности выше серверного значения по умолчанию
+    DurationOverride,
+}
+
+impl ApiKeyScope {
+    /// Разбирает scope из строкового представления
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "duration_override" => Some(ApiKeyScope::DurationOverride),
+            _ => None,
+        }
+    }
+}
+
+/// Ключи доступа к облачному хранилищу (S3 или GCS HMAC)
+#[derive(Clone, PartialEq)]
+pub struct CloudCredentials {
+    /// Access key ID (для GCS - HMAC access ID)
+    pub access_key_id: String,
+    /// Secret access key (для GCS - HMAC secret)
+    pub secret_access_key: String,
+    /// Регион подписи (`us-east-1`; для GCS - `auto`)
+    pub region: String,
+    /// Endpoint хранилища; None - стандартный endpoint AWS для региона
+    pub endpoint: Option<String>,
+}
+
+// Secret не должен попадать в логи вместе с Config
+impl std::fmt::Debug for CloudCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloudCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"***")
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .finish()
+    }
+}
+
+/// Конфигурация приложения
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Лимит длительности обрабатываемого источника в секундах (None = без лимита)
+    ///
+    /// Выход обрезается (`-t`), запрос не отклоняется; ключ со scope
+    /// `DurationOverride` может поднять лимит. Отклонение слишком длинных
+    /// источников - `max_input_duration_secs`.
+    pub max_source_duration_secs: Option<u32>,
+    /// Жёсткий потолок для `max_duration_override` из запроса
+    pub max_duration_ceiling_secs: u32,
+    /// Ключ доступа к `/api/v1/*` (None - API открыт); ключи из
+    /// `api_key_scopes` тоже принимаются
+    pub api_key: Option<String>,
+    /// API ключи и выданные им права
+    pub api_key_scopes: HashMap<String, Vec<ApiKeyScope>>,
+    /// Префикс всех маршрутов (например `/transcoder`), пустая строка - без префикса
+    pub route_prefix: String,
+    /// Объединять одинаковые одновременные запросы в одно транскодирование
+    pub enable_coalescing: bool,
+    /// Окно на получение body запроса целиком, в миллисекундах
+    pub body_read_timeout_ms: u64,
+    /// Запросов на транскодирование в минуту на клиента (API ключ или IP),
+    /// сверх - 429 (None - без ограничения)
+    pub rate_limit_per_minute: Option<u32>,
+    /// Максимальный размер body запроса в байтах (больше - 413)
+    pub max_request_body_bytes: usize,
+    /// Сколько запрос может ждать заголовков ответа, в секундах (больше - 408);
+    /// потоковое body ответа не ограничивает, должен превышать
+    /// `transcode_timeout_secs` с запасом на анализ источника
+    pub request_timeout_secs: u64,
+    /// Хосты, с которых разрешено читать http(s) источники (пусто - любые публичные)
+    pub source_host_allowlist: Vec<String>,
+    /// Читать http(s) источник сервисом и передавать FFmpeg через stdin:
+    /// статус HTTP и таймаут видны до запуска процесса
+    ///
+    /// Без него защита от SSRF ограничена URL запроса: IP-литералы и адреса
+    /// имени хоста на момент проверки. FFmpeg резолвит имя повторно и сам
+    /// выполняет редиректы, так что публичный URL с редиректом на
+    /// `169.254.169.254` не отклоняется. Полная защита - только с загрузкой
+    /// сервисом (каждый редирект проверяется) или на уровне сети
+    pub fetch_source: bool,
+    /// Сколько ждать соединения и заголовков ответа источника, в секундах
+    pub source_fetch_timeout_secs: u64,
+    /// Лимит размера загружаемого источника в байтах (None - без лимита;
+    /// для `/transcode/upload` - `DEFAULT_MAX_UPLOAD_BYTES`)
+    pub max_source_bytes: Option<u64>,
+    /// Лимит отданного клиенту выхода в байтах: превышение убивает FFmpeg
+    /// (None - без лимита)
+    pub max_output_bytes: Option<u64>,
+    /// Источник длиннее стольких секунд отклоняется до запуска FFmpeg; при
+    /// заданном лимите источник без длительности (live) тоже отклоняется
+    ///
+    /// В отличие от `max_source_duration_secs` не обрезает выход и не
+    /// переопределяется запросом: проверяется полная длительность источника,
+    /// даже если `-t` обрезал бы его до лимита. Оба лимита независимы.
+    pub max_input_duration_secs: Option<u64>,
+    /// Сколько ждать свободный слот при исчерпанном лимите потоков, в
+    /// миллисекундах (0 - сразу 503)
+    pub acquire_wait_ms: u64,
+    /// Сколько HLS задач может ждать слот в очереди после `acquire_wait_ms`
+    /// (0 - очереди нет, сразу 503)
+    pub queue_depth: usize,
+    /// Таймаут транскодирования в секундах: сколько FFmpeg может не выдавать
+    /// данные в потоке, либо общее время для буферизованного результата
+    pub transcode_timeout_secs: u64,
+    /// Сколько при остановке ждать завершения выполняющихся транскодирований,
+    /// в секундах; оставшиеся после этого прерываются
+    pub shutdown_grace_secs: u64,
+    /// Отдавать хвост stderr FFmpeg в `details` ответа об ошибке (может
+    /// содержать URL источника и пути - только для отладки)
+    pub expose_ffmpeg_stderr: bool,
+    /// Полные URL источников в debug логе команды FFmpeg; иначе query и
+    /// userinfo скрыты, как во всех остальных логах
+    pub log_source_urls: bool,
+    /// Путь к бинарю FFmpeg
+    pub ffmpeg_path: String,
+    /// Путь к бинарю ffprobe
+    pub ffprobe_path: String,
+    /// Каталог для плейлистов и сегментов HLS (подкаталог на сессию)
+    pub hls_dir: PathBuf,
+    /// Порог битрейта (kbps), на котором и ниже стерео сводится в моно (None = выключено)
+    pub auto_mono_below_kbps: Option<u32>,
+    /// Пороги circuit breaker'а запусков FFmpeg
+    pub breaker: BreakerSettings,
+    /// Сколько permit'ов семафора занимает транскодирование по качеству
+    pub permit_weights: PermitWeights,
+    /// Ключи для `s3://` источников
+    pub s3_credentials: Option<CloudCredentials>,
+    /// Ключи для `gs://` источников
+    pub gcs_credentials: Option<CloudCredentials>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_source_duration_secs: None,
+            max_duration_ceiling_secs: 4 * 60 * 60,
+            api_key: None,
+            api_key_scopes: HashMap::new(),
+            route_prefix: String::new(),
+            enable_coalescing: false,
+            body_read_timeout_ms: 10_000,
+            rate_limit_per_minute: None,
+            max_request_body_bytes: 64 * 1024,
+            request_timeout_secs: 600,
+            transcode_timeout_secs: 300,
+            acquire_wait_ms: 0,
+            queue_depth: 0,
+            shutdown_grace_secs: 30,
+            expose_ffmpeg_stderr: false,
+            log_source_urls: false,
+            source_host_allowlist: Vec::new(),
+            fetch_source: false,
+            source_fetch_timeout_secs: 30,
+            max_source_bytes: None,
+            max_output_bytes: None,
+            max_input_duration_secs: None,
+            ffmpeg_path: "ffmpeg".to_string(),
+            ffprobe_path: "ffprobe".to_string(),
+            hls_dir: std::env::temp_dir().join("rust-transcoder-hls"),
+            auto_mono_below_kbps: None,
+            breaker: BreakerSettings::default(),
+            permit_weights: PermitWeights::default(),
+            s3_credentials: None,
+            gcs_credentials: None,
+        }
+    }
+}
+
+impl Config {
+    /// Загружает конфигурацию из переменных окружения
+    ///
+    /// Некорректное значение переменной - `ConfigError`, а не panic.
+    ///
+    /// * `MAX_SOURCE_DURATION_SECS` - лимит длительности выхода по умолчанию (`-t`)
+    /// * `MAX_DURATION_CEILING_SECS` - потолок для привилегированных ключей
+    /// * `API_KEY` - ключ доступа к `/api/v1/*` (пустое значение - API открыт)
+    /// * `API_KEY_SCOPES` - список вида `key1=duration_override,key2=duration_override`
+    /// * `ROUTE_PREFIX` - префикс маршрутов при монтировании за gateway
+    /// * `ENABLE_COALESCING` - объединение одинаковых запросов (`true`/`false`)
+    /// * `BODY_READ_TIMEOUT_MS` - окно на получение body запроса
+    /// * `RATE_LIMIT_PER_MINUTE` - запросов на транскодирование в минуту на клиента
+    /// * `MAX_REQUEST_BODY_BYTES` - лимит размера body запроса
+    /// * `REQUEST_TIMEOUT_SECONDS` - ожидание заголовков ответа (см. `request_timeout`)
+    /// * `TRANSCODE_TIMEOUT_SECONDS` - таймаут транскодирования (см. `transcode_timeout`)
+    /// * `ACQUIRE_WAIT_MS` - ожидание свободного слота до 503
+    /// * `QUEUE_DEPTH` - очередь HLS задач при занятых слотах (0 - без очереди)
+    /// * `SHUTDOWN_GRACE_SECONDS` - ожидание выполняющихся транскодирований при остановке
+    /// * `EXPOSE_FFMPEG_STDERR` - хвост stderr FFmpeg в ответе об ошибке (`true`/`false`)
+    /// * `LOG_SOURCE_URLS` - полные URL источников в debug логе команды FFmpeg
+    /// * `SOURCE_HOST_ALLOWLIST` - хосты источников через запятую (`.example.com` - с поддоменами)
+    /// * `FETCH_SOURCE` - загрузка источника сервисом вместо FFmpeg (`true`/`false`);
+    ///   без неё редиректы источника не проверяются на SSRF
+    /// * `SOURCE_FETCH_TIMEOUT_SECONDS` - ожидание ответа источника при `FETCH_SOURCE`
+    /// * `MAX_SOURCE_BYTES` - лимит размера источника при `FETCH_SOURCE` и загружаемого файла
+    /// * `MAX_OUTPUT_BYTES` - лимит размера потокового выхода
+    /// * `MAX_INPUT_DURATION` - отклонение источников длиннее стольких секунд (ffprobe)
+    /// * `FFMPEG_PATH`, `FFPROBE_PATH` - пути к бинарям (по умолчанию `ffmpeg`/`ffprobe` из PATH)
+    /// * `HLS_DIR` - каталог вывода HLS (по умолчанию во временном каталоге системы)
+    /// * `AUTO_MONO_BELOW_KBPS` - порог битрейта для автоматического моно
+    /// * `CIRCUIT_BREAKER_THRESHOLD`, `CIRCUIT_BREAKER_WINDOW_SECS`,
+    ///   `CIRCUIT_BREAKER_COOLDOWN_SECS` - сбоев FFmpeg подряд до размыкания,
+    ///   окно для них и пауза после размыкания
+    /// * `PERMIT_WEIGHT_LOSSLESS`, `PERMIT_WEIGHT_HIGH`, `PERMIT_WEIGHT_STANDARD` - сколько
+    ///   permit'ов из `MAX_CONCURRENT_STREAMS` занимает транскодирование (4, 2, 1)
+    /// * `S3_ACCESS_KEY_ID`, `S3_SECRET_ACCESS_KEY`, `S3_REGION`, `S3_ENDPOINT` - ключи для `s3://`
+    /// * `GCS_HMAC_ACCESS_ID`, `GCS_HMAC_SECRET` - HMAC ключи для `gs://`
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        config.apply_env(&|key| std::env::var(key).ok())?;
+        Ok(config)
+    }
+
+    /// Переопределяет поля значениями переменных окружения (список - в `from_env`)
+    ///
+    /// `env` возвращает значение переменной по имени; некорректное значение -
+    /// `ConfigError::Invalid` с именем переменной.
+    pub fn apply_env(&mut self, env: &dyn Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(value) = parse_env(env, "MAX_SOURCE_DURATION_SECS")? {
+            self.max_source_duration_secs = Some(value);
+        }
+
+        if let Some(value) = parse_env(env, "MAX_DURATION_CEILING_SECS")? {
+            self.max_duration_ceiling_secs = value;
+        }
+
+        if let Some(value) = env("API_KEY") {
+            self.api_key = Some(value.trim().to_string()).filter(|key| !key.is_empty());
+        }
+
+        if let Some(value) = env("API_KEY_SCOPES") {
+            self.api_key_scopes =
+                parse_api_key_scopes(&value).map_err(|message| ConfigError::Invalid {
+                    key: "API_KEY_SCOPES",
+                    message,
+                })?;
+        }
+
+        if let Some(value) = env("ROUTE_PREFIX") {
+            self.route_prefix = normalize_route_prefix(&value);
+        }
+
+        if let Some(value) = parse_env(env, "ENABLE_COALESCING")? {
+            self.enable_coalescing = value;
+        }
+
+        if let Some(value) = parse_env(env, "BODY_READ_TIMEOUT_MS")? {
+            self.body_read_timeout_ms = value;
+        }
+
+        if let Some(value) = parse_env(env, "RATE_LIMIT_PER_MINUTE")? {
+            self.rate_limit_per_minute = Some(value);
+        }
+
+        if let Some(value) = parse_env(env, "MAX_REQUEST_BODY_BYTES")? {
+            self.max_request_body_bytes = value;
+        }
+
+        if let Some(value) = parse_env(env, "REQUEST_TIMEOUT_SECONDS")? {
+            self.request_timeout_secs = value;
+        }
+
+        if let Some(value) = env("SOURCE_HOST_ALLOWLIST") {
+            self.source_host_allowlist = parse_host_allowlist(&value);
+        }
+
+        if let Some(value) = parse_env(env, "FETCH_SOURCE")? {
+            self.fetch_source = value;
+        }
+
+        if let Some(value) = parse_env(env, "SOURCE_FETCH_TIMEOUT_SECONDS")? {
+            self.source_fetch_timeout_secs = value;
+        }
+
+        if let Some(value) = parse_env(env, "MAX_SOURCE_BYTES")? {
+            self.max_source_bytes = Some(value);
+        }
+
+        if let Some(value) = parse_env(env, "MAX_OUTPUT_BYTES")? {
+            self.max_output_bytes = Some(value);
+        }
+
+        if let Some(value) = parse_env(env, "MAX_INPUT_DURATION")? {
+            self.max_input_duration_secs = Some(value);
+        }
+
+        if let Some(value) = parse_env(env, "ACQUIRE_WAIT_MS")? {
+            self.acquire_wait_ms = value;
+        }
+        if let Some(value) = parse_env(env, "QUEUE_DEPTH")? {
+            self.queue_depth = value;
+        }
+
+        if let Some(value) = parse_env(env, "SHUTDOWN_GRACE_SECONDS")? {
+            self.shutdown_grace_secs = value;
+        }
+
+        if let Some(value) = parse_env(env, "EXPOSE_FFMPEG_STDERR")? {
+            self.expose_ffmpeg_stderr = value;
+        }
+
+        if let Some(value) = parse_env(env, "LOG_SOURCE_URLS")? {
+            self.log_source_urls = value;
+        }
+
+        if let Some(value) = parse_env(env, "TRANSCODE_TIMEOUT_SECONDS")? {
+            self.transcode_timeout_secs = value;
+        }
+
+        if let Some(value) = env("FFMPEG_PATH") {
+            self.ffmpeg_path = value;
+        }
+
+        if let Some(value) = env("FFPROBE_PATH") {
+            self.ffprobe_path = value;
+        }
+
+        if let Some(value) = env("HLS_DIR") {
+            self.hls_dir = PathBuf::from(value);
+        }
+
+        if let Some(value) = parse_env(env, "AUTO_MONO_BELOW_KBPS")? {
+            self.auto_mono_below_kbps = Some(value);
+        }
+
+        if let Some(value) = parse_env(env, "CIRCUIT_BREAKER_THRESHOLD")? {
+            self.breaker.failure_threshold = value;
+        }
+
+        if let Some(value) = parse_env(env, "CIRCUIT_BREAKER_WINDOW_SECS")? {
+            self.breaker.window = Duration::from_secs(value);
+        }
+
+        if let Some(value) = parse_env(env, "CIRCUIT_BREAKER_COOLDOWN_SECS")? {
+            self.breaker.cooldown = Duration::from_secs(value);
+        }
+
+        if let Some(value) = parse_env(env, "PERMIT_WEIGHT_LOSSLESS")? {
+            self.permit_weights.lossless = value;
+        }
+
+        if let Some(value) = parse_env(env, "PERMIT_WEIGHT_HIGH")? {
+            self.permit_weights.high = value;
+        }
+
+        if let Some(value) = parse_env(env, "PERMIT_WEIGHT_STANDARD")? {
+            self.permit_weights.standard = value;
+        }
+
+        if let (Some(access_key_id), Some(secret_access_key)) =
+            (env("S3_ACCESS_KEY_ID"), env("S3_SECRET_ACCESS_KEY"))
+        {
+            self.s3_credentials = Some(CloudCredentials {
+                access_key_id,
+                secret_access_key,
+                region: env("S3_REGION").unwrap_or_else(|| "us-east-1".to_string()),
+                endpoint: env("S3_ENDPOINT"),
+            });
+        }
+
+        if let (Some(access_key_id), Some(secret_access_key)) =
+            (env("GCS_HMAC_ACCESS_ID"), env("GCS_HMAC_SECRET"))
+        {
+            // GCS принимает V4 подпись в режиме совместимости с S3
+            self.gcs_credentials = Some(CloudCredentials {
+                access_key_id,
+                secret_access_key,
+                region: "auto".to_string(),
+                endpoint: Some("https://storage.googleapis.com".to_string()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Окно на получение body запроса
+    pub fn body_read_timeout(&self) -> Duration {
+        Duration::from_millis(self.body_read_timeout_ms)
+    }
+
+    /// Таймаут запроса до отправки заголовков ответа
+    ///
+    /// Покрывает чтение запроса, ожидание слота, анализ источника и первый
+    /// байт FFmpeg; после заголовков поток ответа живёт по `transcode_timeout`.
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    /// Ожидание соединения и заголовков ответа источника (`fetch_source`)
+    pub fn source_fetch_timeout(&self) -> Duration {
+        Duration::from_secs(self.source_fetch_timeout_secs)
+    }
+
+    /// Лимит загружаемого файла: `max_source_bytes` или `DEFAULT_MAX_UPLOAD_BYTES`
+    pub fn max_upload_bytes(&self) -> u64 {
+        self.max_source_bytes.unwrap_or(DEFAULT_MAX_UPLOAD_BYTES)
+    }
+
+    /// Ожидание свободного слота транскодирования
+    pub fn acquire_wait(&self) -> Duration {
+        Duration::from_millis(self.acquire_wait_ms)
+    }
+
+    /// Grace period остановки сервиса
+    pub fn shutdown_grace(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_secs)
+    }
+
+    /// Таймаут транскодирования
+    ///
+    /// Потоковый ответ - idle таймаут: отсчёт сбрасывается на каждом chunk'е
+    /// stdout, длинный источник, который стабильно выдаёт данные, не
+    /// прерывается. Если FFmpeg молчит дольше таймаута до первого байта,
+    /// клиент получает 504; после начала потока body обрывается. Объединённые
+    /// запросы (`enable_coalescing`) буферизуются целиком, для них это общее
+    /// время транскодирования.
+    pub fn transcode_timeout(&self) -> Duration {
+        Duration::from_secs(self.transcode_timeout_secs)
+    }
+
+    /// Проверяет, выдан ли ключу указанный scope
+    pub fn has_scope(&self, api_key: &str, scope: ApiKeyScope) -> bool {
+        self.api_key_scopes
+            .get(api_key)
+            .is_some_and(|scopes| scopes.contains(&scope))
+    }
+
+    /// Пропускает ли аутентификация запрос с этим ключом
+    ///
+    /// Без `api_key` - любой запрос; иначе `api_key` или ключ из `api_key_scopes`.
+    pub fn accepts_api_key(&self, api_key: Option<&str>) -> bool {
+        let Some(expected) = &self.api_key else {
+            return true;
+        };
+        let Some(key) = api_key else {
+            return false;
+        };
+
+        constant_time_eq(key, expected) || self.api_key_scopes.contains_key(key)
+    }
+
+    /// Вычисляет эффективный лимит длительности для запроса
+    ///
+    /// # Arguments
+    /// * `requested` - значение `max_duration_override` из запроса
+    /// * `can_override` - есть ли у вызывающего scope `DurationOverride`
+    ///
+    /// Привилегированный ключ может поднять лимит до `max_duration_ceiling_secs`,
+    /// остальные могут только уменьшить его относительно значения по умолчанию.
+    pub fn resolve_max_duration(&self, requested: Option<u32>, can_override: bool) -> Option<u32> {
+        match (requested, self.max_source_duration_secs) {
+            (Some(requested), _) if can_override => {
+                Some(requested.min(self.max_duration_ceiling_secs))
+            }
+            (Some(requested), Some(default)) => Some(requested.min(default)),
+            (Some(requested), None) => Some(requested),
+            (None, default) => default,
+        }
+    }
+}
+
+/// Ошибка загрузки конфигурации
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// Некорректное значение переменной окружения или настройки
+    #[error("{key}: {message}")]
+    Invalid { key: &'static str, message: String },
+
+    /// Файл из `CONFIG_PATH` не прочитан
+    #[error("failed to read config file {path}: {source}")]
+    Read {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Файл из `CONFIG_PATH` - некорректный TOML или содержит неизвестные поля
+    #[error("invalid config file {path}: {source}")]
+    Parse {
+        path: String,
+        #[source]
+        source: toml::de::Error,
+    },
+}
+
+/// Разбирает переменную окружения; None - переменная не задана
+fn parse_env<T: FromStr>(
+    env: &dyn Fn(&str) -> Option<String>,
+    key: &'static str,
+) -> Result<Option<T>, ConfigError> {
+    env(key)
+        .map(|value| {
+            value.trim().parse().map_err(|_| ConfigError::Invalid {
+                key,
+                message: format!("invalid value '{}'", value),
+            })
+        })
+        .transpose()
+}
+
+/// Разбирает адрес интерфейса: IPv4 или IPv6, в том числе `[::1]`
+fn parse_bind_addr(key: &'static str, value: &str) -> Result<IpAddr, ConfigError> {
+    let trimmed = value.trim();
+    let unbracketed = trimmed
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(trimmed);
+    unbracketed.parse().map_err(|_| ConfigError::Invalid {
+        key,
+        message: format!("invalid IP address '{}'", value),
+    })
+}
+
+/// Настройки запуска сервиса: адрес, порт, лимит потоков и `Config`
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Адрес интерфейса HTTP сервера (`0.0.0.0` - все интерфейсы IPv4)
+    pub bind_addr: IpAddr,
+    /// Порт HTTP сервера
+    pub port: u16,
+    /// Лимит одновременных транскодирований
+    pub max_concurrent_streams: usize,
+    /// Конфигурация приложения
+    pub config: Config,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bind_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8090,
+            max_concurrent_streams: 50,
+            config: Config::default(),
+        }
+    }
+}
+
+/// Поля TOML файла настроек; отсутствующие остаются значениями по умолчанию
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileSettings {
+    bind_addr: Option<String>,
+    port: Option<u16>,
+    max_concurrent_streams: Option<usize>,
+    transcode_timeout_secs: Option<u64>,
+    acquire_wait_ms: Option<u64>,
+    queue_depth: Option<usize>,
+    shutdown_grace_secs: Option<u64>,
+    max_request_body_bytes: Option<usize>,
+    request_timeout_secs: Option<u64>,
+    ffmpeg_path: Option<String>,
+    ffprobe_path: Option<String>,
+    hls_dir: Option<PathBuf>,
+    source_host_allowlist: Option<Vec<String>>,
+    fetch_source: Option<bool>,
+    source_fetch_timeout_secs: Option<u64>,
+    max_source_bytes: Option<u64>,
+    max_output_bytes: Option<u64>,
+    max_input_duration_secs: Option<u64>,
+}
+
+impl Settings {
+    /// Загружает настройки: значения по умолчанию, затем TOML файл из
+    /// `CONFIG_PATH` (если задан), затем переменные окружения
+    ///
+    /// Переменные окружения имеют приоритет над файлом: `BIND_ADDR`, `PORT`,
+    /// `MAX_CONCURRENT_STREAMS` и переменные `Config::from_env`.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_with(&|key| std::env::var(key).ok())
+    }
+
+    /// `load` с явным источником переменных окружения
+    pub fn load_with(env: &dyn Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut settings = Self::default();
+
+        if let Some(path) = env("CONFIG_PATH") {
+            let text = std::fs::read_to_string(&path).map_err(|source| ConfigError::Read {
+                path: path.clone(),
+                source,
+            })?;
+            let file: FileSettings =
+                toml::from_str(&text).map_err(|source| ConfigError::Parse { path, source })?;
+            settings.apply_file(file)?;
+        }
+
+        if let Some(value) = env("BIND_ADDR") {
+            settings.bind_addr = parse_bind_addr("BIND_ADDR", &value)?;
+        }
+        if let Some(port) = parse_env(env, "PORT")? {
+            settings.port = port;
+        }
+        if let Some(max_concurrent_streams) = parse_env(env, "MAX_CONCURRENT_STREAMS")? {
+            settings.max_concurrent_streams = max_concurrent_streams;
+        }
+        settings.config.apply_env(env)?;
+
+        if settings.max_concurrent_streams == 0 {
+            return Err(ConfigError::Invalid {
+                key: "max_concurrent_streams",
+                message: "must be greater than 0".to_string(),
+            });
+        }
+
+        Ok(settings)
+    }
+
+    /// Адрес, на котором слушает HTTP сервер
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_addr, self.port)
+    }
+
+    fn apply_file(&mut self, file: FileSettings) -> Result<(), ConfigError> {
+        if let Some(value) = file.bind_addr {
+            self.bind_addr = parse_bind_addr("bind_addr", &value)?;
+        }
+        if let Some(port) = file.port {
+            self.port = port;
+        }
+        if let Some(max_concurrent_streams) = file.max_concurrent_streams {
+            self.max_concurrent_streams = max_concurrent_streams;
+        }
+
+        let config = &mut self.config;
+        if let Some(timeout) = file.transcode_timeout_secs {
+            config.transcode_timeout_secs = timeout;
+        }
+        if let Some(wait) = file.acquire_wait_ms {
+            config.acquire_wait_ms = wait;
+        }
+        if let Some(depth) = file.queue_depth {
+            config.queue_depth = depth;
+        }
+        if let Some(grace) = file.shutdown_grace_secs {
+            config.shutdown_grace_secs = grace;
+        }
+        if let Some(limit) = file.max_request_body_bytes {
+            config.max_request_body_bytes = limit;
+        }
+        if let Some(timeout) = file.request_timeout_secs {
+            config.request_timeout_secs = timeout;
+        }
+        if let Some(path) = file.ffmpeg_path {
+            config.ffmpeg_path = path;
+        }
+        if let Some(path) = file.ffprobe_path {
+            config.ffprobe_path = path;
+        }
+        if let Some(path) = file.hls_dir {
+            config.hls_dir = path;
+        }
+        if let Some(hosts) = file.source_host_allowlist {
+            config.source_host_allowlist = parse_host_allowlist(&hosts.join(","));
+        }
+        if let Some(fetch) = file.fetch_source {
+            config.fetch_source = fetch;
+        }
+        if let Some(timeout) = file.source_fetch_timeout_secs {
+            config.source_fetch_timeout_secs = timeout;
+        }
+        if let Some(limit) = file.max_source_bytes {
+            config.max_source_bytes = Some(limit);
+        }
+        if let Some(limit) = file.max_output_bytes {
+            config.max_output_bytes = Some(limit);
+        }
+        if let Some(limit) = file.max_input_duration_secs {
+            config.max_input_duration_secs = Some(limit);
+        }
+        Ok(())
+    }
+}
+
+/// Сравнение ключей без раннего выхода: время не выдаёт совпавший префикс
+fn constant_time_eq(left: &str, right: &str) -> bool {
+    left.len() == right.len()
+        && left
+            .bytes()
+            .zip(right.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Приводит префикс к виду `/segment`: ведущий `/`, без завершающего
+fn normalize_route_prefix(value: &str) -> String {
+    let trimmed = value.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// Разбирает `SOURCE_HOST_ALLOWLIST`: хосты через запятую, регистр не важен
+fn parse_host_allowlist(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|host| host.trim().to_ascii_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+/// Разбирает `API_KEY_SCOPES`: записи через запятую, scopes ключа через `|`
+fn parse_api_key_scopes(value: &str) -> Result<HashMap<String, Vec<ApiKeyScope>>, String> {
+    let mut result = HashMap::new();
+
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (key, scopes) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=scope, got '{}'", entry))?;
+
+        let scopes = scopes
+            .split('|')
+            .map(|s| ApiKeyScope::parse(s).ok_or_else(|| format!("unknown scope '{}'", s)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        result.insert(key.trim().to_string(), scopes);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_default(default: Option<u32>) -> Config {
+        Config {
+            max_source_duration_secs: default,
+            max_duration_ceiling_secs: 7200,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_scoped_key_can_exceed_default() {
+        let config = config_with_default(Some(600));
+        assert_eq!(config.resolve_max_duration(Some(3600), true), Some(3600));
+    }
+
+    #[test]
+    fn test_scoped_key_is_limited_by_ceiling() {
+        let config = config_with_default(Some(600));
+        assert_eq!(config.resolve_max_duration(Some(10_000), true), Some(7200));
+    }
+
+    #[test]
+    fn test_unscoped_key_is_capped_at_default() {
+        let config = config_with_default(Some(600));
+        assert_eq!(config.resolve_max_duration(Some(3600), false), Some(600));
+        assert_eq!(config.resolve_max_duration(Some(300), false), Some(300));
+    }
+
+    #[test]
+    fn test_no_override_uses_default() {
+        let config = config_with_default(Some(600));
+        assert_eq!(config.resolve_max_duration(None, true), Some(600));
+        assert_eq!(
+            config_with_default(None).resolve_max_duration(None, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_api_key_scopes() {
+        let scopes =
+            parse_api_key_scopes("alpha=duration_override, beta=duration_override").unwrap();
+        assert_eq!(scopes.len(), 2);
+        assert_eq!(scopes["alpha"], vec![ApiKeyScope::DurationOverride]);
+
+        assert!(parse_api_key_scopes("alpha").is_err());
+        assert!(parse_api_key_scopes("alpha=admin").is_err());
+    }
+
+    #[test]
+    fn test_parse_host_allowlist() {
+        assert_eq!(
+            parse_host_allowlist(" CDN.example.com, .media.example.org ,,"),
+            vec!["cdn.example.com", ".media.example.org"]
+        );
+        assert!(parse_host_allowlist("").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_route_prefix() {
+        assert_eq!(normalize_route_prefix(""), "");
+        assert_eq!(normalize_route_prefix("/"), "");
+        assert_eq!(normalize_route_prefix("transcoder"), "/transcoder");
+        assert_eq!(normalize_route_prefix("/svc/transcoder/"), "/svc/transcoder");
+    }
+
+    #[test]
+    fn test_has_scope() {
+        let mut config = Config::default();
+        config
+            .api_key_scopes
+            .insert("alpha".to_string(), vec![ApiKeyScope::DurationOverride]);
+
+        assert!(config.has_scope("alpha", ApiKeyScope::DurationOverride));
+        assert!(!config.has_scope("beta", ApiKeyScope::DurationOverride));
+    }
+
+    #[test]
+    fn test_accepts_api_key() {
+        let mut config = Config::default();
+        assert!(config.accepts_api_key(None));
+
+        config.apply_env(&|key| (key == "API_KEY").then(|| " secret ".to_string())).unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("secret"));
+        assert!(config.accepts_api_key(Some("secret")));
+        assert!(!config.accepts_api_key(Some("secreT")));
+        assert!(!config.accepts_api_key(Some("")));
+        assert!(!config.accepts_api_key(None));
+
+        // Ключ с правами - тоже ключ доступа
+        config
+            .api_key_scopes
+            .insert("trusted".to_string(), vec![ApiKeyScope::DurationOverride]);
+        assert!(config.accepts_api_key(Some("trusted")));
+
+        config.apply_env(&|key| (key == "API_KEY").then(String::new)).unwrap();
+        assert!(config.accepts_api_key(None));
+    }
+
+    fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    fn config_file(contents: &str) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), contents).unwrap();
+        file
+    }
+
+    const SAMPLE_TOML: &str = r#"
+bind_addr = "127.0.0.1"
+port = 9000
+max_concurrent_streams = 8
+transcode_timeout_secs = 120
+acquire_wait_ms = 250
+max_request_body_bytes = 32768
+ffmpeg_path = "/opt/ffmpeg/bin/ffmpeg"
+source_host_allowlist = ["CDN.example.com", ".media.example.org"]
+"#;
+
+    #[test]
+    fn test_settings_defaults_without_file() {
+        let settings = Settings::load_with(&env_of(&[])).unwrap();
+        assert_eq!(settings.port, 8090);
+        assert_eq!(settings.socket_addr().to_string(), "0.0.0.0:8090");
+        assert_eq!(settings.max_concurrent_streams, 50);
+        assert_eq!(settings.config.ffmpeg_path, "ffmpeg");
+    }
+
+    #[test]
+    fn test_settings_from_toml_file() {
+        let file = config_file(SAMPLE_TOML);
+        let path = file.path().to_str().unwrap();
+
+        let settings = Settings::load_with(&env_of(&[("CONFIG_PATH", path)])).unwrap();
+        assert_eq!(settings.socket_addr().to_string(), "127.0.0.1:9000");
+        assert_eq!(settings.max_concurrent_streams, 8);
+        assert_eq!(settings.config.transcode_timeout_secs, 120);
+        assert_eq!(settings.config.acquire_wait_ms, 250);
+        assert_eq!(settings.config.max_request_body_bytes, 32 * 1024);
+        assert_eq!(settings.config.ffmpeg_path, "/opt/ffmpeg/bin/ffmpeg");
+        assert_eq!(settings.config.ffprobe_path, "ffprobe");
+        assert_eq!(
+            settings.config.source_host_allowlist,
+            vec!["cdn.example.com", ".media.example.org"]
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_toml_file() {
+        let file = config_file(SAMPLE_TOML);
+        let path = file.path().to_str().unwrap();
+
+        let settings = Settings::load_with(&env_of(&[
+            ("CONFIG_PATH", path),
+            ("BIND_ADDR", "[::1]"),
+            ("PORT", "9100"),
+            ("TRANSCODE_TIMEOUT_SECONDS", "60"),
+            ("SOURCE_HOST_ALLOWLIST", "audio.example.net"),
+            ("FFMPEG_PATH", "/usr/local/bin/ffmpeg7"),
+            ("FFPROBE_PATH", "/usr/local/bin/ffprobe7"),
+            ("HLS_DIR", "/var/cache/transcoder/hls"),
+            ("FETCH_SOURCE", "true"),
+            ("MAX_SOURCE_BYTES", "1048576"),
+            ("MAX_OUTPUT_BYTES", "536870912"),
+            ("MAX_INPUT_DURATION", "14400"),
+            ("LOG_SOURCE_URLS", "true"),
+            ("QUEUE_DEPTH", "20"),
+        ]))
+        .unwrap();
+
+        assert_eq!(settings.socket_addr().to_string(), "[::1]:9100");
+        assert_eq!(settings.config.transcode_timeout_secs, 60);
+        assert_eq!(settings.config.source_host_allowlist, vec!["audio.example.net"]);
+        assert_eq!(settings.config.ffmpeg_path, "/usr/local/bin/ffmpeg7");
+        assert_eq!(settings.config.ffprobe_path, "/usr/local/bin/ffprobe7");
+        assert_eq!(
+            settings.config.hls_dir,
+            PathBuf::from("/var/cache/transcoder/hls")
+        );
+        assert!(settings.config.fetch_source);
+        assert_eq!(settings.config.max_source_bytes, Some(1024 * 1024));
+        assert_eq!(settings.config.max_output_bytes, Some(512 * 1024 * 1024));
+        assert_eq!(settings.config.max_input_duration_secs, Some(4 * 60 * 60));
+        assert!(settings.config.log_source_urls);
+        assert_eq!(settings.config.queue_depth, 20);
+        assert_eq!(settings.config.source_fetch_timeout(), Duration::from_secs(30));
+        // Не переопределённое окружением - из файла
+        assert_eq!(settings.max_concurrent_streams, 8);
+        assert_eq!(settings.config.acquire_wait_ms, 250);
+    }
+
+    #[test]
+    fn test_invalid_settings_are_errors() {
+        let err = Settings::load_with(&env_of(&[("PORT", "http")])).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "PORT", .. }), "{}", err);
+
+        let err = Settings::load_with(&env_of(&[("BIND_ADDR", "localhost")])).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "BIND_ADDR", .. }), "{}", err);
+
+        let file = config_file("bind_addr = \"0.0.0.0:8090\"\n");
+        let path = file.path().to_str().unwrap();
+        let err = Settings::load_with(&env_of(&[("CONFIG_PATH", path)])).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "bind_addr", .. }), "{}", err);
+
+        let err = Settings::load_with(&env_of(&[("ENABLE_COALESCING", "yes")])).unwrap_err();
+        assert!(err.to_string().contains("ENABLE_COALESCING"), "{}", err);
+
+        let err = Settings::load_with(&env_of(&[("MAX_CONCURRENT_STREAMS", "0")])).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { .. }), "{}", err);
+
+        let file = config_file("port = 9000\nworkers = 4\n");
+        let path = file.path().to_str().unwrap();
+        let err = Settings::load_with(&env_of(&[("CONFIG_PATH", path)])).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }), "{}", err);
+
+        let err = Settings::load_with(&env_of(&[("CONFIG_PATH", "/nonexistent/transcoder.toml")]))
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Read { .. }), "{}", err);
+    }
+}
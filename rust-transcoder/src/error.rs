@@ -5,13 +5,19 @@
 use std::io;
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde::Serialize;
 use thiserror::Error;
 use tracing::error;
+use uuid::Uuid;
+
+use crate::api::request_id;
+
+/// `Retry-After` при исчерпании лимита потоков: слоты освобождаются постоянно
+pub const CONCURRENCY_RETRY_AFTER_SECS: u64 = 1;
 
 /// Основной тип ошибки приложения
 #[derive(Debug, Error)]
@@ -28,6 +34,13 @@ pub enum AppError {
     #[error("FFmpeg error: {0}")]
     Ffmpeg(String),
 
+    /// FFmpeg завершился с ненулевым кодом; хвост stderr - для `details` ответа
+    #[error("FFmpeg error: {message}")]
+    FfmpegExited {
+        message: String,
+        stderr_tail: Option<String>,
+    },
+
     /// Ошибка ввода-вывода
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
@@ -36,14 +49,58 @@ pub enum AppError {
     #[error("Source unavailable: {0}")]
     SourceUnavailable(String),
 
+    /// Источник не найден (HTTP 404 или отсутствующий файл)
+    #[error("Source not found: {0}")]
+    SourceNotFound(String),
+
+    /// Ресурс не найден (например, ещё не записанный сегмент HLS)
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// Сессия не найдена (неизвестный ID или уже удалена из реестра)
+    #[error("Session not found: {0}")]
+    SessionNotFound(Uuid),
+
+    /// Сессия уже в конечном статусе и не может быть отменена
+    #[error("Session already finished: {0}")]
+    SessionFinished(Uuid),
+
     /// Превышен лимит concurrent streams
     #[error("Concurrency limit exceeded: max {0} streams allowed")]
     ConcurrencyLimitExceeded(usize),
 
+    /// FFmpeg стабильно падает, circuit breaker разомкнут (секунд до повторной попытки)
+    #[error("Service degraded: retry in {0}s")]
+    ServiceDegraded(u64),
+
+    /// Клиент превысил лимит запросов (секунд до следующей попытки)
+    #[error("Rate limit exceeded: retry in {0}s")]
+    RateLimited(u64),
+
+    /// Нет API ключа или ключ неверный
+    #[error("Missing or invalid API key")]
+    Unauthorized,
+
+    /// Сервис останавливается и не принимает новые транскодирования
+    #[error("Service is shutting down")]
+    ShuttingDown,
+
+    /// FFmpeg не установлен или не запускается (readiness)
+    #[error("FFmpeg unavailable: {0}")]
+    FfmpegUnavailable(String),
+
     /// Таймаут операции
     #[error("Operation timeout: {0}")]
     Timeout(String),
 
+    /// Body запроса больше лимита
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    /// Клиент не передал запрос за отведённое время
+    #[error("Request timeout: {0}")]
+    RequestTimeout(String),
+
     /// Невалидный фильтр
     #[error("Invalid filter: {0}")]
     FilterInvalid(String),
@@ -53,6 +110,11 @@ pub enum AppError {
     Internal(String),
 }
 
+/// Код ошибки ответа в его extensions: middleware метрик читает его, не
+/// разбирая body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorCode(pub String);
+
 /// Структура ответа об ошибке
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
@@ -63,6 +125,9 @@ pub struct ErrorResponse {
     /// Дополнительные детали (опционально)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
+    /// Correlation ID запроса (`X-Request-Id`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl ErrorResponse {
@@ -71,6 +136,7 @@ impl ErrorResponse {
             code: code.into(),
             message: message.into(),
             details: None,
+            request_id: None,
         }
     }
 
@@ -78,6 +144,24 @@ impl ErrorResponse {
         self.details = Some(details.into());
         self
     }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+}
+
+impl AppError {
+    /// Убирает хвост stderr FFmpeg из ошибки (он может раскрывать URL и пути)
+    pub fn without_stderr(self) -> Self {
+        match self {
+            AppError::FfmpegExited { message, .. } => AppError::FfmpegExited {
+                message,
+                stderr_tail: None,
+            },
+            other => other,
+        }
+    }
 }
 
 impl IntoResponse for AppError {
@@ -101,6 +185,21 @@ impl IntoResponse for AppError {
                 )
             }
 
+            AppError::FfmpegExited {
+                message,
+                stderr_tail,
+            } => {
+                error!(error = %message, "FFmpeg process error");
+                let response = ErrorResponse::new("FFMPEG_ERROR", "Transcoding failed");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    match stderr_tail {
+                        Some(tail) => response.with_details(tail.clone()),
+                        None => response,
+                    },
+                )
+            }
+
             AppError::Io(err) => {
                 error!(error = %err, "IO error");
                 (
@@ -114,6 +213,32 @@ impl IntoResponse for AppError {
                 ErrorResponse::new("SOURCE_UNAVAILABLE", msg),
             ),
 
+            AppError::SourceNotFound(msg) => (
+                StatusCode::NOT_FOUND,
+                ErrorResponse::new("SOURCE_NOT_FOUND", msg),
+            ),
+
+            AppError::NotFound(msg) => (
+                StatusCode::NOT_FOUND,
+                ErrorResponse::new("NOT_FOUND", msg),
+            ),
+
+            AppError::SessionNotFound(session_id) => (
+                StatusCode::NOT_FOUND,
+                ErrorResponse::new(
+                    "SESSION_NOT_FOUND",
+                    format!("Transcode session {} not found", session_id),
+                ),
+            ),
+
+            AppError::SessionFinished(session_id) => (
+                StatusCode::CONFLICT,
+                ErrorResponse::new(
+                    "SESSION_ALREADY_FINISHED",
+                    format!("Transcode session {} has already finished", session_id),
+                ),
+            ),
+
             AppError::ConcurrencyLimitExceeded(limit) => (
                 StatusCode::SERVICE_UNAVAILABLE,
                 ErrorResponse::new(
@@ -122,11 +247,58 @@ impl IntoResponse for AppError {
                 ),
             ),
 
+            AppError::ServiceDegraded(retry_after) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorResponse::new(
+                    "SERVICE_DEGRADED",
+                    "Transcoding is temporarily unavailable due to repeated FFmpeg failures",
+                )
+                .with_details(format!("Retry in {} seconds", retry_after)),
+            ),
+
+            AppError::RateLimited(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorResponse::new("RATE_LIMITED", "Too many transcode requests")
+                    .with_details(format!("Retry in {} seconds", retry_after)),
+            ),
+
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                ErrorResponse::new(
+                    "UNAUTHORIZED",
+                    "Missing or invalid API key (Authorization: Bearer <key> or X-API-Key)",
+                ),
+            ),
+
+            AppError::ShuttingDown => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorResponse::new(
+                    "SHUTTING_DOWN",
+                    "Server is shutting down and does not accept new transcodes",
+                ),
+            ),
+
+            AppError::FfmpegUnavailable(msg) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorResponse::new("FFMPEG_UNAVAILABLE", "FFmpeg is not available")
+                    .with_details(msg),
+            ),
+
             AppError::Timeout(msg) => (
                 StatusCode::GATEWAY_TIMEOUT,
                 ErrorResponse::new("TIMEOUT", msg),
             ),
 
+            AppError::PayloadTooLarge(msg) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorResponse::new("PAYLOAD_TOO_LARGE", msg),
+            ),
+
+            AppError::RequestTimeout(msg) => (
+                StatusCode::REQUEST_TIMEOUT,
+                ErrorResponse::new("REQUEST_TIMEOUT", msg),
+            ),
+
             AppError::FilterInvalid(msg) => (
                 StatusCode::BAD_REQUEST,
                 ErrorResponse::new("FILTER_INVALID", msg),
@@ -141,7 +313,33 @@ impl IntoResponse for AppError {
             }
         };
 
-        (status, Json(error_response)).into_response()
+        // Клиенту есть смысл повторить запрос: подсказываем когда
+        let retry_after = match &self {
+            AppError::ConcurrencyLimitExceeded(_) => Some(CONCURRENCY_RETRY_AFTER_SECS),
+            AppError::ServiceDegraded(retry_after) => Some(*retry_after),
+            AppError::RateLimited(retry_after) => Some(*retry_after),
+            _ => None,
+        };
+
+        let error_response = match request_id::current() {
+            Some(request_id) => error_response.with_request_id(request_id),
+            None => error_response,
+        };
+
+        let code = ErrorCode(error_response.code.clone());
+        let mut response = (status, Json(error_response)).into_response();
+        response.extensions_mut().insert(code);
+        if let Some(retry_after) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        if matches!(self, AppError::Unauthorized) {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
     }
 }
 
@@ -160,6 +358,15 @@ mod tests {
         assert!(resp.details.is_none());
     }
 
+    #[test]
+    fn test_error_code_recorded_in_extensions() {
+        let response = AppError::Validation("bad".to_string()).into_response();
+        assert_eq!(
+            response.extensions().get::<ErrorCode>(),
+            Some(&ErrorCode("VALIDATION_ERROR".to_string()))
+        );
+    }
+
     #[test]
     fn test_error_response_with_details() {
         let resp = ErrorResponse::new("TEST_ERROR", "Test message")
@@ -178,4 +385,29 @@ mod tests {
         let err = AppError::ConcurrencyLimitExceeded(50);
         assert!(err.to_string().contains("50"));
     }
+
+    #[test]
+    fn test_retry_after_header() {
+        let response = AppError::ConcurrencyLimitExceeded(50).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+
+        let response = AppError::ServiceDegraded(30).into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+
+        let response = AppError::Validation("bad".into()).into_response();
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+    }
+
+    #[test]
+    fn test_without_stderr_drops_tail() {
+        let err = AppError::FfmpegExited {
+            message: "FFmpeg exited with exit status: 1".into(),
+            stderr_tail: Some("Unknown encoder 'libfoo'".into()),
+        };
+        match err.without_stderr() {
+            AppError::FfmpegExited { stderr_tail, .. } => assert!(stderr_tail.is_none()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
 }
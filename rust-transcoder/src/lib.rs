@@ -3,63 +3,298 @@
 //! Экспортирует публичные типы для тестов и интеграций.
 
 pub mod api;
+pub mod config;
 pub mod error;
+pub mod metrics;
 pub mod models;
+pub mod shutdown;
 pub mod transcoder;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
-use axum::{routing::get, Router};
-use tokio::sync::Semaphore;
+use axum::{body::Bytes, middleware, routing::get, Router};
+use tokio::sync::{OnceCell, Semaphore};
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::timeout::TimeoutLayer;
+
+use crate::api::rate_limit::RateLimiter;
+use crate::config::Config;
+use crate::error::AppResult;
+use crate::transcoder::cloud::Presigner;
+use crate::transcoder::ffmpeg::BufferedError;
+use crate::transcoder::{
+    CircuitBreaker, Coalescer, HlsStore, JobQueue, SessionRegistry, SourceFetcher,
+};
 
 /// Глобальное состояние приложения
 #[derive(Debug)]
 pub struct AppState {
     /// Семафор для ограничения concurrent потоков транскодирования
-    pub transcode_semaphore: Semaphore,
+    ///
+    /// В `Arc`, чтобы permit мог жить в streaming body дольше handler'а.
+    pub transcode_semaphore: Arc<Semaphore>,
     /// Максимальное количество concurrent потоков
     pub max_concurrent_streams: usize,
+    /// Момент запуска сервиса (для uptime)
+    pub start_time: Instant,
+    /// Конфигурация сервиса
+    pub config: Config,
+    /// Выполняющиеся объединённые транскодирования (при `enable_coalescing`)
+    pub coalescer: Coalescer<Result<Bytes, BufferedError>>,
+    /// Реестр сессий для status API
+    pub sessions: SessionRegistry,
+    /// Задачи, ждущие свободного слота (`Config::queue_depth`)
+    pub queue: JobQueue,
+    /// Circuit breaker запусков FFmpeg
+    pub breaker: CircuitBreaker,
+    /// Ограничение частоты транскодирований на клиента
+    pub rate_limiter: RateLimiter,
+    /// Подпись URL облачных источников (`s3://`, `gs://`)
+    pub presigner: Box<dyn Presigner>,
+    /// HTTP клиент источников при `Config::fetch_source`
+    pub fetcher: Option<SourceFetcher>,
+    /// Каталоги HLS вывода сессий (`Config::hls_dir`)
+    pub hls: HlsStore,
+    /// Версия FFmpeg после первой успешной проверки readiness
+    pub ffmpeg_version: OnceCell<String>,
+    /// Сервис останавливается: новые транскодирования отклоняются
+    shutting_down: AtomicBool,
 }
 
 impl AppState {
     /// Создаёт новое состояние с указанным лимитом concurrent потоков
-    pub fn new(max_concurrent_streams: usize) -> Self {
-        Self {
-            transcode_semaphore: Semaphore::new(max_concurrent_streams),
+    pub fn new(max_concurrent_streams: usize) -> AppResult<Self> {
+        Self::with_config(max_concurrent_streams, Config::default())
+    }
+
+    /// Создаёт состояние с явно заданной конфигурацией
+    ///
+    /// Ошибка - не создан HTTP клиент источников (`FETCH_SOURCE`).
+    pub fn with_config(max_concurrent_streams: usize, config: Config) -> AppResult<Self> {
+        #[cfg(feature = "cloud-sources")]
+        let presigner: Box<dyn Presigner> =
+            Box::new(transcoder::cloud::SigV4Presigner::from_config(&config));
+        #[cfg(not(feature = "cloud-sources"))]
+        let presigner: Box<dyn Presigner> = Box::new(transcoder::cloud::DisabledPresigner);
+
+        let transcode_semaphore = Arc::new(Semaphore::new(max_concurrent_streams));
+        transcoder::permit::report_available(&transcode_semaphore);
+
+        let fetcher = config
+            .fetch_source
+            .then(|| {
+                SourceFetcher::new(
+                    config.source_fetch_timeout(),
+                    config.max_source_bytes,
+                    config.source_host_allowlist.clone(),
+                )
+            })
+            .transpose()?;
+
+        let hls = HlsStore::new(config.hls_dir.clone());
+        let queue = JobQueue::new(config.queue_depth);
+
+        Ok(Self {
+            breaker: CircuitBreaker::new(config.breaker),
+            rate_limiter: RateLimiter::new(config.rate_limit_per_minute),
+            transcode_semaphore,
             max_concurrent_streams,
-        }
+            start_time: Instant::now(),
+            config,
+            coalescer: Coalescer::new(),
+            sessions: SessionRegistry::new(),
+            queue,
+            presigner,
+            fetcher,
+            hls,
+            ffmpeg_version: OnceCell::new(),
+            shutting_down: AtomicBool::new(false),
+        })
+    }
+
+    /// Переводит сервис в режим остановки (см. `shutdown::drain`)
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    /// Идёт ли остановка сервиса
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
     }
 }
 
 /// Строит основной Router приложения
+///
+/// Если задан `Config::route_prefix`, все маршруты (включая health и metrics)
+/// монтируются под ним: `{prefix}/api/v1/...`, `{prefix}/health`.
 pub fn build_router(state: Arc<AppState>) -> Router {
-    Router::new()
+    let prefix = state.config.route_prefix.clone();
+    let body_limit = state.config.max_request_body_bytes;
+    let request_timeout = state.config.request_timeout();
+
+    let router = Router::new()
         // Health endpoints
         .route("/health", get(api::health::health_check))
         .route("/health/ready", get(api::health::readiness_check))
         .route("/health/live", get(api::health::liveness_check))
+        // Версия, git SHA и время сборки
+        .route("/version", get(api::version::version_handler))
         // Metrics endpoint
         .route("/metrics", get(api::metrics::metrics_handler))
         // API v1 routes
         .nest("/api/v1", api::routes(state.clone()))
-        .with_state(state)
+        // Body больше лимита - 413; таймаут - до заголовков ответа, поток
+        // транскодирования после них не прерывается
+        .layer(RequestBodyLimitLayer::new(body_limit))
+        // Загрузка файла: свои лимиты полей multipart вместо общего
+        .nest("/api/v1", api::upload_routes(state.clone()))
+        .layer(TimeoutLayer::new(request_timeout))
+        // X-Request-Id: в span запроса, в ответ и в ErrorResponse
+        .layer(middleware::from_fn(api::request_id::propagate))
+        // http_requests_total по шаблону маршрута, включая 408 и 413 слоёв выше
+        .layer(middleware::from_fn(api::metrics::count_requests))
+        .with_state(state);
+
+    if prefix.is_empty() {
+        router
+    } else {
+        Router::new().nest(&prefix, router)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    fn prefixed_router() -> Router {
+        let config = Config {
+            route_prefix: "/transcoder".to_string(),
+            ffmpeg_path: transcoder::ffmpeg::testing::fake_ffmpeg("printf 'fake-audio'"),
+            ..Config::default()
+        };
+        build_router(Arc::new(AppState::with_config(10, config).unwrap()))
+    }
+
+    async fn status_of(router: Router, method: &str, uri: &str, body: &'static str) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        router.oneshot(request).await.unwrap().status()
+    }
 
     #[test]
     fn test_app_state_creation() {
-        let state = AppState::new(50);
+        let state = AppState::new(50).unwrap();
         assert_eq!(state.max_concurrent_streams, 50);
         assert_eq!(state.transcode_semaphore.available_permits(), 50);
     }
 
     #[test]
     fn test_app_state_with_different_limits() {
-        let state = AppState::new(10);
+        let state = AppState::new(10).unwrap();
         assert_eq!(state.max_concurrent_streams, 10);
         assert_eq!(state.transcode_semaphore.available_permits(), 10);
     }
+
+    #[tokio::test]
+    async fn test_routes_resolve_under_prefix() {
+        let router = prefixed_router();
+
+        assert_eq!(
+            status_of(router.clone(), "GET", "/transcoder/health", "").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_of(router.clone(), "GET", "/transcoder/metrics", "").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_of(
+                router.clone(),
+                "POST",
+                "/transcoder/api/v1/transcode",
+                r#"{"source_url": "https://example.com/audio.mp3"}"#
+            )
+            .await,
+            StatusCode::OK
+        );
+
+        // Без префикса маршруты недоступны
+        assert_eq!(
+            status_of(router, "GET", "/health", "").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    fn oversized_transcode_request(body: Body) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/api/v1/transcode")
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap()
+    }
+
+    fn oversized_body() -> String {
+        let padding = "a".repeat(Config::default().max_request_body_bytes);
+        format!(r#"{{"source_url": "https://example.com/{}.mp3"}}"#, padding)
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected() {
+        let state = Arc::new(AppState::new(10).unwrap());
+        let request = oversized_transcode_request(Body::from(oversized_body()));
+
+        let response = build_router(state.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(state.sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_chunked_body_is_rejected() {
+        // Без Content-Length лимит срабатывает при чтении body
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = oversized_body()
+            .as_bytes()
+            .chunks(1024)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect();
+        let body = Body::from_stream(futures::stream::iter(chunks));
+        let request = oversized_transcode_request(body);
+
+        let response = build_router(Arc::new(AppState::new(10).unwrap()))
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_body_limit_is_configurable() {
+        let config = Config {
+            max_request_body_bytes: 16,
+            ..Config::default()
+        };
+        let router = build_router(Arc::new(AppState::with_config(10, config).unwrap()));
+
+        let status = status_of(
+            router,
+            "POST",
+            "/api/v1/transcode",
+            r#"{"source_url": "https://example.com/audio.mp3"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
@@ -5,11 +5,13 @@
 
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use tracing::{info, Level};
+use tracing::info;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-use rust_transcoder::{build_router, AppState};
+use rust_transcoder::config::Settings;
+use rust_transcoder::{build_router, shutdown, AppState};
 
 /// Инициализация structured logging с tracing
 fn init_tracing() {
@@ -36,38 +38,35 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Starting Rust FFmpeg Transcoder Microservice");
 
-    // Конфигурация из переменных окружения
-    let port: u16 = std::env::var("PORT")
-        .unwrap_or_else(|_| "8090".to_string())
-        .parse()
-        .expect("PORT must be a valid u16");
-
-    let max_concurrent: usize = std::env::var("MAX_CONCURRENT_STREAMS")
-        .unwrap_or_else(|_| "50".to_string())
-        .parse()
-        .expect("MAX_CONCURRENT_STREAMS must be a valid usize");
-
-    // Создаём shared state
-    let state = Arc::new(AppState::new(max_concurrent));
+    // Настройки: TOML файл из CONFIG_PATH, поверх - переменные окружения
+    let settings = Settings::load()?;
+    let addr = settings.socket_addr();
 
     info!(
-        port = port,
-        max_concurrent_streams = max_concurrent,
+        %addr,
+        max_concurrent_streams = settings.max_concurrent_streams,
+        max_source_duration_secs = ?settings.config.max_source_duration_secs,
+        route_prefix = %settings.config.route_prefix,
         "Configuration loaded"
     );
 
+    // Создаём shared state
+    let state = Arc::new(AppState::with_config(
+        settings.max_concurrent_streams,
+        settings.config,
+    )?);
+
     // Строим router
-    let app = build_router(state);
+    let app = build_router(state.clone());
+    let grace = state.config.shutdown_grace();
 
-    // Биндим на все интерфейсы
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
     info!(%addr, "Server listening");
 
-    // Запускаем сервер
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
+    // Запускаем сервер (ConnectInfo - IP клиента для rate limiting)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal(state, grace))
         .await?;
 
     info!("Server shutdown complete");
@@ -76,7 +75,10 @@ async fn main() -> anyhow::Result<()> {
 }
 
 /// Обработка сигналов завершения для graceful shutdown
-async fn shutdown_signal() {
+///
+/// После сигнала ждёт выполняющиеся транскодирования не дольше `grace`,
+/// только затем axum закрывает соединения.
+async fn shutdown_signal(state: Arc<AppState>, grace: Duration) {
     let ctrl_c = async {
         tokio::signal::ctrl_c()
             .await
@@ -102,4 +104,6 @@ async fn shutdown_signal() {
             info!("Received SIGTERM, initiating graceful shutdown");
         }
     }
+
+    shutdown::drain(&state, grace).await;
 }
\ No newline at end of file
@@ -0,0 +1,125 @@
+//! Prometheus метрики сервиса
+//!
+//! Все метрики регистрируются в default registry, поэтому `/metrics`
+//! получает их через `prometheus::gather()`.
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+};
+
+/// Запросы на транскодирование по формату, кодеку и HTTP статусу ответа
+pub static TRANSCODE_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "transcode_requests_total",
+        "Transcode requests by output format, codec and response status",
+        &["format", "codec", "status"]
+    )
+    .expect("Failed to register transcode_requests_total")
+});
+
+/// Ошибки маршрутов транскодирования по коду ответа (`VALIDATION_ERROR`, ...)
+pub static TRANSCODE_ERRORS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "transcode_errors_total",
+        "Error responses of transcode routes by machine-readable error code",
+        &["code"]
+    )
+    .expect("Failed to register transcode_errors_total")
+});
+
+/// HTTP запросы по шаблону маршрута и статусу ответа
+pub static HTTP_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "HTTP requests by route template and response status",
+        &["path", "status"]
+    )
+    .expect("Failed to register http_requests_total")
+});
+
+/// Длительность транскодирования от приёма запроса до выхода FFmpeg
+pub static TRANSCODE_DURATION_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "transcode_duration_seconds",
+        "Time from request start until FFmpeg exits",
+        vec![0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0]
+    )
+    .expect("Failed to register transcode_duration_seconds")
+});
+
+/// Байты аудио, отданные клиентам
+pub static TRANSCODE_BYTES_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("transcode_bytes_total", "Audio bytes sent to clients")
+        .expect("Failed to register transcode_bytes_total")
+});
+
+/// Выполняющиеся транскодирования (занятые permits семафора)
+pub static ACTIVE_TRANSCODES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "active_transcodes",
+        "Transcodes currently holding a concurrency permit"
+    )
+    .expect("Failed to register active_transcodes")
+});
+
+/// Свободные permits семафора транскодирования
+pub static TRANSCODE_PERMITS_AVAILABLE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "transcode_permits_available",
+        "Concurrency permits currently available for new transcodes"
+    )
+    .expect("Failed to register transcode_permits_available")
+});
+
+/// Ожидание свободного слота при `ACQUIRE_WAIT_MS` > 0
+pub static TRANSCODE_QUEUE_WAIT_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "transcode_queue_wait_seconds",
+        "Time a transcode request waited for a concurrency permit",
+        vec![0.001, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    )
+    .expect("Failed to register transcode_queue_wait_seconds")
+});
+
+/// Регистрирует метрики без меток, чтобы `/metrics` отдавал их с нуля
+/// до первого транскодирования
+pub fn register() {
+    Lazy::force(&TRANSCODE_DURATION_SECONDS);
+    Lazy::force(&TRANSCODE_BYTES_TOTAL);
+    Lazy::force(&ACTIVE_TRANSCODES);
+    Lazy::force(&TRANSCODE_PERMITS_AVAILABLE);
+    Lazy::force(&TRANSCODE_QUEUE_WAIT_SECONDS);
+    Lazy::force(&SHUTDOWN_SESSIONS_DRAINED_TOTAL);
+    Lazy::force(&SHUTDOWN_SESSIONS_KILLED_TOTAL);
+}
+
+/// Время от приёма запроса до первого байта аудио, по формату
+pub static TRANSCODE_TTFB_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "transcode_ttfb_seconds",
+        "Time from request start to the first audio byte sent to the client",
+        &["format"],
+        vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0]
+    )
+    .expect("Failed to register transcode_ttfb_seconds")
+});
+
+/// Транскодирования, завершившиеся сами во время graceful shutdown
+pub static SHUTDOWN_SESSIONS_DRAINED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "shutdown_sessions_drained_total",
+        "Transcodes that finished on their own during graceful shutdown"
+    )
+    .expect("Failed to register shutdown_sessions_drained_total")
+});
+
+/// Транскодирования, прерванные по истечении grace period при shutdown
+pub static SHUTDOWN_SESSIONS_KILLED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "shutdown_sessions_killed_total",
+        "Transcodes still running when the shutdown grace period expired"
+    )
+    .expect("Failed to register shutdown_sessions_killed_total")
+});
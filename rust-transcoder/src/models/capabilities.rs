@@ -0,0 +1,59 @@
+//! Модели capability API
+//!
+//! `GET /api/v1/formats` и `GET /api/v1/codecs` - поддерживаемые значения
+//! `format` и `codec` запроса транскодирования.
+
+use serde::Serialize;
+
+use super::enums::{AudioCodec, AudioFormat};
+
+/// Описание формата (контейнера)
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatInfo {
+    /// Значение поля `format` в запросе
+    pub name: AudioFormat,
+    /// MIME type результата
+    pub content_type: &'static str,
+    /// Имя muxer'а FFmpeg (`-f`)
+    pub ffmpeg_name: &'static str,
+    /// Расширение файла
+    pub extension: &'static str,
+}
+
+impl From<AudioFormat> for FormatInfo {
+    fn from(format: AudioFormat) -> Self {
+        Self {
+            name: format,
+            content_type: format.content_type(),
+            ffmpeg_name: format.ffmpeg_format(),
+            extension: format.extension(),
+        }
+    }
+}
+
+/// Описание кодека
+#[derive(Debug, Clone, Serialize)]
+pub struct CodecInfo {
+    /// Значение поля `codec` в запросе
+    pub name: AudioCodec,
+    /// Имя encoder'а FFmpeg (`-c:a`)
+    pub ffmpeg_name: &'static str,
+    /// Кодирует без потерь
+    pub lossless: bool,
+    /// Форматы, в которые можно записать кодек
+    pub compatible_formats: Vec<AudioFormat>,
+}
+
+impl From<AudioCodec> for CodecInfo {
+    fn from(codec: AudioCodec) -> Self {
+        Self {
+            name: codec,
+            ffmpeg_name: codec.ffmpeg_codec(),
+            lossless: codec.is_lossless(),
+            compatible_formats: AudioFormat::ALL
+                .into_iter()
+                .filter(|&format| codec.is_compatible_with(format))
+                .collect(),
+        }
+    }
+}
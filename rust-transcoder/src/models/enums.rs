@@ -20,9 +20,22 @@ pub enum AudioFormat {
     Wav,
     /// FLAC
     Flac,
+    /// Matroska audio (Opus, AAC, FLAC, MP3)
+    Mka,
 }
 
 impl AudioFormat {
+    /// Все поддерживаемые форматы (для listing API)
+    pub const ALL: [AudioFormat; 7] = [
+        AudioFormat::Opus,
+        AudioFormat::Mp3,
+        AudioFormat::Aac,
+        AudioFormat::Pcm,
+        AudioFormat::Wav,
+        AudioFormat::Flac,
+        AudioFormat::Mka,
+    ];
+
     /// Возвращает MIME type для формата
     pub fn content_type(&self) -> &'static str {
         match self {
@@ -32,6 +45,7 @@ impl AudioFormat {
             AudioFormat::Pcm => "audio/pcm",
             AudioFormat::Wav => "audio/wav",
             AudioFormat::Flac => "audio/flac",
+            AudioFormat::Mka => "audio/x-matroska",
         }
     }
 
@@ -44,6 +58,7 @@ impl AudioFormat {
             AudioFormat::Pcm => "s16le",
             AudioFormat::Wav => "wav",
             AudioFormat::Flac => "flac",
+            AudioFormat::Mka => "matroska",
         }
     }
 
@@ -56,8 +71,47 @@ impl AudioFormat {
             AudioFormat::Pcm => "pcm",
             AudioFormat::Wav => "wav",
             AudioFormat::Flac => "flac",
+            AudioFormat::Mka => "mka",
         }
     }
+
+    /// Требует ли контейнер seekable вывода для полноценного индекса
+    ///
+    /// Matroska пишет cues и длительность в конце файла через seek; в pipe
+    /// они отсутствуют, и плееры не могут перематывать результат.
+    pub fn prefers_seekable_output(&self) -> bool {
+        matches!(self, AudioFormat::Mka)
+    }
+
+    /// Опции muxer'а для вывода в pipe
+    ///
+    /// WAV хранит размеры RIFF и data чанков в заголовке, а в pipe FFmpeg не
+    /// может вернуться и дописать их: остаётся 0xFFFFFFFF, что плееры читают
+    /// как "до конца потока". RF64 на pipe невозможен (тоже требует seek),
+    /// поэтому заголовок всегда обычный RIFF, а `bitexact` убирает тег версии
+    /// энкодера: без пользовательских metadata LIST/INFO чанка нет, и data
+    /// идёт сразу за fmt, как ждут простые парсеры. Цена: длительность до
+    /// конца приёма неизвестна. PCM (`s16le`) заголовка не имеет.
+    pub fn muxer_options(&self) -> &'static [&'static str] {
+        match self {
+            AudioFormat::Wav => &["-rf64", "never", "-fflags", "+bitexact"],
+            _ => &[],
+        }
+    }
+
+    /// Может ли контейнер хранить сэмплы в данном формате
+    pub fn supports_sample_format(&self, sample_fmt: SampleFormat) -> bool {
+        matches!(
+            (self, sample_fmt),
+            (AudioFormat::Wav, _)
+                | (AudioFormat::Mka, _)
+                | (AudioFormat::Pcm, SampleFormat::S16)
+                | (AudioFormat::Flac, SampleFormat::S16 | SampleFormat::S32)
+                | (AudioFormat::Mp3, SampleFormat::S16 | SampleFormat::Flt)
+                | (AudioFormat::Opus, SampleFormat::S16 | SampleFormat::Flt)
+                | (AudioFormat::Aac, SampleFormat::Flt)
+        )
+    }
 }
 
 impl fmt::Display for AudioFormat {
@@ -69,6 +123,7 @@ impl fmt::Display for AudioFormat {
             AudioFormat::Pcm => write!(f, "pcm"),
             AudioFormat::Wav => write!(f, "wav"),
             AudioFormat::Flac => write!(f, "flac"),
+            AudioFormat::Mka => write!(f, "mka"),
         }
     }
 }
@@ -91,6 +146,15 @@ pub enum AudioCodec {
 }
 
 impl AudioCodec {
+    /// Все поддерживаемые кодеки (для listing API)
+    pub const ALL: [AudioCodec; 5] = [
+        AudioCodec::Libopus,
+        AudioCodec::Libmp3lame,
+        AudioCodec::Aac,
+        AudioCodec::PcmS16le,
+        AudioCodec::Flac,
+    ];
+
     /// Возвращает FFmpeg codec name
     pub fn ffmpeg_codec(&self) -> &'static str {
         match self {
@@ -102,6 +166,60 @@ impl AudioCodec {
         }
     }
 
+    /// Кодирует ли кодек без потерь
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, AudioCodec::PcmS16le | AudioCodec::Flac)
+    }
+
+    /// Значение `-sample_fmt` для encoder'а, None - формат не поддерживается
+    ///
+    /// PCM формат сэмплов задаётся выбором кодека (см. `SampleFormat::pcm_codec`).
+    pub fn sample_fmt_arg(&self, sample_fmt: SampleFormat) -> Option<&'static str> {
+        match (self, sample_fmt) {
+            (AudioCodec::Libopus, SampleFormat::S16) => Some("s16"),
+            (AudioCodec::Libopus, SampleFormat::Flt) => Some("flt"),
+            (AudioCodec::Libmp3lame, SampleFormat::S16) => Some("s16p"),
+            (AudioCodec::Libmp3lame, SampleFormat::Flt) => Some("fltp"),
+            (AudioCodec::Aac, SampleFormat::Flt) => Some("fltp"),
+            (AudioCodec::Flac, SampleFormat::S16) => Some("s16"),
+            (AudioCodec::Flac, SampleFormat::S32) => Some("s32"),
+            (AudioCodec::PcmS16le, _) => Some(sample_fmt.pcm_codec()),
+            _ => None,
+        }
+    }
+
+    /// Sample rates, которые принимает encoder; None - любой
+    pub fn supported_sample_rates(&self) -> Option<&'static [u32]> {
+        match self {
+            AudioCodec::Libopus => Some(&[8000, 12000, 16000, 24000, 48000]),
+            AudioCodec::Libmp3lame => {
+                Some(&[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000])
+            }
+            AudioCodec::Aac => Some(&[
+                7350, 8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200,
+                96000,
+            ]),
+            AudioCodec::PcmS16le | AudioCodec::Flac => None,
+        }
+    }
+
+    /// Ближайший поддерживаемый encoder'ом sample rate
+    ///
+    /// Берётся наименьший поддерживаемый не ниже запрошенного (без потери
+    /// полосы), иначе - максимальный поддерживаемый.
+    pub fn snap_sample_rate(&self, sample_rate: u32) -> u32 {
+        let Some(rates) = self.supported_sample_rates() else {
+            return sample_rate;
+        };
+
+        rates
+            .iter()
+            .copied()
+            .find(|&rate| rate >= sample_rate)
+            .or_else(|| rates.last().copied())
+            .unwrap_or(sample_rate)
+    }
+
     /// Проверяет совместимость кодека с форматом
     pub fn is_compatible_with(&self, format: AudioFormat) -> bool {
         matches!(
@@ -112,6 +230,10 @@ impl AudioCodec {
                 | (AudioCodec::PcmS16le, AudioFormat::Pcm)
                 | (AudioCodec::PcmS16le, AudioFormat::Wav)
                 | (AudioCodec::Flac, AudioFormat::Flac)
+                | (AudioCodec::Libopus, AudioFormat::Mka)
+                | (AudioCodec::Aac, AudioFormat::Mka)
+                | (AudioCodec::Flac, AudioFormat::Mka)
+                | (AudioCodec::Libmp3lame, AudioFormat::Mka)
         )
     }
 }
@@ -218,6 +340,8 @@ pub enum EqPreset {
     Voice,
     /// Усиление высоких частот (+4dB @ 8kHz)
     Treble,
+    /// Телефонная полоса (bandpass 300-3400Hz)
+    Telephone,
 }
 
 impl EqPreset {
@@ -228,6 +352,7 @@ impl EqPreset {
             EqPreset::BassBoost => "Enhanced bass (+6dB @ 100Hz)",
             EqPreset::Voice => "Voice optimized (highpass 80Hz, presence boost)",
             EqPreset::Treble => "Enhanced treble (+4dB @ 8kHz)",
+            EqPreset::Telephone => "Telephone band simulation (300-3400Hz)",
         }
     }
 }
@@ -239,6 +364,215 @@ impl fmt::Display for EqPreset {
             EqPreset::BassBoost => write!(f, "bass_boost"),
             EqPreset::Voice => write!(f, "voice"),
             EqPreset::Treble => write!(f, "treble"),
+            EqPreset::Telephone => write!(f, "telephone"),
+        }
+    }
+}
+
+/// Форма кривой fade (параметр `curve` фильтра afade)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FadeCurve {
+    /// Линейная (по умолчанию в FFmpeg)
+    #[default]
+    Tri,
+    /// Четверть синусоиды
+    Qsin,
+    /// Экспоненциальная синусоида
+    Esin,
+    /// Логарифмическая
+    Log,
+    /// Экспоненциальная
+    Exp,
+}
+
+impl fmt::Display for FadeCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FadeCurve::Tri => write!(f, "tri"),
+            FadeCurve::Qsin => write!(f, "qsin"),
+            FadeCurve::Esin => write!(f, "esin"),
+            FadeCurve::Log => write!(f, "log"),
+            FadeCurve::Exp => write!(f, "exp"),
+        }
+    }
+}
+
+/// Профиль кодирования libopus (`-application`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpusApplication {
+    /// Оптимизация разборчивости речи
+    Voip,
+    /// Музыка и смешанный контент (по умолчанию в libopus)
+    Audio,
+    /// Минимальная задержка кодирования
+    Lowdelay,
+}
+
+impl fmt::Display for OpusApplication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpusApplication::Voip => write!(f, "voip"),
+            OpusApplication::Audio => write!(f, "audio"),
+            OpusApplication::Lowdelay => write!(f, "lowdelay"),
+        }
+    }
+}
+
+/// Режим VBR libopus (`-vbr`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpusVbr {
+    /// Переменный битрейт (по умолчанию в libopus)
+    On,
+    /// Постоянный битрейт
+    Off,
+    /// VBR с ограничением отклонения от целевого битрейта
+    Constrained,
+}
+
+impl fmt::Display for OpusVbr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpusVbr::On => write!(f, "on"),
+            OpusVbr::Off => write!(f, "off"),
+            OpusVbr::Constrained => write!(f, "constrained"),
+        }
+    }
+}
+
+/// Длительность смеси с фоновой дорожкой (параметр `duration` фильтра amix)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MixDuration {
+    /// По основному источнику: фон обрезается вместе с ним
+    #[default]
+    First,
+    /// По более длинному входу
+    Longest,
+}
+
+impl fmt::Display for MixDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MixDuration::First => write!(f, "first"),
+            MixDuration::Longest => write!(f, "longest"),
+        }
+    }
+}
+
+/// Тип тестового сигнала (`POST /api/v1/generate`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalKind {
+    /// Синусоида заданной частоты (lavfi `sine`)
+    Sine,
+    /// Тишина (lavfi `anullsrc`)
+    Silence,
+}
+
+/// Вид картинки превью (`POST /api/v1/waveform`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewKind {
+    /// Огибающая сигнала (`showwavespic`)
+    #[default]
+    Waveform,
+    /// Спектрограмма (`showspectrumpic`)
+    Spectrum,
+}
+
+/// Режим двухпроходной нормализации (`normalize_mode`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizeMode {
+    /// Постоянное усиление по результатам измерения (динамика не меняется)
+    Linear,
+    /// Динамическая нормализация с известными параметрами источника
+    Dynamic,
+}
+
+/// Способ изменения скорости (`audio_filters.speed_mode`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedMode {
+    /// Высота тона сохраняется (atempo)
+    #[default]
+    PreservePitch,
+    /// Высота тона меняется вместе со скоростью (asetrate + aresample)
+    ShiftPitch,
+}
+
+/// Именованный пресет профиля (`TranscodeRequest::profile`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProfilePreset {
+    /// Голосовые сообщения Telegram: Opus 64 kbps с нормализацией
+    TelegramVoice,
+    /// Стриминг: Opus 48 kbps без нормализации
+    LowLatency,
+    /// Opus 128 kbps, нормализация к -14 LUFS
+    HighQuality,
+}
+
+impl ProfilePreset {
+    /// Все пресеты
+    pub const ALL: [ProfilePreset; 3] = [
+        ProfilePreset::TelegramVoice,
+        ProfilePreset::LowLatency,
+        ProfilePreset::HighQuality,
+    ];
+
+    /// Имя пресета в запросе
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProfilePreset::TelegramVoice => "telegram_voice",
+            ProfilePreset::LowLatency => "low_latency",
+            ProfilePreset::HighQuality => "high_quality",
+        }
+    }
+
+    /// Пресет по имени из запроса
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+}
+
+impl fmt::Display for ProfilePreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Формат сэмплов выходного потока (`-sample_fmt`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleFormat {
+    /// Signed 16-bit
+    S16,
+    /// Signed 32-bit
+    S32,
+    /// 32-bit float
+    Flt,
+}
+
+impl SampleFormat {
+    /// PCM кодек с данным форматом сэмплов
+    pub fn pcm_codec(&self) -> &'static str {
+        match self {
+            SampleFormat::S16 => "pcm_s16le",
+            SampleFormat::S32 => "pcm_s32le",
+            SampleFormat::Flt => "pcm_f32le",
+        }
+    }
+}
+
+impl fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleFormat::S16 => write!(f, "s16"),
+            SampleFormat::S32 => write!(f, "s32"),
+            SampleFormat::Flt => write!(f, "flt"),
         }
     }
 }
@@ -281,11 +615,54 @@ mod tests {
         assert!(AudioCodec::Aac.is_compatible_with(AudioFormat::Aac));
     }
 
+    #[test]
+    fn test_wav_muxer_options() {
+        let options = AudioFormat::Wav.muxer_options();
+        assert!(options.windows(2).any(|pair| pair == ["-rf64", "never"]));
+        assert!(AudioFormat::Pcm.muxer_options().is_empty());
+        assert!(AudioFormat::Opus.muxer_options().is_empty());
+    }
+
+    #[test]
+    fn test_mka_format() {
+        assert_eq!(AudioFormat::Mka.content_type(), "audio/x-matroska");
+        assert_eq!(AudioFormat::Mka.ffmpeg_format(), "matroska");
+        assert_eq!(AudioFormat::Mka.extension(), "mka");
+        assert!(AudioFormat::Mka.prefers_seekable_output());
+
+        for codec in [
+            AudioCodec::Libopus,
+            AudioCodec::Aac,
+            AudioCodec::Flac,
+            AudioCodec::Libmp3lame,
+        ] {
+            assert!(codec.is_compatible_with(AudioFormat::Mka));
+        }
+        assert!(!AudioCodec::PcmS16le.is_compatible_with(AudioFormat::Mka));
+    }
+
+    #[test]
+    fn test_codec_is_lossless() {
+        assert!(AudioCodec::Flac.is_lossless());
+        assert!(AudioCodec::PcmS16le.is_lossless());
+        assert!(!AudioCodec::Libopus.is_lossless());
+        assert!(!AudioCodec::Aac.is_lossless());
+    }
+
     #[test]
     fn test_quality_bitrate() {
-        assert_eq!(AudioQuality::Medium.bitrate_for_codec(AudioCodec::Libopus), 64);
-        assert_eq!(AudioQuality::High.bitrate_for_codec(AudioCodec::Libmp3lame), 192);
-        assert_eq!(AudioQuality::Lossless.bitrate_for_codec(AudioCodec::PcmS16le), 0);
+        assert_eq!(
+            AudioQuality::Medium.bitrate_for_codec(AudioCodec::Libopus),
+            64
+        );
+        assert_eq!(
+            AudioQuality::High.bitrate_for_codec(AudioCodec::Libmp3lame),
+            192
+        );
+        assert_eq!(
+            AudioQuality::Lossless.bitrate_for_codec(AudioCodec::PcmS16le),
+            0
+        );
     }
 
     #[test]
@@ -294,6 +671,16 @@ mod tests {
         assert_eq!(AudioQuality::High.sample_rate(), 48000);
     }
 
+    #[test]
+    fn test_snap_sample_rate() {
+        assert_eq!(AudioCodec::Libopus.snap_sample_rate(44100), 48000);
+        assert_eq!(AudioCodec::Libopus.snap_sample_rate(24000), 24000);
+        assert_eq!(AudioCodec::Libopus.snap_sample_rate(96000), 48000);
+        assert_eq!(AudioCodec::Libmp3lame.snap_sample_rate(44100), 44100);
+        assert_eq!(AudioCodec::Libmp3lame.snap_sample_rate(96000), 48000);
+        assert_eq!(AudioCodec::Flac.snap_sample_rate(96000), 96000);
+    }
+
     #[test]
     fn test_transcode_status_display() {
         assert_eq!(TranscodeStatus::Processing.to_string(), "processing");
@@ -306,12 +693,52 @@ mod tests {
         assert_eq!(EqPreset::BassBoost.to_string(), "bass_boost");
         assert_eq!(EqPreset::Voice.to_string(), "voice");
         assert_eq!(EqPreset::Treble.to_string(), "treble");
+        assert_eq!(EqPreset::Telephone.to_string(), "telephone");
+    }
+
+    #[test]
+    fn test_eq_preset_telephone_serde() {
+        let preset: EqPreset = serde_json::from_str("\"telephone\"").unwrap();
+        assert_eq!(preset, EqPreset::Telephone);
+        assert!(preset.description().contains("300-3400Hz"));
+    }
+
+    #[test]
+    fn test_fade_curve_serde() {
+        let curve: FadeCurve = serde_json::from_str("\"qsin\"").unwrap();
+        assert_eq!(curve, FadeCurve::Qsin);
+        assert_eq!(curve.to_string(), "qsin");
+        assert!(serde_json::from_str::<FadeCurve>("\"cubic\"").is_err());
+    }
+
+    #[test]
+    fn test_sample_format_container_support() {
+        assert!(AudioFormat::Wav.supports_sample_format(SampleFormat::Flt));
+        assert!(!AudioFormat::Mp3.supports_sample_format(SampleFormat::S32));
+        assert!(!AudioFormat::Pcm.supports_sample_format(SampleFormat::Flt));
+        assert!(AudioFormat::Flac.supports_sample_format(SampleFormat::S32));
+    }
+
+    #[test]
+    fn test_sample_fmt_arg() {
+        assert_eq!(
+            AudioCodec::Libmp3lame.sample_fmt_arg(SampleFormat::Flt),
+            Some("fltp")
+        );
+        assert_eq!(AudioCodec::Aac.sample_fmt_arg(SampleFormat::S16), None);
+        assert_eq!(
+            AudioCodec::PcmS16le.sample_fmt_arg(SampleFormat::Flt),
+            Some("pcm_f32le")
+        );
     }
 
     #[test]
     fn test_eq_preset_description() {
         assert!(!EqPreset::Flat.description().is_empty());
         assert!(EqPreset::BassBoost.description().contains("bass"));
-        assert!(EqPreset::Voice.description().contains("voice") || EqPreset::Voice.description().contains("Voice"));
+        assert!(
+            EqPreset::Voice.description().contains("voice")
+                || EqPreset::Voice.description().contains("Voice")
+        );
     }
 }
@@ -0,0 +1,152 @@
+//! Модели generate API
+//!
+//! `POST /api/v1/generate` - тестовый сигнал (синус или тишина) вместо
+//! внешнего источника, с обычными параметрами результата.
+
+use serde::Deserialize;
+
+use super::enums::SignalKind;
+use super::transcode::TranscodeRequest;
+
+/// Максимальная длительность тестового сигнала в секундах
+pub const MAX_GENERATE_DURATION_SECS: f32 = 600.0;
+
+/// Допустимая частота синуса в Hz
+pub const SINE_FREQUENCY_RANGE_HZ: std::ops::RangeInclusive<u32> = 20..=20000;
+
+/// Поля `TranscodeRequest`, относящиеся к внешнему источнику
+const SOURCE_FIELDS: &str = "source_url, source_urls, preroll_url, background, \
+    source_codec_hint, clamp_channels_to_source, measure_loudness, normalize_mode, copy, \
+    source_headers";
+
+/// Тестовый сигнал
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct TestSignal {
+    /// Тип сигнала
+    pub kind: SignalKind,
+    /// Частота синуса в Hz (по умолчанию 440)
+    #[serde(default)]
+    pub frequency: Option<u32>,
+    /// Длительность в секундах
+    pub duration: f32,
+}
+
+impl TestSignal {
+    /// Валидация длительности и частоты
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.duration > 0.0 && self.duration <= MAX_GENERATE_DURATION_SECS) {
+            return Err(format!(
+                "duration must be greater than 0 and at most {} seconds",
+                MAX_GENERATE_DURATION_SECS
+            ));
+        }
+        match (self.kind, self.frequency) {
+            (SignalKind::Sine, Some(frequency))
+                if !SINE_FREQUENCY_RANGE_HZ.contains(&frequency) =>
+            {
+                Err("frequency must be between 20 and 20000 Hz".to_string())
+            }
+            (SignalKind::Silence, Some(_)) => {
+                Err("frequency is only supported for kind sine".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Запрос на генерацию тестового сигнала
+///
+/// Остальные поля - параметры результата, как в `TranscodeRequest`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GenerateRequest {
+    /// Сигнал (`kind`, `frequency`, `duration`)
+    #[serde(flatten)]
+    pub signal: TestSignal,
+    /// Параметры результата
+    #[serde(flatten)]
+    pub output: TranscodeRequest,
+}
+
+impl GenerateRequest {
+    /// Валидация сигнала и параметров результата
+    pub fn validate_with_allowlist(&self, host_allowlist: &[String]) -> Result<(), String> {
+        self.signal.validate()?;
+
+        let output = &self.output;
+        let uses_source = !output.source_url.is_empty()
+            || !output.source_urls.is_empty()
+            || output.preroll_url.is_some()
+            || output.background.is_some()
+            || output.source_codec_hint.is_some()
+            || output.clamp_channels_to_source == Some(true)
+            || output.measure_loudness == Some(true)
+            || output.normalize_mode.is_some()
+            || output.copy == Some(true)
+            || output.source_headers.is_some();
+        if uses_source {
+            return Err(format!("generate does not accept {}", SOURCE_FIELDS));
+        }
+
+        output.validate_output(host_allowlist)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AudioFormat;
+
+    fn request(json: serde_json::Value) -> GenerateRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_output_fields_are_flattened() {
+        let req = request(serde_json::json!({
+            "kind": "sine",
+            "frequency": 1000,
+            "duration": 2.5,
+            "format": "mp3",
+            "codec": "libmp3lame",
+        }));
+
+        assert!(req.validate_with_allowlist(&[]).is_ok());
+        assert_eq!(req.output.format, AudioFormat::Mp3);
+        // duration сигнала не попадает в duration результата
+        assert_eq!(req.output.duration, None);
+        assert_eq!(req.signal.frequency, Some(1000));
+        assert_eq!(req.signal.duration, 2.5);
+    }
+
+    #[test]
+    fn test_silence_source() {
+        let req = request(serde_json::json!({ "kind": "silence", "duration": 1 }));
+
+        assert!(req.validate_with_allowlist(&[]).is_ok());
+        assert_eq!(req.signal.kind, SignalKind::Silence);
+        assert_eq!(req.signal.frequency, None);
+    }
+
+    #[test]
+    fn test_generate_validation() {
+        let invalid = [
+            serde_json::json!({ "kind": "sine", "duration": 0 }),
+            serde_json::json!({ "kind": "sine", "duration": 601 }),
+            serde_json::json!({ "kind": "sine", "duration": 1, "frequency": 10 }),
+            serde_json::json!({ "kind": "silence", "duration": 1, "frequency": 440 }),
+            serde_json::json!({
+                "kind": "sine",
+                "duration": 1,
+                "source_url": "https://example.com/a.mp3"
+            }),
+            serde_json::json!({ "kind": "sine", "duration": 1, "bitrate": 4 }),
+        ];
+        for json in invalid {
+            assert!(
+                request(json.clone()).validate_with_allowlist(&[]).is_err(),
+                "{} must be rejected",
+                json
+            );
+        }
+    }
+}
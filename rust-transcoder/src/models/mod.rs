@@ -2,9 +2,27 @@
 //!
 //! Содержит все модели запросов/ответов и перечисления.
 
+pub mod capabilities;
 pub mod enums;
+pub mod generate;
+pub mod probe;
+pub mod source;
 pub mod transcode;
+pub mod waveform;
 
 // Re-export основных типов для удобства
-pub use enums::{AudioCodec, AudioFormat, AudioQuality, EqPreset, TranscodeStatus};
-pub use transcode::{AudioFilters, TranscodeRequest, TranscodeResponse, TranscodeStatusResponse};
+pub use capabilities::{CodecInfo, FormatInfo};
+pub use enums::{
+    AudioCodec, AudioFormat, AudioQuality, EqPreset, FadeCurve, MixDuration, NormalizeMode,
+    OpusApplication, OpusVbr, PreviewKind, ProfilePreset, SampleFormat, SignalKind, SpeedMode,
+    TranscodeStatus,
+};
+pub use generate::{GenerateRequest, TestSignal};
+pub use probe::{LoudnessRequest, ProbeRequest, ProbeResponse};
+pub use source::{seek_mode_for, source_is_seekable, SeekMode};
+pub use transcode::{
+    AudioFilters, BackgroundTrack, CompressorSettings, DryRunResponse, EnvelopePoint, EqBand,
+    HlsResponse, LoudnessMeasurement, LoudnessStats, NoiseGateSettings, SilenceInterval,
+    TranscodeRequest, TranscodeResponse, TranscodeStatusResponse, ValidateResponse,
+};
+pub use waveform::WaveformRequest;
@@ -0,0 +1,107 @@
+//! Модели probe API
+//!
+//! `POST /api/v1/probe` - параметры источника через ffprobe до транскодирования,
+//! `POST /api/v1/loudness` - громкость источника без транскодирования.
+
+use serde::{Deserialize, Serialize};
+
+use super::transcode::{default_target_loudness, validate_source_url};
+
+/// Запрос на анализ источника
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProbeRequest {
+    /// URL источника (те же ограничения, что у `TranscodeRequest::source_url`)
+    pub source_url: String,
+}
+
+impl ProbeRequest {
+    /// Валидация запроса с allowlist хостов источника (`SOURCE_HOST_ALLOWLIST`)
+    pub fn validate_with_allowlist(&self, host_allowlist: &[String]) -> Result<(), String> {
+        if self.source_url.is_empty() {
+            return Err("source_url is required".to_string());
+        }
+        validate_source_url(&self.source_url, host_allowlist)
+    }
+}
+
+/// Запрос на измерение громкости источника
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoudnessRequest {
+    /// URL источника (те же ограничения, что у `TranscodeRequest::source_url`)
+    pub source_url: String,
+    /// Цель нормализации в LUFS, от неё зависит `target_offset` ответа
+    #[serde(default = "default_target_loudness")]
+    pub target_loudness: f32,
+}
+
+impl LoudnessRequest {
+    /// Валидация запроса с allowlist хостов источника (`SOURCE_HOST_ALLOWLIST`)
+    pub fn validate_with_allowlist(&self, host_allowlist: &[String]) -> Result<(), String> {
+        if self.source_url.is_empty() {
+            return Err("source_url is required".to_string());
+        }
+        if !(-70.0..=0.0).contains(&self.target_loudness) {
+            return Err("target_loudness must be between -70 and 0 LUFS".to_string());
+        }
+        validate_source_url(&self.source_url, host_allowlist)
+    }
+}
+
+/// Параметры первого аудио потока источника
+///
+/// Поля, которые ffprobe не сообщил (например длительность live-потока), - `null`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResponse {
+    /// Длительность в секундах
+    pub duration: Option<f64>,
+    /// Контейнер (`format_name` ffprobe)
+    pub format: Option<String>,
+    /// Кодек аудио потока
+    pub codec: Option<String>,
+    /// Sample rate в Hz
+    pub sample_rate: Option<u32>,
+    /// Количество каналов
+    pub channels: Option<u8>,
+    /// Битрейт в бит/с
+    pub bitrate: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_request_reuses_source_url_guard() {
+        let request = |url: &str| ProbeRequest {
+            source_url: url.to_string(),
+        };
+
+        assert!(request("https://example.com/a.mp3")
+            .validate_with_allowlist(&[])
+            .is_ok());
+        assert!(request("").validate_with_allowlist(&[]).is_err());
+        assert!(request("file:///etc/passwd")
+            .validate_with_allowlist(&[])
+            .is_err());
+        assert!(request("http://169.254.169.254/latest")
+            .validate_with_allowlist(&[])
+            .is_err());
+        assert!(request("https://other.com/a.mp3")
+            .validate_with_allowlist(&["example.com".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    fn test_loudness_request_defaults_and_limits() {
+        let request: LoudnessRequest =
+            serde_json::from_str(r#"{"source_url": "https://example.com/a.mp3"}"#).unwrap();
+        assert_eq!(request.target_loudness, -16.0);
+        assert!(request.validate_with_allowlist(&[]).is_ok());
+
+        let request = LoudnessRequest {
+            target_loudness: 3.0,
+            ..request
+        };
+        assert!(request.validate_with_allowlist(&[]).is_err());
+    }
+}
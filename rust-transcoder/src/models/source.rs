@@ -0,0 +1,236 @@
+//! Свойства источника аудио, определяемые по URL
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use ipnet::IpNet;
+use once_cell::sync::Lazy;
+use url::{Host, Url};
+
+/// Схемы live-протоколов, которые FFmpeg не может перематывать
+const LIVE_SCHEMES: &[&str] = &[
+    "rtmp", "rtmps", "rtsp", "rtsps", "rtp", "srt", "udp", "tcp", "mms", "mmsh", "pipe",
+];
+
+/// Расширения плейлистов live-вещания
+const PLAYLIST_EXTENSIONS: &[&str] = &["m3u8", "m3u", "pls"];
+
+/// Сети, обращение к которым из FFmpeg запрещено (SSRF)
+static BLOCKED_NETWORKS: Lazy<Vec<IpNet>> = Lazy::new(|| {
+    [
+        // IPv4: loopback, private, link-local (включая cloud metadata), CGNAT,
+        // benchmark, multicast и зарезервированные (вместе с broadcast)
+        "0.0.0.0/8",
+        "10.0.0.0/8",
+        "100.64.0.0/10",
+        "127.0.0.0/8",
+        "169.254.0.0/16",
+        "172.16.0.0/12",
+        "192.168.0.0/16",
+        "198.18.0.0/15",
+        "224.0.0.0/4",
+        "240.0.0.0/4",
+        // IPv6: unspecified, loopback, link-local, unique local, multicast,
+        // NAT64 локального назначения (RFC 8215)
+        "::/128",
+        "::1/128",
+        "fe80::/10",
+        "fc00::/7",
+        "ff00::/8",
+        "64:ff9b:1::/48",
+    ]
+    .iter()
+    .map(|net| net.parse().expect("invalid blocked network"))
+    .collect()
+});
+
+/// Способ позиционирования при обрезке по времени
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekMode {
+    /// `-ss` перед `-i`: быстрый переход по индексу, требует seekable источник
+    Fast,
+    /// `-ss` после `-i`: декодирование с начала и отбрасывание, работает всегда
+    Accurate,
+}
+
+/// Эвристика: можно ли перематывать источник
+///
+/// Live-протоколы (rtmp, rtsp, udp, ...) и HTTP-плейлисты (HLS, m3u, pls)
+/// считаются non-seekable; файлы и обычные HTTP-ресурсы - seekable.
+pub fn source_is_seekable(source_url: &str) -> bool {
+    let Ok(url) = Url::parse(source_url) else {
+        // Не URL - локальный путь к файлу
+        return true;
+    };
+
+    if LIVE_SCHEMES.contains(&url.scheme()) {
+        return false;
+    }
+
+    let extension = url
+        .path()
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+
+    !matches!(extension, Some(ext) if PLAYLIST_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Выбирает способ позиционирования для источника
+pub fn seek_mode_for(source_url: &str) -> SeekMode {
+    if source_is_seekable(source_url) {
+        SeekMode::Fast
+    } else {
+        SeekMode::Accurate
+    }
+}
+
+/// Принадлежит ли адрес закрытым сетям
+///
+/// IPv6 со встроенным IPv4 (`::ffff:127.0.0.1`, NAT64 `64:ff9b::a9fe:a9fe`,
+/// 6to4) проверяется как IPv4: транслятор доставит запрос на него.
+pub fn is_blocked_ip(ip: IpAddr) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => embedded_ipv4(v6).map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    };
+    BLOCKED_NETWORKS.iter().any(|net| net.contains(&ip))
+}
+
+/// IPv4 внутри IPv6: IPv4-mapped (`::ffff:0:0/96`), NAT64 (`64:ff9b::/96`),
+/// IPv4-compatible (`::/96`) и 6to4 (`2002::/16`)
+fn embedded_ipv4(v6: Ipv6Addr) -> Option<Ipv4Addr> {
+    let octets = v6.octets();
+    let ipv4_at = |i: usize| Ipv4Addr::new(octets[i], octets[i + 1], octets[i + 2], octets[i + 3]);
+    match v6.segments() {
+        [0, 0, 0, 0, 0, 0xffff, _, _]
+        | [0x64, 0xff9b, 0, 0, 0, 0, _, _]
+        | [0, 0, 0, 0, 0, 0, _, _] => Some(ipv4_at(12)),
+        [0x2002, ..] => Some(ipv4_at(2)),
+        _ => None,
+    }
+}
+
+/// IP-литерал из host части URL (`[::1]` разбирается как IPv6)
+pub fn host_ip(url: &Url) -> Option<IpAddr> {
+    match url.host()? {
+        Host::Ipv4(v4) => Some(IpAddr::V4(v4)),
+        Host::Ipv6(v6) => Some(IpAddr::V6(v6)),
+        Host::Domain(_) => None,
+    }
+}
+
+/// Имя `localhost` или его поддомен (RFC 6761: всегда loopback)
+pub fn is_localhost_name(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host == "localhost" || host.ends_with(".localhost")
+}
+
+/// Указывает ли URL на закрытый адрес: IP-литерал из закрытых сетей или
+/// имя `localhost`
+///
+/// Остальные доменные имена здесь не резолвятся: их адреса проверяют
+/// `fetch::check_source_host` до запуска FFmpeg и DNS resolver загрузчика
+/// при `FETCH_SOURCE` (`transcoder::fetch`).
+pub fn url_targets_blocked_ip(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => is_localhost_name(domain),
+        Some(_) => host_ip(url).is_some_and(is_blocked_ip),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seekable_file_allows_fast_seek() {
+        assert!(source_is_seekable("https://example.com/audio.mp3"));
+        assert!(source_is_seekable("/data/audio.flac"));
+        assert_eq!(
+            seek_mode_for("https://example.com/audio.mp3"),
+            SeekMode::Fast
+        );
+    }
+
+    #[test]
+    fn test_live_stream_forces_accurate_seek() {
+        assert!(!source_is_seekable("rtmp://live.example.com/app/stream"));
+        assert!(!source_is_seekable("udp://239.0.0.1:1234"));
+        assert_eq!(
+            seek_mode_for("rtsp://camera.local/audio"),
+            SeekMode::Accurate
+        );
+    }
+
+    #[test]
+    fn test_http_playlist_is_not_seekable() {
+        assert!(!source_is_seekable(
+            "https://cdn.example.com/live/index.M3U8"
+        ));
+        assert!(!source_is_seekable("http://radio.example.com/listen.pls"));
+        assert!(source_is_seekable("https://cdn.example.com/m3u8/track.ogg"));
+    }
+
+    fn blocked(url: &str) -> bool {
+        url_targets_blocked_ip(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_ipv6_loopback_is_blocked() {
+        assert!(blocked("http://[::1]/x"));
+        assert!(blocked("http://[::1]:8080/audio.mp3"));
+        assert!(blocked("http://[fe80::1ff:fe23:4567:890a]/a.mp3"));
+        assert!(blocked("http://[fd12:3456:789a::1]/a.mp3"));
+        // IPv4-mapped loopback
+        assert!(blocked("http://[::ffff:127.0.0.1]/a.mp3"));
+    }
+
+    #[test]
+    fn test_ipv6_with_embedded_ipv4_is_checked_as_ipv4() {
+        // NAT64 и 6to4 трансляторы доставят запрос на metadata/loopback
+        assert!(blocked("http://[64:ff9b::a9fe:a9fe]/latest/meta-data/"));
+        assert!(blocked("http://[64:ff9b::169.254.169.254]/latest/meta-data/"));
+        assert!(blocked("http://[2002:7f00:1::]/a.mp3"));
+        assert!(blocked("http://[::127.0.0.1]/a.mp3"));
+        assert!(blocked("http://[64:ff9b:1::808:808]/a.mp3"));
+
+        assert!(!blocked("http://[64:ff9b::808:808]/a.mp3"));
+        assert!(!blocked("http://[2002:808:808::]/a.mp3"));
+    }
+
+    #[test]
+    fn test_reserved_and_multicast_addresses_are_blocked() {
+        assert!(blocked("http://198.18.0.1/a.mp3"));
+        assert!(blocked("http://224.0.0.1/a.mp3"));
+        assert!(blocked("http://239.255.255.250/a.mp3"));
+        assert!(blocked("http://240.0.0.1/a.mp3"));
+        assert!(blocked("http://255.255.255.255/a.mp3"));
+        assert!(blocked("http://[ff02::1]/a.mp3"));
+    }
+
+    #[test]
+    fn test_public_ipv6_is_allowed() {
+        assert!(!blocked("http://[2001:4860:4860::8888]/a.mp3"));
+        assert!(!blocked("https://example.com/a.mp3"));
+    }
+
+    #[test]
+    fn test_localhost_names_are_blocked() {
+        assert!(blocked("http://localhost:8090/"));
+        assert!(blocked("http://LOCALHOST/a.mp3"));
+        assert!(blocked("http://localhost./a.mp3"));
+        assert!(blocked("http://foo.localhost/a.mp3"));
+        assert!(!blocked("http://localhost.example.com/a.mp3"));
+        assert!(!blocked("http://notlocalhost/a.mp3"));
+    }
+
+    #[test]
+    fn test_private_ipv4_is_blocked() {
+        assert!(blocked("http://127.0.0.1/"));
+        assert!(blocked("http://169.254.169.254/latest/meta-data"));
+        assert!(blocked("http://10.1.2.3/a.mp3"));
+        assert!(!blocked("http://8.8.8.8/a.mp3"));
+    }
+}
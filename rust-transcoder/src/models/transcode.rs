@@ -1,14 +1,29 @@
 //! Модели запросов и ответов для транскодирования
 
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::enums::{AudioCodec, AudioFormat, AudioQuality, EqPreset, TranscodeStatus};
+use super::enums::{
+    AudioCodec, AudioFormat, AudioQuality, EqPreset, FadeCurve, MixDuration, NormalizeMode,
+    OpusApplication, OpusVbr, ProfilePreset, SampleFormat, SpeedMode, TranscodeStatus,
+};
+use super::source::{source_is_seekable, url_targets_blocked_ip};
 
 /// Аудио фильтры для транскодирования
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub struct AudioFilters {
+    /// Шумоподавление (фильтр afftdn), сила в dB (`DENOISE_RANGE_DB`): убирает
+    /// постоянное шипение записи
+    #[serde(default)]
+    pub denoise_db: Option<f32>,
+
+    /// Noise gate: подавление низкоуровневого шума между фразами
+    #[serde(default)]
+    pub noise_gate: Option<NoiseGateSettings>,
+
     /// EQ preset (flat, bass_boost, voice, treble)
     #[serde(default)]
     pub eq_preset: Option<EqPreset>,
@@ -17,34 +32,353 @@ pub struct AudioFilters {
     #[serde(default)]
     pub speed: Option<f32>,
 
+    /// Режим изменения скорости: с сохранением высоты тона (по умолчанию) или со сдвигом
+    #[serde(default)]
+    pub speed_mode: Option<SpeedMode>,
+
     /// Множитель громкости (0.0-2.0, где 1.0 = без изменений)
     #[serde(default)]
     pub volume: Option<f32>,
+
+    /// Ширина стерео базы (0.0 = моно, 1.0 = без изменений, 2.0 = шире)
+    #[serde(default)]
+    pub stereo_width: Option<f32>,
+
+    /// Огибающая громкости: точки с линейной интерполяцией gain между ними
+    #[serde(default)]
+    pub volume_envelope: Option<Vec<EnvelopePoint>>,
+
+    /// Параметрический EQ: до `MAX_EQ_BANDS` полос, применяется после `eq_preset`
+    #[serde(default)]
+    pub custom_eq: Option<Vec<EqBand>>,
+
+    /// Компрессор динамического диапазона (фильтр compand) после EQ
+    #[serde(default)]
+    pub compress: Option<CompressorSettings>,
+
+    /// Частота среза highpass в Hz (20-20000): убирает rumble и гул
+    #[serde(default)]
+    pub highpass_hz: Option<u32>,
+
+    /// Частота среза lowpass в Hz (20-20000, выше highpass_hz)
+    #[serde(default)]
+    pub lowpass_hz: Option<u32>,
+
+    /// Limiter true peak в конце цепочки (alimiter); по умолчанию включается
+    /// сам при усилении (`volume` > 1.0 или `bass_boost`), `false` - выключить
+    #[serde(default)]
+    pub limiter: Option<bool>,
+}
+
+/// Параметры noise gate (фильтр agate)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct NoiseGateSettings {
+    /// Порог срабатывания в dBFS (-80..0)
+    #[serde(default = "default_gate_threshold_db")]
+    pub threshold_db: f32,
+    /// Степень ослабления сигнала ниже порога (1-20)
+    #[serde(default = "default_gate_ratio")]
+    pub ratio: f32,
+    /// Время атаки в миллисекундах
+    #[serde(default = "default_gate_attack")]
+    pub attack: f32,
+    /// Время восстановления в миллисекундах
+    #[serde(default = "default_gate_release")]
+    pub release: f32,
+}
+
+fn default_gate_threshold_db() -> f32 {
+    -40.0
+}
+
+fn default_gate_ratio() -> f32 {
+    2.0
+}
+
+fn default_gate_attack() -> f32 {
+    20.0
+}
+
+fn default_gate_release() -> f32 {
+    250.0
+}
+
+impl NoiseGateSettings {
+    /// Валидация диапазонов (ограничения agate)
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-80.0..=0.0).contains(&self.threshold_db) {
+            return Err("noise_gate threshold_db must be between -80 and 0".to_string());
+        }
+        if !(1.0..=20.0).contains(&self.ratio) {
+            return Err("noise_gate ratio must be between 1 and 20".to_string());
+        }
+        if !(0.01..=9000.0).contains(&self.attack) {
+            return Err("noise_gate attack must be between 0.01 and 9000 ms".to_string());
+        }
+        if !(0.01..=9000.0).contains(&self.release) {
+            return Err("noise_gate release must be between 0.01 and 9000 ms".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Параметры компрессора (фильтр compand)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CompressorSettings {
+    /// Время атаки в секундах (0-1]
+    #[serde(default = "default_compressor_attack")]
+    pub attack: f32,
+    /// Время затухания в секундах (0-5]
+    #[serde(default = "default_compressor_decay")]
+    pub decay: f32,
+    /// Порог сжатия в dBFS (-60..0)
+    #[serde(default = "default_compressor_threshold_db")]
+    pub threshold_db: f32,
+}
+
+fn default_compressor_attack() -> f32 {
+    0.02
+}
+
+fn default_compressor_decay() -> f32 {
+    0.25
+}
+
+fn default_compressor_threshold_db() -> f32 {
+    -12.0
+}
+
+impl CompressorSettings {
+    /// Валидация диапазонов
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.attack > 0.0 && self.attack <= 1.0) {
+            return Err("compress attack must be greater than 0 and at most 1 second".to_string());
+        }
+        if !(self.decay > 0.0 && self.decay <= 5.0) {
+            return Err("compress decay must be greater than 0 and at most 5 seconds".to_string());
+        }
+        if !(-60.0..=0.0).contains(&self.threshold_db) {
+            return Err("compress threshold_db must be between -60 and 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Полоса параметрического EQ (фильтр equalizer)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct EqBand {
+    /// Центральная частота в Hz (20-20000)
+    pub frequency: u32,
+    /// Усиление в dB (-24..24)
+    pub gain_db: f32,
+    /// Добротность: чем выше, тем уже полоса (0.1-10)
+    pub q: f32,
+}
+
+impl EqBand {
+    /// Валидация диапазонов полосы
+    pub fn validate(&self) -> Result<(), String> {
+        if !CUTOFF_RANGE_HZ.contains(&self.frequency) {
+            return Err("custom_eq frequency must be between 20 and 20000 Hz".to_string());
+        }
+        if !(-24.0..=24.0).contains(&self.gain_db) {
+            return Err("custom_eq gain_db must be between -24 and 24".to_string());
+        }
+        if !(0.1..=10.0).contains(&self.q) {
+            return Err("custom_eq q must be between 0.1 and 10".to_string());
+        }
+        Ok(())
+    }
 }
 
+/// Точка огибающей громкости
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct EnvelopePoint {
+    /// Время от начала результата в секундах
+    pub time: f32,
+    /// Усиление в dB (0 = без изменений)
+    pub gain_db: f32,
+}
+
+/// Максимальное количество точек огибающей
+pub const MAX_ENVELOPE_POINTS: usize = 64;
+
+/// Максимальное количество полос `custom_eq`
+pub const MAX_EQ_BANDS: usize = 10;
+
+/// Допустимая сила шумоподавления `denoise_db` в dB
+pub const DENOISE_RANGE_DB: std::ops::RangeInclusive<f32> = 1.0..=40.0;
+
+/// Допустимый диапазон частот среза highpass/lowpass в Hz
+pub const CUTOFF_RANGE_HZ: std::ops::RangeInclusive<u32> = 20..=20000;
+
 impl AudioFilters {
     /// Валидация фильтров
     pub fn validate(&self) -> Result<(), String> {
+        // Проверка denoise_db
+        if let Some(strength) = self.denoise_db {
+            if !DENOISE_RANGE_DB.contains(&strength) {
+                return Err(format!(
+                    "denoise_db must be between {} and {} dB",
+                    DENOISE_RANGE_DB.start(),
+                    DENOISE_RANGE_DB.end()
+                ));
+            }
+        }
+
+        // Проверка noise_gate
+        if let Some(ref gate) = self.noise_gate {
+            gate.validate()?;
+        }
+
         // Проверка speed
         if let Some(speed) = self.speed {
-            if speed < 0.5 || speed > 2.0 {
+            if !(0.5..=2.0).contains(&speed) {
                 return Err("speed must be between 0.5 and 2.0".to_string());
             }
         }
 
         // Проверка volume
         if let Some(volume) = self.volume {
-            if volume < 0.0 || volume > 2.0 {
+            if !(0.0..=2.0).contains(&volume) {
                 return Err("volume must be between 0.0 and 2.0".to_string());
             }
         }
 
+        // Проверка stereo_width
+        if let Some(width) = self.stereo_width {
+            if !(0.0..=2.0).contains(&width) {
+                return Err("stereo_width must be between 0.0 and 2.0".to_string());
+            }
+        }
+
+        // Проверка volume_envelope
+        if let Some(ref points) = self.volume_envelope {
+            if points.is_empty() || points.len() > MAX_ENVELOPE_POINTS {
+                return Err(format!(
+                    "volume_envelope must contain 1 to {} points",
+                    MAX_ENVELOPE_POINTS
+                ));
+            }
+            if points.iter().any(|p| p.time < 0.0 || !p.time.is_finite()) {
+                return Err("volume_envelope time must be >= 0".to_string());
+            }
+            if points.iter().any(|p| !(-60.0..=20.0).contains(&p.gain_db)) {
+                return Err("volume_envelope gain_db must be between -60 and 20".to_string());
+            }
+            if points.windows(2).any(|pair| pair[1].time <= pair[0].time) {
+                return Err("volume_envelope points must be strictly ordered by time".to_string());
+            }
+        }
+
+        // Проверка compress
+        if let Some(ref compress) = self.compress {
+            compress.validate()?;
+        }
+
+        // Проверка custom_eq
+        if let Some(ref bands) = self.custom_eq {
+            if bands.is_empty() || bands.len() > MAX_EQ_BANDS {
+                return Err(format!(
+                    "custom_eq must contain 1 to {} bands",
+                    MAX_EQ_BANDS
+                ));
+            }
+            for band in bands {
+                band.validate()?;
+            }
+        }
+
+        // Проверка частот среза
+        let cutoffs = [
+            ("highpass_hz", self.highpass_hz),
+            ("lowpass_hz", self.lowpass_hz),
+        ];
+        for (name, cutoff) in cutoffs {
+            if cutoff.is_some_and(|hz| !CUTOFF_RANGE_HZ.contains(&hz)) {
+                return Err(format!(
+                    "{} must be between {} and {} Hz",
+                    name,
+                    CUTOFF_RANGE_HZ.start(),
+                    CUTOFF_RANGE_HZ.end()
+                ));
+            }
+        }
+        if let (Some(highpass), Some(lowpass)) = (self.highpass_hz, self.lowpass_hz) {
+            if highpass >= lowpass {
+                return Err("highpass_hz must be lower than lowpass_hz".to_string());
+            }
+        }
+
         Ok(())
     }
 
+    /// Поднимают ли фильтры уровень сигнала (риск клиппинга)
+    pub fn boosts_level(&self) -> bool {
+        self.volume.is_some_and(|volume| volume > 1.0)
+            || self.eq_preset == Some(EqPreset::BassBoost)
+    }
+
+    /// Нужен ли limiter: явное значение или автоматически при усилении
+    pub fn limiter_enabled(&self) -> bool {
+        self.limiter.unwrap_or_else(|| self.boosts_level())
+    }
+
     /// Проверяет, есть ли активные фильтры
     pub fn has_filters(&self) -> bool {
-        self.eq_preset.is_some() || self.speed.is_some() || self.volume.is_some()
+        self.denoise_db.is_some()
+            || self.noise_gate.is_some()
+            || self.eq_preset.is_some()
+            || self.speed.is_some()
+            || self.volume.is_some()
+            || self.stereo_width.is_some()
+            || self.volume_envelope.is_some()
+            || self.custom_eq.is_some()
+            || self.compress.is_some()
+            || self.highpass_hz.is_some()
+            || self.lowpass_hz.is_some()
+            || self.limiter == Some(true)
+    }
+}
+
+/// Фоновая дорожка, подмешиваемая под источник (фильтр amix)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BackgroundTrack {
+    /// URL фоновой дорожки
+    pub url: String,
+    /// Уровень фона в dB (-60..12)
+    #[serde(default = "default_background_gain_db")]
+    pub gain_db: f32,
+    /// Уровень основного источника в dB (-60..12)
+    #[serde(default)]
+    pub primary_gain_db: f32,
+    /// Длительность смеси: по источнику или по более длинному входу
+    #[serde(default)]
+    pub duration: MixDuration,
+}
+
+fn default_background_gain_db() -> f32 {
+    -18.0
+}
+
+impl BackgroundTrack {
+    /// Валидация URL (SSRF) и уровней
+    pub fn validate(&self, host_allowlist: &[String]) -> Result<(), String> {
+        validate_source_url(&self.url, host_allowlist)
+            .map_err(|err| format!("background url: {}", err))?;
+        if !source_is_seekable(&self.url) {
+            return Err("background url must be a finite file, not a live stream".to_string());
+        }
+        if !(-60.0..=12.0).contains(&self.gain_db) {
+            return Err("background gain_db must be between -60 and 12".to_string());
+        }
+        if !(-60.0..=12.0).contains(&self.primary_gain_db) {
+            return Err("background primary_gain_db must be between -60 and 12".to_string());
+        }
+        Ok(())
     }
 }
 
@@ -53,9 +387,14 @@ impl AudioFilters {
 #[serde(rename_all = "snake_case")]
 pub struct TranscodeRequest {
     /// URL источника аудио
+    #[serde(default)]
     pub source_url: String,
 
-    /// Целевой формат (opus, mp3, aac, pcm)
+    /// Несколько источников, склеиваемых по порядку (вместо `source_url`)
+    #[serde(default)]
+    pub source_urls: Vec<String>,
+
+    /// Целевой формат (opus, mp3, aac, pcm, wav, flac, mka)
     #[serde(default = "default_format")]
     pub format: AudioFormat,
 
@@ -76,6 +415,10 @@ pub struct TranscodeRequest {
     pub bitrate: Option<u32>,
 
     /// Sample rate в Hz (если не указан - определяется quality)
+    ///
+    /// Для libopus допустимы только 8000/12000/16000/24000/48000: другие значения
+    /// отклоняются. Остальные кодеки приводят неподдерживаемый rate к ближайшему
+    /// (заголовок `X-Sample-Rate-Adjusted`).
     #[serde(default)]
     pub sample_rate: Option<u32>,
 
@@ -83,6 +426,10 @@ pub struct TranscodeRequest {
     #[serde(default)]
     pub channels: Option<u8>,
 
+    /// Формат сэмплов (s16, s32, flt), None - выбирает encoder
+    #[serde(default)]
+    pub sample_fmt: Option<SampleFormat>,
+
     /// Аудио фильтры (speed, volume, eq_preset)
     #[serde(default)]
     pub audio_filters: Option<AudioFilters>,
@@ -102,31 +449,371 @@ pub struct TranscodeRequest {
     /// Применить fade out (секунды)
     #[serde(default)]
     pub fade_out: Option<f32>,
+
+    /// Форма кривой fade in/out (tri, qsin, esin, log, exp)
+    #[serde(default)]
+    pub fade_curve: Option<FadeCurve>,
+
+    /// Запрошенный лимит длительности в секундах (выше серверного - только для ключей со scope)
+    #[serde(default)]
+    pub max_duration_override: Option<u32>,
+
+    /// URL pre-roll клипа, склеиваемого перед источником
+    #[serde(default)]
+    pub preroll_url: Option<String>,
+
+    /// Фоновая дорожка, смешиваемая с источником
+    #[serde(default)]
+    pub background: Option<BackgroundTrack>,
+
+    /// Сглаживать смену параметров потока (по умолчанию - для live-источников)
+    #[serde(default)]
+    pub normalize_stream_params: Option<bool>,
+
+    /// MIME тип ответа вместо стандартного для формата (формат кодирования не меняется)
+    #[serde(default)]
+    pub content_type_override: Option<String>,
+
+    /// Определить границы сегментов по тишине (аудио не изменяется)
+    #[serde(default)]
+    pub detect_segments: Option<bool>,
+
+    /// Не выдавать больше каналов, чем есть в источнике (требует probe)
+    #[serde(default)]
+    pub clamp_channels_to_source: Option<bool>,
+
+    /// Разрешить sample rate выше, чем у источника; по умолчанию (false)
+    /// sample rate ограничивается частотой источника по данным probe
+    #[serde(default)]
+    pub allow_upsample: Option<bool>,
+
+    /// Вещательный режим: loudnorm -16 LUFS + limiter -1 dBTP
+    #[serde(default)]
+    pub broadcast_ready: Option<bool>,
+
+    /// Известный формат источника: FFmpeg пропускает автоопределение (`-f` на входе)
+    #[serde(default)]
+    pub source_codec_hint: Option<String>,
+
+    /// При сбое FFmpeg посреди потока дописать в конец body маркер ошибки
+    /// (формат - `transcoder::stream::FAILURE_MARKER`)
+    #[serde(default)]
+    pub failure_marker: Option<bool>,
+
+    /// Измерить громкость источника (LUFS, LRA, true peak); аудио не изменяется
+    #[serde(default)]
+    pub measure_loudness: Option<bool>,
+
+    /// Начало фрагмента в секундах источника (быстрый seek, требует seekable источник)
+    #[serde(default)]
+    pub start_time: Option<f32>,
+
+    /// Длительность фрагмента в секундах (не больше `MAX_TRIM_DURATION_SECS`)
+    #[serde(default)]
+    pub duration: Option<f32>,
+
+    /// Двухпроходная нормализация (linear, dynamic): сначала измерение, затем
+    /// loudnorm с измеренными значениями. Требует `normalize`
+    #[serde(default)]
+    pub normalize_mode: Option<NormalizeMode>,
+
+    /// Уровень сжатия FLAC (0-12, `-compression_level`); только для кодека flac
+    #[serde(default)]
+    pub compression_level: Option<u8>,
+
+    /// Качество VBR для MP3 (0 - лучшее, 9 - худшее, `-q:a`) вместо битрейта
+    #[serde(default)]
+    pub vbr_quality: Option<u8>,
+
+    /// Профиль libopus (voip, audio, lowdelay); по умолчанию audio
+    #[serde(default)]
+    pub opus_application: Option<OpusApplication>,
+
+    /// Режим VBR libopus (on, off, constrained); по умолчанию on
+    #[serde(default)]
+    pub opus_vbr: Option<OpusVbr>,
+
+    /// Вернуть аргументы FFmpeg (`DryRunResponse`) вместо запуска транскодирования
+    #[serde(default)]
+    pub dry_run: Option<bool>,
+
+    /// Пресет (telegram_voice, low_latency, high_quality) как основа профиля;
+    /// явно заданные поля запроса имеют приоритет над значениями пресета
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    /// Имя файла для `Content-Disposition`; разделители пути отбрасываются,
+    /// расширение формата добавляется. По умолчанию `<session_id>.<ext>`
+    #[serde(default)]
+    pub filename: Option<String>,
+
+    /// Теги результата (title, artist, album, comment) - ID3/Vorbis comments
+    #[serde(default)]
+    pub metadata: Option<HashMap<String, String>>,
+
+    /// Сохранить теги источника; по умолчанию они удаляются (`-map_metadata -1`)
+    #[serde(default)]
+    pub preserve_metadata: Option<bool>,
+
+    /// Только смена контейнера: поток копируется без перекодирования (`-c:a copy`)
+    #[serde(default)]
+    pub copy: Option<bool>,
+
+    /// Заголовки GET запроса к источнику (`SOURCE_HEADER_NAMES`), например
+    /// токен CDN; передаются только при загрузке сервисом (`FETCH_SOURCE`).
+    /// Редирект на другой хост или порт с ними отклоняется
+    #[serde(default)]
+    pub source_headers: Option<HashMap<String, String>>,
+
+    /// Сохранить сегменты HLS, записанные до отмены сессии (`DELETE`,
+    /// остановка сервиса): статус `cancelled` с `partial: true`
+    #[serde(default)]
+    pub allow_partial_on_cancel: Option<bool>,
+}
+
+/// Максимальная длина `filename` в символах
+pub const MAX_FILENAME_LEN: usize = 255;
+
+/// Теги, которые можно задать через `metadata`
+pub const METADATA_KEYS: &[&str] = &["title", "artist", "album", "comment"];
+
+/// Максимальная длина значения тега в символах
+pub const MAX_METADATA_VALUE_LEN: usize = 1024;
+
+/// Значение тега без управляющих символов (переводы строк, NUL) и пробелов по краям
+fn sanitize_metadata_value(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Заголовки, которые можно передать источнику через `source_headers`
+///
+/// Только авторизация и идентификация клиента: `Host`, `Content-Length`,
+/// `Transfer-Encoding` и подобные меняют разбор запроса (request smuggling).
+pub const SOURCE_HEADER_NAMES: &[&str] =
+    &["authorization", "cookie", "referer", "user-agent", "x-api-key"];
+
+/// Максимальная длина значения заголовка источника в символах
+pub const MAX_SOURCE_HEADER_VALUE_LEN: usize = 4096;
+
+/// Поля, которым нужен URL источника: у загруженного файла его нет, а
+/// stdin FFmpeg читается один раз (ни probe, ни второго прохода)
+const URL_SOURCE_FIELDS: &str = "source_url, source_urls, source_headers, preroll_url, \
+    background, fade_out, clamp_channels_to_source, measure_loudness, normalize_mode";
+
+/// Кодеки вывода HLS: их воспроизводят браузерные плееры
+pub const HLS_CODECS: [AudioCodec; 3] = [AudioCodec::Aac, AudioCodec::Libmp3lame, AudioCodec::Libopus];
+
+/// Поля, не имеющие смысла для HLS: результат - файлы, а не поток ответа,
+/// и параметры, требующие probe или второго прохода
+const HLS_UNSUPPORTED_FIELDS: &str = "copy, dry_run, source_headers, fade_out, \
+    clamp_channels_to_source, measure_loudness, normalize_mode, detect_segments, \
+    failure_marker, filename, content_type_override";
+
+/// Максимальное число источников в `source_urls`
+pub const MAX_SOURCE_URLS: usize = 10;
+
+/// Поля, несовместимые с `source_urls`: относятся к одному входу FFmpeg
+const SINGLE_SOURCE_FIELDS: &str =
+    "preroll_url, start_time, fade_out, source_codec_hint, measure_loudness, normalize_mode";
+
+/// Последний компонент пути без управляющих символов и кавычек
+fn sanitize_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .collect();
+    // "." и ".." как имя файла - тот же обход пути
+    cleaned.trim().trim_start_matches('.').to_string()
+}
+
+/// Percent-encoding для `filename*` (RFC 5987: без кодирования только attr-char)
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Максимальная длительность фрагмента в `duration` (6 часов)
+pub const MAX_TRIM_DURATION_SECS: f32 = 21600.0;
+
+/// Demuxers FFmpeg, допустимые в `source_codec_hint`
+pub const KNOWN_INPUT_DEMUXERS: &[&str] = &[
+    "aac", "flac", "matroska", "mov", "mp3", "mp4", "ogg", "wav", "webm",
+];
+
+/// Схемы, разрешённые для внешних аудио URL
+pub const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https"];
+
+/// Облачные схемы `source_url`: подписываются в `transcoder::cloud`
+pub const CLOUD_SOURCE_SCHEMES: &[&str] = &["s3", "gs"];
+
+/// Проверяет, что строка - корректный MIME тип вида `type/subtype[; param=value]`
+fn is_valid_mime(value: &str) -> bool {
+    fn is_token(s: &str) -> bool {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    }
+
+    let mut parts = value.split(';');
+    let essence = parts.next().unwrap_or_default().trim();
+    let valid_essence = essence
+        .split_once('/')
+        .is_some_and(|(kind, subtype)| is_token(kind) && is_token(subtype));
+
+    valid_essence
+        && parts.all(|param| {
+            param.trim().split_once('=').is_some_and(|(name, value)| {
+                is_token(name.trim()) && !value.trim().is_empty() && value.is_ascii()
+            })
+        })
+}
+
+/// Проверяет дополнительный http(s) URL (без подписи облачных схем)
+///
+/// Хост проверяется как у `source_url`: allowlist и закрытые адреса.
+fn validate_remote_url(field: &str, value: &str, host_allowlist: &[String]) -> Result<(), String> {
+    let url = url::Url::parse(value).map_err(|_| format!("{} must be a valid URL", field))?;
+    if !ALLOWED_URL_SCHEMES.contains(&url.scheme()) {
+        return Err(format!(
+            "{} scheme '{}' is not allowed (allowed: {})",
+            field,
+            url.scheme(),
+            ALLOWED_URL_SCHEMES.join(", ")
+        ));
+    }
+    validate_source_url(value, host_allowlist).map_err(|err| format!("{}: {}", field, err))
+}
+
+/// Проверяет `source_url` перед передачей в `-i` FFmpeg (SSRF)
+///
+/// Разрешены http(s) и облачные `s3://`/`gs://` (их presigned URL
+/// проверяется так же в `cloud::resolve_source_url`); `file://`, локальные
+/// пути и прочие протоколы FFmpeg отклоняются. Непустой `host_allowlist`
+/// ограничивает http(s) источники перечисленными хостами (`example.com`
+/// или `.example.com` вместе с поддоменами); хост из allowlist может быть и
+/// закрытым адресом, остальные IP-литералы из закрытых сетей запрещены.
+/// Доменные имена здесь не резолвятся: перед запуском ffprobe и FFmpeg их
+/// проверяет `fetch::check_source_host`. FFmpeg резолвит имя повторно и сам
+/// следует редиректам; редиректы и подмену DNS закрывает только
+/// `FETCH_SOURCE` (или сетевая политика).
+pub fn validate_source_url(value: &str, host_allowlist: &[String]) -> Result<(), String> {
+    let url = url::Url::parse(value).map_err(|_| "source_url must be a valid URL".to_string())?;
+    let scheme = url.scheme();
+
+    if CLOUD_SOURCE_SCHEMES.contains(&scheme) {
+        return Ok(());
+    }
+    if !ALLOWED_URL_SCHEMES.contains(&scheme) {
+        return Err(format!(
+            "source_url scheme '{}' is not allowed (allowed: {})",
+            scheme,
+            [ALLOWED_URL_SCHEMES, CLOUD_SOURCE_SCHEMES]
+                .concat()
+                .join(", ")
+        ));
+    }
+
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let allowlisted = host_is_allowlisted(&host, host_allowlist);
+    if !host_allowlist.is_empty() && !allowlisted {
+        return Err(format!(
+            "source_url host '{}' is not in the allowlist",
+            host
+        ));
+    }
+    if !allowlisted && url_targets_blocked_ip(&url) {
+        return Err("source_url must not point to a loopback or private address".to_string());
+    }
+
+    Ok(())
+}
+
+/// Входит ли хост в `SOURCE_HOST_ALLOWLIST`
+pub fn host_is_allowlisted(host: &str, host_allowlist: &[String]) -> bool {
+    let host = host.to_ascii_lowercase();
+    host_allowlist
+        .iter()
+        .any(|entry| host_matches(&host, entry))
+}
+
+/// `entry` - точный хост или `.domain` для домена с поддоменами
+fn host_matches(host: &str, entry: &str) -> bool {
+    let entry = entry.trim().to_ascii_lowercase();
+    match entry.strip_prefix('.') {
+        Some(domain) => host == domain || host.ends_with(&entry),
+        // IPv6 в host_str() - в квадратных скобках
+        None => host == entry || host.trim_start_matches('[').trim_end_matches(']') == entry,
+    }
 }
 
-fn default_format() -> AudioFormat {
+pub(crate) fn default_format() -> AudioFormat {
     AudioFormat::Opus
 }
 
-fn default_codec() -> AudioCodec {
+pub(crate) fn default_codec() -> AudioCodec {
     AudioCodec::Libopus
 }
 
-fn default_target_loudness() -> f32 {
+pub(crate) fn default_target_loudness() -> f32 {
     -16.0
 }
 
 impl TranscodeRequest {
     /// Валидация запроса
     pub fn validate(&self) -> Result<(), String> {
-        // Проверка URL
-        if self.source_url.is_empty() {
-            return Err("source_url is required".to_string());
+        self.validate_with_allowlist(&[])
+    }
+
+    /// Валидация запроса с allowlist хостов источника (`SOURCE_HOST_ALLOWLIST`)
+    pub fn validate_with_allowlist(&self, host_allowlist: &[String]) -> Result<(), String> {
+        // Проверка URL: один источник или список для склейки
+        if self.source_urls.is_empty() {
+            if self.source_url.is_empty() {
+                return Err("source_url is required".to_string());
+            }
+            validate_source_url(&self.source_url, host_allowlist)?;
+        } else {
+            self.validate_source_urls(host_allowlist)?;
+        }
+
+        if let Some(ref headers) = self.source_headers {
+            self.validate_source_headers(headers)?;
+        }
+
+        self.validate_output(host_allowlist)
+    }
+
+    /// Валидация параметров без проверки источника
+    ///
+    /// Отдельно нужна для сгенерированного источника (`POST /api/v1/generate`).
+    pub fn validate_output(&self, host_allowlist: &[String]) -> Result<(), String> {
+        // Проверка пресета
+        if let Some(name) = &self.profile {
+            if ProfilePreset::from_name(name).is_none() {
+                return Err(format!(
+                    "unknown profile '{}' (allowed: {})",
+                    name,
+                    ProfilePreset::ALL.map(|preset| preset.name()).join(", ")
+                ));
+            }
         }
 
         // Проверка битрейта
         if let Some(bitrate) = self.bitrate {
-            if bitrate < 8 || bitrate > 512 {
+            if !(8..=512).contains(&bitrate) {
                 return Err("bitrate must be between 8 and 512 kbps".to_string());
             }
         }
@@ -135,16 +822,56 @@ impl TranscodeRequest {
         if let Some(sr) = self.sample_rate {
             let valid_rates = [8000, 12000, 16000, 24000, 44100, 48000, 96000];
             if !valid_rates.contains(&sr) {
+                return Err(format!("sample_rate must be one of: {:?}", valid_rates));
+            }
+        }
+
+        // libopus кодирует только в своих rate; явный 44.1k - почти всегда
+        // скопированная настройка, молча менять её не стоит
+        if let (Some(sr), AudioCodec::Libopus) = (self.sample_rate, self.codec) {
+            let opus_rates = self.codec.supported_sample_rates().unwrap_or_default();
+            if !opus_rates.contains(&sr) {
                 return Err(format!(
-                    "sample_rate must be one of: {:?}",
-                    valid_rates
+                    "sample_rate {} is not supported by libopus, use one of: {:?}",
+                    sr, opus_rates
                 ));
             }
         }
 
+        // Параметры энкодеров
+        if let Some(level) = self.compression_level {
+            if self.codec != AudioCodec::Flac {
+                return Err("compression_level is only supported for the flac codec".to_string());
+            }
+            if level > 12 {
+                return Err("compression_level must be between 0 and 12".to_string());
+            }
+        }
+
+        if let Some(quality) = self.vbr_quality {
+            if self.codec != AudioCodec::Libmp3lame {
+                return Err("vbr_quality is only supported for the libmp3lame codec".to_string());
+            }
+            if quality > 9 {
+                return Err("vbr_quality must be between 0 and 9".to_string());
+            }
+            if self.bitrate.is_some() {
+                return Err("vbr_quality cannot be combined with bitrate".to_string());
+            }
+        }
+
+        if (self.opus_application.is_some() || self.opus_vbr.is_some())
+            && self.codec != AudioCodec::Libopus
+        {
+            return Err(
+                "opus_application and opus_vbr are only supported for the libopus codec"
+                    .to_string(),
+            );
+        }
+
         // Проверка каналов
         if let Some(ch) = self.channels {
-            if ch < 1 || ch > 2 {
+            if !(1..=2).contains(&ch) {
                 return Err("channels must be 1 (mono) or 2 (stereo)".to_string());
             }
         }
@@ -152,145 +879,1386 @@ impl TranscodeRequest {
         // Проверка audio_filters
         if let Some(ref filters) = self.audio_filters {
             filters.validate()?;
+
+            if filters.stereo_width.is_some() && self.channels == Some(1) {
+                return Err("stereo_width requires stereo output (channels = 2)".to_string());
+            }
+
+            // Длительность источника до probe неизвестна - проверяем по запрошенному лимиту
+            let last_point = filters
+                .volume_envelope
+                .as_ref()
+                .and_then(|points| points.last());
+            if let (Some(point), Some(limit)) = (last_point, self.max_duration_override) {
+                if point.time > limit as f32 {
+                    return Err(
+                        "volume_envelope points must be within max_duration_override".to_string(),
+                    );
+                }
+            }
         }
 
         // Проверка fade
         if let Some(fade) = self.fade_in {
-            if fade < 0.0 || fade > 30.0 {
+            if !(0.0..=30.0).contains(&fade) {
                 return Err("fade_in must be between 0 and 30 seconds".to_string());
             }
         }
 
         if let Some(fade) = self.fade_out {
-            if fade < 0.0 || fade > 30.0 {
+            if !(0.0..=30.0).contains(&fade) {
                 return Err("fade_out must be between 0 and 30 seconds".to_string());
             }
+            // Начало fade out считается от длительности, у live её нет
+            if !source_is_seekable(&self.source_url) {
+                return Err("fade_out requires a seekable source, not a live stream".to_string());
+            }
         }
 
         // Проверка target_loudness
-        if self.target_loudness < -70.0 || self.target_loudness > 0.0 {
+        if !(-70.0..=0.0).contains(&self.target_loudness) {
             return Err("target_loudness must be between -70 and 0 LUFS".to_string());
         }
 
-        Ok(())
-    }
-}
+        // Проверка max_duration_override
+        if self.max_duration_override == Some(0) {
+            return Err("max_duration_override must be greater than 0".to_string());
+        }
 
-/// Начальный ответ при старте транскодирования
+        // Проверка обрезки
+        if let Some(start) = self.start_time {
+            if !start.is_finite() || start < 0.0 {
+                return Err("start_time must be a non-negative number of seconds".to_string());
+            }
+            // `-ss` перед `-i` требует перемотки по индексу
+            if start > 0.0 && !source_is_seekable(&self.source_url) {
+                return Err("start_time requires a seekable source, not a live stream".to_string());
+            }
+        }
+
+        if let Some(duration) = self.duration {
+            if !(duration > 0.0 && duration <= MAX_TRIM_DURATION_SECS) {
+                return Err(format!(
+                    "duration must be greater than 0 and at most {} seconds",
+                    MAX_TRIM_DURATION_SECS
+                ));
+            }
+        }
+
+        // Двухпроходная нормализация читает источник дважды
+        if self.normalize_mode.is_some() {
+            if !self.normalize {
+                return Err("normalize_mode requires normalize=true".to_string());
+            }
+            if !source_is_seekable(&self.source_url) {
+                return Err(
+                    "normalize_mode requires a seekable source, not a live stream".to_string(),
+                );
+            }
+        }
+
+        // broadcast_ready сам задаёт громкость - ручные настройки конфликтуют
+        if self.broadcast_ready == Some(true) {
+            let manual_volume = self
+                .audio_filters
+                .as_ref()
+                .is_some_and(|f| f.volume.is_some() || f.volume_envelope.is_some());
+            if self.normalize || manual_volume {
+                return Err(
+                    "broadcast_ready cannot be combined with normalize or volume settings"
+                        .to_string(),
+                );
+            }
+        }
+
+        // Проверка source_codec_hint
+        if let Some(ref hint) = self.source_codec_hint {
+            if !KNOWN_INPUT_DEMUXERS.contains(&hint.as_str()) {
+                return Err(format!(
+                    "source_codec_hint must be one of: {}",
+                    KNOWN_INPUT_DEMUXERS.join(", ")
+                ));
+            }
+        }
+
+        if let Some(ref filename) = self.filename {
+            if filename.chars().count() > MAX_FILENAME_LEN {
+                return Err(format!(
+                    "filename must be at most {} characters",
+                    MAX_FILENAME_LEN
+                ));
+            }
+        }
+
+        if self.copy == Some(true) {
+            self.validate_copy()?;
+        }
+
+        // Проверка тегов: только известные ключи
+        if let Some(ref metadata) = self.metadata {
+            for (key, value) in metadata {
+                if !METADATA_KEYS.contains(&key.as_str()) {
+                    return Err(format!(
+                        "metadata key '{}' is not allowed (allowed: {})",
+                        key,
+                        METADATA_KEYS.join(", ")
+                    ));
+                }
+                if value.chars().count() > MAX_METADATA_VALUE_LEN {
+                    return Err(format!(
+                        "metadata {} must be at most {} characters",
+                        key, MAX_METADATA_VALUE_LEN
+                    ));
+                }
+            }
+        }
+
+        // Проверка content_type_override
+        if let Some(ref content_type) = self.content_type_override {
+            if !is_valid_mime(content_type) {
+                return Err("content_type_override must be a valid MIME type".to_string());
+            }
+        }
+
+        // Фон смешивается только с одиночным источником
+        if let Some(ref background) = self.background {
+            background.validate(host_allowlist)?;
+            if self.preroll_url.is_some() || !self.source_urls.is_empty() {
+                return Err(
+                    "background cannot be combined with preroll_url or source_urls".to_string(),
+                );
+            }
+            if self.normalize_mode.is_some() {
+                return Err("background cannot be combined with normalize_mode".to_string());
+            }
+        }
+
+        // Проверка preroll_url: декодированные потоки приводятся к общему формату
+        // перед concat, поэтому ограничение только на источник
+        if let Some(ref preroll_url) = self.preroll_url {
+            validate_remote_url("preroll_url", preroll_url, host_allowlist)?;
+            if !source_is_seekable(preroll_url) {
+                return Err("preroll_url must be a finite file, not a live stream".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Валидация параметров для файла из `POST /api/v1/transcode/upload`
+    pub fn validate_upload(&self) -> Result<(), String> {
+        let uses_url = !self.source_url.is_empty()
+            || !self.source_urls.is_empty()
+            || self.source_headers.is_some()
+            || self.preroll_url.is_some()
+            || self.background.is_some()
+            || self.fade_out.is_some()
+            || self.clamp_channels_to_source == Some(true)
+            || self.measure_loudness == Some(true)
+            || self.normalize_mode.is_some();
+        if uses_url {
+            return Err(format!("upload does not accept {}", URL_SOURCE_FIELDS));
+        }
+
+        self.validate_output(&[])
+    }
+
+    /// Дополнительные ограничения `POST /api/v1/transcode/hls`
+    ///
+    /// Общая валидация (`validate_with_allowlist`) выполняется отдельно.
+    pub fn validate_hls(&self) -> Result<(), String> {
+        if !HLS_CODECS.contains(&self.codec) {
+            return Err(format!(
+                "codec {} is not supported for HLS (allowed: {})",
+                self.codec,
+                HLS_CODECS.map(|codec| codec.to_string()).join(", ")
+            ));
+        }
+
+        let unsupported = self.copy == Some(true)
+            || self.dry_run == Some(true)
+            || self.source_headers.is_some()
+            || self.fade_out.is_some()
+            || self.clamp_channels_to_source == Some(true)
+            || self.measure_loudness == Some(true)
+            || self.normalize_mode.is_some()
+            || self.detect_segments == Some(true)
+            || self.failure_marker.is_some()
+            || self.filename.is_some()
+            || self.content_type_override.is_some();
+        if unsupported {
+            return Err(format!("hls does not accept {}", HLS_UNSUPPORTED_FIELDS));
+        }
+        Ok(())
+    }
+
+    /// Проверка `source_urls`: каждый URL проходит ту же защиту от SSRF, что
+    /// и `source_url`; все источники, кроме последнего, должны быть конечными
+    fn validate_source_urls(&self, host_allowlist: &[String]) -> Result<(), String> {
+        if !self.source_url.is_empty() {
+            return Err("source_url and source_urls are mutually exclusive".to_string());
+        }
+        if !(2..=MAX_SOURCE_URLS).contains(&self.source_urls.len()) {
+            return Err(format!(
+                "source_urls must contain between 2 and {} URLs",
+                MAX_SOURCE_URLS
+            ));
+        }
+
+        let last = self.source_urls.len() - 1;
+        for (index, url) in self.source_urls.iter().enumerate() {
+            validate_source_url(url, host_allowlist)
+                .map_err(|err| format!("source_urls[{}]: {}", index, err))?;
+            if index < last && !source_is_seekable(url) {
+                return Err(format!(
+                    "source_urls[{}] must be a finite file, only the last source may be live",
+                    index
+                ));
+            }
+        }
+
+        let single_source_only = self.preroll_url.is_some()
+            || self.start_time.is_some()
+            || self.fade_out.is_some()
+            || self.source_codec_hint.is_some()
+            || self.measure_loudness == Some(true)
+            || self.normalize_mode.is_some();
+        if single_source_only {
+            return Err(format!(
+                "source_urls cannot be combined with {}",
+                SINGLE_SOURCE_FIELDS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Проверка `source_headers`: имена из `SOURCE_HEADER_NAMES`, значения без
+    /// управляющих символов (перевод строки начал бы новый заголовок)
+    fn validate_source_headers(&self, headers: &HashMap<String, String>) -> Result<(), String> {
+        if !self.source_urls.is_empty() || self.preroll_url.is_some() || self.background.is_some()
+        {
+            return Err(
+                "source_headers cannot be combined with source_urls, preroll_url or background"
+                    .to_string(),
+            );
+        }
+
+        for (name, value) in headers {
+            if !SOURCE_HEADER_NAMES.contains(&name.to_ascii_lowercase().as_str()) {
+                return Err(format!(
+                    "source_headers: header '{}' is not allowed (allowed: {})",
+                    name,
+                    SOURCE_HEADER_NAMES.join(", ")
+                ));
+            }
+            if value.is_empty() || value.chars().count() > MAX_SOURCE_HEADER_VALUE_LEN {
+                return Err(format!(
+                    "source_headers: {} must be between 1 and {} characters",
+                    name, MAX_SOURCE_HEADER_VALUE_LEN
+                ));
+            }
+            if !value.chars().all(|c| c == '\t' || (' '..='~').contains(&c)) {
+                return Err(format!(
+                    "source_headers: {} must contain printable ASCII only",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remux несовместим со всем, что требует декодирования потока
+    fn validate_copy(&self) -> Result<(), String> {
+        let has_filters = self.audio_filters.as_ref().is_some_and(|f| f.has_filters());
+        let conflicts: Vec<&str> = [
+            ("audio_filters", has_filters),
+            ("normalize", self.normalize || self.normalize_mode.is_some()),
+            ("fade_in", self.fade_in.is_some()),
+            ("fade_out", self.fade_out.is_some()),
+            ("broadcast_ready", self.broadcast_ready == Some(true)),
+            ("detect_segments", self.detect_segments == Some(true)),
+            ("preroll_url", self.preroll_url.is_some()),
+            ("source_urls", !self.source_urls.is_empty()),
+            ("background", self.background.is_some()),
+            ("profile", self.profile.is_some()),
+            ("bitrate", self.bitrate.is_some()),
+            ("sample_rate", self.sample_rate.is_some()),
+            ("channels", self.channels.is_some()),
+            ("sample_fmt", self.sample_fmt.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(field, _)| field)
+        .collect();
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("copy cannot be combined with {}", conflicts.join(", ")))
+        }
+    }
+
+    /// Теги для `-metadata` с очищенными значениями, в порядке ключей
+    ///
+    /// Пустые после очистки значения пропускаются.
+    pub fn metadata_tags(&self) -> BTreeMap<String, String> {
+        self.metadata
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), sanitize_metadata_value(value)))
+            .filter(|(_, value)| !value.is_empty())
+            .collect()
+    }
+
+    /// Content-Type результата: override из запроса или MIME формата
+    pub fn content_type(&self) -> String {
+        self.content_type_override
+            .clone()
+            .unwrap_or_else(|| self.format.content_type().to_string())
+    }
+
+    /// Значение `Content-Disposition` для результата
+    ///
+    /// `filename` из запроса без пути и управляющих символов, иначе
+    /// `session_id`; расширение формата добавляется, если его нет. Не-ASCII
+    /// имя передаётся в `filename*` (RFC 6266), в `filename` - ASCII замена.
+    pub fn content_disposition(&self, session_id: Uuid) -> String {
+        let extension = self.format.extension();
+        let stem = self
+            .filename
+            .as_deref()
+            .map(sanitize_filename)
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| session_id.to_string());
+
+        let filename = if stem
+            .to_ascii_lowercase()
+            .ends_with(&format!(".{}", extension))
+        {
+            stem
+        } else {
+            format!("{}.{}", stem, extension)
+        };
+
+        let ascii: String = filename
+            .chars()
+            .map(|c| if c.is_ascii() { c } else { '_' })
+            .collect();
+
+        if ascii == filename {
+            format!("attachment; filename=\"{}\"", filename)
+        } else {
+            format!(
+                "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+                ascii,
+                percent_encode(&filename)
+            )
+        }
+    }
+
+    /// Нужна ли компенсация смены sample rate/каналов посреди потока
+    ///
+    /// Явное значение из запроса имеет приоритет, иначе включено для live-источников.
+    pub fn normalize_stream_params(&self) -> bool {
+        self.normalize_stream_params
+            .unwrap_or_else(|| !source_is_seekable(&self.source_url))
+    }
+
+    /// Пресет из `profile` (None - не задан или неизвестен)
+    pub fn profile_preset(&self) -> Option<ProfilePreset> {
+        self.profile.as_deref().and_then(ProfilePreset::from_name)
+    }
+
+    /// Проверяет, что кодек можно записать в запрошенный контейнер
+    ///
+    /// Ошибка - текст для `AppError::UnsupportedFormat`.
+    pub fn check_codec_format(&self) -> Result<(), String> {
+        if self.codec.is_compatible_with(self.format) {
+            Ok(())
+        } else {
+            Err(format!(
+                "codec '{}' is not supported by {} container",
+                self.codec, self.format
+            ))
+        }
+    }
+
+    /// Проверяет, что запрошенный формат сэмплов поддерживают контейнер и кодек
+    ///
+    /// Ошибка - текст для `AppError::UnsupportedFormat`.
+    pub fn check_sample_format(&self) -> Result<(), String> {
+        let Some(sample_fmt) = self.sample_fmt else {
+            return Ok(());
+        };
+
+        if !self.format.supports_sample_format(sample_fmt) {
+            return Err(format!(
+                "sample_fmt '{}' is not supported by {} container",
+                sample_fmt, self.format
+            ));
+        }
+        if self.codec.sample_fmt_arg(sample_fmt).is_none() {
+            return Err(format!(
+                "sample_fmt '{}' is not supported by {} codec",
+                sample_fmt, self.codec
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Предупреждения о неочевидной интерпретации параметров запроса
+    ///
+    /// Не блокируют транскодирование, отдаются клиенту в заголовках ответа.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        // quality=lossless для lossy кодека - это только максимальный битрейт
+        if self.quality == AudioQuality::Lossless
+            && !self.codec.is_lossless()
+            && self.bitrate.is_none()
+        {
+            warnings.push(format!(
+                "quality 'lossless' is not lossless with {}: mapped to {} kbps, use flac or pcm_s16le for lossless output",
+                self.codec,
+                self.quality.bitrate_for_codec(self.codec)
+            ));
+        }
+
+        // target_loudness работает только вместе с normalize (пресет может его включить)
+        if !self.normalize
+            && self.profile.is_none()
+            && self.target_loudness != default_target_loudness()
+        {
+            warnings.push("target_loudness ignored because normalize=false".to_string());
+        }
+
+        // Ответ отдаётся через pipe - без seek контейнер теряет индекс
+        if self.format.prefers_seekable_output() {
+            warnings.push(format!(
+                "{} output is streamed through a pipe: seek index (cues) and duration are not written",
+                self.format
+            ));
+        }
+
+        warnings
+    }
+}
+
+/// Ответ `POST /api/v1/transcode/hls`: сессия запущена, файлы появятся по мере
+/// кодирования
+#[derive(Debug, Clone, Serialize)]
+pub struct HlsResponse {
+    /// ID сессии
+    pub session_id: Uuid,
+    /// `processing` или `queued`, если задача ждёт слот в очереди
+    pub status: TranscodeStatus,
+    /// Путь плейлиста (`GET`), полный после статуса `completed`
+    pub playlist_url: String,
+    /// Путь статуса сессии
+    pub status_url: String,
+}
+
+/// Ответ на запрос с `dry_run`: команда FFmpeg без запуска
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunResponse {
+    /// Аргументы FFmpeg (без имени бинаря)
+    pub ffmpeg_args: Vec<String>,
+
+    /// Значение `-af`, если цепочка фильтров не пустая
+    pub filter_chain: Option<String>,
+}
+
+impl DryRunResponse {
+    pub fn new(ffmpeg_args: Vec<String>) -> Self {
+        let filter_chain = ffmpeg_args
+            .iter()
+            .position(|arg| arg == "-af")
+            .and_then(|index| ffmpeg_args.get(index + 1))
+            .cloned();
+        Self {
+            ffmpeg_args,
+            filter_chain,
+        }
+    }
+}
+
+/// Ответ `POST /api/v1/validate` для корректного запроса
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidateResponse {
+    pub valid: bool,
+}
+
+/// Начальный ответ при старте транскодирования
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub struct TranscodeResponse {
     /// Уникальный ID сессии транскодирования
     pub session_id: Uuid,
 
-    /// Статус транскодирования
-    pub status: TranscodeStatus,
+    /// Статус транскодирования
+    pub status: TranscodeStatus,
+
+    /// Content-Type результирующего потока
+    pub content_type: String,
+
+    /// Сообщение (опционально)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl TranscodeResponse {
+    pub fn new(session_id: Uuid, content_type: impl Into<String>) -> Self {
+        Self {
+            session_id,
+            status: TranscodeStatus::Processing,
+            content_type: content_type.into(),
+            message: None,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+/// Ответ о статусе сессии транскодирования
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TranscodeStatusResponse {
+    /// ID сессии
+    pub session_id: Uuid,
+
+    /// Текущий статус
+    pub status: TranscodeStatus,
+
+    /// Время работы в секундах
+    pub duration_seconds: f64,
+
+    /// Переданные байты
+    pub bytes_transferred: u64,
+
+    /// Сообщение об ошибке (если есть)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Интервалы тишины (если запрошен `detect_segments`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<SilenceInterval>>,
+
+    /// Громкость источника (если запрошен `measure_loudness`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loudness: Option<LoudnessMeasurement>,
+
+    /// Измерение первого прохода нормализации (если задан `normalize_mode`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loudness_stats: Option<LoudnessStats>,
+
+    /// Громкость выхода второго прохода нормализации, известна после завершения
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_loudness: Option<LoudnessMeasurement>,
+
+    /// Закодированная длительность выхода в секундах (FFmpeg `-progress`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_time_seconds: Option<f64>,
+
+    /// Записанный FFmpeg объём выхода в байтах (FFmpeg `-progress`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_size_bytes: Option<u64>,
+
+    /// Позиция в очереди, начиная с 1 (только в статусе `queued`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<usize>,
+
+    /// Вывод отменённой сессии сохранён частично (`allow_partial_on_cancel`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partial: Option<bool>,
+}
+
+/// Интервал тишины, найденный `silencedetect`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SilenceInterval {
+    /// Начало тишины в секундах
+    pub start: f64,
+    /// Конец тишины в секундах (None - тишина до конца потока)
+    pub end: Option<f64>,
+}
+
+impl SilenceInterval {
+    /// Длительность интервала, если он закрыт
+    pub fn duration(&self) -> Option<f64> {
+        self.end.map(|end| end - self.start)
+    }
+}
+
+/// Громкость по EBU R128, измеренная `loudnorm`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoudnessMeasurement {
+    /// Интегральная громкость в LUFS
+    pub integrated_lufs: f64,
+    /// Диапазон громкости (LRA) в LU
+    pub loudness_range: f64,
+    /// True peak в dBTP
+    pub true_peak: f64,
+}
+
+/// Полное измерение первого прохода `loudnorm` для второго прохода
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoudnessStats {
+    /// Интегральная громкость в LUFS (`measured_I`)
+    pub input_i: f64,
+    /// True peak в dBTP (`measured_TP`)
+    pub input_tp: f64,
+    /// Диапазон громкости в LU (`measured_LRA`)
+    pub input_lra: f64,
+    /// Порог гейтинга в LUFS (`measured_thresh`)
+    pub input_thresh: f64,
+    /// Поправка до цели в LU (`offset`)
+    pub target_offset: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_request() -> TranscodeRequest {
+        TranscodeRequest {
+            source_url: "https://example.com/audio.mp3".to_string(),
+            source_urls: Vec::new(),
+            format: AudioFormat::Opus,
+            output_format: None,
+            codec: AudioCodec::Libopus,
+            quality: AudioQuality::Medium,
+            bitrate: None,
+            sample_rate: None,
+            channels: None,
+            sample_fmt: None,
+            audio_filters: None,
+            normalize: false,
+            target_loudness: -16.0,
+            fade_in: None,
+            fade_out: None,
+            fade_curve: None,
+            max_duration_override: None,
+            preroll_url: None,
+            background: None,
+            normalize_stream_params: None,
+            content_type_override: None,
+            detect_segments: None,
+            clamp_channels_to_source: None,
+            allow_upsample: None,
+            broadcast_ready: None,
+            source_codec_hint: None,
+            failure_marker: None,
+            measure_loudness: None,
+            start_time: None,
+            duration: None,
+            normalize_mode: None,
+            compression_level: None,
+            vbr_quality: None,
+            opus_application: None,
+            opus_vbr: None,
+            dry_run: None,
+            profile: None,
+            filename: None,
+            metadata: None,
+            preserve_metadata: None,
+            copy: None,
+            source_headers: None,
+            allow_partial_on_cancel: None,
+        }
+    }
+
+    #[test]
+    fn test_denoise_range() {
+        let mut filters = AudioFilters {
+            denoise_db: Some(12.0),
+            ..Default::default()
+        };
+        assert!(filters.validate().is_ok());
+        assert!(filters.has_filters());
+
+        for invalid in [0.0, 0.5, 40.5, f32::NAN] {
+            filters.denoise_db = Some(invalid);
+            assert!(filters.validate().is_err(), "{} must be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn test_valid_request() {
+        let req = valid_request();
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn test_profile_name_validation() {
+        let mut req = valid_request();
+        req.profile = Some("telegram_voice".to_string());
+        assert!(req.validate().is_ok());
+        assert_eq!(req.profile_preset(), Some(ProfilePreset::TelegramVoice));
+
+        req.profile = Some("podcast".to_string());
+        let err = req.validate().unwrap_err();
+        assert!(err.contains("unknown profile 'podcast'"), "{}", err);
+        assert!(err.contains("high_quality"), "{}", err);
+    }
+
+    #[test]
+    fn test_codec_format_compatibility() {
+        let mut req = valid_request();
+        assert!(req.check_codec_format().is_ok());
+
+        req.format = AudioFormat::Mp3;
+        let err = req.check_codec_format().unwrap_err();
+        assert!(err.contains("libopus"), "{}", err);
+    }
+
+    #[test]
+    fn test_sample_fmt_supported_combination() {
+        let mut req = valid_request();
+        req.format = AudioFormat::Wav;
+        req.codec = AudioCodec::PcmS16le;
+        req.sample_fmt = Some(SampleFormat::Flt);
+        assert!(req.check_sample_format().is_ok());
+    }
+
+    #[test]
+    fn test_sample_fmt_unsupported_combination() {
+        let mut req = valid_request();
+        req.format = AudioFormat::Mp3;
+        req.codec = AudioCodec::Libmp3lame;
+        req.sample_fmt = Some(SampleFormat::S32);
+        let err = req.check_sample_format().unwrap_err();
+        assert!(err.contains("s32"));
+        assert!(err.contains("mp3"));
+    }
+
+    #[test]
+    fn test_empty_source_url() {
+        let mut req = valid_request();
+        req.source_url = String::new();
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_source_url_rejects_local_files() {
+        let mut req = valid_request();
+        req.source_url = "file:///etc/passwd".to_string();
+        assert!(req.validate().unwrap_err().contains("scheme 'file'"));
+
+        req.source_url = "/etc/passwd".to_string();
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_source_url_rejects_private_addresses() {
+        let mut req = valid_request();
+        req.source_url = "http://127.0.0.1/".to_string();
+        assert!(req.validate().unwrap_err().contains("private address"));
+
+        req.source_url = "http://169.254.169.254/latest/meta-data".to_string();
+        assert!(req.validate().is_err());
+
+        req.source_url = "http://localhost:8090/".to_string();
+        assert!(req.validate().unwrap_err().contains("private address"));
+
+        req.source_url = "http://foo.localhost/audio.mp3".to_string();
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_source_url_allows_public_and_cloud_sources() {
+        let mut req = valid_request();
+        req.source_url = "https://cdn.example.com/audio.mp3".to_string();
+        assert!(req.validate().is_ok());
+
+        req.source_url = "s3://media/tracks/a.mp3".to_string();
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn test_source_host_allowlist() {
+        let allowlist = vec![".example.com".to_string(), "127.0.0.1".to_string()];
+        let mut req = valid_request();
+
+        req.source_url = "https://cdn.example.com/audio.mp3".to_string();
+        assert!(req.validate_with_allowlist(&allowlist).is_ok());
+
+        req.source_url = "https://example.org/audio.mp3".to_string();
+        assert!(req
+            .validate_with_allowlist(&allowlist)
+            .unwrap_err()
+            .contains("allowlist"));
+
+        // Закрытый адрес из allowlist разрешён явно
+        req.source_url = "http://127.0.0.1:9000/audio.mp3".to_string();
+        assert!(req.validate_with_allowlist(&allowlist).is_ok());
+
+        req.source_url = "http://10.0.0.5/audio.mp3".to_string();
+        assert!(req.validate_with_allowlist(&allowlist).is_err());
+    }
+
+    #[test]
+    fn test_invalid_bitrate() {
+        let mut req = valid_request();
+        req.bitrate = Some(1000); // Too high
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_sample_rate() {
+        let mut req = valid_request();
+        req.sample_rate = Some(22050); // Not in valid list
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_valid_sample_rate() {
+        let mut req = valid_request();
+        req.sample_rate = Some(48000);
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn test_opus_rejects_unsupported_sample_rate() {
+        let mut req = valid_request();
+        req.sample_rate = Some(44100);
+        assert!(req.validate().unwrap_err().contains("libopus"));
+
+        req.sample_rate = Some(96000);
+        assert!(req.validate().is_err());
+
+        req.sample_rate = Some(48000);
+        assert!(req.validate().is_ok());
+
+        // Другие кодеки по-прежнему принимают 44.1k
+        req.format = AudioFormat::Mp3;
+        req.codec = AudioCodec::Libmp3lame;
+        req.sample_rate = Some(44100);
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn test_encoder_tuning_validation() {
+        let mut req = valid_request();
+        req.compression_level = Some(8);
+        assert!(req.validate().is_err(), "compression_level requires flac");
+
+        req.format = AudioFormat::Flac;
+        req.codec = AudioCodec::Flac;
+        assert!(req.validate().is_ok());
+        req.compression_level = Some(13);
+        assert!(req.validate().is_err());
+
+        let mut req = valid_request();
+        req.format = AudioFormat::Mp3;
+        req.codec = AudioCodec::Libmp3lame;
+        req.vbr_quality = Some(2);
+        assert!(req.validate().is_ok());
+        req.vbr_quality = Some(10);
+        assert!(req.validate().is_err());
+        req.vbr_quality = Some(2);
+        req.bitrate = Some(192);
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_opus_options_rejected_for_other_codecs() {
+        let mut req = valid_request();
+        req.opus_application = Some(OpusApplication::Voip);
+        req.opus_vbr = Some(OpusVbr::Constrained);
+        assert!(req.validate().is_ok());
+
+        req.format = AudioFormat::Mp3;
+        req.codec = AudioCodec::Libmp3lame;
+        assert!(req.validate().is_err());
+
+        req.opus_application = None;
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_unknown_opus_application_is_rejected() {
+        let result: Result<TranscodeRequest, _> = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "opus_application": "speech",
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_invalid_channels() {
+        let mut req = valid_request();
+        req.channels = Some(5); // Invalid
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_zero_max_duration_override() {
+        let mut req = valid_request();
+        req.max_duration_override = Some(0);
+        assert!(req.validate().is_err());
+
+        req.max_duration_override = Some(7200);
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn test_trim_validation() {
+        let mut req = valid_request();
+        req.start_time = Some(-1.0);
+        assert!(req.validate().is_err());
+
+        req.start_time = Some(f32::NAN);
+        assert!(req.validate().is_err());
+
+        req.start_time = Some(30.0);
+        req.duration = Some(0.0);
+        assert!(req.validate().is_err());
+
+        req.duration = Some(MAX_TRIM_DURATION_SECS + 1.0);
+        assert!(req.validate().is_err());
+
+        req.duration = Some(MAX_TRIM_DURATION_SECS);
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn test_start_time_rejected_for_live_source() {
+        let mut req = valid_request();
+        req.source_url = "https://example.com/live/stream.m3u8".to_string();
+        req.start_time = Some(10.0);
+        assert!(req.validate().is_err());
+
+        req.start_time = Some(0.0);
+        assert!(req.validate().is_ok());
+    }
+
+    #[test]
+    fn test_normalize_mode_requires_normalize() {
+        let mut req = valid_request();
+        req.normalize_mode = Some(NormalizeMode::Linear);
+        assert!(req.validate().is_err());
+
+        req.normalize = true;
+        assert!(req.validate().is_ok());
 
-    /// Content-Type результирующего потока
-    pub content_type: String,
+        req.source_url = "rtmp://live.example.com/app/stream".to_string();
+        assert!(req.validate().is_err());
+    }
 
-    /// Сообщение (опционально)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub message: Option<String>,
-}
+    #[test]
+    fn test_lossless_with_lossy_codec_warns() {
+        let mut req = valid_request();
+        req.quality = AudioQuality::Lossless;
+        assert!(req.validate().is_ok());
 
-impl TranscodeResponse {
-    pub fn new(session_id: Uuid, content_type: impl Into<String>) -> Self {
-        Self {
-            session_id,
-            status: TranscodeStatus::Processing,
-            content_type: content_type.into(),
-            message: None,
-        }
+        let warnings = req.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("libopus"));
+        assert!(warnings[0].contains("256 kbps"));
     }
 
-    pub fn with_message(mut self, message: impl Into<String>) -> Self {
-        self.message = Some(message.into());
-        self
+    #[test]
+    fn test_lossless_with_flac_has_no_warning() {
+        let mut req = valid_request();
+        req.format = AudioFormat::Flac;
+        req.codec = AudioCodec::Flac;
+        req.quality = AudioQuality::Lossless;
+        assert!(req.warnings().is_empty());
     }
-}
 
-/// Ответ о статусе сессии транскодирования
-#[derive(Debug, Clone, Serialize)]
-#[serde(rename_all = "snake_case")]
-pub struct TranscodeStatusResponse {
-    /// ID сессии
-    pub session_id: Uuid,
+    #[test]
+    fn test_target_loudness_without_normalize_warns() {
+        let mut req = valid_request();
+        req.target_loudness = -23.0;
+        assert_eq!(
+            req.warnings(),
+            vec!["target_loudness ignored because normalize=false".to_string()]
+        );
+
+        req.normalize = true;
+        assert!(req.warnings().is_empty());
+
+        // Значение по умолчанию без normalize - не ошибка вызывающего
+        req.normalize = false;
+        req.target_loudness = -16.0;
+        assert!(req.warnings().is_empty());
+    }
 
-    /// Текущий статус
-    pub status: TranscodeStatus,
+    #[test]
+    fn test_preroll_url_validation() {
+        let mut req = valid_request();
+        req.preroll_url = Some("https://example.com/preroll.mp3".to_string());
+        assert!(req.validate().is_ok());
 
-    /// Время работы в секундах
-    pub duration_seconds: f64,
+        req.preroll_url = Some("file:///etc/passwd".to_string());
+        assert!(req.validate().is_err());
 
-    /// Переданные байты
-    pub bytes_transferred: u64,
+        req.preroll_url = Some("https://example.com/live/index.m3u8".to_string());
+        assert!(req.validate().is_err());
 
-    /// Сообщение об ошибке (если есть)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-}
+        req.preroll_url = Some("not a url".to_string());
+        assert!(req.validate().is_err());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        req.preroll_url = Some("http://[::1]/preroll.mp3".to_string());
+        assert!(req.validate().is_err());
 
-    fn valid_request() -> TranscodeRequest {
-        TranscodeRequest {
-            source_url: "https://example.com/audio.mp3".to_string(),
-            format: AudioFormat::Opus,
-            output_format: None,
-            codec: AudioCodec::Libopus,
-            quality: AudioQuality::Medium,
-            bitrate: None,
-            sample_rate: None,
-            channels: None,
-            audio_filters: None,
-            normalize: false,
-            target_loudness: -16.0,
-            fade_in: None,
-            fade_out: None,
-        }
+        req.preroll_url = Some("s3://bucket/preroll.mp3".to_string());
+        assert!(req.validate().is_err());
     }
 
     #[test]
-    fn test_valid_request() {
-        let req = valid_request();
+    fn test_preroll_url_respects_host_allowlist() {
+        let allowlist = vec!["example.com".to_string()];
+        let mut req = valid_request();
+
+        req.preroll_url = Some("https://example.com/preroll.mp3".to_string());
+        assert!(req.validate_with_allowlist(&allowlist).is_ok());
+
+        req.preroll_url = Some("https://other.example.net/preroll.mp3".to_string());
+        let err = req.validate_with_allowlist(&allowlist).unwrap_err();
+        assert!(err.starts_with("preroll_url: "), "{}", err);
+        assert!(err.contains("allowlist"), "{}", err);
+    }
+
+    #[test]
+    fn test_source_urls_validation() {
+        let mut req = valid_request();
+        req.source_url = String::new();
+        req.source_urls = vec![
+            "https://example.com/jingle.mp3".to_string(),
+            "https://example.com/track.mp3".to_string(),
+        ];
+        assert!(req.validate().is_ok());
+
+        // Взаимоисключающие поля
+        req.source_url = "https://example.com/audio.mp3".to_string();
+        assert!(req.validate().unwrap_err().contains("mutually exclusive"));
+        req.source_url = String::new();
+
+        // Каждый URL проходит SSRF проверку
+        req.source_urls[1] = "http://127.0.0.1/track.mp3".to_string();
+        assert!(req.validate().unwrap_err().starts_with("source_urls[1]"));
+        req.source_urls[1] = "file:///etc/passwd".to_string();
+        assert!(req.validate().is_err());
+
+        // Live-источник допустим только последним
+        req.source_urls[1] = "https://example.com/live/index.m3u8".to_string();
         assert!(req.validate().is_ok());
+        req.source_urls.swap(0, 1);
+        assert!(req.validate().is_err());
     }
 
     #[test]
-    fn test_empty_source_url() {
+    fn test_source_urls_count_and_conflicts() {
         let mut req = valid_request();
         req.source_url = String::new();
+
+        req.source_urls = vec!["https://example.com/a.mp3".to_string()];
+        assert!(req.validate().is_err());
+
+        req.source_urls = vec!["https://example.com/a.mp3".to_string(); MAX_SOURCE_URLS];
+        assert!(req.validate().is_ok());
+        req.source_urls
+            .push("https://example.com/a.mp3".to_string());
+        assert!(req.validate().is_err());
+
+        req.source_urls.truncate(2);
+        req.start_time = Some(5.0);
+        assert!(req.validate().unwrap_err().contains("cannot be combined"));
+        req.start_time = None;
+        req.preroll_url = Some("https://example.com/preroll.mp3".to_string());
         assert!(req.validate().is_err());
     }
 
     #[test]
-    fn test_invalid_bitrate() {
+    fn test_background_validation() {
         let mut req = valid_request();
-        req.bitrate = Some(1000); // Too high
+        let background: BackgroundTrack =
+            serde_json::from_str(r#"{"url": "https://example.com/bed.mp3"}"#).unwrap();
+        assert_eq!(background.gain_db, -18.0);
+        assert_eq!(background.primary_gain_db, 0.0);
+        assert_eq!(background.duration, MixDuration::First);
+        req.background = Some(background.clone());
+        assert!(req.validate().is_ok());
+
+        // URL фона проходит ту же SSRF проверку, что и источник
+        for url in [
+            "http://10.0.0.5/bed.mp3",
+            "file:///etc/passwd",
+            "rtmp://live/bed",
+        ] {
+            req.background = Some(BackgroundTrack {
+                url: url.to_string(),
+                ..background.clone()
+            });
+            assert!(req.validate().is_err(), "{} must be rejected", url);
+        }
+
+        req.background = Some(BackgroundTrack {
+            gain_db: 20.0,
+            ..background.clone()
+        });
+        assert!(req.validate().is_err());
+
+        req.background = Some(background);
+        req.preroll_url = Some("https://example.com/preroll.mp3".to_string());
         assert!(req.validate().is_err());
     }
 
     #[test]
-    fn test_invalid_sample_rate() {
+    fn test_fade_out_requires_seekable_source() {
         let mut req = valid_request();
-        req.sample_rate = Some(22050); // Not in valid list
+        req.fade_out = Some(3.0);
+        assert!(req.validate().is_ok());
+
+        req.source_url = "https://cdn.example.com/live/index.m3u8".to_string();
+        let err = req.validate().unwrap_err();
+        assert!(err.contains("seekable"));
+    }
+
+    #[test]
+    fn test_normalize_stream_params_defaults_on_for_live_sources() {
+        let mut req = valid_request();
+        assert!(!req.normalize_stream_params());
+
+        req.source_url = "https://cdn.example.com/live/index.m3u8".to_string();
+        assert!(req.normalize_stream_params());
+
+        req.normalize_stream_params = Some(false);
+        assert!(!req.normalize_stream_params());
+    }
+
+    #[test]
+    fn test_source_headers_validation() {
+        let mut req = valid_request();
+        req.source_headers = Some(HashMap::from([
+            ("Authorization".to_string(), "Bearer abc.def".to_string()),
+            ("X-Api-Key".to_string(), "key\twith tab".to_string()),
+        ]));
+        assert!(req.validate().is_ok());
+
+        for name in ["Host", "Content-Length", "Transfer-Encoding", "X-Forwarded-For"] {
+            req.source_headers = Some(HashMap::from([(name.to_string(), "x".to_string())]));
+            assert!(
+                req.validate().unwrap_err().contains("is not allowed"),
+                "{}",
+                name
+            );
+        }
+
+        // Перевод строки добавил бы свой заголовок
+        for value in ["token\r\nHost: internal", "token\n", "", "токен"] {
+            req.source_headers = Some(HashMap::from([(
+                "authorization".to_string(),
+                value.to_string(),
+            )]));
+            assert!(req.validate().is_err(), "{:?}", value);
+        }
+
+        req.source_headers = Some(HashMap::from([(
+            "cookie".to_string(),
+            "a".repeat(MAX_SOURCE_HEADER_VALUE_LEN + 1),
+        )]));
+        assert!(req.validate().is_err());
+
+        req.source_headers = Some(HashMap::from([("cookie".to_string(), "a=1".to_string())]));
+        req.preroll_url = Some("https://example.com/preroll.mp3".to_string());
+        assert!(req.validate().unwrap_err().contains("cannot be combined"));
+    }
+
+    #[test]
+    fn test_upload_rejects_url_source_fields() {
+        let mut req: TranscodeRequest = serde_json::from_str(r#"{"format": "mp3"}"#).unwrap();
+        assert!(req.validate_upload().is_ok());
+        // Без файла такой запрос не проходит обычную валидацию
         assert!(req.validate().is_err());
+
+        req.source_url = "https://example.com/a.mp3".to_string();
+        assert!(req.validate_upload().unwrap_err().contains("upload does not accept"));
+        req.source_url = String::new();
+
+        req.fade_out = Some(2.0);
+        assert!(req.validate_upload().is_err());
+        req.fade_out = None;
+
+        req.bitrate = Some(4);
+        assert!(req.validate_upload().unwrap_err().contains("bitrate"));
     }
 
     #[test]
-    fn test_valid_sample_rate() {
+    fn test_hls_codecs_and_fields() {
+        let mut req = valid_request();
+        req.codec = AudioCodec::Aac;
+        assert!(req.validate_hls().is_ok());
+
+        req.codec = AudioCodec::Flac;
+        assert!(req.validate_hls().unwrap_err().contains("not supported for HLS"));
+
+        req.codec = AudioCodec::Libopus;
+        req.dry_run = Some(true);
+        assert!(req.validate_hls().unwrap_err().contains("hls does not accept"));
+        req.dry_run = None;
+        req.fade_out = Some(3.0);
+        assert!(req.validate_hls().is_err());
+    }
+
+    #[test]
+    fn test_metadata_keys_and_sanitizing() {
+        let mut req = valid_request();
+        req.metadata = Some(HashMap::from([
+            ("title".to_string(), "  Episode 12\n-y /tmp/x ".to_string()),
+            ("artist".to_string(), "Radio\0".to_string()),
+            ("comment".to_string(), "\r\n".to_string()),
+        ]));
+        assert!(req.validate().is_ok());
+
+        let tags = req.metadata_tags();
+        assert_eq!(tags["title"], "Episode 12-y /tmp/x");
+        assert_eq!(tags["artist"], "Radio");
+        assert!(!tags.contains_key("comment"));
+
+        req.metadata = Some(HashMap::from([("encoder".to_string(), "x".to_string())]));
+        assert!(req.validate().unwrap_err().contains("metadata key 'encoder'"));
+
+        req.metadata = Some(HashMap::from([(
+            "album".to_string(),
+            "a".repeat(MAX_METADATA_VALUE_LEN + 1),
+        )]));
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_copy_rejects_processing() {
         let mut req = valid_request();
+        req.copy = Some(true);
+        assert!(req.validate().is_ok());
+
+        req.audio_filters = Some(AudioFilters {
+            volume: Some(1.5),
+            ..Default::default()
+        });
+        req.normalize = true;
+        assert_eq!(
+            req.validate().unwrap_err(),
+            "copy cannot be combined with audio_filters, normalize"
+        );
+
+        req.audio_filters = None;
+        req.normalize = false;
+        req.fade_out = Some(2.0);
+        assert!(req.validate().unwrap_err().contains("fade_out"));
+
+        // Без copy те же поля допустимы
+        req.fade_out = None;
         req.sample_rate = Some(48000);
+        assert!(req.validate().is_err());
+        req.copy = Some(false);
         assert!(req.validate().is_ok());
     }
 
     #[test]
-    fn test_invalid_channels() {
+    fn test_content_disposition() {
+        let session_id = Uuid::nil();
         let mut req = valid_request();
-        req.channels = Some(5); // Invalid
+        assert_eq!(
+            req.content_disposition(session_id),
+            format!("attachment; filename=\"{}.ogg\"", session_id)
+        );
+
+        req.format = AudioFormat::Mp3;
+        for (filename, expected) in [
+            ("track", "track.mp3"),
+            ("track.MP3", "track.MP3"),
+            ("../../etc/passwd", "passwd.mp3"),
+            ("C:\\Music\\song", "song.mp3"),
+            ("bad\"name\r\n", "badname.mp3"),
+            ("..", &format!("{}.mp3", session_id)),
+        ] {
+            req.filename = Some(filename.to_string());
+            assert_eq!(
+                req.content_disposition(session_id),
+                format!("attachment; filename=\"{}\"", expected),
+                "{:?}",
+                filename
+            );
+        }
+
+        req.filename = Some("Трек 1".to_string());
+        assert_eq!(
+            req.content_disposition(session_id),
+            "attachment; filename=\"____ 1.mp3\"; \
+             filename*=UTF-8''%D0%A2%D1%80%D0%B5%D0%BA%201.mp3"
+        );
+
+        req.filename = Some("a".repeat(MAX_FILENAME_LEN + 1));
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_content_type_override() {
+        let mut req = valid_request();
+        assert_eq!(req.content_type(), "audio/ogg");
+
+        req.content_type_override = Some("application/octet-stream".to_string());
+        assert!(req.validate().is_ok());
+        assert_eq!(req.content_type(), "application/octet-stream");
+        assert_eq!(req.format, AudioFormat::Opus);
+
+        req.content_type_override = Some("application/vnd.acme.audio+ogg; codecs=opus".to_string());
+        assert!(req.validate().is_ok());
+
+        for invalid in [
+            "",
+            "audio",
+            "audio/",
+            "audio ogg/x",
+            "audio/ogg; codecs",
+            "audio/ogg\r\nX-Injected: 1",
+        ] {
+            req.content_type_override = Some(invalid.to_string());
+            assert!(req.validate().is_err(), "{:?} must be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn test_mka_pipe_output_warns() {
+        let mut req = valid_request();
+        req.format = AudioFormat::Mka;
+        req.codec = AudioCodec::Flac;
+        assert!(req.validate().is_ok());
+
+        let warnings = req.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("cues"));
+    }
+
+    #[test]
+    fn test_broadcast_ready_excludes_manual_loudness() {
+        let mut req = valid_request();
+        req.broadcast_ready = Some(true);
+        assert!(req.validate().is_ok());
+
+        req.normalize = true;
+        assert!(req.validate().is_err());
+
+        req.normalize = false;
+        req.audio_filters = Some(AudioFilters {
+            volume: Some(1.5),
+            ..Default::default()
+        });
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_source_codec_hint_validation() {
+        let mut req = valid_request();
+        req.source_codec_hint = Some("mp3".to_string());
+        assert!(req.validate().is_ok());
+
+        req.source_codec_hint = Some("lavfi".to_string());
         assert!(req.validate().is_err());
     }
 
@@ -308,6 +2276,7 @@ mod tests {
             eq_preset: None,
             speed: Some(1.5),
             volume: None,
+            ..Default::default()
         };
         assert!(filters.validate().is_ok());
     }
@@ -318,6 +2287,7 @@ mod tests {
             eq_preset: None,
             speed: Some(0.3), // < 0.5
             volume: None,
+            ..Default::default()
         };
         assert!(filters.validate().is_err());
     }
@@ -328,6 +2298,7 @@ mod tests {
             eq_preset: None,
             speed: Some(2.5), // > 2.0
             volume: None,
+            ..Default::default()
         };
         assert!(filters.validate().is_err());
     }
@@ -338,6 +2309,7 @@ mod tests {
             eq_preset: None,
             speed: None,
             volume: Some(1.5),
+            ..Default::default()
         };
         assert!(filters.validate().is_ok());
     }
@@ -348,6 +2320,7 @@ mod tests {
             eq_preset: None,
             speed: None,
             volume: Some(-0.5), // < 0.0
+            ..Default::default()
         };
         assert!(filters.validate().is_err());
     }
@@ -358,6 +2331,7 @@ mod tests {
             eq_preset: None,
             speed: None,
             volume: Some(2.5), // > 2.0
+            ..Default::default()
         };
         assert!(filters.validate().is_err());
     }
@@ -371,6 +2345,7 @@ mod tests {
             eq_preset: Some(EqPreset::BassBoost),
             speed: None,
             volume: None,
+            ..Default::default()
         };
         assert!(with_eq.has_filters());
 
@@ -378,10 +2353,217 @@ mod tests {
             eq_preset: None,
             speed: Some(1.25),
             volume: None,
+            ..Default::default()
         };
         assert!(with_speed.has_filters());
     }
 
+    #[test]
+    fn test_audio_filters_stereo_width_range() {
+        let filters = AudioFilters {
+            stereo_width: Some(1.5),
+            ..Default::default()
+        };
+        assert!(filters.validate().is_ok());
+        assert!(filters.has_filters());
+
+        let filters = AudioFilters {
+            stereo_width: Some(2.5),
+            ..Default::default()
+        };
+        assert!(filters.validate().is_err());
+    }
+
+    #[test]
+    fn test_noise_gate_validation() {
+        let gate: NoiseGateSettings = serde_json::from_str(r#"{"threshold_db": -50}"#).unwrap();
+        assert_eq!(gate.ratio, 2.0);
+        assert!(gate.validate().is_ok());
+
+        let filters = |gate| AudioFilters {
+            noise_gate: Some(gate),
+            ..Default::default()
+        };
+        assert!(filters(gate).has_filters());
+        assert!(filters(NoiseGateSettings {
+            threshold_db: 6.0,
+            ..gate
+        })
+        .validate()
+        .is_err());
+        assert!(filters(NoiseGateSettings { ratio: 0.5, ..gate })
+            .validate()
+            .is_err());
+        assert!(filters(NoiseGateSettings {
+            attack: 0.0,
+            ..gate
+        })
+        .validate()
+        .is_err());
+        assert!(filters(NoiseGateSettings {
+            release: 10_000.0,
+            ..gate
+        })
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_volume_envelope_validation() {
+        let point = |time, gain_db| EnvelopePoint { time, gain_db };
+        let filters = |points: Vec<EnvelopePoint>| AudioFilters {
+            volume_envelope: Some(points),
+            ..Default::default()
+        };
+
+        assert!(filters(vec![point(0.0, 0.0), point(5.0, -12.0)])
+            .validate()
+            .is_ok());
+        assert!(filters(vec![]).validate().is_err());
+        assert!(filters(vec![point(5.0, 0.0), point(2.0, -6.0)])
+            .validate()
+            .is_err());
+        assert!(filters(vec![point(1.0, 0.0), point(1.0, -6.0)])
+            .validate()
+            .is_err());
+        assert!(filters(vec![point(-1.0, 0.0)]).validate().is_err());
+        assert!(filters(vec![point(0.0, 40.0)]).validate().is_err());
+
+        let mut req = valid_request();
+        req.max_duration_override = Some(10);
+        req.audio_filters = Some(filters(vec![point(0.0, 0.0), point(30.0, -6.0)]));
+        assert!(req.validate().is_err());
+    }
+
+    #[test]
+    fn test_audio_filters_cutoff_range() {
+        let filters = |highpass_hz, lowpass_hz| AudioFilters {
+            highpass_hz,
+            lowpass_hz,
+            ..Default::default()
+        };
+
+        assert!(filters(Some(80), None).validate().is_ok());
+        assert!(filters(None, Some(20000)).validate().is_ok());
+        assert!(filters(Some(300), Some(3400)).validate().is_ok());
+        assert!(filters(Some(10), None).validate().is_err());
+        assert!(filters(None, Some(22050)).validate().is_err());
+        assert!(filters(Some(300), None).has_filters());
+    }
+
+    #[test]
+    fn test_audio_filters_custom_eq_validation() {
+        let band = |frequency, gain_db, q| EqBand {
+            frequency,
+            gain_db,
+            q,
+        };
+        let filters = |bands: Vec<EqBand>| AudioFilters {
+            custom_eq: Some(bands),
+            ..Default::default()
+        };
+
+        assert!(filters(vec![band(100, 3.0, 1.0), band(8000, -2.5, 0.7)])
+            .validate()
+            .is_ok());
+        assert!(filters(vec![]).validate().is_err());
+        assert!(filters(vec![band(1000, 0.0, 1.0); MAX_EQ_BANDS + 1])
+            .validate()
+            .is_err());
+        assert!(filters(vec![band(10, 3.0, 1.0)]).validate().is_err());
+        assert!(filters(vec![band(25000, 3.0, 1.0)]).validate().is_err());
+        assert!(filters(vec![band(1000, 30.0, 1.0)]).validate().is_err());
+        assert!(filters(vec![band(1000, -30.0, 1.0)]).validate().is_err());
+        assert!(filters(vec![band(1000, 3.0, 0.0)]).validate().is_err());
+    }
+
+    #[test]
+    fn test_audio_filters_compress_validation() {
+        let filters: AudioFilters =
+            serde_json::from_value(serde_json::json!({ "compress": {} })).unwrap();
+        let settings = filters.compress.unwrap();
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.threshold_db, -12.0);
+
+        for invalid in [
+            CompressorSettings {
+                attack: 0.0,
+                ..settings
+            },
+            CompressorSettings {
+                attack: 2.0,
+                ..settings
+            },
+            CompressorSettings {
+                decay: -0.1,
+                ..settings
+            },
+            CompressorSettings {
+                decay: 10.0,
+                ..settings
+            },
+            CompressorSettings {
+                threshold_db: 6.0,
+                ..settings
+            },
+        ] {
+            let filters = AudioFilters {
+                compress: Some(invalid),
+                ..Default::default()
+            };
+            assert!(filters.validate().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_audio_filters_speed_mode() {
+        let filters: AudioFilters = serde_json::from_value(serde_json::json!({
+            "speed": 1.5,
+            "speed_mode": "shift_pitch",
+        }))
+        .unwrap();
+        assert_eq!(filters.speed_mode, Some(SpeedMode::ShiftPitch));
+        assert!(filters.validate().is_ok());
+
+        let too_fast = AudioFilters {
+            speed: Some(2.5),
+            speed_mode: Some(SpeedMode::ShiftPitch),
+            ..Default::default()
+        };
+        assert!(too_fast.validate().is_err());
+    }
+
+    #[test]
+    fn test_audio_filters_inverted_cutoffs() {
+        let filters = AudioFilters {
+            highpass_hz: Some(3400),
+            lowpass_hz: Some(300),
+            ..Default::default()
+        };
+        assert!(filters.validate().is_err());
+
+        let equal = AudioFilters {
+            highpass_hz: Some(1000),
+            lowpass_hz: Some(1000),
+            ..Default::default()
+        };
+        assert!(equal.validate().is_err());
+    }
+
+    #[test]
+    fn test_stereo_width_rejected_for_mono_output() {
+        let mut req = valid_request();
+        req.channels = Some(1);
+        req.audio_filters = Some(AudioFilters {
+            stereo_width: Some(1.5),
+            ..Default::default()
+        });
+        assert!(req.validate().is_err());
+
+        req.channels = Some(2);
+        assert!(req.validate().is_ok());
+    }
+
     #[test]
     fn test_request_with_valid_filters() {
         let mut req = valid_request();
@@ -389,6 +2571,7 @@ mod tests {
             eq_preset: Some(EqPreset::Voice),
             speed: Some(1.0),
             volume: Some(0.8),
+            ..Default::default()
         });
         assert!(req.validate().is_ok());
     }
@@ -400,6 +2583,7 @@ mod tests {
             eq_preset: None,
             speed: Some(3.0), // Invalid
             volume: None,
+            ..Default::default()
         });
         assert!(req.validate().is_err());
     }
@@ -0,0 +1,98 @@
+//! Модели waveform API
+//!
+//! `POST /api/v1/waveform` - PNG превью источника для веб-интерфейса.
+
+use serde::Deserialize;
+
+use super::enums::PreviewKind;
+use super::transcode::validate_source_url;
+
+/// Размер картинки по умолчанию
+pub const DEFAULT_PREVIEW_WIDTH: u32 = 1024;
+pub const DEFAULT_PREVIEW_HEIGHT: u32 = 256;
+
+/// Допустимая ширина картинки в пикселях
+pub const PREVIEW_WIDTH_RANGE: std::ops::RangeInclusive<u32> = 64..=4096;
+/// Допустимая высота картинки в пикселях
+pub const PREVIEW_HEIGHT_RANGE: std::ops::RangeInclusive<u32> = 32..=2048;
+
+/// Запрос превью источника
+#[derive(Debug, Clone, Deserialize)]
+pub struct WaveformRequest {
+    /// URL источника (те же ограничения, что у `TranscodeRequest::source_url`)
+    pub source_url: String,
+    /// Ширина PNG в пикселях
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// Высота PNG в пикселях
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Огибающая или спектрограмма
+    #[serde(default)]
+    pub kind: PreviewKind,
+}
+
+impl WaveformRequest {
+    /// Валидация запроса с allowlist хостов источника (`SOURCE_HOST_ALLOWLIST`)
+    pub fn validate_with_allowlist(&self, host_allowlist: &[String]) -> Result<(), String> {
+        if self.source_url.is_empty() {
+            return Err("source_url is required".to_string());
+        }
+        validate_source_url(&self.source_url, host_allowlist)?;
+
+        if !PREVIEW_WIDTH_RANGE.contains(&self.width()) {
+            return Err(format!(
+                "width must be between {} and {} pixels",
+                PREVIEW_WIDTH_RANGE.start(),
+                PREVIEW_WIDTH_RANGE.end()
+            ));
+        }
+        if !PREVIEW_HEIGHT_RANGE.contains(&self.height()) {
+            return Err(format!(
+                "height must be between {} and {} pixels",
+                PREVIEW_HEIGHT_RANGE.start(),
+                PREVIEW_HEIGHT_RANGE.end()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Ширина с учётом значения по умолчанию
+    pub fn width(&self) -> u32 {
+        self.width.unwrap_or(DEFAULT_PREVIEW_WIDTH)
+    }
+
+    /// Высота с учётом значения по умолчанию
+    pub fn height(&self) -> u32 {
+        self.height.unwrap_or(DEFAULT_PREVIEW_HEIGHT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveform_request_size_limits() {
+        let request: WaveformRequest =
+            serde_json::from_str(r#"{"source_url": "https://example.com/a.mp3"}"#).unwrap();
+        assert!(request.validate_with_allowlist(&[]).is_ok());
+        assert_eq!((request.width(), request.height()), (1024, 256));
+        assert_eq!(request.kind, PreviewKind::Waveform);
+
+        for (width, height) in [(Some(16), None), (Some(8192), None), (None, Some(4096))] {
+            let request = WaveformRequest {
+                width,
+                height,
+                ..request.clone()
+            };
+            assert!(request.validate_with_allowlist(&[]).is_err());
+        }
+
+        let request = WaveformRequest {
+            source_url: "http://127.0.0.1/a.mp3".to_string(),
+            ..request
+        };
+        assert!(request.validate_with_allowlist(&[]).is_err());
+    }
+}
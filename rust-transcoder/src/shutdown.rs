@@ -0,0 +1,246 @@
+//! Graceful shutdown
+//!
+//! После сигнала остановки сервис перестаёт принимать транскодирования и
+//! ждёт, пока выполняющиеся вернут permit'ы семафора. Задачи из очереди
+//! (`QUEUE_DEPTH`) не запускаются и сразу завершаются с ошибкой. Что не
+//! успело за grace period, отменяется через реестр сессий (это убивает
+//! процессы FFmpeg).
+
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::error::AppError;
+use crate::metrics::{SHUTDOWN_SESSIONS_DRAINED_TOTAL, SHUTDOWN_SESSIONS_KILLED_TOTAL};
+use crate::AppState;
+
+/// Итог остановки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrainReport {
+    /// Транскодирования, выполнявшиеся в момент сигнала
+    pub active: usize,
+    /// Задачи из очереди, завершённые с ошибкой без запуска
+    pub queued: usize,
+    /// Завершились сами в пределах grace period
+    pub drained: usize,
+    /// Прерваны по истечении grace period
+    pub killed: usize,
+    /// Сколько длилось ожидание
+    pub elapsed: Duration,
+}
+
+/// Останавливает приём транскодирований и ждёт выполняющиеся не дольше `grace`
+pub async fn drain(state: &AppState, grace: Duration) -> DrainReport {
+    state.begin_shutdown();
+
+    let started = Instant::now();
+
+    // Задача из очереди ещё не запускала FFmpeg: ждать её незачем, а слот,
+    // полученный во время остановки, она всё равно не использует
+    let queued = state.queue.sessions();
+    for &session_id in &queued {
+        state
+            .sessions
+            .fail(session_id, AppError::ShuttingDown.to_string());
+        if let Some(cancel) = state.sessions.cancel_signal(session_id) {
+            cancel.notify_one();
+        }
+    }
+    let queued = queued.len();
+
+    // Транскодирование может занимать несколько permit'ов: считаем сессии
+    let active = state.sessions.running_count();
+
+    info!(
+        active,
+        queued,
+        grace_secs = grace.as_secs(),
+        "Draining active transcodes"
+    );
+
+    // Все permit'ы свободны - ничего не выполняется. Permit'ы держим до выхода
+    // из функции: после остановки новые транскодирования не нужны
+    let all = u32::try_from(state.max_concurrent_streams).unwrap_or(u32::MAX);
+    let acquired = tokio::time::timeout(grace, state.transcode_semaphore.acquire_many(all)).await;
+
+    let killed = match acquired {
+        Ok(_) => 0,
+        Err(_) => {
+            let cancelled = state.sessions.cancel_active();
+            warn!(cancelled, "Shutdown grace period expired, killing remaining transcodes");
+            cancelled.min(active)
+        }
+    };
+
+    let report = DrainReport {
+        active,
+        queued,
+        drained: active - killed,
+        killed,
+        elapsed: started.elapsed(),
+    };
+
+    SHUTDOWN_SESSIONS_DRAINED_TOTAL.inc_by(report.drained as u64);
+    SHUTDOWN_SESSIONS_KILLED_TOTAL.inc_by(report.killed as u64);
+
+    info!(
+        active = report.active,
+        queued = report.queued,
+        drained = report.drained,
+        killed = report.killed,
+        drain_duration_ms = report.elapsed.as_millis() as u64,
+        "Drain complete"
+    );
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::TranscodeStatus;
+    use crate::transcoder::permit::TranscodePermit;
+    use uuid::Uuid;
+
+    /// Счётчики shutdown глобальные: тесты, меняющие их, не идут параллельно
+    static METRICS_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Сессия в статусе `status` с permit'ом
+    fn running_session(state: &AppState, status: TranscodeStatus) -> (Uuid, TranscodePermit) {
+        let permit = TranscodePermit::try_acquire(&state.transcode_semaphore).unwrap();
+        let session_id = Uuid::new_v4();
+        state.sessions.register(session_id);
+        state.sessions.set_status(session_id, status);
+        (session_id, permit)
+    }
+
+    #[tokio::test]
+    async fn test_drain_without_active_transcodes_returns_immediately() {
+        let state = AppState::new(2).unwrap();
+
+        let report = drain(&state, Duration::from_secs(5)).await;
+
+        assert!(state.is_shutting_down());
+        assert_eq!((report.active, report.drained, report.killed), (0, 0, 0));
+        assert!(report.elapsed < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_permit_release() {
+        let _metrics = METRICS_LOCK.lock().await;
+        let state = AppState::new(2).unwrap();
+        let permit = TranscodePermit::try_acquire_many(&state.transcode_semaphore, 2).unwrap();
+        let session_id = Uuid::new_v4();
+        state.sessions.register(session_id);
+        state
+            .sessions
+            .set_status(session_id, TranscodeStatus::Processing);
+
+        let sessions = state.sessions.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(permit);
+            sessions.set_status(session_id, TranscodeStatus::Completed);
+        });
+
+        let report = drain(&state, Duration::from_secs(5)).await;
+
+        assert_eq!((report.active, report.drained, report.killed), (1, 1, 0));
+        assert!(report.elapsed >= Duration::from_millis(100));
+        assert!(report.elapsed < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_drain_kills_transcodes_after_grace_period() {
+        let _metrics = METRICS_LOCK.lock().await;
+        let state = AppState::new(2).unwrap();
+        let _permit = TranscodePermit::try_acquire(&state.transcode_semaphore).unwrap();
+        let session_id = Uuid::new_v4();
+        state.sessions.register(session_id);
+        state
+            .sessions
+            .set_status(session_id, TranscodeStatus::Streaming);
+
+        let report = drain(&state, Duration::from_millis(100)).await;
+
+        assert_eq!((report.active, report.drained, report.killed), (1, 0, 1));
+        assert!(report.elapsed >= Duration::from_millis(100));
+        assert_eq!(
+            state.sessions.get(session_id).unwrap().status,
+            TranscodeStatus::Cancelled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drain_fails_queued_sessions_without_waiting() {
+        let _metrics = METRICS_LOCK.lock().await;
+        let config = Config {
+            queue_depth: 1,
+            ..Config::default()
+        };
+        let state = AppState::with_config(1, config).unwrap();
+        let permit = TranscodePermit::try_acquire(&state.transcode_semaphore).unwrap();
+        let running = Uuid::new_v4();
+        state.sessions.register(running);
+        state.sessions.set_status(running, TranscodeStatus::Processing);
+
+        let queued = Uuid::new_v4();
+        let ticket = state.queue.enqueue(queued).unwrap();
+        state.sessions.register(queued);
+        let cancel = state.sessions.cancel_signal(queued).unwrap();
+
+        let sessions = state.sessions.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(permit);
+            sessions.set_status(running, TranscodeStatus::Completed);
+        });
+
+        let report = drain(&state, Duration::from_secs(5)).await;
+
+        // Очередь не считается выполняющимися транскодированиями
+        assert_eq!(
+            (report.active, report.queued, report.drained, report.killed),
+            (1, 1, 1, 0)
+        );
+        let status = state.sessions.get(queued).unwrap();
+        assert_eq!(status.status, TranscodeStatus::Failed);
+        assert_eq!(status.error.unwrap(), AppError::ShuttingDown.to_string());
+        // Задача очереди разбужена и выходит, не дожидаясь слота
+        tokio::time::timeout(Duration::from_secs(1), cancel.notified())
+            .await
+            .unwrap();
+        drop(ticket);
+    }
+
+    #[tokio::test]
+    async fn test_drain_counts_drained_and_killed_sessions() {
+        let _metrics = METRICS_LOCK.lock().await;
+        let drained_before = SHUTDOWN_SESSIONS_DRAINED_TOTAL.get();
+        let killed_before = SHUTDOWN_SESSIONS_KILLED_TOTAL.get();
+
+        let state = AppState::new(3).unwrap();
+        // Две сессии завершаются во время grace period, третья зависла
+        for status in [TranscodeStatus::Processing, TranscodeStatus::Streaming] {
+            let (session_id, permit) = running_session(&state, status);
+            let sessions = state.sessions.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                drop(permit);
+                sessions.set_status(session_id, TranscodeStatus::Completed);
+            });
+        }
+        let (hung, _permit) = running_session(&state, TranscodeStatus::Streaming);
+
+        let report = drain(&state, Duration::from_millis(300)).await;
+
+        assert_eq!((report.active, report.drained, report.killed), (3, 2, 1));
+        assert_eq!(SHUTDOWN_SESSIONS_DRAINED_TOTAL.get() - drained_before, 2);
+        assert_eq!(SHUTDOWN_SESSIONS_KILLED_TOTAL.get() - killed_before, 1);
+        assert_eq!(
+            state.sessions.get(hung).unwrap().status,
+            TranscodeStatus::Cancelled
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! Анализ вывода FFmpeg
+//!
+//! Разбор метаданных, которые фильтры-анализаторы пишут в stderr.
+
+use serde::Deserialize;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{LoudnessMeasurement, LoudnessStats, SilenceInterval};
+
+/// JSON блок `loudnorm=print_format=json` (значения - строки)
+#[derive(Debug, Deserialize)]
+struct LoudnormStats {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    #[serde(default)]
+    input_thresh: Option<String>,
+    #[serde(default)]
+    target_offset: Option<String>,
+    #[serde(default)]
+    output_i: Option<String>,
+    #[serde(default)]
+    output_tp: Option<String>,
+    #[serde(default)]
+    output_lra: Option<String>,
+}
+
+/// Ключи блока `-progress`; строки с ними не попадают в собранный stderr
+const PROGRESS_KEYS: &[&str] = &[
+    "frame",
+    "fps",
+    "bitrate",
+    "total_size",
+    "out_time_us",
+    "out_time_ms",
+    "out_time",
+    "dup_frames",
+    "drop_frames",
+    "speed",
+    "progress",
+];
+
+/// Прогресс кодирования из вывода `-progress`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FfmpegProgress {
+    /// Позиция выхода в секундах
+    pub out_time_seconds: Option<f64>,
+    /// Записанный объём выхода в байтах
+    pub total_size: Option<u64>,
+}
+
+/// Результат разбора одной строки stderr
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressLine {
+    /// Обычная строка лога
+    Other,
+    /// Поле блока прогресса
+    Field,
+    /// Строка `progress=...` - блок закончен
+    Block(FfmpegProgress),
+}
+
+/// Разбор `-progress`: поля копятся до строки `progress=continue|end`
+///
+/// ```text
+/// out_time_us=1500000
+/// out_time_ms=1500000
+/// total_size=24576
+/// progress=continue
+/// ```
+/// `out_time_ms` FFmpeg исторически пишет в микросекундах, как и `out_time_us`.
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+    current: FfmpegProgress,
+}
+
+impl ProgressParser {
+    /// Обрабатывает строку stderr
+    pub fn feed(&mut self, line: &str) -> ProgressLine {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            return ProgressLine::Other;
+        };
+        if !PROGRESS_KEYS.contains(&key) && !key.starts_with("stream_") {
+            return ProgressLine::Other;
+        }
+
+        match key {
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(micros) = value.parse::<i64>() {
+                    self.current.out_time_seconds = Some(micros.max(0) as f64 / 1_000_000.0);
+                }
+            }
+            "total_size" => {
+                if let Ok(size) = value.parse() {
+                    self.current.total_size = Some(size);
+                }
+            }
+            "progress" => return ProgressLine::Block(self.current),
+            _ => {}
+        }
+        ProgressLine::Field
+    }
+}
+
+/// Разбирает вывод `silencedetect` в список интервалов тишины
+///
+/// Ожидаемые строки:
+/// ```text
+/// [silencedetect @ 0x55d5c8] silence_start: 12.345
+/// [silencedetect @ 0x55d5c8] silence_end: 15.0 | silence_duration: 2.655
+/// ```
+/// Незакрытый в конце потока интервал возвращается с `end: None`.
+pub fn parse_silencedetect(stderr: &str) -> Vec<SilenceInterval> {
+    let mut intervals = Vec::new();
+    let mut open_start: Option<f64> = None;
+
+    for line in stderr.lines() {
+        if let Some(start) = value_after(line, "silence_start:") {
+            if let Some(previous) = open_start.replace(start) {
+                // Два start подряд - предыдущий интервал без конца
+                intervals.push(SilenceInterval {
+                    start: previous,
+                    end: None,
+                });
+            }
+        } else if let Some(end) = value_after(line, "silence_end:") {
+            // end без start - тишина с самого начала потока
+            let start = open_start.take().unwrap_or(0.0);
+            intervals.push(SilenceInterval {
+                start,
+                end: Some(end),
+            });
+        }
+    }
+
+    if let Some(start) = open_start {
+        intervals.push(SilenceInterval { start, end: None });
+    }
+
+    intervals
+}
+
+/// Разбирает измерение первого прохода `loudnorm=print_format=json`
+///
+/// FFmpeg пишет в stderr после обработки:
+/// ```text
+/// [Parsed_loudnorm_0 @ 0x5581d8]
+/// {
+///     "input_i" : "-23.96",
+///     "input_tp" : "-5.04",
+///     "input_lra" : "7.60",
+///     ...
+/// }
+/// ```
+pub fn parse_loudnorm(stderr: &str) -> AppResult<LoudnessMeasurement> {
+    let stats = loudnorm_block(stderr)?;
+
+    Ok(LoudnessMeasurement {
+        integrated_lufs: loudnorm_number("input_i", &stats.input_i)?,
+        loudness_range: loudnorm_number("input_lra", &stats.input_lra)?,
+        true_peak: loudnorm_number("input_tp", &stats.input_tp)?,
+    })
+}
+
+/// Разбирает полное измерение первого прохода для двухпроходного `loudnorm`
+///
+/// В отличие от `parse_loudnorm` требует `input_thresh` и `target_offset`,
+/// которые второй проход получает как `measured_thresh` и `offset`.
+pub fn parse_loudnorm_stats(stderr: &str) -> AppResult<LoudnessStats> {
+    let stats = loudnorm_block(stderr)?;
+    let required = |name: &str, value: Option<String>| {
+        value.ok_or_else(|| AppError::Ffmpeg(format!("loudnorm output has no {}", name)))
+    };
+
+    Ok(LoudnessStats {
+        input_i: loudnorm_number("input_i", &stats.input_i)?,
+        input_tp: loudnorm_number("input_tp", &stats.input_tp)?,
+        input_lra: loudnorm_number("input_lra", &stats.input_lra)?,
+        input_thresh: loudnorm_number(
+            "input_thresh",
+            &required("input_thresh", stats.input_thresh)?,
+        )?,
+        target_offset: loudnorm_number(
+            "target_offset",
+            &required("target_offset", stats.target_offset)?,
+        )?,
+    })
+}
+
+/// Разбирает громкость выхода второго прохода `loudnorm`
+///
+/// Второй проход печатает тот же JSON блок после окончания потока;
+/// `output_*` - громкость уже нормализованного сигнала.
+pub fn parse_loudnorm_output(stderr: &str) -> AppResult<LoudnessMeasurement> {
+    let stats = loudnorm_block(stderr)?;
+    let required = |name: &str, value: Option<String>| {
+        value.ok_or_else(|| AppError::Ffmpeg(format!("loudnorm output has no {}", name)))
+    };
+
+    Ok(LoudnessMeasurement {
+        integrated_lufs: loudnorm_number("output_i", &required("output_i", stats.output_i)?)?,
+        loudness_range: loudnorm_number("output_lra", &required("output_lra", stats.output_lra)?)?,
+        true_peak: loudnorm_number("output_tp", &required("output_tp", stats.output_tp)?)?,
+    })
+}
+
+/// Находит JSON блок после последнего `Parsed_loudnorm` в stderr
+fn loudnorm_block(stderr: &str) -> AppResult<LoudnormStats> {
+    let missing = || AppError::Ffmpeg("loudnorm measurement not found in FFmpeg output".into());
+
+    let (_, after_marker) = stderr.rsplit_once("Parsed_loudnorm").ok_or_else(missing)?;
+    let start = after_marker.find('{').ok_or_else(missing)?;
+    let end = after_marker[start..].find('}').ok_or_else(missing)? + start;
+
+    serde_json::from_str(&after_marker[start..=end])
+        .map_err(|e| AppError::Ffmpeg(format!("Invalid loudnorm output: {}", e)))
+}
+
+/// Значение loudnorm - число в строке (`"-23.96"`)
+fn loudnorm_number(name: &str, value: &str) -> AppResult<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| AppError::Ffmpeg(format!("Invalid loudnorm {}: {}", name, value)))
+}
+
+/// Извлекает число, следующее за `key` в строке
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    let (_, rest) = line.split_once(key)?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_silencedetect() {
+        let stderr = "\
+[silencedetect @ 0x55d5c8a3c0] silence_start: 12.345
+[silencedetect @ 0x55d5c8a3c0] silence_end: 15 | silence_duration: 2.655
+size=     512kB time=00:00:20.00 bitrate= 209.7kbits/s speed=40x
+[silencedetect @ 0x55d5c8a3c0] silence_start: 31.5
+[silencedetect @ 0x55d5c8a3c0] silence_end: 33.25 | silence_duration: 1.75
+[silencedetect @ 0x55d5c8a3c0] silence_start: 58.1
+";
+
+        let intervals = parse_silencedetect(stderr);
+        assert_eq!(
+            intervals,
+            vec![
+                SilenceInterval {
+                    start: 12.345,
+                    end: Some(15.0)
+                },
+                SilenceInterval {
+                    start: 31.5,
+                    end: Some(33.25)
+                },
+                SilenceInterval {
+                    start: 58.1,
+                    end: None
+                },
+            ]
+        );
+        assert!((intervals[1].duration().unwrap() - 1.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_silencedetect_leading_silence_and_noise() {
+        let stderr = "\
+Input #0, mp3, from 'https://example.com/audio.mp3':
+[silencedetect @ 0x1] silence_end: 0.8 | silence_duration: 0.8
+[silencedetect @ 0x1] silence_start: garbage
+";
+
+        assert_eq!(
+            parse_silencedetect(stderr),
+            vec![SilenceInterval {
+                start: 0.0,
+                end: Some(0.8)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_loudnorm() {
+        let stderr = "\
+size=N/A time=00:03:12.00 bitrate=N/A speed= 310x
+[Parsed_loudnorm_0 @ 0x5581d8f2b2c0] 
+{
+\t\"input_i\" : \"-23.96\",
+\t\"input_tp\" : \"-5.04\",
+\t\"input_lra\" : \"7.60\",
+\t\"input_thresh\" : \"-34.30\",
+\t\"output_i\" : \"-16.06\",
+\t\"output_tp\" : \"-1.50\",
+\t\"output_lra\" : \"6.20\",
+\t\"output_thresh\" : \"-26.30\",
+\t\"normalization_type\" : \"dynamic\",
+\t\"target_offset\" : \"0.06\"
+}
+";
+
+        assert_eq!(
+            parse_loudnorm(stderr).unwrap(),
+            LoudnessMeasurement {
+                integrated_lufs: -23.96,
+                loudness_range: 7.6,
+                true_peak: -5.04,
+            }
+        );
+    }
+
+    #[test]
+    fn test_progress_parser() {
+        let mut parser = ProgressParser::default();
+        let lines = [
+            "bitrate= 128.0kbits/s",
+            "total_size=24576",
+            "out_time_us=1500000",
+            "out_time_ms=1500000",
+            "out_time=00:00:01.500000",
+            "speed=30.1x",
+        ];
+        for line in lines {
+            assert_eq!(parser.feed(line), ProgressLine::Field, "{}", line);
+        }
+        assert_eq!(
+            parser.feed("progress=continue"),
+            ProgressLine::Block(FfmpegProgress {
+                out_time_seconds: Some(1.5),
+                total_size: Some(24576),
+            })
+        );
+
+        parser.feed("out_time_ms=3250000");
+        parser.feed("total_size=N/A");
+        assert_eq!(
+            parser.feed("progress=end"),
+            ProgressLine::Block(FfmpegProgress {
+                out_time_seconds: Some(3.25),
+                total_size: Some(24576),
+            })
+        );
+    }
+
+    #[test]
+    fn test_progress_parser_ignores_log_lines() {
+        let mut parser = ProgressParser::default();
+        assert_eq!(
+            parser.feed("[mp3 @ 0x1] Estimating duration from bitrate, this may be inaccurate"),
+            ProgressLine::Other
+        );
+        assert_eq!(parser.feed("Error: size=1 too small"), ProgressLine::Other);
+        assert_eq!(parser.feed("stream_0_0_q=-1.0"), ProgressLine::Field);
+    }
+
+    #[test]
+    fn test_parse_loudnorm_stats() {
+        let stderr = "\
+[Parsed_loudnorm_0 @ 0x5581d8f2b2c0] 
+{
+\t\"input_i\" : \"-27.61\",
+\t\"input_tp\" : \"-4.47\",
+\t\"input_lra\" : \"18.06\",
+\t\"input_thresh\" : \"-39.20\",
+\t\"output_i\" : \"-16.58\",
+\t\"output_tp\" : \"-1.50\",
+\t\"output_lra\" : \"14.78\",
+\t\"output_thresh\" : \"-27.71\",
+\t\"normalization_type\" : \"dynamic\",
+\t\"target_offset\" : \"0.58\"
+}
+";
+
+        assert_eq!(
+            parse_loudnorm_stats(stderr).unwrap(),
+            LoudnessStats {
+                input_i: -27.61,
+                input_tp: -4.47,
+                input_lra: 18.06,
+                input_thresh: -39.2,
+                target_offset: 0.58,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_loudnorm_output() {
+        let stderr = "\
+frame=1 fps=0.0
+[Parsed_loudnorm_1 @ 0x5581d8f2b2c0] 
+{
+\t\"input_i\" : \"-27.61\",
+\t\"input_tp\" : \"-4.47\",
+\t\"input_lra\" : \"18.06\",
+\t\"input_thresh\" : \"-39.20\",
+\t\"output_i\" : \"-16.02\",
+\t\"output_tp\" : \"-1.50\",
+\t\"output_lra\" : \"11.20\",
+\t\"output_thresh\" : \"-27.71\",
+\t\"normalization_type\" : \"linear\",
+\t\"target_offset\" : \"0.02\"
+}
+";
+
+        assert_eq!(
+            parse_loudnorm_output(stderr).unwrap(),
+            LoudnessMeasurement {
+                integrated_lufs: -16.02,
+                loudness_range: 11.2,
+                true_peak: -1.5,
+            }
+        );
+
+        // Блок первого прохода без output_* - ошибка
+        let measurement = "[Parsed_loudnorm_0 @ 0x1] \n{\n\"input_i\" : \"-23.96\",\n\
+                           \"input_tp\" : \"-5.04\",\n\"input_lra\" : \"7.60\"\n}\n";
+        assert!(matches!(
+            parse_loudnorm_output(measurement),
+            Err(AppError::Ffmpeg(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_loudnorm_stats_requires_threshold() {
+        let stderr = "[Parsed_loudnorm_0 @ 0x1] \n{\n\"input_i\" : \"-23.96\",\n\
+                      \"input_tp\" : \"-5.04\",\n\"input_lra\" : \"7.60\"\n}\n";
+        assert!(parse_loudnorm(stderr).is_ok());
+        assert!(matches!(parse_loudnorm_stats(stderr), Err(AppError::Ffmpeg(_))));
+    }
+
+    #[test]
+    fn test_parse_loudnorm_missing_block() {
+        let stderr = "https://example.com/audio.mp3: Server returned 404 Not Found\n";
+        assert!(matches!(parse_loudnorm(stderr), Err(AppError::Ffmpeg(_))));
+
+        let truncated = "[Parsed_loudnorm_0 @ 0x1] \n{\n\t\"input_i\" : \"-23.96\",\n";
+        assert!(parse_loudnorm(truncated).is_err());
+    }
+}
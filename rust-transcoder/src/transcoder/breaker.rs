@@ -0,0 +1,377 @@
+//! Circuit breaker для запусков FFmpeg
+//!
+//! Если FFmpeg падает на каждом запросе (удалён кодек, нет места на диске),
+//! нет смысла запускать заведомо неудачные процессы. После
+//! `failure_threshold` подряд идущих сбоев в пределах `window` breaker
+//! размыкается и на `cooldown` отклоняет запросы, затем пропускает один
+//! пробный запрос: успех замыкает breaker, сбой снова размыкает.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::error::{AppError, AppResult};
+
+/// Состояние breaker'а (для `/health/ready`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Запросы проходят
+    Closed,
+    /// Запросы отклоняются до конца cooldown
+    Open,
+    /// Cooldown истёк, пробный запрос проверяет восстановление
+    HalfOpen,
+}
+
+impl fmt::Display for BreakerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakerState::Closed => write!(f, "closed"),
+            BreakerState::Open => write!(f, "open"),
+            BreakerState::HalfOpen => write!(f, "half_open"),
+        }
+    }
+}
+
+/// Пороги breaker'а
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerSettings {
+    /// Сбоев подряд до размыкания
+    pub failure_threshold: u32,
+    /// Окно, в которое должны уложиться сбои
+    pub window: Duration,
+    /// Сколько отклонять запросы после размыкания
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerSettings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Inner {
+    Closed {
+        failures: u32,
+        first_failure: Option<Instant>,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probe_in_flight: bool,
+    },
+}
+
+/// Разделяемый circuit breaker (clone - тот же breaker)
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    settings: BreakerSettings,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(BreakerSettings::default())
+    }
+}
+
+impl CircuitBreaker {
+    /// Создаёт замкнутый breaker
+    pub fn new(settings: BreakerSettings) -> Self {
+        Self {
+            settings,
+            inner: Arc::new(Mutex::new(Inner::Closed {
+                failures: 0,
+                first_failure: None,
+            })),
+        }
+    }
+
+    /// Текущее состояние
+    pub fn state(&self) -> BreakerState {
+        let mut inner = self.lock();
+        Self::expire_cooldown(&mut inner);
+        match *inner {
+            Inner::Closed { .. } => BreakerState::Closed,
+            Inner::Open { .. } => BreakerState::Open,
+            Inner::HalfOpen { .. } => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Пропускает запрос или отклоняет его с `AppError::ServiceDegraded`
+    ///
+    /// Пропущенный запрос обязан сообщить итог: `record_success`,
+    /// `record_failure` или `record_cancelled`.
+    pub fn check(&self) -> AppResult<()> {
+        let mut inner = self.lock();
+        Self::expire_cooldown(&mut inner);
+        match *inner {
+            Inner::Closed { .. } => Ok(()),
+            Inner::Open { until } => Err(AppError::ServiceDegraded(
+                until
+                    .saturating_duration_since(Instant::now())
+                    .as_secs()
+                    .max(1),
+            )),
+            Inner::HalfOpen {
+                ref mut probe_in_flight,
+            } => {
+                if *probe_in_flight {
+                    Err(AppError::ServiceDegraded(
+                        self.settings.cooldown.as_secs().max(1),
+                    ))
+                } else {
+                    *probe_in_flight = true;
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// `check` с пропуском, который сам освобождает место пробного запроса
+    ///
+    /// Пропуск, сброшенный без итога (ошибка источника, отключение клиента,
+    /// таймаут запроса), считается `record_cancelled`.
+    pub fn admit(&self) -> AppResult<BreakerPass> {
+        self.check()?;
+        Ok(BreakerPass {
+            breaker: self.clone(),
+            recorded: false,
+        })
+    }
+
+    /// FFmpeg отработал успешно
+    pub fn record_success(&self) {
+        let mut inner = self.lock();
+        if matches!(*inner, Inner::HalfOpen { .. }) {
+            info!("FFmpeg recovered, circuit breaker closed");
+        }
+        *inner = Inner::Closed {
+            failures: 0,
+            first_failure: None,
+        };
+    }
+
+    /// FFmpeg не запустился или завершился с ошибкой
+    pub fn record_failure(&self) {
+        let mut inner = self.lock();
+        let now = Instant::now();
+
+        let trip = match *inner {
+            Inner::Closed {
+                ref mut failures,
+                ref mut first_failure,
+            } => {
+                // Сбои, растянутые дольше окна, не считаются серией
+                if first_failure.map_or(true, |first| now - first > self.settings.window) {
+                    *failures = 0;
+                    *first_failure = Some(now);
+                }
+                *failures += 1;
+                *failures >= self.settings.failure_threshold
+            }
+            Inner::HalfOpen { .. } => true,
+            Inner::Open { .. } => false,
+        };
+
+        if trip {
+            warn!(
+                cooldown_secs = self.settings.cooldown.as_secs(),
+                "FFmpeg keeps failing, circuit breaker opened"
+            );
+            *inner = Inner::Open {
+                until: now + self.settings.cooldown,
+            };
+        }
+    }
+
+    /// Запрос прерван до итога (клиент отключился, сессия отменена)
+    ///
+    /// Освобождает место пробного запроса, не меняя счётчик сбоев.
+    pub fn record_cancelled(&self) {
+        if let Inner::HalfOpen {
+            ref mut probe_in_flight,
+        } = *self.lock()
+        {
+            *probe_in_flight = false;
+        }
+    }
+
+    fn expire_cooldown(inner: &mut Inner) {
+        if let Inner::Open { until } = *inner {
+            if Instant::now() >= until {
+                *inner = Inner::HalfOpen {
+                    probe_in_flight: false,
+                };
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("circuit breaker poisoned")
+    }
+}
+
+/// Запрос, пропущенный breaker'ом (`CircuitBreaker::admit`)
+#[derive(Debug)]
+#[must_use = "dropping the pass records the request as cancelled"]
+pub struct BreakerPass {
+    breaker: CircuitBreaker,
+    recorded: bool,
+}
+
+impl BreakerPass {
+    /// FFmpeg отработал успешно
+    pub fn success(mut self) {
+        self.recorded = true;
+        self.breaker.record_success();
+    }
+
+    /// FFmpeg не запустился или завершился с ошибкой
+    pub fn failure(mut self) {
+        self.recorded = true;
+        self.breaker.record_failure();
+    }
+
+    /// Итог сообщит владелец запущенного процесса (поток, задача завершения)
+    pub fn hand_off(mut self) {
+        self.recorded = true;
+    }
+}
+
+impl Drop for BreakerPass {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.breaker.record_cancelled();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(BreakerSettings {
+            failure_threshold: 3,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_trips_after_threshold_failures() {
+        let breaker = breaker();
+
+        for _ in 0..2 {
+            breaker.check().unwrap();
+            breaker.record_failure();
+        }
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(matches!(
+            breaker.check(),
+            Err(AppError::ServiceDegraded(30))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_success_resets_failure_count() {
+        let breaker = breaker();
+
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_outside_window_do_not_trip() {
+        let breaker = breaker();
+
+        breaker.record_failure();
+        breaker.record_failure();
+        tokio::time::advance(Duration::from_secs(61)).await;
+        breaker.record_failure();
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recovers_after_cooldown() {
+        let breaker = breaker();
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        // Один пробный запрос, остальные ждут его итога
+        breaker.check().unwrap();
+        assert!(breaker.check().is_err());
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.check().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_probe_reopens() {
+        let breaker = breaker();
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        tokio::time::advance(Duration::from_secs(30)).await;
+
+        breaker.check().unwrap();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_probe_frees_slot() {
+        let breaker = breaker();
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        tokio::time::advance(Duration::from_secs(30)).await;
+
+        breaker.check().unwrap();
+        breaker.record_cancelled();
+        assert!(breaker.check().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropped_pass_frees_probe_slot() {
+        let breaker = breaker();
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        tokio::time::advance(Duration::from_secs(31)).await;
+
+        let pass = breaker.admit().unwrap();
+        assert!(breaker.admit().is_err());
+        drop(pass);
+
+        // Пробный запрос без итога не блокирует следующий
+        breaker.admit().unwrap().success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+}
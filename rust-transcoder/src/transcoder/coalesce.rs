@@ -0,0 +1,209 @@
+//! Объединение одинаковых одновременных запросов
+//!
+//! Первый запрос с данным ключом запускает работу отдельной задачей,
+//! все запросы ждут и получают копию того же (буферизованного) результата.
+//! Работа доводится до конца, даже если ждущих не осталось: иначе
+//! захваченный ею permit жил бы в реестре, пока не придёт такой же запрос.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+
+type InFlight<T> = Arc<Mutex<HashMap<u64, Shared<BoxFuture<'static, T>>>>>;
+
+/// Реестр выполняющихся задач по ключу нормализованного запроса
+pub struct Coalescer<T: Clone> {
+    in_flight: InFlight<T>,
+}
+
+impl<T: Clone> Default for Coalescer<T> {
+    fn default() -> Self {
+        Self {
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<T: Clone> fmt::Debug for Coalescer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let in_flight = self.in_flight.lock().map(|m| m.len()).unwrap_or_default();
+        f.debug_struct("Coalescer")
+            .field("in_flight", &in_flight)
+            .finish()
+    }
+}
+
+impl<T> Coalescer<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Создаёт пустой реестр
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Выполняет `work` или присоединяется к уже выполняющейся задаче с тем же ключом
+    ///
+    /// # Arguments
+    /// * `key` - хэш нормализованного запроса (см. `TranscodeProfile::coalescing_key`)
+    /// * `work` - фабрика задачи, вызывается только если задачи с ключом нет;
+    ///   иначе сбрасывается сразу, до ожидания результата, вместе с
+    ///   захваченными ресурсами (permit). Задача выполняется в `tokio::spawn`
+    ///   и не отменяется, когда сбрасываются все ждущие
+    pub async fn run<F, Fut>(&self, key: u64, work: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let shared = {
+            let mut in_flight = self.in_flight.lock().expect("coalescer lock poisoned");
+            match in_flight.get(&key) {
+                Some(shared) => {
+                    drop(work);
+                    shared.clone()
+                }
+                None => {
+                    let finished = Finished {
+                        registry: Arc::clone(&self.in_flight),
+                        key,
+                    };
+                    let fut = work();
+                    let handle = tokio::spawn(async move {
+                        let _finished = finished;
+                        fut.await
+                    });
+                    let shared = async move {
+                        match handle.await {
+                            Ok(output) => output,
+                            Err(err) => std::panic::resume_unwind(err.into_panic()),
+                        }
+                    }
+                    .boxed()
+                    .shared();
+                    in_flight.insert(key, shared.clone());
+                    shared
+                }
+            }
+        };
+
+        shared.await
+    }
+
+    /// Количество выполняющихся задач
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+            .lock()
+            .expect("coalescer lock poisoned")
+            .len()
+    }
+}
+
+/// Снимает ключ с реестра по завершении задачи (в том числе паникой):
+/// следующий запрос после этого запускает новую работу
+struct Finished<T: Clone> {
+    registry: InFlight<T>,
+    key: u64,
+}
+
+impl<T: Clone> Drop for Finished<T> {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.registry.lock() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    async fn fake_transcode(spawns: Arc<AtomicUsize>) -> Arc<Vec<u8>> {
+        spawns.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Arc::new(vec![1, 2, 3])
+    }
+
+    #[tokio::test]
+    async fn test_identical_requests_share_one_spawn() {
+        let coalescer = Coalescer::new();
+        let spawns = Arc::new(AtomicUsize::new(0));
+
+        let (a, b) = tokio::join!(
+            coalescer.run(42, || fake_transcode(spawns.clone())),
+            coalescer.run(42, || fake_transcode(spawns.clone())),
+        );
+
+        assert_eq!(spawns.load(Ordering::SeqCst), 1);
+        assert_eq!(a, b);
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_joined_request_drops_its_work_factory() {
+        let coalescer = Coalescer::new();
+        let spawns = Arc::new(AtomicUsize::new(0));
+        let resource = Arc::new(());
+
+        let leader = coalescer.run(7, || fake_transcode(spawns.clone()));
+        let held = resource.clone();
+        let follower_spawns = spawns.clone();
+        let follower = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            coalescer
+                .run(7, move || {
+                    let _held = held;
+                    fake_transcode(follower_spawns)
+                })
+                .await
+        };
+        let check = async {
+            tokio::time::sleep(Duration::from_millis(25)).await;
+            // Присоединившийся запрос ещё ждёт, но фабрика уже сброшена
+            assert_eq!(Arc::strong_count(&resource), 1);
+        };
+
+        tokio::join!(leader, follower, check);
+        assert_eq!(spawns.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_work_finishes_without_waiters() {
+        let coalescer = Coalescer::new();
+        let spawns = Arc::new(AtomicUsize::new(0));
+        let resource = Arc::new(());
+
+        let held = resource.clone();
+        let work_spawns = spawns.clone();
+        let waiter = coalescer.run(3, move || async move {
+            let _held = held;
+            fake_transcode(work_spawns).await
+        });
+        // Ждущий сброшен до результата
+        let _ = tokio::time::timeout(Duration::from_millis(10), waiter).await;
+        assert_eq!(coalescer.in_flight(), 1);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(coalescer.in_flight(), 0);
+        assert_eq!(Arc::strong_count(&resource), 1);
+        assert_eq!(spawns.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_run_separately() {
+        let coalescer = Coalescer::new();
+        let spawns = Arc::new(AtomicUsize::new(0));
+
+        tokio::join!(
+            coalescer.run(1, || fake_transcode(spawns.clone())),
+            coalescer.run(2, || fake_transcode(spawns.clone())),
+        );
+
+        assert_eq!(spawns.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,656 @@
+//! Загрузка источника сервисом (`Config::fetch_source`)
+//!
+//! Вместо HTTP клиента FFmpeg источник читает reqwest: статус ответа,
+//! редиректы и таймаут проверяются до запуска процесса, и ошибка источника
+//! не выглядит как сбой энкодера. Body передаётся в stdin FFmpeg (`-i pipe:0`)
+//! через `PipedSource` - так же, как файл из `POST /api/v1/transcode/upload`.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{redirect, StatusCode};
+use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
+use tracing::debug;
+
+use crate::error::{AppError, AppResult};
+use crate::models::source::is_blocked_ip;
+use crate::models::source_is_seekable;
+use crate::models::transcode::{host_is_allowlisted, validate_source_url};
+
+use super::profiles::TranscodeProfile;
+
+/// Вход FFmpeg для загруженного сервисом источника
+pub const PIPE_INPUT: &str = "pipe:0";
+
+/// Максимум редиректов до источника
+const MAX_REDIRECTS: usize = 5;
+
+/// HTTP клиент для источников
+#[derive(Debug, Clone)]
+pub struct SourceFetcher {
+    client: reqwest::Client,
+    /// Клиент для запросов с `source_headers`: редиректы только в пределах
+    /// хоста и порта источника
+    same_origin_client: reqwest::Client,
+    timeout: Duration,
+    max_bytes: Option<u64>,
+}
+
+impl SourceFetcher {
+    /// Клиент с таймаутом соединения и заголовков `timeout`
+    ///
+    /// Каждый редирект проходит ту же проверку, что и `source_url` (SSRF,
+    /// `host_allowlist`): иначе публичный URL мог бы перенаправить на
+    /// внутренний адрес. Адреса, в которые резолвится имя хоста, тоже
+    /// проверяются (`PublicResolver`). Ошибка - клиент не создан (например,
+    /// не инициализирован TLS).
+    pub fn new(
+        timeout: Duration,
+        max_bytes: Option<u64>,
+        host_allowlist: Vec<String>,
+    ) -> AppResult<Self> {
+        let resolver = Arc::new(PublicResolver {
+            host_allowlist: host_allowlist.clone(),
+        });
+        let client = |same_origin| {
+            reqwest::Client::builder()
+                .connect_timeout(timeout)
+                .dns_resolver(resolver.clone())
+                .redirect(redirect_policy(host_allowlist.clone(), same_origin))
+                .build()
+                .map_err(|err| AppError::Internal(format!("Failed to build HTTP client: {}", err)))
+        };
+
+        Ok(Self {
+            client: client(false)?,
+            same_origin_client: client(true)?,
+            timeout,
+            max_bytes,
+        })
+    }
+
+    /// Запрашивает источник и проверяет ответ
+    ///
+    /// 404 и 410 - `SourceNotFound`, прочие неуспешные статусы и ошибки
+    /// соединения - `SourceUnavailable`, нет заголовков ответа за `timeout` -
+    /// `Timeout`. Body не читается: его забирает `PipedSource::pipe_into`.
+    ///
+    /// `headers` (`TranscodeRequest::source_headers`) уходят только на хост
+    /// источника: reqwest при редиректе на другой хост убирает лишь
+    /// `Authorization` и `Cookie`, поэтому с заголовками редирект на другой
+    /// хост или порт - ошибка.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        headers: Option<&HashMap<String, String>>,
+    ) -> AppResult<PipedSource> {
+        let client = match headers {
+            Some(_) => &self.same_origin_client,
+            None => &self.client,
+        };
+        let request = client
+            .get(url)
+            .headers(header_map(headers.into_iter().flatten())?);
+        let response = tokio::time::timeout(self.timeout, request.send())
+            .await
+            .map_err(|_| {
+                AppError::Timeout(format!(
+                    "Source did not respond within {} seconds",
+                    self.timeout.as_secs()
+                ))
+            })?
+            .map_err(fetch_error)?;
+
+        let status = response.status();
+        if matches!(status, StatusCode::NOT_FOUND | StatusCode::GONE) {
+            return Err(AppError::SourceNotFound(format!(
+                "source returned HTTP {}",
+                status.as_u16()
+            )));
+        }
+        if !status.is_success() {
+            return Err(AppError::SourceUnavailable(format!(
+                "source returned HTTP {}",
+                status.as_u16()
+            )));
+        }
+
+        if let (Some(limit), Some(length)) = (self.max_bytes, response.content_length()) {
+            if length > limit {
+                return Err(AppError::SourceUnavailable(format!(
+                    "source is {} bytes, the limit is {}",
+                    length, limit
+                )));
+            }
+        }
+
+        let body = response
+            .bytes_stream()
+            .map_err(|err| io::Error::other(err.without_url()));
+        Ok(PipedSource::new(body, self.max_bytes))
+    }
+}
+
+/// Политика редиректов: лимит, проверка как у `source_url` и, при
+/// `same_origin`, запрет смены хоста или порта
+fn redirect_policy(host_allowlist: Vec<String>, same_origin: bool) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error(format!("more than {} redirects", MAX_REDIRECTS));
+        }
+        if same_origin {
+            let origin = |url: &url::Url| {
+                (
+                    url.host_str().map(str::to_owned),
+                    url.port_or_known_default(),
+                )
+            };
+            if attempt.previous().first().map(origin) != Some(origin(attempt.url())) {
+                return attempt
+                    .error("source with source_headers redirected to another host".to_string());
+            }
+        }
+        match validate_source_url(attempt.url().as_str(), &host_allowlist) {
+            Ok(()) => attempt.follow(),
+            Err(err) => attempt.error(err),
+        }
+    })
+}
+
+/// DNS resolver загрузчика: имя, которое резолвится хотя бы в один закрытый
+/// адрес, отклоняется
+///
+/// `validate_source_url` видит только IP-литералы; соединение идёт ровно на
+/// проверенные здесь адреса, так что подмена DNS между проверкой и запросом
+/// не помогает. Хосты из allowlist, как и там, могут быть закрытыми.
+struct PublicResolver {
+    host_allowlist: Vec<String>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let allowlisted = host_is_allowlisted(&host, &self.host_allowlist);
+
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if !allowlisted && addrs.iter().any(|addr| is_blocked_ip(addr.ip())) {
+                return Err(blocked_host_message(&host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn blocked_host_message(host: &str) -> String {
+    format!(
+        "source host '{}' resolves to a loopback or private address",
+        host
+    )
+}
+
+/// Проверка `PublicResolver` для источника, который откроет ffprobe или
+/// FFmpeg, а не загрузчик
+///
+/// Имя хоста http(s) URL резолвится заранее; закрытый адрес -
+/// `AppError::Validation`, ошибка DNS - `SourceUnavailable` (иначе
+/// временный сбой или split-horizon DNS пропустили бы проверку). FFmpeg
+/// резолвит имя повторно и сам следует редиректам: без `FETCH_SOURCE`
+/// проверка не защищает от редиректа на внутренний адрес.
+pub async fn check_source_host(source_url: &str, host_allowlist: &[String]) -> AppResult<()> {
+    let Ok(url) = url::Url::parse(source_url) else {
+        return Ok(());
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return Ok(());
+    }
+    // IP-литералы проверяет `validate_source_url`
+    let host = match url.host() {
+        Some(url::Host::Domain(host)) => host.to_string(),
+        _ => return Ok(()),
+    };
+    if host_is_allowlisted(&host, host_allowlist) {
+        return Ok(());
+    }
+
+    let addrs = match lookup_source_host(&host).await {
+        Ok(addrs) => addrs,
+        Err(err) => {
+            debug!(host, error = %err, "Source host does not resolve");
+            return Err(AppError::SourceUnavailable(format!(
+                "source host '{}' does not resolve",
+                host
+            )));
+        }
+    };
+    if addrs.iter().any(|addr| is_blocked_ip(addr.ip())) {
+        return Err(AppError::Validation(blocked_host_message(&host)));
+    }
+    Ok(())
+}
+
+/// Адреса хоста источника
+async fn lookup_source_host(host: &str) -> io::Result<Vec<SocketAddr>> {
+    #[cfg(test)]
+    if let Some(addrs) = testing::lookup(host) {
+        return Ok(addrs);
+    }
+    Ok(tokio::net::lookup_host((host, 0)).await?.collect())
+}
+
+/// Заголовки запроса к источнику
+fn header_map<'a>(headers: impl Iterator<Item = (&'a String, &'a String)>) -> AppResult<HeaderMap> {
+    headers
+        .map(|(name, value)| {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                AppError::Validation(format!("source_headers: invalid header name '{}'", name))
+            })?;
+            let value = HeaderValue::from_str(value).map_err(|_| {
+                AppError::Validation(format!("source_headers: invalid value for {}", name))
+            })?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Ошибка reqwest как ошибка источника
+///
+/// Текст без URL: reqwest дописывает его целиком, с query (подпись
+/// presigned URL) и userinfo, а ошибка попадает в лог и статус сессии.
+fn fetch_error(err: reqwest::Error) -> AppError {
+    let err = err.without_url();
+    if err.is_timeout() {
+        AppError::Timeout(format!("Source request timed out: {}", err))
+    } else if err.is_redirect() {
+        AppError::SourceUnavailable(format!("Source redirect rejected: {}", err))
+    } else {
+        AppError::SourceUnavailable(format!("Failed to fetch source: {}", err))
+    }
+}
+
+/// Можно ли передать источник профиля через stdin
+///
+/// Только один http(s) вход с конечным файлом: HLS плейлист FFmpeg должен
+/// читать сам (сегменты - отдельные запросы), а pre-roll, склейка и фон
+/// добавляют другие `-i`.
+pub fn can_fetch(profile: &TranscodeProfile) -> bool {
+    let single_input = profile.preroll_url.is_none()
+        && profile.extra_sources.is_empty()
+        && profile.background.is_none()
+        && profile.input_format.as_deref() != Some("lavfi");
+    let http = url::Url::parse(&profile.source_url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+
+    single_input && http && source_is_seekable(&profile.source_url)
+}
+
+/// Body источника для stdin FFmpeg: ответ источника или загруженный файл
+pub struct PipedSource {
+    body: BoxStream<'static, io::Result<Bytes>>,
+    max_bytes: Option<u64>,
+}
+
+impl std::fmt::Debug for PipedSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipedSource")
+            .field("max_bytes", &self.max_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PipedSource {
+    /// Источник из потока chunk'ов; больше `max_bytes` - ошибка при копировании
+    pub fn new(
+        body: impl futures::Stream<Item = io::Result<Bytes>> + Send + 'static,
+        max_bytes: Option<u64>,
+    ) -> Self {
+        Self {
+            body: body.boxed(),
+            max_bytes,
+        }
+    }
+
+    /// Копирует body в stdin FFmpeg и закрывает его; возвращает число байт
+    ///
+    /// Превышение `max_bytes` или обрыв загрузки - ошибка; FFmpeg в этом
+    /// случае получает закрытый stdin.
+    pub async fn pipe_into(mut self, mut stdin: ChildStdin) -> io::Result<u64> {
+        let mut written = 0u64;
+
+        while let Some(chunk) = self.body.next().await {
+            let chunk = chunk?;
+            written += chunk.len() as u64;
+            if let Some(limit) = self.max_bytes.filter(|&limit| written > limit) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("source exceeds {} bytes", limit),
+                ));
+            }
+            stdin.write_all(&chunk).await?;
+        }
+
+        stdin.shutdown().await?;
+        Ok(written)
+    }
+}
+
+/// DNS для тестов без сети
+#[cfg(test)]
+mod testing {
+    use std::net::SocketAddr;
+
+    /// Зарезервированные для примеров домены (RFC 2606)
+    const EXAMPLE_DOMAINS: &[&str] = &["example.com", "example.net", "example.org"];
+
+    /// Имена RFC 2606 резолвятся в публичный адрес, остальные - через DNS
+    pub(super) fn lookup(host: &str) -> Option<Vec<SocketAddr>> {
+        let host = host.to_ascii_lowercase();
+        EXAMPLE_DOMAINS
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+            .then(|| vec![SocketAddr::from(([93, 184, 215, 14], 0))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        http::{header, HeaderMap as AxumHeaderMap},
+        response::{IntoResponse, Redirect},
+        routing::get,
+        Router,
+    };
+    use std::net::SocketAddr;
+
+    /// Локальный HTTP сервер источника
+    async fn source_server() -> SocketAddr {
+        let app = Router::new()
+            .route("/audio.mp3", get(|| async { "source-bytes" }))
+            .route(
+                "/missing.mp3",
+                get(|| async { (axum::http::StatusCode::NOT_FOUND, "not found") }),
+            )
+            .route(
+                "/error.mp3",
+                get(|| async { axum::http::StatusCode::SERVICE_UNAVAILABLE }),
+            )
+            .route(
+                "/internal.mp3",
+                get(|| async { Redirect::temporary("http://10.0.0.5/audio.mp3") }),
+            )
+            .route(
+                "/private.mp3",
+                get(|headers: AxumHeaderMap| async move {
+                    let authorized = headers
+                        .get(header::AUTHORIZATION)
+                        .is_some_and(|value| value == "Bearer secret");
+                    if !authorized {
+                        return axum::http::StatusCode::UNAUTHORIZED.into_response();
+                    }
+                    let api_key = headers.get("x-api-key").map(|key| key.as_bytes().to_vec());
+                    api_key.unwrap_or_default().into_response()
+                }),
+            )
+            .route(
+                "/moved.mp3",
+                get(|| async { Redirect::temporary("/private.mp3") }),
+            )
+            .route(
+                "/large.mp3",
+                get(|| async { ([(header::CONTENT_TYPE, "audio/mpeg")], vec![0u8; 4096]) }),
+            );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    fn fetcher(max_bytes: Option<u64>) -> SourceFetcher {
+        SourceFetcher::new(
+            Duration::from_secs(5),
+            max_bytes,
+            vec!["127.0.0.1".to_string()],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_http_status_maps_to_source_errors() {
+        let addr = source_server().await;
+        let fetcher = fetcher(None);
+
+        let err = fetcher
+            .fetch(&format!("http://{}/missing.mp3", addr), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::SourceNotFound(_)), "{:?}", err);
+        let response = err.into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+
+        let err = fetcher
+            .fetch(&format!("http://{}/error.mp3", addr), None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, AppError::SourceUnavailable(msg) if msg.contains("503")),
+            "{:?}",
+            err
+        );
+
+        assert!(fetcher
+            .fetch(&format!("http://{}/audio.mp3", addr), None)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_private_address_is_rejected() {
+        let addr = source_server().await;
+
+        let err = fetcher(None)
+            .fetch(&format!("http://{}/internal.mp3", addr), None)
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(&err, AppError::SourceUnavailable(msg) if msg.contains("redirect")),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_error_hides_source_url() {
+        // Порт без слушателя: ошибка соединения
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let err = fetcher(None)
+            .fetch(
+                &format!("http://user:pass@{}/a.mp3?X-Amz-Signature=secret", addr),
+                None,
+            )
+            .await
+            .unwrap_err();
+
+        let message = err.to_string();
+        assert!(matches!(err, AppError::SourceUnavailable(_)), "{:?}", err);
+        assert!(!message.contains("Signature"), "{}", message);
+        assert!(!message.contains("secret"), "{}", message);
+        assert!(!message.contains("pass"), "{}", message);
+        assert!(!message.contains('?'), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_host_resolving_to_private_address_is_rejected() {
+        let addr = source_server().await;
+        let url = format!("http://localhost:{}/audio.mp3", addr.port());
+
+        // Имя не IP-литерал: адрес проверяет resolver
+        let err = SourceFetcher::new(Duration::from_secs(5), None, Vec::new())
+            .unwrap()
+            .fetch(&url, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, AppError::SourceUnavailable(msg) if msg.contains("private address")),
+            "{:?}",
+            err
+        );
+
+        // Хост из allowlist может быть закрытым
+        let fetcher =
+            SourceFetcher::new(Duration::from_secs(5), None, vec!["localhost".into()]).unwrap();
+        assert!(fetcher.fetch(&url, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_source_host_resolving_to_private_address_is_rejected() {
+        let err = check_source_host("http://localhost:8080/a.mp3", &[])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, AppError::Validation(msg) if msg.contains("private address")),
+            "{:?}",
+            err
+        );
+
+        for (url, allowlist) in [
+            ("http://localhost:8080/a.mp3", vec!["localhost".to_string()]),
+            ("https://cdn.example.com/a.mp3", Vec::new()),
+            ("https://93.184.215.14/a.mp3", Vec::new()),
+            ("s3://media/a.mp3", Vec::new()),
+        ] {
+            assert!(check_source_host(url, &allowlist).await.is_ok(), "{}", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unresolvable_source_host_is_rejected() {
+        // Сбой DNS не пропускает источник к FFmpeg без проверки адресов
+        let err = check_source_host("https://audio.invalid/a.mp3", &[])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, AppError::SourceUnavailable(msg) if msg.contains("does not resolve")),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_content_length_over_limit_is_rejected() {
+        let addr = source_server().await;
+        let url = format!("http://{}/large.mp3", addr);
+
+        let err = fetcher(Some(1024)).fetch(&url, None).await.unwrap_err();
+        assert!(matches!(err, AppError::SourceUnavailable(_)), "{:?}", err);
+
+        assert!(fetcher(Some(4096)).fetch(&url, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_source_headers_reach_source() {
+        let addr = source_server().await;
+        let url = format!("http://{}/private.mp3", addr);
+        let fetcher = fetcher(None);
+
+        let err = fetcher.fetch(&url, None).await.unwrap_err();
+        assert!(
+            matches!(&err, AppError::SourceUnavailable(msg) if msg.contains("401")),
+            "{:?}",
+            err
+        );
+
+        let headers = HashMap::from([
+            ("Authorization".to_string(), "Bearer secret".to_string()),
+            ("X-Api-Key".to_string(), "cdn-key".to_string()),
+        ]);
+        let source = fetcher.fetch(&url, Some(&headers)).await.unwrap();
+        let body: Vec<Bytes> = source.body.try_collect().await.unwrap();
+        assert_eq!(body.concat(), b"cdn-key");
+
+        // Редирект в пределах хоста сохраняет заголовки
+        let moved = format!("http://{}/moved.mp3", addr);
+        let source = fetcher.fetch(&moved, Some(&headers)).await.unwrap();
+        let body: Vec<Bytes> = source.body.try_collect().await.unwrap();
+        assert_eq!(body.concat(), b"cdn-key");
+    }
+
+    #[tokio::test]
+    async fn test_source_headers_do_not_follow_cross_host_redirect() {
+        // Чужой хост (другой порт) записывает полученный x-api-key
+        let leaked = Arc::new(std::sync::Mutex::new(None::<String>));
+        let recorder = leaked.clone();
+        let other = Router::new().route(
+            "/audio.mp3",
+            get(move |headers: AxumHeaderMap| async move {
+                let key = headers
+                    .get("x-api-key")
+                    .map(|key| key.to_str().unwrap().to_string());
+                *recorder.lock().unwrap() = key;
+                "other-bytes"
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let other_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, other).await.unwrap() });
+
+        let target = format!("http://{}/audio.mp3", other_addr);
+        let source = Router::new().route(
+            "/audio.mp3",
+            get(move || async move { Redirect::temporary(&target) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, source).await.unwrap() });
+        let url = format!("http://{}/audio.mp3", addr);
+
+        let headers = HashMap::from([("X-Api-Key".to_string(), "cdn-key".to_string())]);
+        let err = fetcher(None).fetch(&url, Some(&headers)).await.unwrap_err();
+        assert!(
+            matches!(&err, AppError::SourceUnavailable(msg) if msg.contains("redirect")),
+            "{:?}",
+            err
+        );
+        assert_eq!(*leaked.lock().unwrap(), None);
+
+        // Без заголовков редирект на другой хост разрешён
+        assert!(fetcher(None).fetch(&url, None).await.is_ok());
+    }
+
+    #[test]
+    fn test_can_fetch_only_single_http_file() {
+        let profile = TranscodeProfile::telegram_voice("https://example.com/a.mp3");
+        assert!(can_fetch(&profile));
+
+        for url in [
+            "https://example.com/live/index.m3u8",
+            "rtmp://live.example.com/app",
+            "/tmp/audio.mp3",
+        ] {
+            assert!(
+                !can_fetch(&TranscodeProfile::telegram_voice(url)),
+                "{}",
+                url
+            );
+        }
+
+        let mut profile = TranscodeProfile::telegram_voice("https://example.com/a.mp3");
+        profile.preroll_url = Some("https://example.com/jingle.mp3".to_string());
+        assert!(!can_fetch(&profile));
+    }
+}
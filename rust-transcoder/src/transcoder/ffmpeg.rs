@@ -2,15 +2,23 @@
 //!
 //! Управление FFmpeg subprocess для транскодирования аудио.
 
+use std::io;
 use std::process::Stdio;
+use std::time::Duration;
 
-use tokio::process::{Child, Command};
-use tracing::{debug, instrument};
+use axum::body::Bytes;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::{Child, ChildStderr, Command};
+use tokio::task::JoinHandle;
+use tracing::{debug, instrument, warn, Instrument};
 
 use crate::error::{AppError, AppResult};
-use crate::models::{AudioCodec, AudioFormat, AudioQuality};
+use crate::models::LoudnessMeasurement;
 
+use super::analysis::{self, FfmpegProgress, ProgressLine, ProgressParser};
+use super::fetch::{PipedSource, PIPE_INPUT};
 use super::profiles::TranscodeProfile;
+use super::redact::{redact_args, redact_url};
 
 /// FFmpeg процесс для транскодирования
 #[derive(Debug)]
@@ -22,24 +30,73 @@ pub struct FfmpegProcess {
 }
 
 impl FfmpegProcess {
-    /// Запускает FFmpeg процесс с указанным профилем
-    #[instrument(skip(profile), fields(source = %profile.source_url))]
+    /// Запускает FFmpeg процесс с указанным профилем (`ffmpeg` из PATH)
+    ///
+    /// Сервис запускает бинарь из `Config::ffmpeg_path` через `spawn_with_binary`.
     pub async fn spawn(profile: TranscodeProfile) -> AppResult<Self> {
+        Self::spawn_with_binary("ffmpeg", profile).await
+    }
+
+    /// Запускает указанный бинарь FFmpeg с профилем
+    #[instrument(skip(profile), fields(source = %redact_url(&profile.source_url)))]
+    pub async fn spawn_with_binary(binary: &str, profile: TranscodeProfile) -> AppResult<Self> {
+        Self::spawn_process(binary, profile, Stdio::null())
+    }
+
+    /// Запускает FFmpeg с источником в stdin (`-i pipe:0`): загруженным
+    /// сервисом или переданным клиентом
+    ///
+    /// Body источника копируется в stdin фоновой задачей; обрыв загрузки
+    /// закрывает stdin, и FFmpeg завершается с тем, что успел получить.
+    #[instrument(skip(profile, input), fields(source = %redact_url(&profile.source_url)))]
+    pub async fn spawn_with_input(
+        binary: &str,
+        mut profile: TranscodeProfile,
+        input: PipedSource,
+    ) -> AppResult<Self> {
+        profile.source_url = PIPE_INPUT.to_string();
+        let mut process = Self::spawn_process(binary, profile, Stdio::piped())?;
+        let stdin = process
+            .child
+            .stdin
+            .take()
+            .ok_or_else(|| AppError::Ffmpeg("FFmpeg stdin is not available".into()))?;
+
+        tokio::spawn(
+            async move {
+                match input.pipe_into(stdin).await {
+                    Ok(bytes) => debug!(bytes, "Source piped to FFmpeg"),
+                    // FFmpeg закончил раньше (лимит длительности, отмена)
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                        debug!("FFmpeg closed stdin before the source ended")
+                    }
+                    Err(err) => warn!(error = %err, "Source body failed mid-stream"),
+                }
+            }
+            .in_current_span(),
+        );
+
+        Ok(process)
+    }
+
+    fn spawn_process(binary: &str, profile: TranscodeProfile, stdin: Stdio) -> AppResult<Self> {
         let args = profile.build_ffmpeg_args();
 
         debug!(
-            args = ?args,
+            args = ?redact_args(&args),
             "Spawning FFmpeg process"
         );
 
-        let child = Command::new("ffmpeg")
+        // Прогресс key=value в stderr; collect_stderr отделяет его от лога
+        let child = Command::new(binary)
+            .args(["-progress", "pipe:2"])
             .args(&args)
-            .stdin(Stdio::null())
+            .stdin(stdin)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .kill_on_drop(true)
             .spawn()
-            .map_err(|e| AppError::Ffmpeg(format!("Failed to spawn FFmpeg: {}", e)))?;
+            .map_err(|e| spawn_error(binary, e))?;
 
         Ok(Self { child, profile })
     }
@@ -81,16 +138,380 @@ impl FfmpegProcess {
     }
 }
 
-/// Проверяет доступность FFmpeg
+/// Выполняет транскодирование целиком и возвращает результат в памяти
+///
+/// Для объединённых запросов: один результат раздаётся нескольким клиентам.
+/// `input` - источник для stdin (загруженный сервисом); выход больше
+/// `max_bytes` - `AppError::PayloadTooLarge`, процесс убивается.
+pub async fn transcode_to_bytes(
+    binary: &str,
+    profile: TranscodeProfile,
+    input: Option<PipedSource>,
+    max_bytes: Option<u64>,
+) -> AppResult<Bytes> {
+    let mut process = match input {
+        Some(input) => FfmpegProcess::spawn_with_input(binary, profile, input).await?,
+        None => FfmpegProcess::spawn_with_binary(binary, profile).await?,
+    };
+
+    let mut stdout = process
+        .take_stdout()
+        .ok_or_else(|| AppError::Ffmpeg("FFmpeg stdout is not available".into()))?;
+    let stderr = process.take_stderr().map(collect_stderr);
+
+    let mut output = Vec::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let read = stdout.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        output.extend_from_slice(&chunk[..read]);
+        if let Some(limit) = max_bytes.filter(|&limit| output.len() as u64 > limit) {
+            warn!(limit, "Output size limit exceeded, killing FFmpeg");
+            let _ = process.kill().await;
+            return Err(AppError::PayloadTooLarge(format!(
+                "Output exceeded the limit of {} bytes",
+                limit
+            )));
+        }
+    }
+
+    let status = process.wait().await?;
+    if !status.success() {
+        let stderr = match stderr {
+            Some(task) => task.await.unwrap_or_default(),
+            None => String::new(),
+        };
+        return Err(exit_failure(status, &stderr));
+    }
+
+    Ok(Bytes::from(output))
+}
+
+/// Ошибка буферизованного транскодирования
+///
+/// Clone: один результат объединённого запроса раздаётся всем ожидающим.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BufferedError {
+    /// FFmpeg не запустился или завершился с ошибкой
+    Failed(String),
+    /// FFmpeg завершился с ненулевым кодом (с хвостом stderr)
+    Exited {
+        message: String,
+        stderr_tail: Option<String>,
+    },
+    /// Источник не отдал данные
+    SourceUnavailable(String),
+    /// Источник не найден
+    SourceNotFound(String),
+    /// Транскодирование не уложилось в таймаут, процесс убит
+    TimedOut(String),
+    /// Выход больше `max_output_bytes`, процесс убит
+    OutputTooLarge(String),
+}
+
+impl BufferedError {
+    /// Текст ошибки (для статуса сессии)
+    pub fn message(&self) -> &str {
+        match self {
+            BufferedError::Failed(message)
+            | BufferedError::Exited { message, .. }
+            | BufferedError::SourceUnavailable(message)
+            | BufferedError::SourceNotFound(message)
+            | BufferedError::TimedOut(message)
+            | BufferedError::OutputTooLarge(message) => message,
+        }
+    }
+
+    /// Ошибка чтения источника, а не сбой FFmpeg
+    pub fn is_source_error(&self) -> bool {
+        matches!(
+            self,
+            BufferedError::SourceUnavailable(_) | BufferedError::SourceNotFound(_)
+        )
+    }
+}
+
+impl From<AppError> for BufferedError {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::Ffmpeg(message) => BufferedError::Failed(message),
+            AppError::FfmpegExited {
+                message,
+                stderr_tail,
+            } => BufferedError::Exited {
+                message,
+                stderr_tail,
+            },
+            AppError::SourceUnavailable(message) => BufferedError::SourceUnavailable(message),
+            AppError::SourceNotFound(message) => BufferedError::SourceNotFound(message),
+            AppError::Timeout(message) => BufferedError::TimedOut(message),
+            AppError::PayloadTooLarge(message) => BufferedError::OutputTooLarge(message),
+            other => BufferedError::Failed(other.to_string()),
+        }
+    }
+}
+
+impl From<BufferedError> for AppError {
+    fn from(err: BufferedError) -> Self {
+        match err {
+            BufferedError::Failed(message) => AppError::Ffmpeg(message),
+            BufferedError::Exited {
+                message,
+                stderr_tail,
+            } => AppError::FfmpegExited {
+                message,
+                stderr_tail,
+            },
+            BufferedError::SourceUnavailable(message) => AppError::SourceUnavailable(message),
+            BufferedError::SourceNotFound(message) => AppError::SourceNotFound(message),
+            BufferedError::TimedOut(message) => AppError::Timeout(message),
+            BufferedError::OutputTooLarge(message) => AppError::PayloadTooLarge(message),
+        }
+    }
+}
+
+/// `transcode_to_bytes` с ограничением общего времени
+///
+/// По истечении `timeout` future транскодирования сбрасывается, и процесс
+/// убивается (`kill_on_drop`).
+pub async fn transcode_to_bytes_within(
+    binary: &str,
+    profile: TranscodeProfile,
+    input: Option<PipedSource>,
+    max_bytes: Option<u64>,
+    timeout: Duration,
+) -> Result<Bytes, BufferedError> {
+    match tokio::time::timeout(timeout, transcode_to_bytes(binary, profile, input, max_bytes)).await
+    {
+        Ok(output) => output.map_err(BufferedError::from),
+        Err(_) => Err(BufferedError::TimedOut(format!(
+            "Transcode did not finish within {} seconds",
+            timeout.as_secs()
+        ))),
+    }
+}
+
+/// Измеряет громкость источника отдельным проходом FFmpeg без вывода аудио
+///
+/// `max_duration` ограничивает анализ так же, как транскодирование; проход
+/// дольше `timeout` убивается (`AppError::Timeout`).
+#[instrument(skip(source_url), fields(source = %redact_url(source_url)))]
+pub async fn measure_loudness(
+    binary: &str,
+    source_url: &str,
+    max_duration: Option<u32>,
+    timeout: Duration,
+) -> AppResult<LoudnessMeasurement> {
+    let mut args = vec!["-hide_banner".to_string(), "-nostats".to_string()];
+    args.extend(rw_timeout_args(timeout));
+    args.extend(["-i".to_string(), source_url.to_string()]);
+    if let Some(max_duration) = max_duration {
+        args.extend(["-t".to_string(), max_duration.to_string()]);
+    }
+    args.extend(["-vn", "-af", "loudnorm=print_format=json", "-f", "null", "-"].map(String::from));
+
+    let stderr = run_analysis_pass_within(binary, &args, timeout).await?;
+    let measurement = analysis::parse_loudnorm(&stderr)?;
+    debug!(measurement = ?measurement, "Source loudness measured");
+
+    Ok(measurement)
+}
+
+/// Запускает проход FFmpeg без вывода аудио и возвращает его stderr
+///
+/// Фильтры-анализаторы (`loudnorm`, `silencedetect`) пишут результаты в stderr.
+pub async fn run_analysis_pass(binary: &str, args: &[String]) -> AppResult<String> {
+    let output = Command::new(binary)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| spawn_error(binary, e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if !output.status.success() {
+        return Err(AppError::Ffmpeg(exit_error(output.status, &stderr)));
+    }
+
+    Ok(stderr)
+}
+
+/// `run_analysis_pass` с ограничением общего времени
+///
+/// Проход, занимающий слот транскодирования, не должен висеть на медленном
+/// источнике: по истечении `timeout` процесс убивается (`kill_on_drop`).
+pub async fn run_analysis_pass_within(
+    binary: &str,
+    args: &[String],
+    timeout: Duration,
+) -> AppResult<String> {
+    tokio::time::timeout(timeout, run_analysis_pass(binary, args))
+        .await
+        .map_err(|_| {
+            AppError::Timeout(format!(
+                "Analysis pass did not finish within {} seconds",
+                timeout.as_secs()
+            ))
+        })?
+}
+
+/// Входная опция `-rw_timeout` (микросекунды): зависшее чтение сети
+/// обрывается самим FFmpeg
+pub fn rw_timeout_args(timeout: Duration) -> [String; 2] {
+    ["-rw_timeout".to_string(), timeout.as_micros().to_string()]
+}
+
+/// Лимит сохраняемого stderr; остальное только логируется
+const MAX_STDERR_BYTES: usize = 1024 * 1024;
+
+/// Вычитывает stderr FFmpeg в фоне: построчно в debug лог и в буфер
+///
+/// Задача завершается вместе с процессом и возвращает накопленный вывод
+/// (для `silencedetect` и текста ошибки).
+pub fn collect_stderr(stderr: ChildStderr) -> JoinHandle<String> {
+    collect_stderr_with_progress(stderr, |_| {})
+}
+
+/// `collect_stderr`, передающий каждый завершённый блок `-progress` в `on_progress`
+///
+/// Строки прогресса не попадают в буфер: иначе последней строкой stderr
+/// оказался бы `progress=end`, а не причина ошибки.
+pub fn collect_stderr_with_progress<F>(stderr: ChildStderr, on_progress: F) -> JoinHandle<String>
+where
+    F: Fn(FfmpegProgress) + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut collected = String::new();
+        let mut progress = ProgressParser::default();
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            match progress.feed(&line) {
+                ProgressLine::Block(block) => {
+                    on_progress(block);
+                    continue;
+                }
+                ProgressLine::Field => continue,
+                ProgressLine::Other => {}
+            }
+            debug!(target: "ffmpeg", "{}", line);
+            if collected.len() + line.len() < MAX_STDERR_BYTES {
+                collected.push_str(&line);
+                collected.push('\n');
+            }
+        }
+        collected
+    })
+}
+
+/// Текст ошибки для ненулевого кода выхода
+pub fn exit_error(status: std::process::ExitStatus, stderr: &str) -> String {
+    match last_error_line(stderr) {
+        Some(line) => format!("FFmpeg exited with {}: {}", status, line),
+        None => format!("FFmpeg exited with {}", status),
+    }
+}
+
+/// Сколько последних байт stderr попадает в детали ошибки
+pub const STDERR_TAIL_BYTES: usize = 4096;
+
+/// Сообщения FFmpeg об ошибке чтения источника (а не кодирования)
+///
+/// Порядок важен: первый совпавший шаблон определяет ошибку, поэтому
+/// 404 проверяется раньше общего "Server returned 4xx".
+const SOURCE_ERROR_PATTERNS: &[(&str, SourceFailure)] = &[
+    ("404 Not Found", SourceFailure::NotFound),
+    ("No such file or directory", SourceFailure::NotFound),
+    ("Server returned 4", SourceFailure::Unavailable),
+    ("Connection refused", SourceFailure::Unavailable),
+    ("Connection timed out", SourceFailure::Unavailable),
+    ("Failed to resolve hostname", SourceFailure::Unavailable),
+];
+
+/// Класс ошибки чтения источника
+#[derive(Clone, Copy)]
+enum SourceFailure {
+    /// 404 `SOURCE_NOT_FOUND`
+    NotFound,
+    /// 400 `SOURCE_UNAVAILABLE`
+    Unavailable,
+}
+
+/// Ошибка для ненулевого кода выхода FFmpeg
+///
+/// Ошибка чтения источника - `SourceNotFound` / `SourceUnavailable`
+/// (проблема запроса, повтор не поможет), остальное - `FfmpegExited`
+/// с хвостом stderr.
+pub fn exit_failure(status: std::process::ExitStatus, stderr: &str) -> AppError {
+    if let Some((failure, line)) = source_failure(stderr) {
+        let line = line.to_string();
+        return match failure {
+            SourceFailure::NotFound => AppError::SourceNotFound(line),
+            SourceFailure::Unavailable => AppError::SourceUnavailable(line),
+        };
+    }
+
+    let tail = stderr_tail(stderr).trim();
+    AppError::FfmpegExited {
+        message: exit_error(status, stderr),
+        stderr_tail: (!tail.is_empty()).then(|| tail.to_string()),
+    }
+}
+
+/// Класс и строка stderr с ошибкой чтения источника, если есть
+fn source_failure(stderr: &str) -> Option<(SourceFailure, &str)> {
+    SOURCE_ERROR_PATTERNS.iter().find_map(|&(pattern, failure)| {
+        stderr
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| line.contains(pattern))
+            .map(|line| (failure, line))
+    })
+}
+
+/// Последние `STDERR_TAIL_BYTES` байт stderr (по границе символа)
+pub fn stderr_tail(stderr: &str) -> &str {
+    let mut start = stderr.len().saturating_sub(STDERR_TAIL_BYTES);
+    while !stderr.is_char_boundary(start) {
+        start += 1;
+    }
+    &stderr[start..]
+}
+
+/// Последняя непустая строка stderr - обычно причина ошибки FFmpeg
+pub fn last_error_line(stderr: &str) -> Option<&str> {
+    stderr.lines().rev().map(str::trim).find(|line| !line.is_empty())
+}
+
+/// Ошибка запуска бинаря FFmpeg или ffprobe (с путём - для диагностики конфигурации)
+pub fn spawn_error(binary: &str, err: std::io::Error) -> AppError {
+    AppError::Ffmpeg(format!("Failed to spawn '{}': {}", binary, err))
+}
+
+/// Проверяет доступность FFmpeg (`ffmpeg` из PATH)
 pub async fn check_ffmpeg_available() -> AppResult<String> {
-    let output = Command::new("ffmpeg")
+    check_ffmpeg_available_with_binary("ffmpeg").await
+}
+
+/// `check_ffmpeg_available` с явным путём к FFmpeg; возвращает строку версии
+pub async fn check_ffmpeg_available_with_binary(binary: &str) -> AppResult<String> {
+    let output = Command::new(binary)
         .arg("-version")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
         .output()
         .await
-        .map_err(|e| AppError::Ffmpeg(format!("FFmpeg not found: {}", e)))?;
+        .map_err(|e| {
+            AppError::FfmpegUnavailable(format!("FFmpeg not found at '{}': {}", binary, e))
+        })?;
 
     if !output.status.success() {
-        return Err(AppError::Ffmpeg("FFmpeg returned non-zero exit code".into()));
+        return Err(AppError::FfmpegUnavailable(
+            "FFmpeg returned non-zero exit code".into(),
+        ));
     }
 
     let version = String::from_utf8_lossy(&output.stdout);
@@ -99,13 +520,198 @@ pub async fn check_ffmpeg_available() -> AppResult<String> {
     Ok(first_line.to_string())
 }
 
+/// Fake FFmpeg для тестов без установленного бинаря
 #[cfg(test)]
-mod tests {
-    use super::*;
+pub(crate) mod testing {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use once_cell::sync::Lazy;
+    use tempfile::TempDir;
 
+    static DIR: Lazy<TempDir> = Lazy::new(|| TempDir::new().expect("temp dir"));
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// Создаёт shell-скрипт, который выполняется вместо FFmpeg; возвращает путь
+    pub fn fake_ffmpeg(script: &str) -> String {
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = DIR.path().join(format!("ffmpeg-{}", id));
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).expect("write fake ffmpeg");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                .expect("chmod fake ffmpeg");
+        }
+        path.to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
     #[test]
     fn test_ffmpeg_process_creation() {
         // Unit test - не запускает реальный процесс
         // Интеграционные тесты в tests/
     }
+
+    #[tokio::test]
+    async fn test_transcode_to_bytes_returns_stdout() {
+        use super::*;
+
+        let binary = testing::fake_ffmpeg("printf 'encoded'");
+        let output = transcode_to_bytes(&binary, TranscodeProfile::default(), None, None)
+            .await
+            .unwrap();
+        assert_eq!(&output[..], b"encoded");
+
+        let binary = testing::fake_ffmpeg("printf 'partial'; echo 'Invalid data' >&2; exit 1");
+        let err = transcode_to_bytes(&binary, TranscodeProfile::default(), None, None)
+            .await
+            .unwrap_err();
+        match err {
+            AppError::FfmpegExited {
+                message,
+                stderr_tail,
+            } => {
+                assert!(message.ends_with(": Invalid data"));
+                assert_eq!(stderr_tail.as_deref(), Some("Invalid data"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcode_to_bytes_enforces_output_limit() {
+        use super::*;
+
+        let binary = testing::fake_ffmpeg("printf '0123456789'; exec sleep 30");
+        let err = transcode_to_bytes(&binary, TranscodeProfile::default(), None, Some(4))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::PayloadTooLarge(_)), "{:?}", err);
+
+        // Источник из stdin, выход в пределах лимита
+        let binary = testing::fake_ffmpeg("cat");
+        let input = PipedSource::new(
+            futures::stream::iter([Ok(Bytes::from_static(b"piped"))]),
+            None,
+        );
+        let output = transcode_to_bytes(&binary, TranscodeProfile::default(), Some(input), Some(5))
+            .await
+            .unwrap();
+        assert_eq!(&output[..], b"piped");
+    }
+
+    #[tokio::test]
+    async fn test_missing_binary_error_names_path() {
+        use super::*;
+
+        let binary = "/nonexistent/bin/ffmpeg7";
+        let err = FfmpegProcess::spawn_with_binary(binary, TranscodeProfile::default())
+            .await
+            .err()
+            .unwrap();
+        match err {
+            AppError::Ffmpeg(message) => assert!(message.contains(binary), "{}", message),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let err = check_ffmpeg_available_with_binary(binary).await.unwrap_err();
+        assert!(err.to_string().contains(binary), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_failure_classifies_source_errors() {
+        use std::os::unix::process::ExitStatusExt;
+
+        use super::*;
+
+        let status = std::process::ExitStatus::from_raw(1 << 8);
+        let stderr = "[https @ 0x1] HTTP error 404 Not Found
+                      https://example.com/a.mp3: Server returned 404 Not Found
+";
+        match exit_failure(status, stderr) {
+            AppError::SourceNotFound(line) => assert!(line.contains("404"), "{}", line),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let not_found = "/data/missing.mp3: No such file or directory\n";
+        assert!(matches!(
+            exit_failure(status, not_found),
+            AppError::SourceNotFound(_)
+        ));
+
+        for stderr in [
+            "https://example.com/a.mp3: Server returned 403 Forbidden (access denied)\n",
+            "Connection to tcp://example.com:443 failed: Connection refused\n",
+            "Connection to tcp://example.com:443 failed: Connection timed out\n",
+            "Failed to resolve hostname example.invalid: Name or service not known\n",
+        ] {
+            match exit_failure(status, stderr) {
+                AppError::SourceUnavailable(line) => assert_eq!(line, stderr.trim()),
+                other => panic!("unexpected error for {:?}: {:?}", stderr, other),
+            }
+        }
+
+        let stderr = "Unknown encoder 'libfoo'
+";
+        assert!(matches!(
+            exit_failure(status, stderr),
+            AppError::FfmpegExited { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_loudness_pass_times_out() {
+        use super::*;
+
+        // Аргументы прохода сохраняются до зависания
+        let args = tempfile::NamedTempFile::new().unwrap();
+        let binary = testing::fake_ffmpeg(&format!(
+            "echo \"$@\" > {}; exec sleep 30",
+            args.path().display()
+        ));
+
+        let err = measure_loudness(
+            &binary,
+            "https://example.com/a.mp3",
+            None,
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, AppError::Timeout(_)), "{:?}", err);
+
+        let args = std::fs::read_to_string(args.path()).unwrap();
+        assert!(
+            args.contains("-rw_timeout 200000 -i https://example.com/a.mp3"),
+            "{}",
+            args
+        );
+    }
+
+    #[test]
+    fn test_stderr_tail_is_bounded() {
+        use super::{stderr_tail, STDERR_TAIL_BYTES};
+
+        let stderr = format!("{}ошибка\n", "x".repeat(STDERR_TAIL_BYTES * 2));
+        let tail = stderr_tail(&stderr);
+        assert!(tail.len() <= STDERR_TAIL_BYTES);
+        assert!(tail.ends_with("ошибка\n"));
+
+        // Граница среза внутри многобайтового символа
+        let cyrillic = "ё".repeat(STDERR_TAIL_BYTES);
+        assert!(stderr_tail(&cyrillic).len() <= STDERR_TAIL_BYTES);
+        assert_eq!(stderr_tail("short"), "short");
+    }
+
+    #[test]
+    fn test_last_error_line() {
+        use super::last_error_line;
+
+        let stderr = "Input #0, mp3\n[mp3 @ 0x1] Header missing\n\n";
+        assert_eq!(last_error_line(stderr), Some("[mp3 @ 0x1] Header missing"));
+        assert_eq!(last_error_line(""), None);
+    }
 }
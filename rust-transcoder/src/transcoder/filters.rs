@@ -2,14 +2,29 @@
 //!
 //! Генерация строк фильтров для FFmpeg -af опции.
 
-use crate::models::EqPreset;
+use crate::models::{
+    AudioFilters, EnvelopePoint, EqBand, EqPreset, FadeCurve, LoudnessStats, MixDuration,
+    NormalizeMode, SpeedMode,
+};
+
+/// Потолок true peak для `loudnorm` по умолчанию (dBTP)
+pub const DEFAULT_TRUE_PEAK_DB: f32 = -1.5;
 
 /// Генерирует фильтр fade in
 ///
 /// # Arguments
 /// * `duration` - длительность fade in в секундах
 pub fn fade_in(duration: f32) -> String {
-    format!("afade=t=in:st=0:d={:.2}", duration)
+    fade_in_with_curve(duration, FadeCurve::default())
+}
+
+/// Генерирует фильтр fade in с заданной формой кривой
+///
+/// # Arguments
+/// * `duration` - длительность fade in в секундах
+/// * `curve` - форма кривой (для `tri` параметр не добавляется)
+pub fn fade_in_with_curve(duration: f32, curve: FadeCurve) -> String {
+    format!("afade=t=in:st=0:d={:.2}{}", duration, curve_param(curve))
 }
 
 /// Генерирует фильтр fade out
@@ -18,7 +33,30 @@ pub fn fade_in(duration: f32) -> String {
 /// * `start` - время начала fade out в секундах
 /// * `duration` - длительность fade out в секундах
 pub fn fade_out(start: f32, duration: f32) -> String {
-    format!("afade=t=out:st={:.2}:d={:.2}", start, duration)
+    fade_out_with_curve(start, duration, FadeCurve::default())
+}
+
+/// Генерирует фильтр fade out с заданной формой кривой
+///
+/// # Arguments
+/// * `start` - время начала fade out в секундах
+/// * `duration` - длительность fade out в секундах
+/// * `curve` - форма кривой (для `tri` параметр не добавляется)
+pub fn fade_out_with_curve(start: f32, duration: f32, curve: FadeCurve) -> String {
+    format!(
+        "afade=t=out:st={:.2}:d={:.2}{}",
+        start,
+        duration,
+        curve_param(curve)
+    )
+}
+
+/// Параметр `curve` для afade; линейная кривая - значение FFmpeg по умолчанию
+fn curve_param(curve: FadeCurve) -> String {
+    match curve {
+        FadeCurve::Tri => String::new(),
+        other => format!(":curve={}", other),
+    }
 }
 
 /// Генерирует фильтр loudnorm для нормализации громкости
@@ -26,12 +64,67 @@ pub fn fade_out(start: f32, duration: f32) -> String {
 /// # Arguments
 /// * `target_lufs` - целевой уровень в LUFS (обычно -16 или -14)
 pub fn loudnorm(target_lufs: f32) -> String {
+    loudnorm_with_peak(target_lufs, DEFAULT_TRUE_PEAK_DB)
+}
+
+/// Генерирует фильтр loudnorm с заданным потолком true peak
+///
+/// # Arguments
+/// * `target_lufs` - целевой уровень в LUFS
+/// * `true_peak_db` - максимальный true peak в dBTP
+pub fn loudnorm_with_peak(target_lufs: f32, true_peak_db: f32) -> String {
+    format!(
+        "loudnorm=I={:.1}:TP={:.1}:LRA=11:print_format=none",
+        target_lufs, true_peak_db
+    )
+}
+
+/// Генерирует первый (измерительный) проход двухпроходного loudnorm
+///
+/// Цели должны совпадать со вторым проходом: от них зависит `target_offset`.
+pub fn loudnorm_measure(target_lufs: f32, true_peak_db: f32) -> String {
+    format!(
+        "loudnorm=I={:.1}:TP={:.1}:LRA=11:print_format=json",
+        target_lufs, true_peak_db
+    )
+}
+
+/// Генерирует второй проход loudnorm с измеренными параметрами источника
+///
+/// # Arguments
+/// * `target_lufs` - целевой уровень в LUFS
+/// * `true_peak_db` - максимальный true peak в dBTP
+/// * `stats` - результат первого прохода
+/// * `mode` - `Linear` (постоянное усиление) или `Dynamic`
+pub fn loudnorm_two_pass(
+    target_lufs: f32,
+    true_peak_db: f32,
+    stats: &LoudnessStats,
+    mode: NormalizeMode,
+) -> String {
     format!(
-        "loudnorm=I={:.1}:TP=-1.5:LRA=11:print_format=none",
-        target_lufs
+        "loudnorm=I={:.1}:TP={:.1}:LRA=11:measured_I={:.2}:measured_TP={:.2}:\
+         measured_LRA={:.2}:measured_thresh={:.2}:offset={:.2}:linear={}:print_format=json",
+        target_lufs,
+        true_peak_db,
+        stats.input_i,
+        stats.input_tp,
+        stats.input_lra,
+        stats.input_thresh,
+        stats.target_offset,
+        mode == NormalizeMode::Linear
     )
 }
 
+/// Генерирует фильтр alimiter (peak limiter)
+///
+/// # Arguments
+/// * `limit_db` - потолок в dBFS (alimiter принимает линейное значение)
+pub fn limiter(limit_db: f32) -> String {
+    let linear = 10f32.powf(limit_db / 20.0);
+    format!("alimiter=limit={:.3}", linear)
+}
+
 /// Генерирует фильтр volume для изменения громкости
 ///
 /// # Arguments
@@ -76,9 +169,50 @@ pub fn equalizer(frequency: u32, width_type: char, width: f32, gain: f32) -> Str
 /// * `attack` - время атаки в секундах
 /// * `decay` - время затухания в секундах
 pub fn compressor(attack: f32, decay: f32) -> String {
+    compressor_with_threshold(attack, decay, -12.0)
+}
+
+/// Генерирует фильтр compand с заданным порогом
+///
+/// Ниже порога сигнал не меняется, выше - сжимается 2:1 до 0 dBFS.
+///
+/// # Arguments
+/// * `attack` - время атаки в секундах
+/// * `decay` - время затухания в секундах
+/// * `threshold_db` - порог сжатия в dBFS
+pub fn compressor_with_threshold(attack: f32, decay: f32, threshold_db: f32) -> String {
+    let ceiling = threshold_db / 2.0;
+    format!(
+        "compand=attacks={:.3}:decays={:.3}:points=-80/-80|{t}/{t}|0/{c}|20/{c}",
+        attack,
+        decay,
+        t = threshold_db,
+        c = ceiling
+    )
+}
+
+/// Генерирует фильтр afftdn (шумоподавление по FFT)
+///
+/// # Arguments
+/// * `strength_db` - насколько ослабить шум, в dB
+pub fn denoise(strength_db: f32) -> String {
+    format!("afftdn=nr={:.1}", strength_db)
+}
+
+/// Генерирует фильтр agate (noise gate)
+///
+/// # Arguments
+/// * `threshold_db` - порог срабатывания в dBFS (agate принимает линейное значение)
+/// * `ratio` - степень ослабления сигнала ниже порога
+/// * `attack` - время атаки в миллисекундах
+/// * `release` - время восстановления в миллисекундах
+pub fn noise_gate(threshold_db: f32, ratio: f32, attack: f32, release: f32) -> String {
     format!(
-        "compand=attacks={:.3}:decays={:.3}:points=-80/-80|-12/-12|0/-6|20/-6",
-        attack, decay
+        "agate=threshold={:.6}:ratio={:.2}:attack={:.2}:release={:.2}",
+        10f32.powf(threshold_db / 20.0),
+        ratio,
+        attack,
+        release
     )
 }
 
@@ -90,6 +224,14 @@ pub fn resample(sample_rate: u32) -> String {
     format!("aresample={}", sample_rate)
 }
 
+/// Генерирует фильтр aresample с компенсацией изменений параметров потока
+///
+/// `async=1` растягивает/сжимает аудио по timestamps, поэтому смена
+/// sample rate или разрывы в live-источнике не ломают энкодер.
+pub fn resample_async() -> String {
+    "aresample=async=1".to_string()
+}
+
 /// Генерирует фильтр pan для изменения каналов
 ///
 /// # Arguments
@@ -120,6 +262,189 @@ pub fn tempo(factor: f32) -> String {
     }
 }
 
+/// Генерирует изменение скорости со сдвигом высоты тона
+///
+/// `asetrate` переинтерпретирует сэмплы с другой частотой, `aresample` возвращает
+/// исходную. Начальный `aresample` фиксирует частоту входа, иначе `asetrate`
+/// считался бы от неизвестной частоты источника.
+///
+/// # Arguments
+/// * `factor` - множитель скорости (0.5-2.0)
+/// * `sample_rate` - sample rate выходного потока
+pub fn pitch_shift(factor: f32, sample_rate: u32) -> String {
+    let shifted = (sample_rate as f32 * factor).round() as u32;
+    format!(
+        "aresample={sr},asetrate={},aresample={sr}",
+        shifted,
+        sr = sample_rate
+    )
+}
+
+/// Генерирует фильтр изменения скорости в выбранном режиме
+pub fn speed(factor: f32, mode: SpeedMode, sample_rate: u32) -> String {
+    match mode {
+        SpeedMode::PreservePitch => tempo(factor),
+        SpeedMode::ShiftPitch => pitch_shift(factor, sample_rate),
+    }
+}
+
+/// Генерирует фильтр extrastereo для изменения ширины стерео базы
+///
+/// # Arguments
+/// * `width` - 0.0 = моно, 1.0 = без изменений, 2.0 = расширенная база
+///
+/// # Returns
+/// FFmpeg filter string или пустая строка для 1.0
+pub fn stereo_width(width: f32) -> String {
+    if (width - 1.0).abs() < 0.001 {
+        String::new()
+    } else {
+        format!("extrastereo=m={:.2}", width)
+    }
+}
+
+/// Генерирует фильтр aformat, приводящий поток к общему формату
+///
+/// # Arguments
+/// * `sample_rate` - целевой sample rate
+/// * `channels` - количество каналов (1=mono, 2=stereo)
+pub fn aformat(sample_rate: u32, channels: u8) -> String {
+    let layout = match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        n => format!("{}c", n),
+    };
+    format!(
+        "aformat=sample_fmts=fltp:sample_rates={}:channel_layouts={}",
+        sample_rate, layout
+    )
+}
+
+/// Источник lavfi: синусоида (`-f lavfi -i sine=...`)
+///
+/// # Arguments
+/// * `frequency` - частота в Hz
+/// * `duration` - длительность в секундах
+pub fn sine_source(frequency: u32, duration: f32) -> String {
+    format!("sine=frequency={}:duration={}", frequency, duration)
+}
+
+/// Источник lavfi: тишина (`-f lavfi -i anullsrc=...`)
+///
+/// # Arguments
+/// * `duration` - длительность в секундах
+pub fn silence_source(duration: f32) -> String {
+    format!("anullsrc=duration={}", duration)
+}
+
+/// Генерирует граф склейки pre-roll и основного источника
+///
+/// Входы `[0:a]` (pre-roll) и `[1:a]` (источник) приводятся к одному формату,
+/// иначе concat отказывается соединять потоки с разными параметрами.
+///
+/// # Arguments
+/// * `sample_rate` - sample rate результата
+/// * `channels` - количество каналов результата
+/// * `output` - имя выходной метки графа
+pub fn concat_preroll(sample_rate: u32, channels: u8, output: &str) -> String {
+    let format = aformat(sample_rate, channels);
+    format!(
+        "[0:a]{format}[preroll];[1:a]{format}[main];[preroll][main]concat=n=2:v=0:a=1[{output}]"
+    )
+}
+
+/// Генерирует граф склейки нескольких источников по порядку входов
+///
+/// Каждый вход `[i:a]` приводится к общему формату (как в `concat_preroll`),
+/// затем потоки соединяются одним `concat`.
+///
+/// # Arguments
+/// * `inputs` - количество входов FFmpeg
+/// * `sample_rate` - sample rate результата
+/// * `channels` - количество каналов результата
+/// * `output` - имя выходной метки графа
+pub fn concat_sources(inputs: usize, sample_rate: u32, channels: u8, output: &str) -> String {
+    let format = aformat(sample_rate, channels);
+    let mut graph = String::new();
+    for index in 0..inputs {
+        graph.push_str(&format!("[{index}:a]{format}[a{index}];"));
+    }
+    for index in 0..inputs {
+        graph.push_str(&format!("[a{index}]"));
+    }
+    graph.push_str(&format!("concat=n={inputs}:v=0:a=1[{output}]"));
+    graph
+}
+
+/// Генерирует граф смешивания источника `[0:a]` с фоном `[1:a]`
+///
+/// Уровни входов задаются `volume` до `amix`; `normalize=0` отключает
+/// деление на число входов, иначе источник стал бы тише на 6 dB.
+///
+/// # Arguments
+/// * `primary_db` - уровень источника в dB
+/// * `background_db` - уровень фона в dB
+/// * `duration` - длительность смеси
+/// * `sample_rate` - sample rate результата
+/// * `channels` - количество каналов результата
+/// * `output` - имя выходной метки графа
+pub fn mix_background(
+    primary_db: f32,
+    background_db: f32,
+    duration: MixDuration,
+    sample_rate: u32,
+    channels: u8,
+    output: &str,
+) -> String {
+    let format = aformat(sample_rate, channels);
+    format!(
+        "[0:a]{format},{primary}[primary];[1:a]{format},{background}[bed];\
+         [primary][bed]amix=inputs=2:duration={duration}:normalize=0[{output}]",
+        primary = volume(primary_db),
+        background = volume(background_db),
+    )
+}
+
+/// Генерирует volume фильтр с огибающей громкости во времени
+///
+/// Gain в dB линейно интерполируется между соседними точками, до первой
+/// и после последней точки держится их значение. Выражение вычисляется
+/// на каждый фрейм (`eval=frame`).
+///
+/// # Arguments
+/// * `points` - точки огибающей, упорядоченные по времени
+pub fn volume_envelope(points: &[EnvelopePoint]) -> String {
+    let Some(last) = points.last() else {
+        return String::new();
+    };
+
+    // Собираем вложенный if с конца: после последней точки - её gain
+    let mut expr = format!("{:.2}", last.gain_db);
+    for pair in points.windows(2).rev() {
+        let (from, to) = (pair[0], pair[1]);
+        let slope = (to.gain_db - from.gain_db) / (to.time - from.time);
+        expr = format!(
+            "if(lt(t,{t1:.3}),{g0:.2}{slope:+.4}*(t-{t0:.3}),{expr})",
+            t0 = from.time,
+            t1 = to.time,
+            g0 = from.gain_db,
+        );
+    }
+    let first = points[0];
+    expr = format!("if(lt(t,{:.3}),{:.2},{})", first.time, first.gain_db, expr);
+
+    format!("volume='pow(10,({})/20)':eval=frame", expr)
+}
+
+/// Генерирует фильтр silencedetect (только анализ, аудио не меняется)
+///
+/// # Arguments
+/// * `noise_db` - порог тишины в dB
+/// * `min_duration` - минимальная длительность тишины в секундах
+pub fn silencedetect(noise_db: f32, min_duration: f32) -> String {
+    format!("silencedetect=noise={:.1}dB:d={:.2}", noise_db, min_duration)
+}
+
 /// Объединяет несколько фильтров в цепочку
 pub fn chain(filters: &[String]) -> String {
     filters
@@ -155,9 +480,24 @@ pub fn eq_preset_to_filter(preset: EqPreset) -> String {
             // High shelf boost: +4dB на 8kHz
             equalizer(8000, 'o', 1.5, 4.0)
         }
+        EqPreset::Telephone => {
+            // Полоса телефонного канала: 300-3400Hz
+            chain(&[highpass(300), lowpass(3400)])
+        }
     }
 }
 
+/// Генерирует цепочку equalizer из полос параметрического EQ
+///
+/// Полосы применяются в порядке запроса, ширина задаётся добротностью (`width_type=q`).
+pub fn custom_eq(bands: &[EqBand]) -> String {
+    let filters: Vec<String> = bands
+        .iter()
+        .map(|band| equalizer(band.frequency, 'q', band.q, band.gain_db))
+        .collect();
+    chain(&filters)
+}
+
 /// Генерирует volume filter из коэффициента (не dB)
 /// 
 /// # Arguments
@@ -190,31 +530,97 @@ pub fn build_audio_filter_chain(
     speed: Option<f32>,
     volume_level: Option<f32>,
 ) -> String {
+    // Без speed_mode частота не участвует в цепочке
+    build_filter_chain(
+        &AudioFilters {
+            eq_preset,
+            speed,
+            volume: volume_level,
+            ..Default::default()
+        },
+        48000,
+    )
+}
+
+/// Строит цепочку фильтров из всех параметров `AudioFilters`
+///
+/// Порядок: highpass/lowpass → denoise → noise gate → EQ preset → custom EQ → compressor →
+/// stereo width → speed → volume → volume envelope (время точек огибающей - по
+/// выходному потоку, т.е. после speed) → limiter. `sample_rate` нужен для
+/// `SpeedMode::ShiftPitch`.
+pub fn build_filter_chain(audio_filters: &AudioFilters, sample_rate: u32) -> String {
     let mut filters = Vec::new();
+
+    // Ограничение полосы - первым, чтобы rumble не открывал gate и не усиливался EQ
+    if let Some(frequency) = audio_filters.highpass_hz {
+        filters.push(highpass(frequency));
+    }
+    if let Some(frequency) = audio_filters.lowpass_hz {
+        filters.push(lowpass(frequency));
+    }
+
+    // Шумоподавление до gate: gate срабатывает по уже очищенному сигналу
+    if let Some(strength) = audio_filters.denoise_db {
+        filters.push(denoise(strength));
+    }
+
+    // 0. Noise gate (до EQ, чтобы усиление полос не поднимало шум над порогом)
+    if let Some(gate) = audio_filters.noise_gate {
+        filters.push(noise_gate(
+            gate.threshold_db,
+            gate.ratio,
+            gate.attack,
+            gate.release,
+        ));
+    }
     
     // 1. EQ preset (первым, до изменения скорости)
-    if let Some(preset) = eq_preset {
-        let eq_filter = eq_preset_to_filter(preset);
-        if !eq_filter.is_empty() {
-            filters.push(eq_filter);
-        }
+    if let Some(preset) = audio_filters.eq_preset {
+        filters.push(eq_preset_to_filter(preset));
+    }
+
+    // Custom EQ поверх preset'а - уточняет его, а не заменяет
+    if let Some(ref bands) = audio_filters.custom_eq {
+        filters.push(custom_eq(bands));
+    }
+
+    // Компрессор после EQ: выравнивает уже скорректированный по частотам сигнал
+    if let Some(settings) = audio_filters.compress {
+        filters.push(compressor_with_threshold(
+            settings.attack,
+            settings.decay,
+            settings.threshold_db,
+        ));
+    }
+
+    // 2. Ширина стерео базы
+    if let Some(width) = audio_filters.stereo_width {
+        filters.push(stereo_width(width));
     }
     
-    // 2. Speed (atempo)
-    if let Some(s) = speed {
+    // 3. Speed (atempo)
+    if let Some(s) = audio_filters.speed {
         if (s - 1.0).abs() > 0.001 {
-            filters.push(tempo(s));
+            let mode = audio_filters.speed_mode.unwrap_or_default();
+            filters.push(speed(s, mode, sample_rate));
         }
     }
     
-    // 3. Volume (последним, после всех других обработок)
-    if let Some(v) = volume_level {
-        let vol_filter = volume_factor(v);
-        if !vol_filter.is_empty() {
-            filters.push(vol_filter);
-        }
+    // 4. Volume (последним, после всех других обработок)
+    if let Some(v) = audio_filters.volume {
+        filters.push(volume_factor(v));
     }
-    
+
+    // 5. Огибающая громкости
+    if let Some(ref points) = audio_filters.volume_envelope {
+        filters.push(volume_envelope(points));
+    }
+
+    // 6. Limiter - последним, ловит пики после всех усилений
+    if audio_filters.limiter_enabled() {
+        filters.push(limiter(DEFAULT_TRUE_PEAK_DB));
+    }
+
     chain(&filters)
 }
 
@@ -233,6 +639,19 @@ mod tests {
         assert_eq!(fade_out(10.0, 2.0), "afade=t=out:st=10.00:d=2.00");
     }
 
+    #[test]
+    fn test_fade_curve() {
+        assert_eq!(
+            fade_in_with_curve(2.0, FadeCurve::Qsin),
+            "afade=t=in:st=0:d=2.00:curve=qsin"
+        );
+        assert_eq!(
+            fade_out_with_curve(10.0, 2.0, FadeCurve::Qsin),
+            "afade=t=out:st=10.00:d=2.00:curve=qsin"
+        );
+        assert_eq!(fade_in_with_curve(2.0, FadeCurve::Tri), fade_in(2.0));
+    }
+
     #[test]
     fn test_loudnorm() {
         let filter = loudnorm(-16.0);
@@ -306,6 +725,12 @@ mod tests {
         assert!(filter.contains("f=8000"), "Treble should target 8kHz");
     }
 
+    #[test]
+    fn test_eq_preset_telephone() {
+        let filter = eq_preset_to_filter(EqPreset::Telephone);
+        assert_eq!(filter, "highpass=f=300,lowpass=f=3400");
+    }
+
     #[test]
     fn test_volume_factor_unity() {
         let filter = volume_factor(1.0);
@@ -358,4 +783,299 @@ mod tests {
         assert!(eq_pos < tempo_pos, "EQ should come before tempo");
         assert!(tempo_pos < vol_pos, "Tempo should come before volume");
     }
+
+    #[test]
+    fn test_build_filter_chain_band_limits_first() {
+        let chain = build_filter_chain(
+            &AudioFilters {
+                highpass_hz: Some(300),
+                lowpass_hz: Some(3400),
+                eq_preset: Some(EqPreset::Voice),
+                ..Default::default()
+            },
+            48000,
+        );
+        assert!(chain.starts_with("highpass=f=300,lowpass=f=3400,"), "{}", chain);
+        assert!(chain.contains("equalizer"));
+    }
+
+    #[test]
+    fn test_custom_eq_three_bands_in_order() {
+        let bands = [
+            EqBand { frequency: 60, gain_db: 4.0, q: 0.7 },
+            EqBand { frequency: 1000, gain_db: -2.5, q: 1.0 },
+            EqBand { frequency: 10000, gain_db: 3.0, q: 2.0 },
+        ];
+
+        assert_eq!(
+            custom_eq(&bands),
+            "equalizer=f=60:width_type=q:width=0.70:g=4.0,\
+             equalizer=f=1000:width_type=q:width=1.00:g=-2.5,\
+             equalizer=f=10000:width_type=q:width=2.00:g=3.0"
+        );
+    }
+
+    #[test]
+    fn test_custom_eq_follows_preset() {
+        let chain = build_filter_chain(
+            &AudioFilters {
+                eq_preset: Some(EqPreset::BassBoost),
+                custom_eq: Some(vec![EqBand { frequency: 5000, gain_db: 2.0, q: 1.0 }]),
+                ..Default::default()
+            },
+            48000,
+        );
+        let preset = chain.find("f=100:").unwrap();
+        let band = chain.find("f=5000:").unwrap();
+        assert!(preset < band, "{}", chain);
+    }
+
+    #[test]
+    fn test_compressor_default_threshold_matches_legacy_points() {
+        assert_eq!(
+            compressor(0.02, 0.25),
+            "compand=attacks=0.020:decays=0.250:points=-80/-80|-12/-12|0/-6|20/-6"
+        );
+        assert!(compressor_with_threshold(0.02, 0.25, -20.0).contains("|-20/-20|0/-10|20/-10"));
+    }
+
+    #[test]
+    fn test_build_filter_chain_with_compress() {
+        use crate::models::CompressorSettings;
+
+        let chain = build_filter_chain(
+            &AudioFilters {
+                eq_preset: Some(EqPreset::Voice),
+                compress: Some(CompressorSettings {
+                    attack: 0.01,
+                    decay: 0.3,
+                    threshold_db: -18.0,
+                }),
+                volume: Some(0.8),
+                ..Default::default()
+            },
+            48000,
+        );
+
+        let compand = chain.find("compand").expect("compand in chain");
+        assert!(chain.find("equalizer").unwrap() < compand, "{}", chain);
+        assert!(compand < chain.find("volume=").unwrap(), "{}", chain);
+    }
+
+    #[test]
+    fn test_speed_modes() {
+        assert_eq!(speed(1.5, SpeedMode::PreservePitch, 48000), "atempo=1.5000");
+        assert_eq!(
+            speed(1.5, SpeedMode::ShiftPitch, 48000),
+            "aresample=48000,asetrate=72000,aresample=48000"
+        );
+        assert_eq!(
+            speed(0.5, SpeedMode::ShiftPitch, 44100),
+            "aresample=44100,asetrate=22050,aresample=44100"
+        );
+    }
+
+    #[test]
+    fn test_build_filter_chain_shift_pitch() {
+        let audio_filters = AudioFilters {
+            speed: Some(1.25),
+            speed_mode: Some(SpeedMode::ShiftPitch),
+            ..Default::default()
+        };
+
+        let chain = build_filter_chain(&audio_filters, 24000);
+        assert!(chain.contains("asetrate=30000,aresample=24000"), "{}", chain);
+        assert!(!chain.contains("atempo"), "{}", chain);
+    }
+
+    #[test]
+    fn test_loudnorm_two_pass() {
+        let stats = LoudnessStats {
+            input_i: -27.61,
+            input_tp: -4.47,
+            input_lra: 18.06,
+            input_thresh: -39.2,
+            target_offset: 0.58,
+        };
+
+        assert_eq!(
+            loudnorm_two_pass(-16.0, -1.5, &stats, NormalizeMode::Linear),
+            "loudnorm=I=-16.0:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.47:\
+             measured_LRA=18.06:measured_thresh=-39.20:offset=0.58:linear=true:print_format=json"
+        );
+        assert!(loudnorm_two_pass(-16.0, -1.5, &stats, NormalizeMode::Dynamic)
+            .contains(":linear=false:"));
+        assert_eq!(
+            loudnorm_measure(-16.0, -1.5),
+            "loudnorm=I=-16.0:TP=-1.5:LRA=11:print_format=json"
+        );
+    }
+
+    #[test]
+    fn test_concat_sources_two_inputs() {
+        let format = aformat(48000, 2);
+        assert_eq!(
+            concat_sources(2, 48000, 2, "out"),
+            format!("[0:a]{format}[a0];[1:a]{format}[a1];[a0][a1]concat=n=2:v=0:a=1[out]")
+        );
+    }
+
+    #[test]
+    fn test_concat_sources_three_inputs() {
+        let format = aformat(44100, 1);
+        assert_eq!(
+            concat_sources(3, 44100, 1, "joined"),
+            format!(
+                "[0:a]{format}[a0];[1:a]{format}[a1];[2:a]{format}[a2];\
+                 [a0][a1][a2]concat=n=3:v=0:a=1[joined]"
+            )
+        );
+    }
+
+    #[test]
+    fn test_mix_background_graph() {
+        let graph = mix_background(0.0, -18.0, MixDuration::First, 48000, 2, "out");
+
+        assert!(graph.contains("[0:a]aformat"), "{}", graph);
+        assert!(graph.contains(",volume=0.0dB[primary]"), "{}", graph);
+        assert!(graph.contains(",volume=-18.0dB[bed]"), "{}", graph);
+        assert!(graph.ends_with("[primary][bed]amix=inputs=2:duration=first:normalize=0[out]"));
+
+        let graph = mix_background(-3.0, -12.0, MixDuration::Longest, 48000, 2, "joined");
+        assert!(graph.contains("amix=inputs=2:duration=longest"), "{}", graph);
+    }
+
+    #[test]
+    fn test_concat_preroll_order() {
+        let graph = concat_preroll(48000, 2, "out");
+        let preroll = graph.find("[0:a]").unwrap();
+        let main = graph.find("[1:a]").unwrap();
+        assert!(preroll < main);
+        assert!(graph.contains("[preroll][main]concat=n=2:v=0:a=1[out]"));
+        assert!(graph.contains("sample_rates=48000:channel_layouts=stereo"));
+    }
+
+    #[test]
+    fn test_volume_envelope_three_points() {
+        let points = [
+            EnvelopePoint { time: 0.0, gain_db: 0.0 },
+            EnvelopePoint { time: 10.0, gain_db: -12.0 },
+            EnvelopePoint { time: 15.0, gain_db: 0.0 },
+        ];
+
+        assert_eq!(
+            volume_envelope(&points),
+            "volume='pow(10,(if(lt(t,0.000),0.00,\
+             if(lt(t,10.000),0.00-1.2000*(t-0.000),\
+             if(lt(t,15.000),-12.00+2.4000*(t-10.000),0.00))))/20)':eval=frame"
+        );
+    }
+
+    #[test]
+    fn test_limiter() {
+        assert_eq!(limiter(-1.0), "alimiter=limit=0.891");
+        assert_eq!(limiter(0.0), "alimiter=limit=1.000");
+    }
+
+    #[test]
+    fn test_silencedetect() {
+        assert_eq!(silencedetect(-30.0, 0.5), "silencedetect=noise=-30.0dB:d=0.50");
+    }
+
+    #[test]
+    fn test_stereo_width() {
+        assert_eq!(stereo_width(1.5), "extrastereo=m=1.50");
+        assert_eq!(stereo_width(0.0), "extrastereo=m=0.00");
+        assert!(stereo_width(1.0).is_empty(), "Width 1.0 should produce empty filter");
+    }
+
+    #[test]
+    fn test_build_filter_chain_with_stereo_width() {
+        let audio_filters = AudioFilters {
+            eq_preset: Some(EqPreset::BassBoost),
+            stereo_width: Some(1.5),
+            volume: Some(0.8),
+            ..Default::default()
+        };
+        let chain = build_filter_chain(&audio_filters, 48000);
+
+        assert!(chain.contains("extrastereo=m=1.50"));
+        let eq_pos = chain.find("equalizer").unwrap();
+        let width_pos = chain.find("extrastereo").unwrap();
+        let vol_pos = chain.find("volume").unwrap();
+        assert!(eq_pos < width_pos && width_pos < vol_pos);
+    }
+
+    #[test]
+    fn test_noise_gate_before_eq() {
+        use crate::models::NoiseGateSettings;
+
+        let audio_filters = AudioFilters {
+            noise_gate: Some(NoiseGateSettings {
+                threshold_db: -40.0,
+                ratio: 4.0,
+                attack: 10.0,
+                release: 200.0,
+            }),
+            eq_preset: Some(EqPreset::Voice),
+            ..Default::default()
+        };
+        let chain = build_filter_chain(&audio_filters, 48000);
+
+        // -40 dBFS = 0.01 линейно
+        assert!(chain.starts_with("agate=threshold=0.010000:ratio=4.00:attack=10.00:release=200.00"));
+        assert!(chain.find("agate").unwrap() < chain.find("highpass").unwrap());
+    }
+
+    #[test]
+    fn test_limiter_is_last_in_chain() {
+        let audio_filters = AudioFilters {
+            eq_preset: Some(EqPreset::Voice),
+            speed: Some(1.25),
+            volume: Some(0.8),
+            limiter: Some(true),
+            ..Default::default()
+        };
+        let chain = build_filter_chain(&audio_filters, 48000);
+        assert!(chain.ends_with(",alimiter=limit=0.841"), "{}", chain);
+
+        // Усиление включает limiter само, явный false - выключает
+        let boosted = AudioFilters {
+            eq_preset: Some(EqPreset::BassBoost),
+            volume: Some(1.5),
+            ..Default::default()
+        };
+        assert!(build_filter_chain(&boosted, 48000).ends_with("alimiter=limit=0.841"));
+
+        let disabled = AudioFilters {
+            limiter: Some(false),
+            ..boosted
+        };
+        assert!(!build_filter_chain(&disabled, 48000).contains("alimiter"));
+
+        let quiet = AudioFilters {
+            volume: Some(0.5),
+            ..Default::default()
+        };
+        assert!(!build_filter_chain(&quiet, 48000).contains("alimiter"));
+    }
+
+    #[test]
+    fn test_denoise_early_in_chain() {
+        assert_eq!(denoise(12.0), "afftdn=nr=12.0");
+
+        let audio_filters = AudioFilters {
+            denoise_db: Some(20.0),
+            highpass_hz: Some(80),
+            speed: Some(1.25),
+            volume: Some(1.5),
+            ..Default::default()
+        };
+        let chain = build_filter_chain(&audio_filters, 48000);
+
+        let denoise_pos = chain.find("afftdn=nr=20.0").unwrap();
+        assert!(chain.find("highpass").unwrap() < denoise_pos, "{}", chain);
+        assert!(denoise_pos < chain.find("atempo").unwrap(), "{}", chain);
+        assert!(denoise_pos < chain.find("volume").unwrap(), "{}", chain);
+    }
 }
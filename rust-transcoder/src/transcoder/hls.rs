@@ -0,0 +1,271 @@
+//! HLS вывод
+//!
+//! `POST /api/v1/transcode/hls` пишет VOD плейлист и сегменты в каталог
+//! сессии (`Config::hls_dir`), `GET /api/v1/hls/:session_id/:file` их раздаёт.
+//! Каталог удаляется при `DELETE` сессии или после того, как сессия ушла
+//! из реестра (`SESSION_RETENTION`).
+
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::AudioCodec;
+
+use super::session::SessionRegistry;
+
+/// Длительность сегмента в секундах (`-hls_time`)
+pub const SEGMENT_SECS: u32 = 4;
+
+/// Имя плейлиста в каталоге сессии
+pub const PLAYLIST: &str = "playlist.m3u8";
+
+/// Init сегмент fMP4 (`-hls_fmp4_init_filename`)
+pub const INIT_SEGMENT: &str = "init.mp4";
+
+/// Конец VOD плейлиста: сегментов больше не будет
+const ENDLIST: &str = "#EXT-X-ENDLIST";
+
+/// Префикс имён сегментов
+const SEGMENT_PREFIX: &str = "segment_";
+
+/// Контейнер сегментов
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentType {
+    /// MPEG-TS (`.ts`) - AAC и MP3
+    MpegTs,
+    /// Fragmented MP4 (`.m4s` + `init.mp4`) - Opus в MPEG-TS не помещается
+    Fmp4,
+}
+
+impl SegmentType {
+    /// Контейнер сегментов для кодека
+    pub fn for_codec(codec: AudioCodec) -> Self {
+        match codec {
+            AudioCodec::Libopus | AudioCodec::Flac => SegmentType::Fmp4,
+            _ => SegmentType::MpegTs,
+        }
+    }
+
+    /// Шаблон имени сегмента для `-hls_segment_filename`
+    pub fn filename_pattern(&self) -> String {
+        let extension = match self {
+            SegmentType::MpegTs => "ts",
+            SegmentType::Fmp4 => "m4s",
+        };
+        format!("{}%03d.{}", SEGMENT_PREFIX, extension)
+    }
+}
+
+/// MIME тип файла HLS; None - имя не похоже на файл, который пишет muxer
+///
+/// Имя проверяется целиком, поэтому пути (`../`, `/`) не проходят.
+pub fn content_type(file: &str) -> Option<&'static str> {
+    if file == PLAYLIST {
+        return Some("application/vnd.apple.mpegurl");
+    }
+    if file == INIT_SEGMENT {
+        return Some("audio/mp4");
+    }
+
+    let (number, extension) = file.strip_prefix(SEGMENT_PREFIX)?.split_once('.')?;
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    match extension {
+        "ts" => Some("video/mp2t"),
+        "m4s" => Some("video/iso.segment"),
+        _ => None,
+    }
+}
+
+/// Каталоги HLS сессий (clone - то же хранилище)
+#[derive(Debug, Clone)]
+pub struct HlsStore {
+    root: PathBuf,
+    sessions: Arc<Mutex<HashSet<Uuid>>>,
+}
+
+impl HlsStore {
+    /// Хранилище с каталогами сессий внутри `root`
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            sessions: Arc::default(),
+        }
+    }
+
+    /// Создаёт каталог сессии и возвращает его путь
+    pub async fn create(&self, session_id: Uuid) -> io::Result<PathBuf> {
+        let dir = self.dir(session_id);
+        tokio::fs::create_dir_all(&dir).await?;
+        self.lock().insert(session_id);
+        Ok(dir)
+    }
+
+    /// Есть ли у сессии HLS вывод
+    pub fn contains(&self, session_id: Uuid) -> bool {
+        self.lock().contains(&session_id)
+    }
+
+    /// Удаляет каталог сессии; false - у сессии нет HLS вывода
+    ///
+    /// Каталог удаляется и у уже удалённой сессии: убитый при отмене FFmpeg
+    /// мог успеть дописать сегмент после первого удаления.
+    pub async fn remove(&self, session_id: Uuid) -> bool {
+        let known = self.lock().remove(&session_id);
+
+        let dir = self.dir(session_id);
+        if let Err(err) = tokio::fs::remove_dir_all(&dir).await {
+            if err.kind() != io::ErrorKind::NotFound {
+                warn!(error = %err, dir = %dir.display(), "Failed to remove HLS directory");
+            }
+        }
+        known
+    }
+
+    /// Закрывает плейлист сессии, прерванной во время кодирования
+    ///
+    /// Убитый FFmpeg не дописывает `#EXT-X-ENDLIST`, и плеер ждал бы новых
+    /// сегментов. false - не записано ни одного сегмента, сохранять нечего.
+    pub async fn finalize_partial(&self, session_id: Uuid) -> io::Result<bool> {
+        let path = self.dir(session_id).join(PLAYLIST);
+        let mut playlist = match tokio::fs::read_to_string(&path).await {
+            Ok(playlist) => playlist,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        if !playlist.lines().any(|line| line.starts_with("#EXTINF")) {
+            return Ok(false);
+        }
+
+        if !playlist.lines().any(|line| line.trim() == ENDLIST) {
+            if !playlist.ends_with('\n') {
+                playlist.push('\n');
+            }
+            playlist.push_str(ENDLIST);
+            playlist.push('\n');
+            tokio::fs::write(&path, playlist).await?;
+        }
+        Ok(true)
+    }
+
+    /// Удаляет каталоги сессий, которых уже нет в реестре
+    pub async fn prune(&self, sessions: &SessionRegistry) {
+        let expired: Vec<Uuid> = self
+            .lock()
+            .iter()
+            .copied()
+            .filter(|&session_id| sessions.get(session_id).is_none())
+            .collect();
+
+        for session_id in expired {
+            self.remove(session_id).await;
+        }
+    }
+
+    /// Содержимое файла сессии и его MIME тип
+    pub async fn read(&self, session_id: Uuid, file: &str) -> AppResult<(Vec<u8>, &'static str)> {
+        if !self.contains(session_id) {
+            return Err(AppError::SessionNotFound(session_id));
+        }
+        let content_type = content_type(file)
+            .ok_or_else(|| AppError::Validation(format!("'{}' is not an HLS file", file)))?;
+
+        match tokio::fs::read(self.dir(session_id).join(file)).await {
+            Ok(bytes) => Ok((bytes, content_type)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(AppError::NotFound(format!(
+                "{} of session {} is not available",
+                file, session_id
+            ))),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn dir(&self, session_id: Uuid) -> PathBuf {
+        self.root.join(session_id.to_string())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashSet<Uuid>> {
+        self.sessions.lock().expect("HLS store poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TranscodeStatus;
+    use std::time::Duration;
+
+    #[test]
+    fn test_content_type_accepts_only_muxer_files() {
+        assert_eq!(
+            content_type("playlist.m3u8"),
+            Some("application/vnd.apple.mpegurl")
+        );
+        assert_eq!(content_type("segment_000.ts"), Some("video/mp2t"));
+        assert_eq!(content_type("segment_1234.m4s"), Some("video/iso.segment"));
+        assert_eq!(content_type("init.mp4"), Some("audio/mp4"));
+
+        for file in [
+            "../playlist.m3u8",
+            "segment_.ts",
+            "segment_00a.ts",
+            "segment_000.ts/x",
+            "segment_000.mp3",
+            "other.m3u8",
+            "",
+        ] {
+            assert_eq!(content_type(file), None, "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_segment_type_by_codec() {
+        assert_eq!(SegmentType::for_codec(AudioCodec::Aac), SegmentType::MpegTs);
+        assert_eq!(
+            SegmentType::for_codec(AudioCodec::Libopus),
+            SegmentType::Fmp4
+        );
+        assert_eq!(SegmentType::MpegTs.filename_pattern(), "segment_%03d.ts");
+    }
+
+    #[tokio::test]
+    async fn test_store_lifecycle_and_prune() {
+        let root = tempfile::TempDir::new().unwrap();
+        let store = HlsStore::new(root.path().to_path_buf());
+        let sessions = SessionRegistry::new();
+
+        let session_id = Uuid::new_v4();
+        sessions.register(session_id);
+        let dir = store.create(session_id).await.unwrap();
+        std::fs::write(dir.join(PLAYLIST), "#EXTM3U\n").unwrap();
+
+        let (bytes, content_type) = store.read(session_id, PLAYLIST).await.unwrap();
+        assert_eq!(bytes, b"#EXTM3U\n");
+        assert_eq!(content_type, "application/vnd.apple.mpegurl");
+        assert!(matches!(
+            store.read(session_id, "segment_000.ts").await,
+            Err(AppError::NotFound(_))
+        ));
+        assert!(matches!(
+            store.read(Uuid::new_v4(), PLAYLIST).await,
+            Err(AppError::SessionNotFound(_))
+        ));
+
+        // Сессия в реестре - каталог остаётся
+        store.prune(&sessions).await;
+        assert!(dir.exists());
+
+        // Сессия истекла - каталог удаляется
+        sessions.set_status(session_id, TranscodeStatus::Completed);
+        sessions.prune(Duration::ZERO);
+        store.prune(&sessions).await;
+        assert!(!dir.exists());
+        assert!(!store.contains(session_id));
+    }
+}
@@ -2,10 +2,32 @@
 //!
 //! Содержит FFmpeg wrapper и профили транскодирования.
 
+pub mod analysis;
+pub mod breaker;
+pub mod cloud;
+pub mod coalesce;
+pub mod fetch;
 pub mod ffmpeg;
 pub mod filters;
+pub mod hls;
+pub mod permit;
+pub mod preview;
+pub mod probe;
 pub mod profiles;
+pub mod queue;
+pub mod redact;
+pub mod session;
+pub mod stream;
 
 // Re-export основных типов
+pub use breaker::{BreakerPass, BreakerState, CircuitBreaker};
+pub use coalesce::Coalescer;
+pub use fetch::{PipedSource, SourceFetcher};
 pub use ffmpeg::FfmpegProcess;
+pub use hls::HlsStore;
+pub use permit::{PermitWeights, TranscodePermit};
+pub use probe::SourceInfo;
 pub use profiles::TranscodeProfile;
+pub use queue::JobQueue;
+pub use session::SessionRegistry;
+pub use stream::{MeteredStream, TranscodeStream};
@@ -0,0 +1,179 @@
+//! Permit на одно транскодирование
+//!
+//! Оборачивает permit'ы семафора и ведёт gauges нагрузки: `active_transcodes`
+//! растёт при выдаче permit'а и уменьшается при его освобождении, где бы
+//! permit ни был отпущен (handler, поток, задача завершения), а
+//! `transcode_permits_available` повторяет `available_permits()` семафора.
+//!
+//! Тяжёлое транскодирование занимает несколько permit'ов семафора
+//! (`PermitWeights`), чтобы lossless задачи не перегружали CPU.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+use crate::metrics::{
+    ACTIVE_TRANSCODES, TRANSCODE_PERMITS_AVAILABLE, TRANSCODE_QUEUE_WAIT_SECONDS,
+};
+use crate::models::{AudioCodec, AudioQuality};
+
+/// Сколько permit'ов занимает транскодирование в зависимости от нагрузки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermitWeights {
+    /// `quality: lossless` или кодирование во FLAC
+    pub lossless: u32,
+    /// `quality: high`
+    pub high: u32,
+    /// Остальные транскодирования
+    pub standard: u32,
+}
+
+impl Default for PermitWeights {
+    fn default() -> Self {
+        Self {
+            lossless: 4,
+            high: 2,
+            standard: 1,
+        }
+    }
+}
+
+impl PermitWeights {
+    /// Вес транскодирования, не меньше одного permit'а
+    ///
+    /// PCM формально lossless, но не кодируется - считается обычным.
+    pub fn cost(&self, quality: AudioQuality, codec: AudioCodec) -> u32 {
+        let weight = match quality {
+            AudioQuality::Lossless => self.lossless,
+            _ if codec == AudioCodec::Flac => self.lossless,
+            AudioQuality::High => self.high,
+            AudioQuality::Low | AudioQuality::Medium => self.standard,
+        };
+        weight.max(1)
+    }
+}
+
+/// Занятый слот транскодирования; освобождается при drop
+#[derive(Debug)]
+pub struct TranscodePermit {
+    permit: Option<OwnedSemaphorePermit>,
+    semaphore: Arc<Semaphore>,
+}
+
+impl TranscodePermit {
+    /// Забирает один permit без ожидания
+    pub fn try_acquire(semaphore: &Arc<Semaphore>) -> Result<Self, TryAcquireError> {
+        Self::try_acquire_many(semaphore, 1)
+    }
+
+    /// Забирает `weight` permit'ов без ожидания
+    pub fn try_acquire_many(
+        semaphore: &Arc<Semaphore>,
+        weight: u32,
+    ) -> Result<Self, TryAcquireError> {
+        let permit = Arc::clone(semaphore).try_acquire_many_owned(weight)?;
+        Ok(Self::track(permit, semaphore))
+    }
+
+    /// Ждёт `weight` permit'ов не дольше `wait`; `Duration::ZERO` - как
+    /// `try_acquire_many`
+    ///
+    /// None - слоты не освободились за отведённое время.
+    pub async fn acquire_within(
+        semaphore: &Arc<Semaphore>,
+        weight: u32,
+        wait: Duration,
+    ) -> Option<Self> {
+        if wait.is_zero() {
+            return Self::try_acquire_many(semaphore, weight).ok();
+        }
+
+        let started = Instant::now();
+        let permit = tokio::time::timeout(wait, Arc::clone(semaphore).acquire_many_owned(weight))
+            .await
+            .ok()?
+            // Семафор не закрывается
+            .ok()?;
+        TRANSCODE_QUEUE_WAIT_SECONDS.observe(started.elapsed().as_secs_f64());
+        Some(Self::track(permit, semaphore))
+    }
+
+    /// Ждёт `weight` permit'ов без ограничения времени (для очереди задач)
+    pub async fn acquire(semaphore: &Arc<Semaphore>, weight: u32) -> Self {
+        let started = Instant::now();
+        let permit = Arc::clone(semaphore)
+            .acquire_many_owned(weight)
+            .await
+            .expect("transcode semaphore is never closed");
+        TRANSCODE_QUEUE_WAIT_SECONDS.observe(started.elapsed().as_secs_f64());
+        Self::track(permit, semaphore)
+    }
+
+    /// Сколько permit'ов семафора занято
+    pub fn weight(&self) -> u32 {
+        self.permit
+            .as_ref()
+            .map_or(0, |permit| permit.num_permits() as u32)
+    }
+
+    fn track(permit: OwnedSemaphorePermit, semaphore: &Arc<Semaphore>) -> Self {
+        ACTIVE_TRANSCODES.inc();
+        report_available(semaphore);
+        Self {
+            permit: Some(permit),
+            semaphore: Arc::clone(semaphore),
+        }
+    }
+}
+
+impl Drop for TranscodePermit {
+    fn drop(&mut self) {
+        // Сначала вернуть permit, чтобы gauge видел освободившийся слот
+        self.permit.take();
+        ACTIVE_TRANSCODES.dec();
+        report_available(&self.semaphore);
+    }
+}
+
+/// Обновляет `transcode_permits_available` по семафору
+pub fn report_available(semaphore: &Semaphore) {
+    TRANSCODE_PERMITS_AVAILABLE.set(semaphore.available_permits() as i64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permit_cost_by_quality_and_codec() {
+        let weights = PermitWeights::default();
+
+        assert_eq!(weights.cost(AudioQuality::Lossless, AudioCodec::Libopus), 4);
+        assert_eq!(weights.cost(AudioQuality::Medium, AudioCodec::Flac), 4);
+        assert_eq!(weights.cost(AudioQuality::High, AudioCodec::Aac), 2);
+        assert_eq!(weights.cost(AudioQuality::Low, AudioCodec::Libopus), 1);
+        assert_eq!(weights.cost(AudioQuality::Medium, AudioCodec::PcmS16le), 1);
+
+        let weights = PermitWeights {
+            standard: 0,
+            ..PermitWeights::default()
+        };
+        assert_eq!(weights.cost(AudioQuality::Low, AudioCodec::Libopus), 1);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_permit_holds_many_slots() {
+        let semaphore = Arc::new(Semaphore::new(5));
+
+        let permit = TranscodePermit::acquire_within(&semaphore, 4, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(permit.weight(), 4);
+        assert_eq!(semaphore.available_permits(), 1);
+        assert!(TranscodePermit::try_acquire_many(&semaphore, 2).is_err());
+
+        drop(permit);
+        assert_eq!(semaphore.available_permits(), 5);
+    }
+}
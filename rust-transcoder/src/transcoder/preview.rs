@@ -0,0 +1,127 @@
+//! PNG превью источника
+//!
+//! Один проход FFmpeg с `showwavespic`/`showspectrumpic`: фильтр читает
+//! источник до конца и выдаёт единственный кадр, который кодируется в PNG
+//! и пишется в stdout.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::Command;
+use tracing::{debug, instrument};
+
+use crate::error::{AppError, AppResult};
+use crate::models::PreviewKind;
+
+use super::ffmpeg::{exit_failure, spawn_error};
+use super::redact::redact_url;
+
+/// Аргументы FFmpeg для превью `width`x`height`
+///
+/// `max_duration` ограничивает чтение источника (опция входа), иначе
+/// длинный поток рендерился бы до таймаута.
+pub fn preview_args(
+    source_url: &str,
+    kind: PreviewKind,
+    width: u32,
+    height: u32,
+    max_duration: Option<u32>,
+) -> Vec<String> {
+    let mut args = vec![
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(),
+        "error".to_string(),
+    ];
+    if let Some(max_duration) = max_duration {
+        args.extend(["-t".to_string(), max_duration.to_string()]);
+    }
+    args.extend(["-i".to_string(), source_url.to_string()]);
+
+    let filter = match kind {
+        PreviewKind::Waveform => format!("showwavespic=s={}x{}", width, height),
+        PreviewKind::Spectrum => format!("showspectrumpic=s={}x{}:legend=0", width, height),
+    };
+    args.extend(["-filter_complex".to_string(), filter]);
+    args.extend(
+        [
+            "-frames:v",
+            "1",
+            "-c:v",
+            "png",
+            "-f",
+            "image2pipe",
+            "pipe:1",
+        ]
+        .map(String::from),
+    );
+    args
+}
+
+/// Запускает FFmpeg и возвращает PNG из stdout
+///
+/// По истечении `timeout` процесс убивается (`kill_on_drop`).
+#[instrument(skip(binary, args, source_url), fields(source = %redact_url(source_url)))]
+pub async fn render_preview(
+    binary: &str,
+    source_url: &str,
+    args: &[String],
+    timeout: Duration,
+) -> AppResult<Vec<u8>> {
+    let output = Command::new(binary)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    let output = match tokio::time::timeout(timeout, output).await {
+        Ok(output) => output.map_err(|e| spawn_error(binary, e))?,
+        Err(_) => {
+            return Err(AppError::Timeout(format!(
+                "Preview did not finish within {} seconds",
+                timeout.as_secs()
+            )))
+        }
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(exit_failure(output.status, &stderr));
+    }
+    if output.stdout.is_empty() {
+        return Err(AppError::Ffmpeg("FFmpeg produced no preview image".into()));
+    }
+
+    debug!(bytes = output.stdout.len(), "Preview rendered");
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_args() {
+        let args = preview_args(
+            "https://example.com/a.mp3",
+            PreviewKind::Waveform,
+            800,
+            200,
+            Some(600),
+        );
+        assert_eq!(
+            args.join(" "),
+            "-hide_banner -loglevel error -t 600 -i https://example.com/a.mp3 \
+             -filter_complex showwavespic=s=800x200 -frames:v 1 -c:v png -f image2pipe pipe:1"
+        );
+
+        let args = preview_args(
+            "https://example.com/a.mp3",
+            PreviewKind::Spectrum,
+            800,
+            200,
+            None,
+        );
+        assert!(!args.contains(&"-t".to_string()));
+        assert!(args.contains(&"showspectrumpic=s=800x200:legend=0".to_string()));
+    }
+}
@@ -0,0 +1,393 @@
+//! Анализ источника через ffprobe
+//!
+//! Получение параметров аудио потока источника до запуска транскодирования.
+
+use std::process::{Output, Stdio};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::process::Command;
+use tracing::{debug, instrument};
+
+use crate::error::{AppError, AppResult};
+use crate::models::LoudnessStats;
+
+use super::analysis;
+use super::ffmpeg;
+use super::filters;
+use super::profiles::TranscodeProfile;
+use super::redact::redact_url;
+
+/// Сколько ждать ffprobe; по истечении процесс убивается
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Параметры первого аудио потока источника
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceInfo {
+    /// Название кодека (например `mp3`, `opus`)
+    pub codec: Option<String>,
+    /// Количество каналов
+    pub channels: Option<u8>,
+    /// Sample rate в Hz
+    pub sample_rate: Option<u32>,
+    /// Длительность в секундах (None для live-источников)
+    pub duration: Option<f64>,
+    /// Контейнер (`format_name` ffprobe, например `mp3` или `mov,mp4,m4a,3gp,3g2,mj2`)
+    pub format: Option<String>,
+    /// Битрейт в бит/с (потока, иначе контейнера)
+    pub bitrate: Option<u64>,
+}
+
+/// Вывод `ffprobe -print_format json`
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+    channels: Option<u8>,
+    sample_rate: Option<String>,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+    format_name: Option<String>,
+    bit_rate: Option<String>,
+}
+
+/// Запускает ffprobe для источника (`ffprobe` из PATH)
+pub async fn probe_source(source_url: &str) -> AppResult<SourceInfo> {
+    probe_source_with_binary("ffprobe", source_url).await
+}
+
+/// `probe_source` с явным путём к ffprobe
+#[instrument(skip(source_url), fields(source = %redact_url(source_url)))]
+pub async fn probe_source_with_binary(binary: &str, source_url: &str) -> AppResult<SourceInfo> {
+    let args = [
+        "-v",
+        "error",
+        "-select_streams",
+        "a:0",
+        "-show_entries",
+        "stream=codec_name,channels,sample_rate,duration,bit_rate\
+         :format=duration,format_name,bit_rate",
+        "-print_format",
+        "json",
+    ];
+    let output = run_probe(binary, &args, source_url, PROBE_TIMEOUT).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::SourceUnavailable(format!(
+            "ffprobe failed: {}",
+            stderr.trim()
+        )));
+    }
+
+    let info = parse_probe_output(&String::from_utf8_lossy(&output.stdout))?;
+    debug!(info = ?info, "Source probed");
+
+    Ok(info)
+}
+
+/// Длительность источника в секундах через ffprobe
+///
+/// Для источников без длительности (live) - `AppError::Validation`.
+pub async fn probe_duration(source_url: &str) -> AppResult<f64> {
+    probe_duration_with_binary("ffprobe", source_url).await
+}
+
+/// `probe_duration` с явным путём к ffprobe
+#[instrument(skip(source_url), fields(source = %redact_url(source_url)))]
+pub async fn probe_duration_with_binary(binary: &str, source_url: &str) -> AppResult<f64> {
+    let args = [
+        "-v",
+        "error",
+        "-show_entries",
+        "format=duration",
+        "-of",
+        "default=noprint_wrappers=1:nokey=1",
+    ];
+    let output = run_probe(binary, &args, source_url, PROBE_TIMEOUT).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::SourceUnavailable(format!(
+            "ffprobe failed: {}",
+            stderr.trim()
+        )));
+    }
+
+    let duration = parse_duration(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+        AppError::Validation("Source duration is unknown (live stream?)".to_string())
+    })?;
+    debug!(duration, "Source duration probed");
+
+    Ok(duration)
+}
+
+/// Запускает ffprobe с таймаутом
+///
+/// `-rw_timeout` обрывает зависшее чтение сети, а по истечении `timeout`
+/// процесс убивается (`kill_on_drop`).
+async fn run_probe(
+    binary: &str,
+    args: &[&str],
+    source_url: &str,
+    timeout: Duration,
+) -> AppResult<Output> {
+    let rw_timeout = timeout.as_micros().to_string();
+    let output = Command::new(binary)
+        .args(args)
+        .args(["-rw_timeout", rw_timeout.as_str(), source_url])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(timeout, output).await {
+        Ok(output) => output.map_err(|e| ffmpeg::spawn_error(binary, e)),
+        Err(_) => Err(AppError::Timeout(format!(
+            "ffprobe did not finish within {} seconds",
+            timeout.as_secs()
+        ))),
+    }
+}
+
+/// Первый проход двухпроходной нормализации: измерение громкости источника
+///
+/// Анализируется тот же фрагмент, что попадёт в результат (`-ss`, `-t`), с целями
+/// loudnorm профиля - от них зависит `target_offset` для второго прохода.
+/// Фильтры профиля до loudnorm (EQ, gate и т.п.) в измерении не участвуют.
+/// Проход дольше `timeout` убивается (`AppError::Timeout`).
+#[instrument(skip(profile), fields(source_url = %redact_url(&profile.source_url)))]
+pub async fn measure_loudness_stats(
+    binary: &str,
+    profile: &TranscodeProfile,
+    timeout: Duration,
+) -> AppResult<LoudnessStats> {
+    let mut args = vec!["-hide_banner".to_string(), "-nostats".to_string()];
+    args.extend(ffmpeg::rw_timeout_args(timeout));
+    if let Some(start_time) = profile.start_time {
+        args.extend(["-ss".to_string(), start_time.to_string()]);
+    }
+    args.extend(["-i".to_string(), profile.source_url.clone()]);
+    if let Some(limit) = profile.output_limit() {
+        // Лимит задан по выходу - после atempo; измеряем соответствующий отрезок источника
+        let span = limit * profile.speed.unwrap_or(1.0);
+        args.extend(["-t".to_string(), span.to_string()]);
+    }
+    args.extend([
+        "-vn".to_string(),
+        "-af".to_string(),
+        filters::loudnorm_measure(profile.target_loudness, filters::DEFAULT_TRUE_PEAK_DB),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]);
+
+    let stderr = ffmpeg::run_analysis_pass_within(binary, &args, timeout).await?;
+    let stats = analysis::parse_loudnorm_stats(&stderr)?;
+    debug!(stats = ?stats, "Loudness measured for two-pass normalization");
+
+    Ok(stats)
+}
+
+/// Измерение громкости источника без профиля транскодирования
+///
+/// Для `POST /api/v1/loudness`: источник целиком (не дольше `max_duration`),
+/// `target_offset` считается относительно `target_lufs`.
+#[instrument(skip(source_url), fields(source_url = %redact_url(source_url)))]
+pub async fn measure_source_loudness(
+    binary: &str,
+    source_url: &str,
+    target_lufs: f32,
+    max_duration: Option<u32>,
+) -> AppResult<LoudnessStats> {
+    let mut args = vec![
+        "-hide_banner".to_string(),
+        "-nostats".to_string(),
+        "-i".to_string(),
+        source_url.to_string(),
+    ];
+    if let Some(max_duration) = max_duration {
+        args.extend(["-t".to_string(), max_duration.to_string()]);
+    }
+    args.extend([
+        "-vn".to_string(),
+        "-af".to_string(),
+        filters::loudnorm_measure(target_lufs, filters::DEFAULT_TRUE_PEAK_DB),
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ]);
+
+    let stderr = ffmpeg::run_analysis_pass(binary, &args).await?;
+    let stats = analysis::parse_loudnorm_stats(&stderr)?;
+    debug!(stats = ?stats, "Source loudness measured");
+
+    Ok(stats)
+}
+
+/// Разбирает вывод `-show_entries format=duration` (`183.040000` или `N/A`)
+pub fn parse_duration(output: &str) -> Option<f64> {
+    output
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|duration| duration.is_finite() && *duration > 0.0)
+}
+
+/// Разбирает JSON вывод ffprobe
+pub fn parse_probe_output(json: &str) -> AppResult<SourceInfo> {
+    let output: ProbeOutput = serde_json::from_str(json)
+        .map_err(|e| AppError::Ffmpeg(format!("Invalid ffprobe output: {}", e)))?;
+
+    let stream = output
+        .streams
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::SourceUnavailable("Source has no audio stream".to_string()))?;
+
+    let format = output.format;
+
+    // Длительность и битрейт потока точнее, но есть не у всех контейнеров
+    let duration = stream
+        .duration
+        .or_else(|| format.as_ref().and_then(|f| f.duration.clone()))
+        .and_then(|d| d.parse().ok());
+    let bitrate = stream
+        .bit_rate
+        .or_else(|| format.as_ref().and_then(|f| f.bit_rate.clone()))
+        .and_then(|b| b.parse().ok());
+
+    Ok(SourceInfo {
+        codec: stream.codec_name,
+        channels: stream.channels,
+        sample_rate: stream.sample_rate.and_then(|sr| sr.parse().ok()),
+        duration,
+        format: format.and_then(|f| f.format_name),
+        bitrate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe_output() {
+        let json = r#"{
+            "programs": [],
+            "streams": [
+                { "codec_name": "mp3", "sample_rate": "44100", "channels": 1 }
+            ],
+            "format": { "duration": "183.040000", "format_name": "mp3", "bit_rate": "128000" }
+        }"#;
+
+        let info = parse_probe_output(json).unwrap();
+        assert_eq!(info.codec.as_deref(), Some("mp3"));
+        assert_eq!(info.channels, Some(1));
+        assert_eq!(info.sample_rate, Some(44100));
+        assert_eq!(info.duration, Some(183.04));
+        assert_eq!(info.format.as_deref(), Some("mp3"));
+        assert_eq!(info.bitrate, Some(128000));
+    }
+
+    #[test]
+    fn test_parse_probe_output_live_source() {
+        let json = r#"{
+            "streams": [{ "codec_name": "aac", "sample_rate": "48000", "channels": 2 }],
+            "format": { "duration": "N/A" }
+        }"#;
+
+        assert_eq!(parse_probe_output(json).unwrap().duration, None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("183.040000\n"), Some(183.04));
+        assert_eq!(parse_duration("N/A\n"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_duration_of_live_source_is_validation_error() {
+        let ffprobe = crate::transcoder::ffmpeg::testing::fake_ffmpeg("echo N/A");
+
+        let err = probe_duration_with_binary(&ffprobe, "rtmp://live.example.com/stream")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::Validation(_)));
+    }
+
+    #[tokio::test]
+    async fn test_probe_duration() {
+        let ffprobe = crate::transcoder::ffmpeg::testing::fake_ffmpeg("echo 42.5");
+
+        let duration = probe_duration_with_binary(&ffprobe, "https://example.com/a.mp3")
+            .await
+            .unwrap();
+        assert_eq!(duration, 42.5);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_probe_times_out() {
+        let ffprobe = crate::transcoder::ffmpeg::testing::fake_ffmpeg("exec sleep 30");
+
+        let started = std::time::Instant::now();
+        let err = run_probe(&ffprobe, &[], "https://example.com/a.mp3", Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::Timeout(_)), "{:?}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_loudness_stats_pass_times_out() {
+        let args = tempfile::NamedTempFile::new().unwrap();
+        let ffmpeg = crate::transcoder::ffmpeg::testing::fake_ffmpeg(&format!(
+            "echo \"$@\" > {}; exec sleep 30",
+            args.path().display()
+        ));
+        let mut profile = TranscodeProfile::telegram_voice("https://example.com/a.mp3");
+        profile.start_time = Some(5.0);
+
+        let err = measure_loudness_stats(&ffmpeg, &profile, Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::Timeout(_)), "{:?}", err);
+
+        // -rw_timeout - входная опция, до -i
+        let args = std::fs::read_to_string(args.path()).unwrap();
+        assert!(
+            args.contains("-rw_timeout 200000 -ss 5 -i https://example.com/a.mp3"),
+            "{}",
+            args
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_ffprobe_error_names_path() {
+        let binary = "/nonexistent/bin/ffprobe7";
+        let err = probe_source_with_binary(binary, "https://example.com/a.mp3")
+            .await
+            .unwrap_err();
+        match err {
+            AppError::Ffmpeg(message) => assert!(message.contains(binary), "{}", message),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_probe_output_without_audio() {
+        let err = parse_probe_output(r#"{ "streams": [] }"#).unwrap_err();
+        assert!(matches!(err, AppError::SourceUnavailable(_)));
+    }
+}
@@ -2,7 +2,27 @@
 //!
 //! Определяет параметры транскодирования и генерирует FFmpeg аргументы.
 
-use crate::models::{AudioCodec, AudioFormat, AudioQuality, TranscodeRequest};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::models::{
+    seek_mode_for, transcode, AudioCodec, AudioFormat, AudioQuality, BackgroundTrack, CompressorSettings,
+    EnvelopePoint, EqBand, EqPreset, FadeCurve, LoudnessStats, NoiseGateSettings, NormalizeMode,
+    OpusApplication, OpusVbr, ProfilePreset, SampleFormat, SeekMode, SignalKind, SpeedMode,
+    TestSignal, TranscodeRequest,
+};
+
+/// Целевая громкость вещательного режима (EBU R128 / стриминговые платформы)
+const BROADCAST_TARGET_LUFS: f32 = -16.0;
+
+/// Потолок true peak вещательного режима
+const BROADCAST_TRUE_PEAK_DB: f32 = -1.0;
+
+/// Порог тишины для `detect_segments`
+const SILENCE_THRESHOLD_DB: f32 = -30.0;
+
+/// Минимальная пауза между сегментами для `detect_segments`
+const SILENCE_MIN_DURATION_SECS: f32 = 0.5;
 
 /// Профиль транскодирования с полной конфигурацией FFmpeg
 #[derive(Debug, Clone)]
@@ -17,62 +37,508 @@ pub struct TranscodeProfile {
     pub bitrate: u32,
     /// Sample rate в Hz
     pub sample_rate: u32,
+    /// Уровень сжатия FLAC (`-compression_level`)
+    pub compression_level: Option<u8>,
+    /// Качество VBR MP3 (`-q:a`), заменяет битрейт
+    pub vbr_quality: Option<u8>,
+    /// Профиль libopus (`-application`)
+    pub opus_application: Option<OpusApplication>,
+    /// Режим VBR libopus (`-vbr`)
+    pub opus_vbr: Option<OpusVbr>,
     /// Количество каналов
     pub channels: u8,
+    /// Формат сэмплов, None - по умолчанию для encoder'а
+    pub sample_fmt: Option<SampleFormat>,
     /// Применить нормализацию
     pub normalize: bool,
     /// Целевой уровень громкости (LUFS)
     pub target_loudness: f32,
+    /// Режим двухпроходной нормализации, None - один проход
+    pub normalize_mode: Option<NormalizeMode>,
+    /// Измерение первого прохода (задаётся перед запуском второго)
+    pub loudness_stats: Option<LoudnessStats>,
     /// Fade in (секунды)
     pub fade_in: Option<f32>,
     /// Fade out (секунды)
     pub fade_out: Option<f32>,
+    /// Форма кривой fade
+    pub fade_curve: FadeCurve,
+    /// Длительность источника в секундах (нужна для fade out)
+    pub source_duration: Option<f64>,
+    /// Частота среза highpass (`audio_filters.highpass_hz`)
+    pub highpass_hz: Option<u32>,
+    /// Частота среза lowpass (`audio_filters.lowpass_hz`)
+    pub lowpass_hz: Option<u32>,
+    /// Сила шумоподавления в dB (`audio_filters.denoise_db`)
+    pub denoise_db: Option<f32>,
+    /// Noise gate (`audio_filters.noise_gate`)
+    pub noise_gate: Option<NoiseGateSettings>,
+    /// EQ preset (`audio_filters.eq_preset`)
+    pub eq_preset: Option<EqPreset>,
+    /// Полосы параметрического EQ (`audio_filters.custom_eq`)
+    pub custom_eq: Option<Vec<EqBand>>,
+    /// Компрессор (`audio_filters.compress`)
+    pub compress: Option<CompressorSettings>,
+    /// Ширина стерео базы (`audio_filters.stereo_width`)
+    pub stereo_width: Option<f32>,
+    /// Множитель скорости (`audio_filters.speed`)
+    pub speed: Option<f32>,
+    /// Режим изменения скорости (`audio_filters.speed_mode`)
+    pub speed_mode: SpeedMode,
+    /// Множитель громкости (`audio_filters.volume`)
+    pub volume: Option<f32>,
+    /// Огибающая громкости (`audio_filters.volume_envelope`)
+    pub volume_envelope: Option<Vec<EnvelopePoint>>,
+    /// Лимит длительности результата в секундах
+    pub max_duration: Option<u32>,
+    /// Начало фрагмента в секундах источника (`-ss` перед `-i`)
+    pub start_time: Option<f32>,
+    /// Длительность фрагмента в секундах результата (`-t`)
+    pub trim_duration: Option<f32>,
+    /// URL pre-roll клипа, проигрываемого перед источником
+    pub preroll_url: Option<String>,
+    /// Источники, склеиваемые после `source_url` (`source_urls` запроса)
+    pub extra_sources: Vec<String>,
+    /// Фоновая дорожка, смешиваемая с источником
+    pub background: Option<BackgroundTrack>,
+    /// Remux без перекодирования (`-c:a copy`)
+    pub copy: bool,
+    /// Теги результата (`-metadata key=value`)
+    pub metadata: BTreeMap<String, String>,
+    /// Сохранить теги источника вместо `-map_metadata -1`
+    pub preserve_metadata: bool,
+    /// Компенсировать смену параметров потока (`aresample=async=1`)
+    pub normalize_stream_params: bool,
+    /// Искать границы сегментов по тишине (`silencedetect`)
+    pub detect_segments: bool,
+    /// Вещательный режим: loudnorm + limiter с фиксированными целями
+    pub broadcast_ready: bool,
+    /// Limiter true peak после громкости (`AudioFilters::limiter_enabled`)
+    pub limiter: bool,
+    /// Demuxer источника (`-f` перед `-i`), None - автоопределение
+    pub input_format: Option<String>,
+    /// Каталог вывода HLS вместо потока в stdout (`POST /api/v1/transcode/hls`)
+    pub hls_dir: Option<PathBuf>,
+}
+
+impl Default for TranscodeProfile {
+    fn default() -> Self {
+        Self {
+            source_url: String::new(),
+            format: AudioFormat::default(),
+            codec: AudioCodec::default(),
+            bitrate: 64,
+            sample_rate: 48000,
+            compression_level: None,
+            vbr_quality: None,
+            opus_application: None,
+            opus_vbr: None,
+            channels: 2,
+            sample_fmt: None,
+            normalize: false,
+            target_loudness: -16.0,
+            normalize_mode: None,
+            loudness_stats: None,
+            fade_in: None,
+            fade_out: None,
+            fade_curve: FadeCurve::default(),
+            source_duration: None,
+            highpass_hz: None,
+            lowpass_hz: None,
+            denoise_db: None,
+            noise_gate: None,
+            eq_preset: None,
+            custom_eq: None,
+            compress: None,
+            stereo_width: None,
+            speed: None,
+            speed_mode: SpeedMode::default(),
+            volume: None,
+            volume_envelope: None,
+            max_duration: None,
+            start_time: None,
+            trim_duration: None,
+            preroll_url: None,
+            extra_sources: Vec::new(),
+            background: None,
+            copy: false,
+            metadata: BTreeMap::new(),
+            preserve_metadata: false,
+            normalize_stream_params: false,
+            detect_segments: false,
+            broadcast_ready: false,
+            limiter: false,
+            input_format: None,
+            hls_dir: None,
+        }
+    }
 }
 
 impl TranscodeProfile {
     /// Создаёт профиль из TranscodeRequest
+    ///
+    /// С `profile` основой служит пресет: его значения берутся для полей, не
+    /// заданных в запросе. Поле со значением по умолчанию (format, codec,
+    /// quality, target_loudness) считается не заданным; `normalize` пресета
+    /// запросом не отключается.
     pub fn from_request(req: &TranscodeRequest) -> Self {
+        let preset = req
+            .profile_preset()
+            .map(|preset| Self::from_preset(preset, &req.source_url));
+        let preset = preset.as_ref();
+
+        let format = match preset {
+            Some(preset) if req.format == transcode::default_format() => preset.format,
+            _ => req.format,
+        };
+        let codec = match preset {
+            Some(preset) if req.codec == transcode::default_codec() => preset.codec,
+            _ => req.codec,
+        };
+        // quality задаёт битрейт и sample rate, только если явно отличается от пресета
+        let preset_quality = preset.filter(|_| req.quality == AudioQuality::default());
+
         let bitrate = req
             .bitrate
-            .unwrap_or_else(|| req.quality.bitrate_for_codec(req.codec));
-        let sample_rate = req.sample_rate.unwrap_or_else(|| req.quality.sample_rate());
-        let channels = req.channels.unwrap_or(2);
+            .or(preset_quality.map(|preset| preset.bitrate))
+            .unwrap_or_else(|| req.quality.bitrate_for_codec(codec));
+        // Encoder отвергает неподдерживаемый rate - приводим к ближайшему допустимому
+        let sample_rate = codec.snap_sample_rate(
+            req.sample_rate
+                .or(preset_quality.map(|preset| preset.sample_rate))
+                .unwrap_or_else(|| req.quality.sample_rate()),
+        );
+        let channels = req
+            .channels
+            .or(preset.map(|preset| preset.channels))
+            .unwrap_or(2);
+        let normalize = req.normalize || preset.is_some_and(|preset| preset.normalize);
+        let target_loudness = match preset {
+            Some(preset) if req.target_loudness == transcode::default_target_loudness() => {
+                preset.target_loudness
+            }
+            _ => req.target_loudness,
+        };
+        let filters = req.audio_filters.as_ref();
+
+        // При склейке первый из source_urls - основной источник профиля
+        let source_url = req.source_urls.first().unwrap_or(&req.source_url);
 
         Self {
-            source_url: req.source_url.clone(),
-            format: req.format,
-            codec: req.codec,
+            source_url: source_url.clone(),
+            format,
+            codec,
             bitrate,
             sample_rate,
+            compression_level: req.compression_level,
+            vbr_quality: req.vbr_quality,
+            opus_application: req.opus_application,
+            opus_vbr: req.opus_vbr,
             channels,
-            normalize: req.normalize,
-            target_loudness: req.target_loudness,
+            sample_fmt: req.sample_fmt,
+            normalize,
+            target_loudness,
+            normalize_mode: req.normalize_mode,
+            loudness_stats: None,
             fade_in: req.fade_in,
             fade_out: req.fade_out,
+            fade_curve: req.fade_curve.unwrap_or_default(),
+            source_duration: None,
+            highpass_hz: filters.and_then(|f| f.highpass_hz),
+            lowpass_hz: filters.and_then(|f| f.lowpass_hz),
+            denoise_db: filters.and_then(|f| f.denoise_db),
+            noise_gate: filters.and_then(|f| f.noise_gate),
+            eq_preset: filters.and_then(|f| f.eq_preset),
+            custom_eq: filters.and_then(|f| f.custom_eq.clone()),
+            compress: filters.and_then(|f| f.compress),
+            stereo_width: filters.and_then(|f| f.stereo_width),
+            speed: filters.and_then(|f| f.speed),
+            speed_mode: filters.and_then(|f| f.speed_mode).unwrap_or_default(),
+            volume: filters.and_then(|f| f.volume),
+            volume_envelope: filters.and_then(|f| f.volume_envelope.clone()),
+            max_duration: None,
+            start_time: req.start_time.filter(|&start| start > 0.0),
+            trim_duration: req.duration,
+            preroll_url: req.preroll_url.clone(),
+            extra_sources: req.source_urls.iter().skip(1).cloned().collect(),
+            background: req.background.clone(),
+            copy: req.copy == Some(true),
+            metadata: req.metadata_tags(),
+            preserve_metadata: req.preserve_metadata.unwrap_or(false),
+            normalize_stream_params: req.normalize_stream_params(),
+            detect_segments: req.detect_segments.unwrap_or(false),
+            broadcast_ready: req.broadcast_ready.unwrap_or(false),
+            limiter: filters.is_some_and(|f| f.limiter_enabled()),
+            input_format: req.source_codec_hint.clone(),
+            hls_dir: None,
+        }
+    }
+
+    /// Ограничивает длительность результата (`-t`)
+    pub fn with_max_duration(mut self, max_duration: Option<u32>) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    /// Итоговый лимит `-t`: меньшее из `max_duration` и запрошенной длительности
+    pub(crate) fn output_limit(&self) -> Option<f32> {
+        let max_duration = self.max_duration.map(|max| max as f32);
+        match (max_duration, self.trim_duration) {
+            (Some(max), Some(trim)) => Some(max.min(trim)),
+            (max, trim) => max.or(trim),
+        }
+    }
+
+    /// Длительность результата в секундах по длительности источника
+    ///
+    /// `None`, пока ffprobe не измерил источник или когда к нему
+    /// добавляются pre-roll и другие источники (их длина не измеряется).
+    pub fn output_duration(&self) -> Option<f64> {
+        if self.preroll_url.is_some() || !self.extra_sources.is_empty() {
+            return None;
+        }
+        let remaining =
+            (self.source_duration? - f64::from(self.start_time.unwrap_or(0.0))).max(0.0);
+        let output = remaining / f64::from(self.speed.unwrap_or(1.0));
+        Some(match self.output_limit() {
+            Some(limit) => output.min(f64::from(limit)),
+            None => output,
+        })
+    }
+
+    /// Точки `volume_envelope` не позже конца результата, если он известен
+    pub fn check_volume_envelope(&self) -> Result<(), String> {
+        let last_point = self.volume_envelope.as_ref().and_then(|points| points.last());
+        if let (Some(point), Some(end)) = (last_point, self.output_duration()) {
+            if f64::from(point.time) > end {
+                return Err(format!(
+                    "volume_envelope points must be within the output duration of {:.2} seconds",
+                    end
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Задаёт измерение первого прохода нормализации
+    pub fn with_loudness_stats(mut self, stats: LoudnessStats) -> Self {
+        self.loudness_stats = Some(stats);
+        self
+    }
+
+    /// Печатает ли FFmpeg громкость выхода (второй проход нормализации)
+    pub fn reports_output_loudness(&self) -> bool {
+        !self.broadcast_ready
+            && self.normalize
+            && self.normalize_mode.is_some()
+            && self.loudness_stats.is_some()
+    }
+
+    /// Задаёт длительность источника (результат ffprobe)
+    pub fn with_source_duration(mut self, duration: f64) -> Self {
+        self.source_duration = Some(duration);
+        self
+    }
+
+    /// Источник - тестовый сигнал lavfi вместо URL (`POST /api/v1/generate`)
+    ///
+    /// Длительность известна заранее: fade out не требует ffprobe, `-t`
+    /// ограничивает результат, даже если фильтр источника её не учтёт.
+    pub fn with_test_signal(mut self, signal: &TestSignal) -> Self {
+        use super::filters;
+
+        self.source_url = match signal.kind {
+            SignalKind::Sine => {
+                filters::sine_source(signal.frequency.unwrap_or(440), signal.duration)
+            }
+            SignalKind::Silence => filters::silence_source(signal.duration),
+        };
+        self.input_format = Some("lavfi".to_string());
+        self.source_duration = Some(f64::from(signal.duration));
+        self.trim_duration = Some(signal.duration);
+        self
+    }
+
+    /// Ограничивает количество каналов количеством каналов источника
+    ///
+    /// Предотвращает фиктивный upmix (моно → стерео и т.п.).
+    pub fn clamp_channels_to(mut self, source_channels: Option<u8>) -> Self {
+        if let Some(source_channels) = source_channels.filter(|&c| c > 0) {
+            self.channels = self.channels.min(source_channels);
+        }
+        self
+    }
+
+    /// Ограничивает sample rate частотой источника (без фиктивного upsample)
+    ///
+    /// Берётся ближайшая поддерживаемая кодеком частота не ниже частоты
+    /// источника: 22050 Hz для Opus становится 24000 Hz.
+    pub fn clamp_sample_rate_to(mut self, source_rate: Option<u32>) -> Self {
+        if let Some(source_rate) = source_rate.filter(|&rate| rate > 0) {
+            self.sample_rate = self.sample_rate.min(self.codec.snap_sample_rate(source_rate));
+        }
+        self
+    }
+
+    /// Сводит в моно, если битрейт не выше `threshold_kbps`
+    ///
+    /// На низких битрейтах стерео съедает биты, которых не хватает на качество.
+    /// Lossless профили (bitrate 0) не затрагиваются.
+    pub fn downmix_at_or_below(mut self, threshold_kbps: Option<u32>) -> Self {
+        if let Some(threshold_kbps) = threshold_kbps {
+            if self.bitrate > 0 && self.bitrate <= threshold_kbps {
+                self.channels = self.channels.min(1);
+            }
         }
+        self
     }
 
     /// Строит список аргументов для FFmpeg
     pub fn build_ffmpeg_args(&self) -> Vec<String> {
         let mut args = Vec::new();
 
+        // Анализаторы (silencedetect, второй проход loudnorm) пишут на уровне info
+        let reports = self.detect_segments || self.reports_output_loudness();
+        let loglevel = if reports { "info" } else { "warning" };
+
         // Глобальные опции
         args.extend([
             "-hide_banner".to_string(),
             "-loglevel".to_string(),
-            "warning".to_string(),
+            loglevel.to_string(),
             "-y".to_string(), // Overwrite output
         ]);
 
-        // Input
+        // Input: pre-roll (если есть) идёт первым входом
+        if let Some(ref preroll_url) = self.preroll_url {
+            args.extend(["-i".to_string(), preroll_url.clone()]);
+        }
+        if let Some(ref input_format) = self.input_format {
+            args.extend(["-f".to_string(), input_format.clone()]);
+        }
+        // Быстрый seek по индексу: относится только к источнику, не к pre-roll.
+        // Non-seekable вход (загруженный источник в `pipe:0`) перемотать
+        // нельзя - там `-ss` ставится после входов и отбрасывает декодированное.
+        let seek_mode = seek_mode_for(&self.source_url);
+        if let Some(start_time) = self.start_time.filter(|_| seek_mode == SeekMode::Fast) {
+            args.extend(["-ss".to_string(), start_time.to_string()]);
+        }
         args.extend(["-i".to_string(), self.source_url.clone()]);
+        for source in &self.extra_sources {
+            args.extend(["-i".to_string(), source.clone()]);
+        }
+        if let Some(ref background) = self.background {
+            args.extend(["-i".to_string(), background.url.clone()]);
+        }
+        if let Some(start_time) = self.start_time.filter(|_| seek_mode == SeekMode::Accurate) {
+            args.extend(["-ss".to_string(), start_time.to_string()]);
+        }
+
+        // Лимит длительности
+        if let Some(limit) = self.output_limit() {
+            args.extend(["-t".to_string(), limit.to_string()]);
+        }
+
+        if self.copy {
+            // Remux: поток копируется без декодирования, энкодер и фильтры не участвуют
+            args.extend(["-c:a".to_string(), "copy".to_string()]);
+        } else {
+            self.push_encoding_args(&mut args);
+        }
 
-        // Audio codec
-        args.extend(["-c:a".to_string(), self.codec.ffmpeg_codec().to_string()]);
+        // Теги: по умолчанию теги источника не переносятся в результат
+        if !self.preserve_metadata {
+            args.extend(["-map_metadata".to_string(), "-1".to_string()]);
+        }
+        for (key, value) in &self.metadata {
+            args.extend(["-metadata".to_string(), format!("{}={}", key, value)]);
+        }
+
+        match self.hls_dir {
+            // HLS: плейлист и сегменты пишутся в каталог сессии
+            Some(ref dir) => self.push_hls_args(&mut args, dir),
+            None => {
+                // Output format (WAV - с заголовком для потокового вывода)
+                args.extend(self.format.muxer_options().iter().map(|o| o.to_string()));
+                args.extend(["-f".to_string(), self.format.ffmpeg_format().to_string()]);
 
-        // Bitrate (если применимо)
-        if self.bitrate > 0 {
-            args.extend(["-b:a".to_string(), format!("{}k", self.bitrate)]);
+                // Output to stdout for streaming
+                args.push("pipe:1".to_string());
+            }
+        }
+
+        args
+    }
+
+    /// Muxer HLS: VOD плейлист и сегменты по `hls::SEGMENT_SECS` секунд
+    fn push_hls_args(&self, args: &mut Vec<String>, dir: &Path) {
+        use super::hls;
+
+        let segment_type = hls::SegmentType::for_codec(self.codec);
+        args.extend([
+            "-f".to_string(),
+            "hls".to_string(),
+            "-hls_time".to_string(),
+            hls::SEGMENT_SECS.to_string(),
+            "-hls_playlist_type".to_string(),
+            "vod".to_string(),
+        ]);
+        if segment_type == hls::SegmentType::Fmp4 {
+            args.extend([
+                "-hls_segment_type".to_string(),
+                "fmp4".to_string(),
+                "-hls_fmp4_init_filename".to_string(),
+                hls::INIT_SEGMENT.to_string(),
+            ]);
+        }
+        args.extend([
+            "-hls_segment_filename".to_string(),
+            dir.join(segment_type.filename_pattern())
+                .to_string_lossy()
+                .into_owned(),
+            dir.join(hls::PLAYLIST).to_string_lossy().into_owned(),
+        ]);
+    }
+
+    /// Кодек, параметры энкодера, формат потока и фильтры
+    fn push_encoding_args(&self, args: &mut Vec<String>) {
+        // Audio codec: для PCM формат сэмплов задаётся самим кодеком
+        match (self.codec, self.sample_fmt) {
+            (AudioCodec::PcmS16le, Some(sample_fmt)) => {
+                args.extend(["-c:a".to_string(), sample_fmt.pcm_codec().to_string()]);
+            }
+            (codec, sample_fmt) => {
+                args.extend(["-c:a".to_string(), codec.ffmpeg_codec().to_string()]);
+                if let Some(arg) = sample_fmt.and_then(|f| codec.sample_fmt_arg(f)) {
+                    args.extend(["-sample_fmt".to_string(), arg.to_string()]);
+                }
+            }
+        }
+
+        // Параметры энкодера: VBR MP3 задаётся качеством, а не битрейтом
+        match (self.codec, self.vbr_quality, self.compression_level) {
+            (AudioCodec::Libmp3lame, Some(quality), _) => {
+                args.extend(["-q:a".to_string(), quality.to_string()]);
+            }
+            (codec, _, level) => {
+                if let (AudioCodec::Flac, Some(level)) = (codec, level) {
+                    args.extend(["-compression_level".to_string(), level.to_string()]);
+                }
+                // Bitrate (если применимо)
+                if self.bitrate > 0 {
+                    args.extend(["-b:a".to_string(), format!("{}k", self.bitrate)]);
+                }
+            }
+        }
+
+        // Без явных значений libopus остаётся на своих умолчаниях (audio, vbr on)
+        if self.codec == AudioCodec::Libopus {
+            if let Some(application) = self.opus_application {
+                args.extend(["-application".to_string(), application.to_string()]);
+            }
+            if let Some(vbr) = self.opus_vbr {
+                args.extend(["-vbr".to_string(), vbr.to_string()]);
+            }
         }
 
         // Sample rate
@@ -83,17 +549,54 @@ impl TranscodeProfile {
 
         // Audio filters
         let filters = self.build_audio_filters();
-        if !filters.is_empty() {
+        if self.preroll_url.is_some() || !self.extra_sources.is_empty() || self.background.is_some()
+        {
+            // Склейка и смешивание требуют filter_complex, остальные фильтры
+            // идут после concat/amix
+            let output = if filters.is_empty() { "out" } else { "joined" };
+            let mut graph = if let Some(ref background) = self.background {
+                super::filters::mix_background(
+                    background.primary_gain_db,
+                    background.gain_db,
+                    background.duration,
+                    self.sample_rate,
+                    self.channels,
+                    output,
+                )
+            } else if self.extra_sources.is_empty() {
+                super::filters::concat_preroll(self.sample_rate, self.channels, output)
+            } else {
+                super::filters::concat_sources(
+                    self.extra_sources.len() + 1,
+                    self.sample_rate,
+                    self.channels,
+                    output,
+                )
+            };
+            if !filters.is_empty() {
+                graph.push_str(&format!(";[joined]{}[out]", filters));
+            }
+            args.extend([
+                "-filter_complex".to_string(),
+                graph,
+                "-map".to_string(),
+                "[out]".to_string(),
+            ]);
+        } else if !filters.is_empty() {
             args.extend(["-af".to_string(), filters]);
         }
+    }
 
-        // Output format
-        args.extend(["-f".to_string(), self.format.ffmpeg_format().to_string()]);
-
-        // Output to stdout for streaming
-        args.push("pipe:1".to_string());
+    /// Ключ для объединения одинаковых запросов
+    ///
+    /// Хэш итоговых аргументов FFmpeg: запросы, различающиеся только
+    /// несущественными полями, получают одинаковый ключ.
+    pub fn coalescing_key(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
 
-        args
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.build_ffmpeg_args().hash(&mut hasher);
+        hasher.finish()
     }
 
     /// Строит цепочку аудио фильтров
@@ -102,17 +605,126 @@ impl TranscodeProfile {
 
         let mut filter_parts = Vec::new();
 
-        // Fade in
+        // Сначала выравниваем поток, чтобы остальные фильтры получали стабильный формат
+        if self.normalize_stream_params {
+            filter_parts.push(filters::resample_async());
+        }
+
+        // Ограничение полосы до gate и EQ
+        if let Some(frequency) = self.highpass_hz {
+            filter_parts.push(filters::highpass(frequency));
+        }
+        if let Some(frequency) = self.lowpass_hz {
+            filter_parts.push(filters::lowpass(frequency));
+        }
+
+        // Шумоподавление до gate: gate срабатывает по уже очищенному сигналу
+        if let Some(strength) = self.denoise_db {
+            filter_parts.push(filters::denoise(strength));
+        }
+
+        // Noise gate до EQ, чтобы усиление полос не поднимало шум над порогом
+        if let Some(gate) = self.noise_gate {
+            filter_parts.push(filters::noise_gate(
+                gate.threshold_db,
+                gate.ratio,
+                gate.attack,
+                gate.release,
+            ));
+        }
+
+        if let Some(preset) = self.eq_preset {
+            filter_parts.push(filters::eq_preset_to_filter(preset));
+        }
+
+        if let Some(ref bands) = self.custom_eq {
+            filter_parts.push(filters::custom_eq(bands));
+        }
+
+        if let Some(settings) = self.compress {
+            filter_parts.push(filters::compressor_with_threshold(
+                settings.attack,
+                settings.decay,
+                settings.threshold_db,
+            ));
+        }
+
+        if let Some(width) = self.stereo_width {
+            filter_parts.push(filters::stereo_width(width));
+        }
+
+        // Fade in/out - до atempo, время в секундах источника
         if let Some(duration) = self.fade_in {
-            filter_parts.push(filters::fade_in(duration));
+            filter_parts.push(filters::fade_in_with_curve(duration, self.fade_curve));
+        }
+
+        // Fade out - от конца результата: источник после `-ss`, обрезанный `-t`.
+        // Время фильтров отсчитывается от точки seek, а не от начала файла
+        if let (Some(fade_out), Some(duration)) = (self.fade_out, self.source_duration) {
+            let remaining = (duration - f64::from(self.start_time.unwrap_or(0.0))).max(0.0);
+            let end = match self.output_limit() {
+                // `-t` считается по выходу, т.е. после изменения скорости
+                Some(limit) => {
+                    let speed = f64::from(self.speed.unwrap_or(1.0));
+                    remaining.min(f64::from(limit) * speed)
+                }
+                None => remaining,
+            };
+            let start = (end - f64::from(fade_out)).max(0.0);
+            filter_parts.push(filters::fade_out_with_curve(
+                start as f32,
+                fade_out,
+                self.fade_curve,
+            ));
+        }
+
+        if let Some(speed) = self.speed {
+            if (speed - 1.0).abs() > 0.001 {
+                filter_parts.push(filters::speed(speed, self.speed_mode, self.sample_rate));
+            }
+        }
+
+        // Нормализация loudness: вещательный режим заменяет ручные настройки
+        if self.broadcast_ready {
+            filter_parts.push(filters::loudnorm_with_peak(
+                BROADCAST_TARGET_LUFS,
+                BROADCAST_TRUE_PEAK_DB,
+            ));
+            filter_parts.push(filters::limiter(BROADCAST_TRUE_PEAK_DB));
+        } else if self.normalize {
+            match (self.normalize_mode, self.loudness_stats) {
+                (Some(mode), Some(stats)) => filter_parts.push(filters::loudnorm_two_pass(
+                    self.target_loudness,
+                    filters::DEFAULT_TRUE_PEAK_DB,
+                    &stats,
+                    mode,
+                )),
+                _ => filter_parts.push(filters::loudnorm(self.target_loudness)),
+            }
+        }
+
+        // Громкость - после нормализации; точки огибающей - по выходному времени
+        if let Some(volume) = self.volume {
+            let filter = filters::volume_factor(volume);
+            if !filter.is_empty() {
+                filter_parts.push(filter);
+            }
+        }
+        if let Some(ref points) = self.volume_envelope {
+            filter_parts.push(filters::volume_envelope(points));
         }
 
-        // Fade out (требует знания длительности, пока пропускаем)
-        // TODO: Реализовать fade out с duration detection
+        // Limiter - после всех усилений, до анализа тишины (он сигнал не меняет)
+        if self.limiter {
+            filter_parts.push(filters::limiter(filters::DEFAULT_TRUE_PEAK_DB));
+        }
 
-        // Нормализация loudness
-        if self.normalize {
-            filter_parts.push(filters::loudnorm(self.target_loudness));
+        // Анализ тишины - последним, по итоговому сигналу
+        if self.detect_segments {
+            filter_parts.push(filters::silencedetect(
+                SILENCE_THRESHOLD_DB,
+                SILENCE_MIN_DURATION_SECS,
+            ));
         }
 
         filter_parts.join(",")
@@ -121,6 +733,15 @@ impl TranscodeProfile {
 
 /// Предопределённые профили для типичных сценариев
 impl TranscodeProfile {
+    /// Профиль именованного пресета
+    pub fn from_preset(preset: ProfilePreset, source_url: &str) -> Self {
+        match preset {
+            ProfilePreset::TelegramVoice => Self::telegram_voice(source_url),
+            ProfilePreset::LowLatency => Self::low_latency(source_url),
+            ProfilePreset::HighQuality => Self::high_quality(source_url),
+        }
+    }
+
     /// Профиль для Telegram voice
     pub fn telegram_voice(source_url: &str) -> Self {
         Self {
@@ -129,11 +750,47 @@ impl TranscodeProfile {
             codec: AudioCodec::Libopus,
             bitrate: 64,
             sample_rate: 48000,
+            compression_level: None,
+            vbr_quality: None,
+            opus_application: None,
+            opus_vbr: None,
             channels: 2,
+            sample_fmt: None,
             normalize: true,
             target_loudness: -16.0,
+            normalize_mode: None,
+            loudness_stats: None,
             fade_in: None,
             fade_out: None,
+            fade_curve: FadeCurve::default(),
+            source_duration: None,
+            highpass_hz: None,
+            lowpass_hz: None,
+            denoise_db: None,
+            noise_gate: None,
+            eq_preset: None,
+            custom_eq: None,
+            compress: None,
+            stereo_width: None,
+            speed: None,
+            speed_mode: SpeedMode::default(),
+            volume: None,
+            volume_envelope: None,
+            max_duration: None,
+            start_time: None,
+            trim_duration: None,
+            preroll_url: None,
+            extra_sources: Vec::new(),
+            background: None,
+            copy: false,
+            metadata: BTreeMap::new(),
+            preserve_metadata: false,
+            normalize_stream_params: false,
+            detect_segments: false,
+            broadcast_ready: false,
+            limiter: false,
+            input_format: None,
+            hls_dir: None,
         }
     }
 
@@ -145,11 +802,47 @@ impl TranscodeProfile {
             codec: AudioCodec::Libopus,
             bitrate: 48,
             sample_rate: 48000,
+            compression_level: None,
+            vbr_quality: None,
+            opus_application: None,
+            opus_vbr: None,
             channels: 2,
+            sample_fmt: None,
             normalize: false,
             target_loudness: -16.0,
+            normalize_mode: None,
+            loudness_stats: None,
             fade_in: None,
             fade_out: None,
+            fade_curve: FadeCurve::default(),
+            source_duration: None,
+            highpass_hz: None,
+            lowpass_hz: None,
+            denoise_db: None,
+            noise_gate: None,
+            eq_preset: None,
+            custom_eq: None,
+            compress: None,
+            stereo_width: None,
+            speed: None,
+            speed_mode: SpeedMode::default(),
+            volume: None,
+            volume_envelope: None,
+            max_duration: None,
+            start_time: None,
+            trim_duration: None,
+            preroll_url: None,
+            extra_sources: Vec::new(),
+            background: None,
+            copy: false,
+            metadata: BTreeMap::new(),
+            preserve_metadata: false,
+            normalize_stream_params: false,
+            detect_segments: false,
+            broadcast_ready: false,
+            limiter: false,
+            input_format: None,
+            hls_dir: None,
         }
     }
 
@@ -161,11 +854,47 @@ impl TranscodeProfile {
             codec: AudioCodec::Libopus,
             bitrate: 128,
             sample_rate: 48000,
+            compression_level: None,
+            vbr_quality: None,
+            opus_application: None,
+            opus_vbr: None,
             channels: 2,
+            sample_fmt: None,
             normalize: true,
             target_loudness: -14.0,
+            normalize_mode: None,
+            loudness_stats: None,
             fade_in: None,
             fade_out: None,
+            fade_curve: FadeCurve::default(),
+            source_duration: None,
+            highpass_hz: None,
+            lowpass_hz: None,
+            denoise_db: None,
+            noise_gate: None,
+            eq_preset: None,
+            custom_eq: None,
+            compress: None,
+            stereo_width: None,
+            speed: None,
+            speed_mode: SpeedMode::default(),
+            volume: None,
+            volume_envelope: None,
+            max_duration: None,
+            start_time: None,
+            trim_duration: None,
+            preroll_url: None,
+            extra_sources: Vec::new(),
+            background: None,
+            copy: false,
+            metadata: BTreeMap::new(),
+            preserve_metadata: false,
+            normalize_stream_params: false,
+            detect_segments: false,
+            broadcast_ready: false,
+            limiter: false,
+            input_format: None,
+            hls_dir: None,
         }
     }
 }
@@ -173,6 +902,73 @@ impl TranscodeProfile {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::AudioQuality;
+
+    fn request(format: AudioFormat, codec: AudioCodec, quality: AudioQuality) -> TranscodeRequest {
+        serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "format": format,
+            "codec": codec,
+            "quality": quality,
+        }))
+        .unwrap()
+    }
+
+    fn preset_request(extra: serde_json::Value) -> TranscodeRequest {
+        let mut json = serde_json::json!({ "source_url": "https://example.com/audio.mp3" });
+        json.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_profile_name_selects_preset() {
+        for (name, bitrate, normalize, target_loudness) in [
+            ("telegram_voice", 64, true, -16.0),
+            ("low_latency", 48, false, -16.0),
+            ("high_quality", 128, true, -14.0),
+        ] {
+            let req = preset_request(serde_json::json!({ "profile": name }));
+            let profile = TranscodeProfile::from_request(&req);
+
+            assert_eq!(profile.format, AudioFormat::Opus, "{}", name);
+            assert_eq!(profile.codec, AudioCodec::Libopus, "{}", name);
+            assert_eq!(profile.bitrate, bitrate, "{}", name);
+            assert_eq!(profile.sample_rate, 48000, "{}", name);
+            assert_eq!(profile.normalize, normalize, "{}", name);
+            assert_eq!(profile.target_loudness, target_loudness, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_request_fields_override_preset() {
+        let req = preset_request(serde_json::json!({
+            "profile": "high_quality",
+            "format": "mp3",
+            "codec": "libmp3lame",
+            "bitrate": 96,
+            "channels": 1,
+            "target_loudness": -20.0,
+        }));
+        let profile = TranscodeProfile::from_request(&req);
+
+        assert_eq!(profile.format, AudioFormat::Mp3);
+        assert_eq!(profile.codec, AudioCodec::Libmp3lame);
+        assert_eq!(profile.bitrate, 96);
+        assert_eq!(profile.channels, 1);
+        assert_eq!(profile.target_loudness, -20.0);
+        // Не переопределённое - из пресета
+        assert!(profile.normalize);
+        assert_eq!(profile.sample_rate, 48000);
+
+        // Явный quality заменяет битрейт пресета
+        let req =
+            preset_request(serde_json::json!({ "profile": "telegram_voice", "quality": "low" }));
+        let profile = TranscodeProfile::from_request(&req);
+        assert_eq!(profile.bitrate, AudioQuality::Low.bitrate_for_codec(AudioCodec::Libopus));
+        assert_eq!(profile.sample_rate, 24000);
+    }
 
     #[test]
     fn test_telegram_voice_profile() {
@@ -193,11 +989,18 @@ mod tests {
             codec: AudioCodec::Libmp3lame,
             bitrate: 128,
             sample_rate: 44100,
+            compression_level: None,
+            vbr_quality: None,
+            opus_application: None,
+            opus_vbr: None,
             channels: 2,
             normalize: false,
             target_loudness: -16.0,
+            normalize_mode: None,
+            loudness_stats: None,
             fade_in: None,
             fade_out: None,
+            ..Default::default()
         };
 
         let args = profile.build_ffmpeg_args();
@@ -218,11 +1021,18 @@ mod tests {
             codec: AudioCodec::Libopus,
             bitrate: 64,
             sample_rate: 48000,
+            compression_level: None,
+            vbr_quality: None,
+            opus_application: None,
+            opus_vbr: None,
             channels: 2,
             normalize: true,
             target_loudness: -16.0,
+            normalize_mode: None,
+            loudness_stats: None,
             fade_in: Some(2.0),
             fade_out: None,
+            ..Default::default()
         };
 
         let args = profile.build_ffmpeg_args();
@@ -234,4 +1044,742 @@ mod tests {
         assert!(filters.contains("afade"));
         assert!(filters.contains("loudnorm"));
     }
+
+    #[test]
+    fn test_max_duration_adds_t_after_input() {
+        let profile = TranscodeProfile::telegram_voice("https://example.com/audio.mp3")
+            .with_max_duration(Some(600));
+        let args = profile.build_ffmpeg_args();
+
+        let i_idx = args.iter().position(|a| a == "-i").unwrap();
+        let t_idx = args.iter().position(|a| a == "-t").unwrap();
+        assert!(t_idx > i_idx, "-t must be an output option");
+        assert_eq!(args[t_idx + 1], "600");
+    }
+
+    #[test]
+    fn test_hls_output_replaces_stdout() {
+        let mut profile = TranscodeProfile::telegram_voice("https://example.com/a.mp3");
+        profile.hls_dir = Some(PathBuf::from("/tmp/hls/session"));
+        let args = profile.build_ffmpeg_args();
+
+        assert!(!args.contains(&"pipe:1".to_string()));
+        assert_eq!(args.last().unwrap(), "/tmp/hls/session/playlist.m3u8");
+        let joined = args.join(" ");
+        assert!(joined.contains("-f hls -hls_time 4 -hls_playlist_type vod"), "{}", joined);
+        // Opus - в fMP4 сегментах
+        assert!(joined.contains("-hls_segment_type fmp4 -hls_fmp4_init_filename init.mp4"));
+        assert!(joined.contains("-hls_segment_filename /tmp/hls/session/segment_%03d.m4s"));
+
+        profile.codec = AudioCodec::Aac;
+        let joined = profile.build_ffmpeg_args().join(" ");
+        assert!(!joined.contains("fmp4"));
+        assert!(joined.contains("segment_%03d.ts"));
+    }
+
+    #[test]
+    fn test_start_time_seeks_before_source_input() {
+        let mut profile = TranscodeProfile::telegram_voice("https://example.com/audio.mp3");
+        profile.start_time = Some(90.5);
+        let args = profile.build_ffmpeg_args();
+
+        let ss_idx = args.iter().position(|a| a == "-ss").unwrap();
+        let i_idx = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[ss_idx + 1], "90.5");
+        assert_eq!(ss_idx + 2, i_idx, "-ss must directly precede the source -i");
+    }
+
+    #[test]
+    fn test_start_time_applies_to_source_not_preroll() {
+        let mut profile = TranscodeProfile::telegram_voice("https://example.com/audio.mp3");
+        profile.preroll_url = Some("https://example.com/jingle.mp3".to_string());
+        profile.start_time = Some(30.0);
+        let args = profile.build_ffmpeg_args();
+
+        let ss_idx = args.iter().position(|a| a == "-ss").unwrap();
+        assert_eq!(args[ss_idx + 1], "30");
+        assert_eq!(args[ss_idx + 3], "https://example.com/audio.mp3");
+    }
+
+    #[test]
+    fn test_start_time_seeks_after_piped_source() {
+        let mut profile = TranscodeProfile::telegram_voice(crate::transcoder::fetch::PIPE_INPUT);
+        profile.start_time = Some(30.0);
+        let args = profile.build_ffmpeg_args();
+
+        let ss_idx = args.iter().position(|a| a == "-ss").unwrap();
+        let i_idx = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[i_idx + 1], "pipe:0");
+        assert!(ss_idx > i_idx, "stdin cannot be seeked, -ss must be an output option");
+        assert_eq!(args[ss_idx + 1], "30");
+    }
+
+    #[test]
+    fn test_trim_duration_sets_t() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "start_time": 10.0,
+            "duration": 12.5,
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let i_idx = args.iter().position(|a| a == "-i").unwrap();
+        let t_idx = args.iter().position(|a| a == "-t").unwrap();
+        assert!(t_idx > i_idx, "-t must be an output option");
+        assert_eq!(args[t_idx + 1], "12.5");
+        assert_eq!(args[args.iter().position(|a| a == "-ss").unwrap() + 1], "10");
+    }
+
+    #[test]
+    fn test_trim_duration_is_capped_by_max_duration() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3").with_max_duration(Some(60));
+        profile.trim_duration = Some(90.0);
+        let args = profile.build_ffmpeg_args();
+        assert_eq!(args[args.iter().position(|a| a == "-t").unwrap() + 1], "60");
+
+        profile.trim_duration = Some(45.0);
+        let args = profile.build_ffmpeg_args();
+        assert_eq!(args[args.iter().position(|a| a == "-t").unwrap() + 1], "45");
+    }
+
+    #[test]
+    fn test_zero_start_time_omits_ss() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "start_time": 0.0,
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        assert!(!args.contains(&"-ss".to_string()));
+    }
+
+    #[test]
+    fn test_flac_compression_level() {
+        let mut req = request(AudioFormat::Flac, AudioCodec::Flac, AudioQuality::Lossless);
+        req.compression_level = Some(8);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let idx = args.iter().position(|a| a == "-compression_level").unwrap();
+        assert_eq!(args[idx + 1], "8");
+    }
+
+    #[test]
+    fn test_mp3_vbr_quality_replaces_bitrate() {
+        let mut req = request(AudioFormat::Mp3, AudioCodec::Libmp3lame, AudioQuality::High);
+        req.vbr_quality = Some(2);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let idx = args.iter().position(|a| a == "-q:a").unwrap();
+        assert_eq!(args[idx + 1], "2");
+        assert!(!args.contains(&"-b:a".to_string()));
+    }
+
+    #[test]
+    fn test_opus_voip_constrained_args() {
+        let mut req = request(AudioFormat::Opus, AudioCodec::Libopus, AudioQuality::Low);
+        req.opus_application = Some(OpusApplication::Voip);
+        req.opus_vbr = Some(OpusVbr::Constrained);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let app_idx = args.iter().position(|a| a == "-application").unwrap();
+        assert_eq!(args[app_idx + 1], "voip");
+        let vbr_idx = args.iter().position(|a| a == "-vbr").unwrap();
+        assert_eq!(args[vbr_idx + 1], "constrained");
+    }
+
+    #[test]
+    fn test_opus_defaults_omit_application_and_vbr() {
+        let req = request(AudioFormat::Opus, AudioCodec::Libopus, AudioQuality::Medium);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        assert!(!args.contains(&"-application".to_string()));
+        assert!(!args.contains(&"-vbr".to_string()));
+    }
+
+    #[test]
+    fn test_no_max_duration_omits_t() {
+        let args = TranscodeProfile::telegram_voice("test.mp3").build_ffmpeg_args();
+        assert!(!args.contains(&"-t".to_string()));
+    }
+
+    #[test]
+    fn test_lossless_flac_has_no_bitrate() {
+        let req = request(AudioFormat::Flac, AudioCodec::Flac, AudioQuality::Lossless);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        assert!(args.contains(&"flac".to_string()));
+        assert!(!args.contains(&"-b:a".to_string()));
+    }
+
+    #[test]
+    fn test_lossless_opus_maps_to_max_bitrate() {
+        let req = request(AudioFormat::Opus, AudioCodec::Libopus, AudioQuality::Lossless);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let b_idx = args.iter().position(|a| a == "-b:a").unwrap();
+        assert_eq!(args[b_idx + 1], "256k");
+    }
+
+    #[test]
+    fn test_preroll_is_concatenated_before_source() {
+        let mut profile = TranscodeProfile::telegram_voice("https://example.com/main.mp3");
+        profile.preroll_url = Some("https://example.com/preroll.mp3".to_string());
+        let args = profile.build_ffmpeg_args();
+
+        // Pre-roll - первый вход, источник - второй
+        let inputs: Vec<_> = args
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| *a == "-i")
+            .map(|(i, _)| args[i + 1].as_str())
+            .collect();
+        assert_eq!(
+            inputs,
+            ["https://example.com/preroll.mp3", "https://example.com/main.mp3"]
+        );
+
+        let graph_idx = args.iter().position(|a| a == "-filter_complex").unwrap();
+        let graph = &args[graph_idx + 1];
+        assert!(graph.contains("[preroll][main]concat=n=2:v=0:a=1"));
+        // loudnorm из профиля применяется к склеенному потоку
+        assert!(graph.contains(";[joined]loudnorm"));
+        assert!(!args.contains(&"-af".to_string()));
+        assert!(args.contains(&"[out]".to_string()));
+    }
+
+    #[test]
+    fn test_source_urls_are_concatenated_in_order() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_urls": [
+                "https://example.com/intro.mp3",
+                "https://example.com/track.mp3",
+                "https://example.com/outro.mp3",
+            ],
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let inputs: Vec<_> = args
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| *a == "-i")
+            .map(|(i, _)| args[i + 1].as_str())
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                "https://example.com/intro.mp3",
+                "https://example.com/track.mp3",
+                "https://example.com/outro.mp3"
+            ]
+        );
+
+        let graph_idx = args.iter().position(|a| a == "-filter_complex").unwrap();
+        assert!(args[graph_idx + 1].ends_with("[a0][a1][a2]concat=n=3:v=0:a=1[out]"));
+        assert!(!args.contains(&"-af".to_string()));
+        assert!(args.contains(&"[out]".to_string()));
+    }
+
+    #[test]
+    fn test_background_is_mixed_under_source() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/voice.mp3",
+            "background": {
+                "url": "https://example.com/bed.mp3",
+                "gain_db": -20.0,
+                "primary_gain_db": 2.0,
+                "duration": "longest"
+            },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let bed_idx = args.iter().position(|a| a == "https://example.com/bed.mp3").unwrap();
+        assert_eq!(args[bed_idx - 1], "-i");
+        assert!(args.iter().position(|a| a == "https://example.com/voice.mp3").unwrap() < bed_idx);
+
+        let graph_idx = args.iter().position(|a| a == "-filter_complex").unwrap();
+        let graph = &args[graph_idx + 1];
+        assert!(graph.contains("volume=2.0dB[primary]"), "{}", graph);
+        assert!(graph.contains("volume=-20.0dB[bed]"), "{}", graph);
+        assert!(graph.contains("amix=inputs=2:duration=longest"), "{}", graph);
+        assert!(!args.contains(&"-af".to_string()));
+        assert!(args.contains(&"[out]".to_string()));
+    }
+
+    #[test]
+    fn test_copy_remuxes_without_encoding() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/voice.ogg",
+            "format": "opus",
+            "copy": true,
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        assert!(args.windows(2).any(|pair| pair == ["-c:a", "copy"]));
+        assert!(args.windows(2).any(|pair| pair == ["-f", "ogg"]));
+        for option in ["-af", "-filter_complex", "-b:a", "-ar", "-ac", "-application"] {
+            assert!(!args.contains(&option.to_string()), "{} in {:?}", option, args);
+        }
+    }
+
+    #[test]
+    fn test_metadata_args() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "format": "mp3",
+            "codec": "libmp3lame",
+            "metadata": { "title": "Episode 12", "artist": "Radio -y" },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        assert!(args.windows(2).any(|pair| pair == ["-map_metadata", "-1"]));
+        assert!(args.windows(2).any(|pair| pair == ["-metadata", "title=Episode 12"]));
+        // Значение остаётся частью одного аргумента и не становится опцией
+        assert!(args.windows(2).any(|pair| pair == ["-metadata", "artist=Radio -y"]));
+        assert_eq!(args.iter().filter(|a| *a == "-y").count(), 1);
+
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "preserve_metadata": true,
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        assert!(!args.contains(&"-map_metadata".to_string()));
+        assert!(!args.contains(&"-metadata".to_string()));
+    }
+
+    #[test]
+    fn test_live_source_adds_async_resample() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://cdn.example.com/live/index.m3u8",
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let af_idx = args.iter().position(|a| a == "-af").unwrap();
+        assert!(args[af_idx + 1].starts_with("aresample=async=1"));
+
+        // Файловый источник - без ресемплинга
+        let args = TranscodeProfile::from_request(&request(
+            AudioFormat::Opus,
+            AudioCodec::Libopus,
+            AudioQuality::Medium,
+        ))
+        .build_ffmpeg_args();
+        assert!(!args.iter().any(|a| a.contains("aresample")));
+    }
+
+    #[test]
+    fn test_coalescing_key_depends_on_args() {
+        let a = TranscodeProfile::telegram_voice("https://example.com/a.mp3");
+        let b = TranscodeProfile::telegram_voice("https://example.com/a.mp3");
+        let c = TranscodeProfile::telegram_voice("https://example.com/b.mp3");
+
+        assert_eq!(a.coalescing_key(), b.coalescing_key());
+        assert_ne!(a.coalescing_key(), c.coalescing_key());
+        assert_ne!(
+            a.coalescing_key(),
+            a.clone().with_max_duration(Some(60)).coalescing_key()
+        );
+    }
+
+    #[test]
+    fn test_detect_segments_adds_silencedetect() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.detect_segments = true;
+        let args = profile.build_ffmpeg_args();
+
+        let af_idx = args.iter().position(|a| a == "-af").unwrap();
+        assert!(args[af_idx + 1].ends_with("silencedetect=noise=-30.0dB:d=0.50"));
+        assert!(args.contains(&"info".to_string()));
+    }
+
+    #[test]
+    fn test_wav_streaming_header_args() {
+        let req = request(AudioFormat::Wav, AudioCodec::PcmS16le, AudioQuality::High);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let f_idx = args.iter().position(|a| a == "-f").unwrap();
+        assert_eq!(args[f_idx + 1], "wav");
+        let rf64_idx = args.iter().position(|a| a == "-rf64").unwrap();
+        assert_eq!(args[rf64_idx + 1], "never");
+        assert!(rf64_idx < f_idx);
+        assert!(args.windows(2).any(|pair| pair == ["-fflags", "+bitexact"]));
+
+        // Raw PCM без заголовка и без опций muxer'а
+        let req = request(AudioFormat::Pcm, AudioCodec::PcmS16le, AudioQuality::High);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        assert!(!args.contains(&"-rf64".to_string()));
+        assert!(!args.contains(&"-fflags".to_string()));
+    }
+
+    #[test]
+    fn test_mka_muxing_args() {
+        let req = request(AudioFormat::Mka, AudioCodec::Libopus, AudioQuality::High);
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let f_idx = args.iter().position(|a| a == "-f").unwrap();
+        assert_eq!(args[f_idx + 1], "matroska");
+        let c_idx = args.iter().position(|a| a == "-c:a").unwrap();
+        assert_eq!(args[c_idx + 1], "libopus");
+        assert_eq!(args.last().unwrap(), "pipe:1");
+    }
+
+    #[test]
+    fn test_stereo_request_against_mono_source_is_clamped() {
+        let profile = TranscodeProfile::telegram_voice("test.mp3").clamp_channels_to(Some(1));
+        let args = profile.build_ffmpeg_args();
+
+        let ac_idx = args.iter().position(|a| a == "-ac").unwrap();
+        assert_eq!(args[ac_idx + 1], "1");
+    }
+
+    #[test]
+    fn test_clamp_never_increases_channels() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.channels = 1;
+
+        assert_eq!(profile.clone().clamp_channels_to(Some(6)).channels, 1);
+        assert_eq!(profile.clamp_channels_to(None).channels, 1);
+    }
+
+    #[test]
+    fn test_low_bitrate_is_downmixed_to_mono() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.bitrate = 24;
+
+        assert_eq!(profile.clone().downmix_at_or_below(Some(32)).channels, 1);
+        assert_eq!(profile.clone().downmix_at_or_below(Some(24)).channels, 1);
+        assert_eq!(profile.clone().downmix_at_or_below(Some(16)).channels, 2);
+        assert_eq!(profile.downmix_at_or_below(None).channels, 2);
+    }
+
+    #[test]
+    fn test_lossless_is_never_downmixed() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.bitrate = 0;
+
+        assert_eq!(profile.downmix_at_or_below(Some(32)).channels, 2);
+    }
+
+    #[test]
+    fn test_broadcast_ready_composes_loudnorm_and_limiter() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.broadcast_ready = true;
+        profile.target_loudness = -23.0;
+        let args = profile.build_ffmpeg_args();
+
+        let af_idx = args.iter().position(|a| a == "-af").unwrap();
+        assert!(args[af_idx + 1]
+            .contains("loudnorm=I=-16.0:TP=-1.0:LRA=11:print_format=none,alimiter=limit=0.891"));
+        // Ручная цель громкости игнорируется
+        assert!(!args[af_idx + 1].contains("I=-23.0"));
+    }
+
+    #[test]
+    fn test_source_codec_hint_sets_input_format() {
+        let mut profile = TranscodeProfile::telegram_voice("https://example.com/audio.mp3");
+        profile.input_format = Some("mp3".to_string());
+        let args = profile.build_ffmpeg_args();
+
+        let i_idx = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[i_idx - 2], "-f");
+        assert_eq!(args[i_idx - 1], "mp3");
+
+        // Без подсказки единственный -f - выходной формат
+        let args = TranscodeProfile::telegram_voice("test.mp3").build_ffmpeg_args();
+        let i_idx = args.iter().position(|a| a == "-i").unwrap();
+        assert!(!args[..i_idx].contains(&"-f".to_string()));
+    }
+
+    #[test]
+    fn test_sample_fmt_args() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.format = AudioFormat::Mp3;
+        profile.codec = AudioCodec::Libmp3lame;
+        profile.sample_fmt = Some(SampleFormat::Flt);
+        let args = profile.build_ffmpeg_args();
+        let idx = args.iter().position(|a| a == "-sample_fmt").unwrap();
+        assert_eq!(args[idx + 1], "fltp");
+
+        // PCM: формат сэмплов выбирает кодек, без -sample_fmt
+        profile.format = AudioFormat::Wav;
+        profile.codec = AudioCodec::PcmS16le;
+        let args = profile.build_ffmpeg_args();
+        let idx = args.iter().position(|a| a == "-c:a").unwrap();
+        assert_eq!(args[idx + 1], "pcm_f32le");
+        assert!(!args.contains(&"-sample_fmt".to_string()));
+    }
+
+    #[test]
+    fn test_fade_out_ends_at_source_duration() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3").with_source_duration(183.04);
+        profile.fade_out = Some(3.0);
+
+        let args = profile.build_ffmpeg_args();
+        let af_idx = args.iter().position(|a| a == "-af").unwrap();
+        assert!(args[af_idx + 1].contains("afade=t=out:st=180.04:d=3.00"));
+    }
+
+    #[test]
+    fn test_fade_out_respects_max_duration() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3")
+            .with_source_duration(183.04)
+            .with_max_duration(Some(60));
+        profile.fade_out = Some(5.0);
+
+        let args = profile.build_ffmpeg_args();
+        let af_idx = args.iter().position(|a| a == "-af").unwrap();
+        assert!(args[af_idx + 1].contains("afade=t=out:st=55.00:d=5.00"));
+    }
+
+    #[test]
+    fn test_fade_out_accounts_for_start_time() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3").with_source_duration(183.04);
+        profile.fade_out = Some(3.0);
+        profile.start_time = Some(100.0);
+
+        // После seek остаётся 83.04 с источника
+        let args = profile.build_ffmpeg_args();
+        assert!(af_value(&args).contains("afade=t=out:st=80.04:d=3.00"));
+    }
+
+    #[test]
+    fn test_fade_out_respects_trim_duration() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3").with_source_duration(183.04);
+        profile.fade_out = Some(2.0);
+        profile.start_time = Some(60.0);
+        profile.trim_duration = Some(30.0);
+
+        let args = profile.build_ffmpeg_args();
+        assert!(af_value(&args).contains("afade=t=out:st=28.00:d=2.00"));
+    }
+
+    #[test]
+    fn test_volume_envelope_checked_against_output_duration() {
+        let point = |time| EnvelopePoint { time, gain_db: -6.0 };
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.volume_envelope = Some(vec![point(0.0), point(90.0)]);
+
+        // Длительность источника неизвестна - проверять не с чем
+        assert!(profile.check_volume_envelope().is_ok());
+
+        profile = profile.with_source_duration(120.0);
+        assert!(profile.check_volume_envelope().is_ok());
+
+        profile.start_time = Some(60.0);
+        let err = profile.check_volume_envelope().unwrap_err();
+        assert!(err.contains("60.00 seconds"), "{}", err);
+
+        // Ускорение сокращает результат
+        profile.start_time = None;
+        profile.speed = Some(2.0);
+        assert!(profile.check_volume_envelope().is_err());
+    }
+
+    #[test]
+    fn test_fade_out_without_duration_is_skipped() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3");
+        profile.fade_out = Some(3.0);
+
+        assert!(!profile.build_ffmpeg_args().iter().any(|a| a.contains("t=out")));
+    }
+
+    fn af_value(args: &[String]) -> &str {
+        let af_idx = args.iter().position(|a| a == "-af").unwrap();
+        &args[af_idx + 1]
+    }
+
+    #[test]
+    fn test_audio_filters_are_applied() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "audio_filters": { "eq_preset": "bass_boost", "speed": 1.5, "volume": 0.8 },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let af = af_value(&args);
+        assert!(af.contains("equalizer"), "{}", af);
+        assert!(af.contains("atempo=1.5"), "{}", af);
+        assert!(af.contains("volume="), "{}", af);
+    }
+
+    #[test]
+    fn test_audio_filters_order() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "normalize": true,
+            "fade_in": 1.0,
+            "audio_filters": { "eq_preset": "voice", "speed": 1.25, "volume": 1.2 },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        let af = af_value(&args);
+
+        let position = |needle: &str| {
+            af.find(needle)
+                .unwrap_or_else(|| panic!("{} in {}", needle, af))
+        };
+        assert!(position("equalizer") < position("afade=t=in"));
+        assert!(position("afade=t=in") < position("atempo"));
+        assert!(position("atempo") < position("loudnorm"));
+        assert!(position("loudnorm") < position("volume="));
+    }
+
+    #[test]
+    fn test_limiter_follows_volume() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "audio_filters": { "volume": 1.8 },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        let af = af_value(&args);
+
+        assert!(af.ends_with(",alimiter=limit=0.841"), "{}", af);
+        assert!(af.find("volume=").unwrap() < af.find("alimiter").unwrap());
+    }
+
+    #[test]
+    fn test_denoise_is_applied_before_tempo_and_volume() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "audio_filters": { "denoise_db": 15, "speed": 1.5, "volume": 1.5 },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        let af = af_value(&args);
+
+        let denoise = af.find("afftdn=nr=15.0").unwrap_or_else(|| panic!("{}", af));
+        assert!(denoise < af.find("atempo").unwrap(), "{}", af);
+        assert!(denoise < af.find("volume=").unwrap(), "{}", af);
+    }
+
+    #[test]
+    fn test_cutoff_filters_are_applied() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "audio_filters": { "highpass_hz": 80, "lowpass_hz": 12000, "eq_preset": "voice" },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        let af = af_value(&args);
+
+        assert!(af.contains("highpass=f=80"), "{}", af);
+        assert!(af.contains("lowpass=f=12000"), "{}", af);
+        assert!(af.find("lowpass").unwrap() < af.find("equalizer").unwrap());
+    }
+
+    #[test]
+    fn test_compress_adds_compand() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "audio_filters": { "eq_preset": "voice", "compress": { "threshold_db": -20.0 } },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        let af = af_value(&args);
+
+        assert!(af.contains("compand="), "{}", af);
+        assert!(af.find("equalizer").unwrap() < af.find("compand").unwrap());
+    }
+
+    #[test]
+    fn test_shift_pitch_uses_output_sample_rate() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "audio_filters": { "speed": 2.0, "speed_mode": "shift_pitch" },
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+        let af = af_value(&args);
+
+        assert!(af.contains("asetrate=96000,aresample=48000"), "{}", af);
+        assert!(!af.contains("atempo"), "{}", af);
+    }
+
+    #[test]
+    fn test_two_pass_loudnorm_uses_measured_values() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "normalize": true,
+            "normalize_mode": "linear",
+        }))
+        .unwrap();
+        let profile = TranscodeProfile::from_request(&req);
+
+        // Без измерения - обычный однопроходный loudnorm
+        let args = profile.build_ffmpeg_args();
+        assert!(!af_value(&args).contains("measured_I"));
+
+        let args = profile
+            .with_loudness_stats(LoudnessStats {
+                input_i: -23.96,
+                input_tp: -5.04,
+                input_lra: 7.6,
+                input_thresh: -34.3,
+                target_offset: 0.06,
+            })
+            .build_ffmpeg_args();
+        let af = af_value(&args);
+        assert!(af.contains("measured_I=-23.96"), "{}", af);
+        assert!(af.contains("linear=true"), "{}", af);
+    }
+
+    #[test]
+    fn test_fade_out_with_speed_and_max_duration() {
+        let mut profile = TranscodeProfile::telegram_voice("test.mp3")
+            .with_source_duration(183.04)
+            .with_max_duration(Some(60));
+        profile.fade_out = Some(5.0);
+        profile.speed = Some(2.0);
+
+        // 60 с выхода на скорости 2x - 120 с источника
+        let args = profile.build_ffmpeg_args();
+        assert!(af_value(&args).contains("afade=t=out:st=115.00:d=5.00"));
+    }
+
+    #[test]
+    fn test_sample_rate_clamped_to_source() {
+        let profile = TranscodeProfile::telegram_voice("test.mp3");
+        assert_eq!(profile.sample_rate, 48000);
+
+        assert_eq!(profile.clone().clamp_sample_rate_to(Some(24000)).sample_rate, 24000);
+        // Opus не поддерживает 22050 Hz - ближайшая частота выше
+        assert_eq!(profile.clone().clamp_sample_rate_to(Some(22050)).sample_rate, 24000);
+        // Downsample и неизвестная частота не меняются
+        assert_eq!(profile.clone().clamp_sample_rate_to(Some(96000)).sample_rate, 48000);
+        assert_eq!(profile.clone().clamp_sample_rate_to(None).sample_rate, 48000);
+        assert_eq!(profile.clamp_sample_rate_to(Some(0)).sample_rate, 48000);
+    }
+
+    #[test]
+    fn test_sample_rate_is_snapped_to_codec() {
+        let mut req = request(AudioFormat::Opus, AudioCodec::Libopus, AudioQuality::Medium);
+        assert_eq!(TranscodeProfile::from_request(&req).sample_rate, 48000);
+
+        req.sample_rate = Some(16000);
+        assert_eq!(TranscodeProfile::from_request(&req).sample_rate, 16000);
+
+        let req = request(AudioFormat::Mp3, AudioCodec::Libmp3lame, AudioQuality::Medium);
+        assert_eq!(TranscodeProfile::from_request(&req).sample_rate, 44100);
+    }
+
+    #[test]
+    fn test_fade_curve_from_request() {
+        let req: TranscodeRequest = serde_json::from_value(serde_json::json!({
+            "source_url": "https://example.com/audio.mp3",
+            "fade_in": 2.0,
+            "fade_curve": "qsin",
+        }))
+        .unwrap();
+        let args = TranscodeProfile::from_request(&req).build_ffmpeg_args();
+
+        let af_idx = args.iter().position(|a| a == "-af").unwrap();
+        assert!(args[af_idx + 1].contains("afade=t=in:st=0:d=2.00:curve=qsin"));
+    }
 }
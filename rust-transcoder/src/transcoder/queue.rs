@@ -0,0 +1,179 @@
+//! Очередь задач при исчерпанном лимите потоков
+//!
+//! Задача, которая не получила permit за `ACQUIRE_WAIT_MS`, может встать в
+//! очередь глубиной `QUEUE_DEPTH` вместо 503. Задачи получают permit'ы строго
+//! в порядке постановки: семафор запрашивает только первая в очереди.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Notify, Semaphore};
+use uuid::Uuid;
+
+use super::permit::TranscodePermit;
+
+/// Ограниченная FIFO очередь сессий (clone - та же очередь)
+#[derive(Debug, Clone, Default)]
+pub struct JobQueue {
+    inner: Arc<QueueInner>,
+}
+
+#[derive(Debug, Default)]
+struct QueueInner {
+    /// Максимум задач в очереди (0 - очередь выключена)
+    depth: usize,
+    waiting: Mutex<VecDeque<Uuid>>,
+    /// Будит задачи при каждом выходе из очереди
+    changed: Notify,
+}
+
+impl JobQueue {
+    /// Создаёт очередь на `depth` задач
+    pub fn new(depth: usize) -> Self {
+        Self {
+            inner: Arc::new(QueueInner {
+                depth,
+                ..QueueInner::default()
+            }),
+        }
+    }
+
+    /// Принимает ли очередь задачи
+    pub fn is_enabled(&self) -> bool {
+        self.inner.depth > 0
+    }
+
+    /// Сколько задач ждёт
+    pub fn len(&self) -> usize {
+        self.waiting().len()
+    }
+
+    /// Пуста ли очередь
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Сессии в очереди в порядке постановки
+    pub fn sessions(&self) -> Vec<Uuid> {
+        self.waiting().iter().copied().collect()
+    }
+
+    /// Позиция сессии в очереди, начиная с 1 (None - не в очереди)
+    pub fn position(&self, session_id: Uuid) -> Option<usize> {
+        self.waiting()
+            .iter()
+            .position(|id| *id == session_id)
+            .map(|index| index + 1)
+    }
+
+    /// Ставит сессию в конец очереди; None - очередь заполнена или выключена
+    pub fn enqueue(&self, session_id: Uuid) -> Option<QueueTicket> {
+        let mut waiting = self.waiting();
+        if waiting.len() >= self.inner.depth {
+            return None;
+        }
+        waiting.push_back(session_id);
+
+        Some(QueueTicket {
+            queue: self.clone(),
+            session_id,
+        })
+    }
+
+    fn waiting(&self) -> std::sync::MutexGuard<'_, VecDeque<Uuid>> {
+        self.inner.waiting.lock().expect("job queue poisoned")
+    }
+}
+
+/// Место в очереди; при drop сессия покидает очередь
+#[derive(Debug)]
+pub struct QueueTicket {
+    queue: JobQueue,
+    session_id: Uuid,
+}
+
+impl QueueTicket {
+    /// Ждёт, пока задача станет первой, затем `weight` permit'ов семафора
+    ///
+    /// Permit выдаётся уже вне очереди: следующая задача сразу становится первой.
+    pub async fn acquire(self, semaphore: &Arc<Semaphore>, weight: u32) -> TranscodePermit {
+        loop {
+            // Future создаётся до проверки, чтобы не пропустить notify_waiters
+            let changed = self.queue.inner.changed.notified();
+            if self.queue.position(self.session_id) == Some(1) {
+                break;
+            }
+            changed.await;
+        }
+
+        TranscodePermit::acquire(semaphore, weight).await
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        self.queue.waiting().retain(|id| *id != self.session_id);
+        self.queue.inner.changed.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_queue_full_rejects() {
+        let queue = JobQueue::new(2);
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let first_ticket = queue.enqueue(first).unwrap();
+        let _second_ticket = queue.enqueue(second).unwrap();
+        assert!(queue.enqueue(third).is_none());
+        assert_eq!(queue.position(second), Some(2));
+
+        // Освободившееся место снова доступно, позиции сдвигаются
+        drop(first_ticket);
+        assert_eq!(queue.position(first), None);
+        assert_eq!(queue.position(second), Some(1));
+        assert!(queue.enqueue(third).is_some());
+
+        assert!(!JobQueue::new(0).is_enabled());
+        assert!(JobQueue::new(0).enqueue(first).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queued_jobs_acquire_in_order() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let busy = TranscodePermit::try_acquire(&semaphore).unwrap();
+        let queue = JobQueue::new(3);
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let tickets: Vec<QueueTicket> = ids.iter().map(|id| queue.enqueue(*id).unwrap()).collect();
+        // Задачи запускаются в обратном порядке - порядок задаёт очередь
+        for (id, ticket) in ids.iter().copied().zip(tickets).rev() {
+            let semaphore = Arc::clone(&semaphore);
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let permit = ticket.acquire(&semaphore, 1).await;
+                order_tx.send(id).unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                drop(permit);
+            });
+        }
+        drop(order_tx);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queue.len(), 3, "nothing runs while the slot is busy");
+        drop(busy);
+
+        let mut order = Vec::new();
+        while let Some(id) = order_rx.recv().await {
+            order.push(id);
+        }
+        assert_eq!(order, ids);
+        assert!(queue.is_empty());
+    }
+}
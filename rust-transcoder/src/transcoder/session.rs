@@ -0,0 +1,462 @@
+//! Реестр сессий транскодирования
+//!
+//! Хранит состояние каждой сессии для `GET /api/v1/transcode/:id`.
+//! Завершённые сессии живут ещё `SESSION_RETENTION`, чтобы клиент успел
+//! запросить итоговый статус.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    LoudnessMeasurement, LoudnessStats, SilenceInterval, TranscodeStatus, TranscodeStatusResponse,
+};
+
+use super::analysis::FfmpegProgress;
+
+/// Сколько хранить завершённую сессию
+pub const SESSION_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// Состояние одной сессии
+#[derive(Debug, Clone)]
+pub struct SessionState {
+    /// Текущий статус
+    pub status: TranscodeStatus,
+    /// Момент регистрации
+    pub started_at: Instant,
+    /// Момент перехода в конечный статус
+    pub finished_at: Option<Instant>,
+    /// Отданные клиенту байты
+    pub bytes_transferred: u64,
+    /// Причина ошибки для `Failed`
+    pub error: Option<String>,
+    /// Интервалы тишины (`detect_segments`), известны после завершения
+    pub segments: Option<Vec<SilenceInterval>>,
+    /// Громкость источника (`measure_loudness`), известна до начала потока
+    pub loudness: Option<LoudnessMeasurement>,
+    /// Измерение первого прохода нормализации (`normalize_mode`)
+    pub loudness_stats: Option<LoudnessStats>,
+    /// Громкость выхода второго прохода, известна после завершения
+    pub output_loudness: Option<LoudnessMeasurement>,
+    /// Прогресс кодирования из `-progress` FFmpeg
+    pub progress: FfmpegProgress,
+    /// Сохранять вывод при отмене (`allow_partial_on_cancel`)
+    pub allow_partial: bool,
+    /// Вывод отменённой сессии сохранён частично
+    pub partial: bool,
+    /// Сигнал отмены для задачи, владеющей процессом FFmpeg
+    cancel: Arc<Notify>,
+}
+
+impl SessionState {
+    fn new() -> Self {
+        Self {
+            status: TranscodeStatus::Queued,
+            started_at: Instant::now(),
+            finished_at: None,
+            bytes_transferred: 0,
+            error: None,
+            segments: None,
+            loudness: None,
+            loudness_stats: None,
+            output_loudness: None,
+            progress: FfmpegProgress::default(),
+            allow_partial: false,
+            partial: false,
+            cancel: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Находится ли сессия в конечном статусе
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            TranscodeStatus::Completed | TranscodeStatus::Failed | TranscodeStatus::Cancelled
+        )
+    }
+
+    /// Формирует ответ для status API
+    pub fn to_response(&self, session_id: Uuid) -> TranscodeStatusResponse {
+        let end = self.finished_at.unwrap_or_else(Instant::now);
+
+        TranscodeStatusResponse {
+            session_id,
+            status: self.status,
+            duration_seconds: end.duration_since(self.started_at).as_secs_f64(),
+            bytes_transferred: self.bytes_transferred,
+            error: self.error.clone(),
+            segments: self.segments.clone(),
+            loudness: self.loudness,
+            loudness_stats: self.loudness_stats,
+            output_loudness: self.output_loudness,
+            output_time_seconds: self.progress.out_time_seconds,
+            output_size_bytes: self.progress.total_size,
+            queue_position: None,
+            partial: self.partial.then_some(true),
+        }
+    }
+}
+
+/// Разделяемый реестр сессий (clone - тот же реестр)
+#[derive(Debug, Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<RwLock<HashMap<Uuid, SessionState>>>,
+}
+
+impl SessionRegistry {
+    /// Создаёт пустой реестр
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Регистрирует сессию в статусе `Queued`, попутно удаляя устаревшие
+    pub fn register(&self, session_id: Uuid) {
+        self.prune(SESSION_RETENTION);
+        self.sessions
+            .write()
+            .expect("session registry poisoned")
+            .insert(session_id, SessionState::new());
+    }
+
+    /// Удаляет сессии, завершённые раньше чем `retention` назад
+    pub fn prune(&self, retention: Duration) {
+        self.sessions
+            .write()
+            .expect("session registry poisoned")
+            .retain(|_, session| {
+                session
+                    .finished_at
+                    .map_or(true, |finished| finished.elapsed() < retention)
+            });
+    }
+
+    /// Переводит сессию в новый статус
+    ///
+    /// Конечный статус не перезаписывается: отменённая сессия не станет `Completed`.
+    pub fn set_status(&self, session_id: Uuid, status: TranscodeStatus) {
+        self.update(session_id, |session| {
+            if session.is_finished() {
+                return;
+            }
+            session.status = status;
+            if session.is_finished() {
+                session.finished_at = Some(Instant::now());
+            }
+        });
+    }
+
+    /// Завершает сессию с ошибкой
+    pub fn fail(&self, session_id: Uuid, error: impl Into<String>) {
+        let error = error.into();
+        self.update(session_id, |session| {
+            if !session.is_finished() {
+                session.error = Some(error);
+            }
+        });
+        self.set_status(session_id, TranscodeStatus::Failed);
+    }
+
+    /// Учитывает отданные клиенту байты
+    pub fn add_bytes(&self, session_id: Uuid, bytes: u64) {
+        self.update(session_id, |session| session.bytes_transferred += bytes);
+    }
+
+    /// Сохраняет найденные интервалы тишины
+    pub fn set_segments(&self, session_id: Uuid, segments: Vec<SilenceInterval>) {
+        self.update(session_id, |session| session.segments = Some(segments));
+    }
+
+    /// Сохраняет измеренную громкость источника
+    pub fn set_loudness(&self, session_id: Uuid, loudness: LoudnessMeasurement) {
+        self.update(session_id, |session| session.loudness = Some(loudness));
+    }
+
+    /// Сохраняет измерение первого прохода нормализации
+    pub fn set_loudness_stats(&self, session_id: Uuid, stats: LoudnessStats) {
+        self.update(session_id, |session| session.loudness_stats = Some(stats));
+    }
+
+    /// Сохраняет громкость выхода второго прохода нормализации
+    pub fn set_output_loudness(&self, session_id: Uuid, loudness: LoudnessMeasurement) {
+        self.update(session_id, |session| {
+            session.output_loudness = Some(loudness)
+        });
+    }
+
+    /// Разрешает сохранить вывод сессии при отмене
+    pub fn set_allow_partial(&self, session_id: Uuid) {
+        self.update(session_id, |session| session.allow_partial = true);
+    }
+
+    /// Отмечает, что вывод отменённой сессии сохранён частично
+    pub fn set_partial(&self, session_id: Uuid) {
+        self.update(session_id, |session| session.partial = true);
+    }
+
+    /// Обновляет прогресс кодирования
+    pub fn set_progress(&self, session_id: Uuid, progress: FfmpegProgress) {
+        self.update(session_id, |session| session.progress = progress);
+    }
+
+    /// Отменяет выполняющуюся сессию
+    ///
+    /// Сессия сразу переходит в `Cancelled`, процесс FFmpeg убивает задача,
+    /// ожидающая `cancel_signal`. Сигнал запоминается, даже если процесс
+    /// ещё не запущен.
+    pub fn cancel(&self, session_id: Uuid) -> AppResult<()> {
+        let mut sessions = self.sessions.write().expect("session registry poisoned");
+        let session = sessions
+            .get_mut(&session_id)
+            .ok_or(AppError::SessionNotFound(session_id))?;
+
+        if session.is_finished() {
+            return Err(AppError::SessionFinished(session_id));
+        }
+
+        session.status = TranscodeStatus::Cancelled;
+        session.finished_at = Some(Instant::now());
+        session.cancel.notify_one();
+        Ok(())
+    }
+
+    /// Отменяет все незавершённые сессии (shutdown); возвращает их количество
+    pub fn cancel_active(&self) -> usize {
+        let mut sessions = self.sessions.write().expect("session registry poisoned");
+        let mut cancelled = 0;
+
+        for session in sessions.values_mut().filter(|session| !session.is_finished()) {
+            session.status = TranscodeStatus::Cancelled;
+            session.finished_at = Some(Instant::now());
+            session.cancel.notify_one();
+            cancelled += 1;
+        }
+
+        cancelled
+    }
+
+    /// Сигнал отмены сессии (см. `cancel`)
+    pub fn cancel_signal(&self, session_id: Uuid) -> Option<Arc<Notify>> {
+        self.sessions
+            .read()
+            .expect("session registry poisoned")
+            .get(&session_id)
+            .map(|session| Arc::clone(&session.cancel))
+    }
+
+    /// Снимок состояния сессии
+    pub fn get(&self, session_id: Uuid) -> Option<SessionState> {
+        self.sessions
+            .read()
+            .expect("session registry poisoned")
+            .get(&session_id)
+            .cloned()
+    }
+
+    /// Количество незавершённых сессий
+    pub fn active_count(&self) -> usize {
+        self.sessions
+            .read()
+            .expect("session registry poisoned")
+            .values()
+            .filter(|session| !session.is_finished())
+            .count()
+    }
+
+    /// Количество сессий с запущенным FFmpeg (`Processing`, `Streaming`)
+    pub fn running_count(&self) -> usize {
+        self.sessions
+            .read()
+            .expect("session registry poisoned")
+            .values()
+            .filter(|session| {
+                matches!(
+                    session.status,
+                    TranscodeStatus::Processing | TranscodeStatus::Streaming
+                )
+            })
+            .count()
+    }
+
+    /// Количество сессий в реестре
+    pub fn len(&self) -> usize {
+        self.sessions
+            .read()
+            .expect("session registry poisoned")
+            .len()
+    }
+
+    /// Пуст ли реестр
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn update(&self, session_id: Uuid, apply: impl FnOnce(&mut SessionState)) {
+        if let Some(session) = self
+            .sessions
+            .write()
+            .expect("session registry poisoned")
+            .get_mut(&session_id)
+        {
+            apply(session);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_transitions() {
+        let registry = SessionRegistry::new();
+        let id = Uuid::new_v4();
+
+        registry.register(id);
+        assert_eq!(registry.get(id).unwrap().status, TranscodeStatus::Queued);
+
+        registry.set_status(id, TranscodeStatus::Processing);
+        registry.set_status(id, TranscodeStatus::Streaming);
+        registry.add_bytes(id, 100);
+        registry.add_bytes(id, 28);
+        registry.set_status(id, TranscodeStatus::Completed);
+
+        let session = registry.get(id).unwrap();
+        assert_eq!(session.status, TranscodeStatus::Completed);
+        assert!(session.finished_at.is_some());
+
+        let response = session.to_response(id);
+        assert_eq!(response.session_id, id);
+        assert_eq!(response.bytes_transferred, 128);
+        assert!(response.error.is_none());
+    }
+
+    #[test]
+    fn test_finished_status_is_final() {
+        let registry = SessionRegistry::new();
+        let id = Uuid::new_v4();
+
+        registry.register(id);
+        registry.set_status(id, TranscodeStatus::Cancelled);
+        registry.fail(id, "late failure");
+        registry.set_status(id, TranscodeStatus::Completed);
+
+        let session = registry.get(id).unwrap();
+        assert_eq!(session.status, TranscodeStatus::Cancelled);
+        assert!(session.error.is_none());
+    }
+
+    #[test]
+    fn test_fail_records_error() {
+        let registry = SessionRegistry::new();
+        let id = Uuid::new_v4();
+
+        registry.register(id);
+        registry.fail(id, "FFmpeg exited with exit status: 1");
+
+        let response = registry.get(id).unwrap().to_response(id);
+        assert_eq!(response.status, TranscodeStatus::Failed);
+        assert_eq!(
+            response.error.as_deref(),
+            Some("FFmpeg exited with exit status: 1")
+        );
+    }
+
+    #[test]
+    fn test_expired_sessions_are_pruned() {
+        let registry = SessionRegistry::new();
+        let old = Uuid::new_v4();
+        let running = Uuid::new_v4();
+
+        registry.register(old);
+        registry.register(running);
+        registry.set_status(old, TranscodeStatus::Completed);
+
+        // Свежая завершённая сессия переживает регистрацию новой
+        registry.register(Uuid::new_v4());
+        assert!(registry.get(old).is_some());
+
+        registry.prune(Duration::ZERO);
+        assert!(registry.get(old).is_none());
+        assert!(registry.get(running).is_some());
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_signals_running_session() {
+        let registry = SessionRegistry::new();
+        let id = Uuid::new_v4();
+
+        registry.register(id);
+        let signal = registry.cancel_signal(id).unwrap();
+        registry.cancel(id).unwrap();
+
+        // Сигнал, отправленный до ожидания, не теряется
+        tokio::time::timeout(Duration::from_secs(1), signal.notified())
+            .await
+            .unwrap();
+        assert_eq!(registry.get(id).unwrap().status, TranscodeStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_cancel_finished_or_unknown_session() {
+        let registry = SessionRegistry::new();
+        let id = Uuid::new_v4();
+
+        assert!(matches!(
+            registry.cancel(id),
+            Err(AppError::SessionNotFound(_))
+        ));
+
+        registry.register(id);
+        registry.set_status(id, TranscodeStatus::Completed);
+        assert!(matches!(
+            registry.cancel(id),
+            Err(AppError::SessionFinished(_))
+        ));
+    }
+
+    #[test]
+    fn test_cancel_active_skips_finished_sessions() {
+        let registry = SessionRegistry::new();
+        let running = Uuid::new_v4();
+        let done = Uuid::new_v4();
+
+        registry.register(running);
+        registry.register(done);
+        registry.set_status(done, TranscodeStatus::Completed);
+
+        assert_eq!(registry.active_count(), 1);
+        assert_eq!(registry.cancel_active(), 1);
+        assert_eq!(registry.active_count(), 0);
+        assert_eq!(registry.get(running).unwrap().status, TranscodeStatus::Cancelled);
+        assert_eq!(registry.get(done).unwrap().status, TranscodeStatus::Completed);
+    }
+
+    #[test]
+    fn test_running_count_excludes_queued_sessions() {
+        let registry = SessionRegistry::new();
+        let queued = Uuid::new_v4();
+        let processing = Uuid::new_v4();
+        let streaming = Uuid::new_v4();
+
+        for id in [queued, processing, streaming] {
+            registry.register(id);
+        }
+        registry.set_status(processing, TranscodeStatus::Processing);
+        registry.set_status(streaming, TranscodeStatus::Streaming);
+
+        assert_eq!(registry.active_count(), 3);
+        assert_eq!(registry.running_count(), 2);
+    }
+
+    #[test]
+    fn test_unknown_session() {
+        let registry = SessionRegistry::new();
+        registry.add_bytes(Uuid::new_v4(), 10);
+        assert!(registry.is_empty());
+    }
+}
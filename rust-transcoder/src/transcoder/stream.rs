@@ -0,0 +1,519 @@
+//! Адаптер выходного потока транскодирования
+//!
+//! Оборачивает поток stdout FFmpeg и снимает метрики по мере отдачи данных.
+//!
+//! # Маркер ошибки
+//!
+//! Статус 200 уходит до первых байт, поэтому сбой FFmpeg посреди потока
+//! неотличим от нормального EOF. С `failure_marker` поток после EOF ждёт
+//! выхода FFmpeg и при ошибке дописывает последним chunk'ом:
+//!
+//! ```text
+//! \n#TRANSCODE-FAILED# <сообщение об ошибке>\n
+//! ```
+//!
+//! Клиент проверяет, заканчивается ли body строкой с префиксом
+//! `FAILURE_MARKER`. При успешном завершении маркер не пишется, body -
+//! чистый выход FFmpeg.
+//!
+//! # Idle таймаут
+//!
+//! С `with_idle_timeout` поток, не получивший от FFmpeg ни одного chunk'а за
+//! таймаут, завершает сессию с ошибкой (процесс убивается) и отдаёт
+//! `io::ErrorKind::TimedOut`. Отсчёт сбрасывается на каждом chunk'е.
+//!
+//! # Сбой до первых байт
+//!
+//! Если stdout закончился пустым, заголовки ещё не отправлены: `failure`
+//! дожидается выхода FFmpeg и возвращает ошибку для ответа со статусом.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::body::Bytes;
+use futures::{Future, Stream};
+use tokio::process::ChildStdout;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Sleep;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::metrics::{TRANSCODE_BYTES_TOTAL, TRANSCODE_DURATION_SECONDS, TRANSCODE_TTFB_SECONDS};
+use crate::models::{AudioFormat, TranscodeStatus};
+
+use super::analysis;
+use super::breaker::CircuitBreaker;
+use super::ffmpeg::{self, FfmpegProcess};
+use super::permit::TranscodePermit;
+use super::session::SessionRegistry;
+
+/// Префикс строки, которой заканчивается body при сбое FFmpeg (см. модуль)
+pub const FAILURE_MARKER: &str = "\n#TRANSCODE-FAILED# ";
+
+/// Неуспешный итог сессии
+#[derive(Debug)]
+struct Failure {
+    /// Текст для статуса сессии и маркера
+    message: String,
+    /// Ошибка для ответа, пока заголовки не отправлены
+    error: AppError,
+}
+
+/// Поток с замером time-to-first-byte
+pub struct MeteredStream<S> {
+    inner: S,
+    format: AudioFormat,
+    started_at: Instant,
+    first_byte_seen: bool,
+}
+
+impl<S> MeteredStream<S> {
+    /// Оборачивает поток; `started_at` - момент приёма запроса
+    pub fn new(inner: S, format: AudioFormat, started_at: Instant) -> Self {
+        Self {
+            inner,
+            format,
+            started_at,
+            first_byte_seen: false,
+        }
+    }
+}
+
+impl<S, T, E> Stream for MeteredStream<S>
+where
+    S: Stream<Item = Result<T, E>> + Unpin,
+    T: AsRef<[u8]>,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            if !self.first_byte_seen && !chunk.as_ref().is_empty() {
+                self.first_byte_seen = true;
+                TRANSCODE_TTFB_SECONDS
+                    .with_label_values(&[&self.format.to_string()])
+                    .observe(self.started_at.elapsed().as_secs_f64());
+            }
+        }
+
+        poll
+    }
+}
+
+/// Выходной поток транскодирования для response body
+///
+/// Отдаёт stdout FFmpeg и ведёт статус сессии в реестре. Процессом владеет
+/// отдельная задача: после конца потока она дожидается выхода FFmpeg, и только
+/// тогда освобождает permit семафора. Если клиент отключился или сессия
+/// отменена через реестр, задача убивает процесс, а сессия становится
+/// `Cancelled`. При отключении клиента permit освобождается сразу.
+pub struct TranscodeStream {
+    inner: MeteredStream<ReaderStream<ChildStdout>>,
+    sessions: SessionRegistry,
+    session_id: Uuid,
+    streaming: bool,
+    /// Сообщает задаче завершения, чем закончился поток
+    end: Option<oneshot::Sender<StreamEnd>>,
+    /// Permit, общий с задачей завершения; освобождает тот, кто заберёт первым
+    permit: SharedPermit,
+    /// Итог сессии от задачи завершения (ошибка или None)
+    outcome: Option<oneshot::Receiver<Option<Failure>>>,
+    /// Дописывать `FAILURE_MARKER` при сбое
+    failure_marker: bool,
+    /// Idle таймаут и момент его срабатывания
+    idle: Option<(Duration, Pin<Box<Sleep>>)>,
+    /// Лимит выхода в байтах и сколько уже отдано
+    max_output_bytes: Option<u64>,
+    output_bytes: u64,
+}
+
+impl TranscodeStream {
+    /// Забирает stdout процесса; stderr собирается в фоне
+    pub fn new(
+        mut process: FfmpegProcess,
+        permit: TranscodePermit,
+        sessions: SessionRegistry,
+        breaker: CircuitBreaker,
+        session_id: Uuid,
+        started_at: Instant,
+    ) -> AppResult<Self> {
+        let Some(stdout) = process.take_stdout() else {
+            breaker.record_failure();
+            return Err(AppError::Ffmpeg("FFmpeg stdout is not available".into()));
+        };
+
+        // Без чтения stderr FFmpeg заблокируется на заполненном pipe
+        // Блоки `-progress` сразу обновляют сессию
+        let stderr = process.take_stderr().map(|stderr| {
+            let sessions = sessions.clone();
+            ffmpeg::collect_stderr_with_progress(stderr, move |progress| {
+                sessions.set_progress(session_id, progress)
+            })
+        });
+
+        let format = process.profile().format;
+
+        let (end_tx, end_rx) = oneshot::channel();
+        let (outcome_tx, outcome_rx) = oneshot::channel();
+        let permit = Arc::new(Mutex::new(Some(permit)));
+        let completion = Completion {
+            process,
+            permit: Arc::clone(&permit),
+            stderr,
+            breaker,
+            started_at,
+        };
+        tokio::spawn(completion.finish(
+            sessions.clone(),
+            session_id,
+            end_rx,
+            outcome_tx,
+            sessions.cancel_signal(session_id),
+        ));
+
+        Ok(Self {
+            inner: MeteredStream::new(ReaderStream::new(stdout), format, started_at),
+            sessions,
+            session_id,
+            streaming: false,
+            end: Some(end_tx),
+            permit,
+            outcome: Some(outcome_rx),
+            failure_marker: false,
+            idle: None,
+            max_output_bytes: None,
+            output_bytes: 0,
+        })
+    }
+
+    /// Ошибка сессии, если stdout закончился без данных (см. модуль)
+    ///
+    /// Вызывается после того, как поток вернул `None`; ждёт выхода FFmpeg.
+    pub async fn failure(&mut self) -> Option<AppError> {
+        let outcome = self.outcome.take()?;
+        outcome.await.ok().flatten().map(|failure| failure.error)
+    }
+
+    /// Включает маркер ошибки в конце body (см. модуль)
+    pub fn with_failure_marker(mut self, enabled: bool) -> Self {
+        self.failure_marker = enabled;
+        self
+    }
+
+    /// Включает idle таймаут (см. модуль)
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle = Some((timeout, Box::pin(tokio::time::sleep(timeout))));
+        self
+    }
+
+    /// Ограничивает размер выхода: chunk сверх лимита не отдаётся, FFmpeg
+    /// убивается, а сессия становится `Failed`
+    pub fn with_max_output_bytes(mut self, limit: Option<u64>) -> Self {
+        self.max_output_bytes = limit;
+        self
+    }
+
+    /// Истёк ли idle таймаут; регистрирует waker, если ещё нет
+    fn idle_expired(&mut self, cx: &mut Context<'_>) -> bool {
+        match self.idle.as_mut() {
+            Some((_, deadline)) => deadline.as_mut().poll(cx).is_ready(),
+            None => false,
+        }
+    }
+
+    fn reset_idle(&mut self) {
+        if let Some((timeout, deadline)) = self.idle.as_mut() {
+            let next = tokio::time::Instant::now() + *timeout;
+            deadline.as_mut().reset(next);
+        }
+    }
+
+    /// После конца stdout: маркер, если FFmpeg завершился с ошибкой
+    fn poll_outcome(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        let Some(outcome) = self.outcome.as_mut() else {
+            return Poll::Ready(None);
+        };
+
+        let result = match Pin::new(outcome).poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.outcome = None;
+
+        Poll::Ready(match result {
+            Ok(Some(failure)) => {
+                let error = failure.message.replace('\n', " ");
+                Some(Bytes::from(format!("{}{}\n", FAILURE_MARKER, error)))
+            }
+            // Успех или сессия отменена
+            _ => None,
+        })
+    }
+
+    fn complete(&mut self, end: StreamEnd) {
+        if let Some(sender) = self.end.take() {
+            // Задача уже завершилась, если сессию отменили
+            let _ = sender.send(end);
+        }
+    }
+}
+
+impl Stream for TranscodeStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // stdout уже закончился, ждём итог для маркера
+        if self.end.is_none() {
+            return if self.failure_marker {
+                self.poll_outcome(cx).map(|marker| marker.map(Ok))
+            } else {
+                Poll::Ready(None)
+            };
+        }
+
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                let output_bytes = self.output_bytes + chunk.len() as u64;
+                if let Some(limit) = self.max_output_bytes.filter(|&limit| output_bytes > limit) {
+                    let message = format!("Output exceeded the limit of {} bytes", limit);
+                    warn!(
+                        session_id = %self.session_id,
+                        limit,
+                        "Output size limit exceeded, killing FFmpeg"
+                    );
+                    let streaming = self.streaming;
+                    self.complete(StreamEnd::OutputLimit(message.clone()));
+                    if streaming && self.failure_marker {
+                        return self.poll_outcome(cx).map(|marker| marker.map(Ok));
+                    }
+                    return Poll::Ready(Some(Err(io::Error::other(message))));
+                }
+                self.output_bytes = output_bytes;
+                if !self.streaming {
+                    self.streaming = true;
+                    self.sessions
+                        .set_status(self.session_id, TranscodeStatus::Streaming);
+                }
+                self.sessions.add_bytes(self.session_id, chunk.len() as u64);
+                TRANSCODE_BYTES_TOTAL.inc_by(chunk.len() as u64);
+                self.reset_idle();
+            }
+            Poll::Ready(Some(Err(err))) => {
+                let message = format!("Failed to read FFmpeg output: {}", err);
+                self.complete(StreamEnd::Failed(message));
+                if self.failure_marker {
+                    return self.poll_outcome(cx).map(|marker| marker.map(Ok));
+                }
+            }
+            Poll::Ready(None) => {
+                self.complete(StreamEnd::Finished);
+                if self.failure_marker {
+                    return self.poll_outcome(cx).map(|marker| marker.map(Ok));
+                }
+            }
+            Poll::Pending => {
+                if self.idle_expired(cx) {
+                    let timeout = self
+                        .idle
+                        .as_ref()
+                        .map_or(0, |(timeout, _)| timeout.as_secs());
+                    let message = format!("FFmpeg produced no output for {} seconds", timeout);
+                    let streaming = self.streaming;
+                    self.complete(StreamEnd::Failed(message.clone()));
+                    // Начатый body с маркером заканчивается маркером, а не обрывом
+                    if streaming && self.failure_marker {
+                        return self.poll_outcome(cx).map(|marker| marker.map(Ok));
+                    }
+                    return Poll::Ready(Some(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        message,
+                    ))));
+                }
+            }
+        }
+
+        poll
+    }
+}
+
+impl Drop for TranscodeStream {
+    fn drop(&mut self) {
+        // Поток не дочитан - клиент отключился; закрытый канал убьёт процесс
+        if self.end.is_some() {
+            self.sessions
+                .set_status(self.session_id, TranscodeStatus::Cancelled);
+            self.permit.lock().expect("permit slot poisoned").take();
+        }
+    }
+}
+
+/// Чем закончилась отдача stdout клиенту
+enum StreamEnd {
+    /// stdout дочитан до EOF
+    Finished,
+    /// Ошибка чтения stdout
+    Failed(String),
+    /// Выход превысил `max_output_bytes`
+    OutputLimit(String),
+}
+
+type SharedPermit = Arc<Mutex<Option<TranscodePermit>>>;
+
+/// Процесс, permit и сборщик stderr одной сессии
+struct Completion {
+    process: FfmpegProcess,
+    permit: SharedPermit,
+    stderr: Option<JoinHandle<String>>,
+    breaker: CircuitBreaker,
+    started_at: Instant,
+}
+
+impl Completion {
+    /// Дожидается конца потока и выхода FFmpeg, переводит сессию в конечный статус
+    ///
+    /// Закрытый `end` (клиент отключился) или сигнал `cancel` - процесс
+    /// убивается, статус `Cancelled` уже выставлен.
+    async fn finish(
+        mut self,
+        sessions: SessionRegistry,
+        session_id: Uuid,
+        end: oneshot::Receiver<StreamEnd>,
+        outcome: oneshot::Sender<Option<Failure>>,
+        cancel: Option<Arc<Notify>>,
+    ) {
+        let cancelled = async {
+            match cancel {
+                Some(cancel) => cancel.notified().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let end = tokio::select! {
+            end = end => end.ok(),
+            _ = cancelled => None,
+        };
+
+        if !matches!(end, Some(StreamEnd::Finished)) {
+            let _ = self.process.kill().await;
+        }
+        let exit = self.process.wait().await;
+        TRANSCODE_DURATION_SECONDS.observe(self.started_at.elapsed().as_secs_f64());
+        let stderr = match self.stderr {
+            Some(task) => task.await.unwrap_or_default(),
+            None => String::new(),
+        };
+
+        // Permit возвращается до публикации статуса: клиент, увидевший
+        // конечный статус, может сразу запускать следующий поток
+        self.permit.lock().expect("permit slot poisoned").take();
+
+        let Some(end) = end else {
+            self.breaker.record_cancelled();
+            return;
+        };
+
+        if self.process.profile().detect_segments {
+            sessions.set_segments(session_id, analysis::parse_silencedetect(&stderr));
+        }
+        // Громкость выхода печатается только после полного прохода
+        let completed =
+            matches!((&end, &exit), (StreamEnd::Finished, Ok(status)) if status.success());
+        if completed && self.process.profile().reports_output_loudness() {
+            match analysis::parse_loudnorm_output(&stderr) {
+                Ok(loudness) => sessions.set_output_loudness(session_id, loudness),
+                Err(err) => warn!(error = %err, "Output loudness not reported"),
+            }
+        }
+
+        let failure = match (end, exit) {
+            (StreamEnd::Failed(message), _) => Some(Failure {
+                error: AppError::Ffmpeg(message.clone()),
+                message,
+            }),
+            (StreamEnd::OutputLimit(message), _) => Some(Failure {
+                error: AppError::PayloadTooLarge(message.clone()),
+                message,
+            }),
+            (StreamEnd::Finished, Ok(status)) if status.success() => None,
+            (StreamEnd::Finished, Ok(status)) => Some(Failure {
+                message: ffmpeg::exit_error(status, &stderr),
+                error: ffmpeg::exit_failure(status, &stderr),
+            }),
+            (StreamEnd::Finished, Err(err)) => Some(Failure {
+                message: err.to_string(),
+                error: err,
+            }),
+        };
+
+        match failure {
+            Some(ref failure) => {
+                self.breaker.record_failure();
+                sessions.fail(session_id, failure.message.clone());
+            }
+            None => {
+                self.breaker.record_success();
+                sessions.set_status(session_id, TranscodeStatus::Completed);
+            }
+        }
+        // Поток мог уже закончиться без маркера
+        let _ = outcome.send(failure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ttfb_recorded_on_first_chunk() {
+        let histogram = TRANSCODE_TTFB_SECONDS.with_label_values(&["flac"]);
+        let before = histogram.get_sample_count();
+
+        // Fake транскод: первый байт приходит с задержкой
+        let first = futures::stream::once(async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok::<_, io::Error>(vec![0u8; 16])
+        });
+        let rest = futures::stream::iter(vec![Ok(vec![1u8; 16]), Ok(vec![2u8; 16])]);
+        let fake = Box::pin(first.chain(rest));
+
+        let chunks: Vec<_> = MeteredStream::new(fake, AudioFormat::Flac, Instant::now())
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 3);
+
+        // Одно наблюдение на поток, не на каждый chunk
+        assert_eq!(histogram.get_sample_count(), before + 1);
+        assert!(histogram.get_sample_sum() >= 0.05);
+
+        let families = prometheus::gather();
+        assert!(families
+            .iter()
+            .any(|f| f.get_name() == "transcode_ttfb_seconds"));
+    }
+
+    #[tokio::test]
+    async fn test_ttfb_ignores_empty_chunks() {
+        let histogram = TRANSCODE_TTFB_SECONDS.with_label_values(&["pcm"]);
+        let before = histogram.get_sample_count();
+
+        let fake = futures::stream::iter(vec![Ok::<_, io::Error>(Vec::<u8>::new())]);
+        let _: Vec<_> = MeteredStream::new(fake, AudioFormat::Pcm, Instant::now())
+            .collect()
+            .await;
+
+        assert_eq!(histogram.get_sample_count(), before);
+    }
+}
@@ -1,20 +1,67 @@
 //! Общие утилиты для тестов
 
+#![allow(dead_code)]
+
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use axum::Router;
+use once_cell::sync::Lazy;
 
 // Re-export from main crate
-use rust_transcoder::{AppState, build_router};
+use rust_transcoder::{config::Config, AppState, build_router};
+
+/// Fake FFmpeg: игнорирует аргументы и пишет фиксированные байты в stdout
+static FAKE_FFMPEG: Lazy<PathBuf> = Lazy::new(|| write_fake_ffmpeg("printf 'fake-audio'"));
+
+/// Fake FFmpeg, который после первых байт "транскодирует" ещё 30 секунд
+static SLOW_FFMPEG: Lazy<PathBuf> =
+    Lazy::new(|| write_fake_ffmpeg("printf 'fake-audio'\nexec sleep 30"));
+
+pub fn write_fake_ffmpeg(script: &str) -> PathBuf {
+    let dir = tempfile::tempdir().expect("temp dir").keep();
+    let path = dir.join("ffmpeg");
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).expect("write fake ffmpeg");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .expect("chmod fake ffmpeg");
+    }
+    path
+}
+
+/// Хост источников тестов: из allowlist, поэтому DNS (недоступный без
+/// сети) перед запуском FFmpeg не спрашивается
+fn source_host_allowlist() -> Vec<String> {
+    vec!["example.com".to_string()]
+}
+
+/// Конфигурация с fake FFmpeg вместо системного бинаря
+pub fn test_config() -> Config {
+    Config {
+        ffmpeg_path: FAKE_FFMPEG.to_string_lossy().into_owned(),
+        source_host_allowlist: source_host_allowlist(),
+        ..Config::default()
+    }
+}
+
+/// Конфигурация с долгоживущим fake FFmpeg (поток не завершается сам)
+pub fn slow_test_config() -> Config {
+    Config {
+        ffmpeg_path: SLOW_FFMPEG.to_string_lossy().into_owned(),
+        source_host_allowlist: source_host_allowlist(),
+        ..Config::default()
+    }
+}
 
 /// Создаёт тестовое приложение с ограниченным concurrency
 pub fn create_test_app() -> Router {
-    let state = Arc::new(AppState::new(10));
-    build_router(state)
+    create_test_app_with_limit(10)
 }
 
 /// Создаёт тестовое приложение с кастомным concurrency limit
 pub fn create_test_app_with_limit(max_concurrent: usize) -> Router {
-    let state = Arc::new(AppState::new(max_concurrent));
+    let state = Arc::new(AppState::with_config(max_concurrent, test_config()).unwrap());
     build_router(state)
 }
@@ -0,0 +1,71 @@
+//! Contract tests для /api/v1/formats и /api/v1/codecs
+//!
+//! Проверяет структуру listing'ов поддерживаемых форматов и кодеков
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use http_body_util::BodyExt;
+use rust_transcoder::{build_router, AppState};
+use serde_json::Value;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+mod common;
+
+async fn get_json(uri: &str) -> Value {
+    let state = Arc::new(AppState::with_config(10, common::test_config()).unwrap());
+    let app = build_router(state);
+
+    let request = Request::builder()
+        .method("GET")
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice(&body).unwrap()
+}
+
+/// Test: GET /api/v1/formats содержит opus с audio/ogg
+#[tokio::test]
+async fn test_formats_listing_contains_opus() {
+    let json = get_json("/api/v1/formats").await;
+    let formats = json.as_array().expect("formats must be an array");
+
+    let opus = formats
+        .iter()
+        .find(|format| format["name"] == "opus")
+        .expect("opus must be listed");
+    assert_eq!(opus["content_type"], "audio/ogg");
+    assert_eq!(opus["ffmpeg_name"], "ogg");
+    assert_eq!(opus["extension"], "ogg");
+
+    for format in formats {
+        for field in ["name", "content_type", "ffmpeg_name", "extension"] {
+            assert!(format[field].is_string(), "{} must have {}", format, field);
+        }
+    }
+}
+
+/// Test: GET /api/v1/codecs содержит совместимые форматы
+#[tokio::test]
+async fn test_codecs_listing_contains_compatible_formats() {
+    let json = get_json("/api/v1/codecs").await;
+    let codecs = json.as_array().expect("codecs must be an array");
+
+    let opus = codecs
+        .iter()
+        .find(|codec| codec["name"] == "libopus")
+        .expect("libopus must be listed");
+    assert_eq!(opus["ffmpeg_name"], "libopus");
+    assert_eq!(opus["lossless"], false);
+
+    let compatible = opus["compatible_formats"].as_array().unwrap();
+    assert!(compatible.contains(&Value::from("opus")));
+    assert!(!compatible.contains(&Value::from("mp3")));
+}
@@ -6,19 +6,16 @@ use axum::{
     body::Body,
     http::{Request, StatusCode},
 };
-use http_body_util::BodyExt;
 use rust_transcoder::{build_router, AppState};
 use serde_json::json;
 use std::sync::Arc;
-use tokio::sync::Semaphore;
 use tower::ServiceExt;
 
-/// Создаёт тестовое AppState
+mod common;
+
+/// Создаёт тестовое AppState с fake FFmpeg
 fn create_test_state() -> Arc<AppState> {
-    Arc::new(AppState {
-        semaphore: Arc::new(Semaphore::new(10)),
-        start_time: std::time::Instant::now(),
-    })
+    Arc::new(AppState::with_config(10, common::test_config()).unwrap())
 }
 
 /// Test: POST /transcode с eq_preset=bass_boost возвращает 200
@@ -39,7 +36,7 @@ async fn test_transcode_with_eq_preset_bass_boost() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -71,7 +68,7 @@ async fn test_transcode_with_eq_preset_voice() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -101,7 +98,7 @@ async fn test_transcode_with_speed_filter() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -131,7 +128,7 @@ async fn test_transcode_with_invalid_speed_too_low() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -161,7 +158,7 @@ async fn test_transcode_with_invalid_speed_too_high() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -191,7 +188,7 @@ async fn test_transcode_with_volume_filter() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -221,7 +218,7 @@ async fn test_transcode_with_invalid_volume_negative() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -251,7 +248,7 @@ async fn test_transcode_with_invalid_volume_too_high() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
@@ -281,17 +278,18 @@ async fn test_transcode_with_invalid_eq_preset() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
 
     let response = app.oneshot(request).await.unwrap();
 
+    // Неизвестный вариант enum отклоняется при разборе body
     assert_eq!(
         response.status(),
         StatusCode::BAD_REQUEST,
-        "Invalid eq_preset should return 400"
+        "Invalid eq_preset should be rejected"
     );
 }
 
@@ -313,7 +311,7 @@ async fn test_transcode_with_combined_filters() {
 
     let request = Request::builder()
         .method("POST")
-        .uri("/transcode")
+        .uri("/api/v1/transcode")
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&body).unwrap()))
         .unwrap();
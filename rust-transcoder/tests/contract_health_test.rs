@@ -10,9 +10,12 @@ use http_body_util::BodyExt;
 use rust_transcoder::{build_router, AppState};
 use serde_json::Value;
 use std::sync::Arc;
+use tower::ServiceExt;
+
+mod common;
 
 fn create_test_state() -> Arc<AppState> {
-    Arc::new(AppState::new(10))
+    Arc::new(AppState::with_config(10, common::test_config()).unwrap())
 }
 
 /// Test: GET /health возвращает 200 и JSON с обязательными полями
@@ -38,6 +41,9 @@ async fn test_health_returns_required_fields() {
     assert!(json.get("status").is_some(), "Missing 'status' field");
     assert!(json.get("service").is_some(), "Missing 'service' field");
     assert!(json.get("version").is_some(), "Missing 'version' field");
+    assert!(json.get("uptime_seconds").is_some(), "Missing 'uptime_seconds' field");
+    assert_eq!(json["active_transcodes"], 0);
+    assert_eq!(json["max_concurrent_streams"], 10);
 }
 
 /// Test: GET /health status должен быть "healthy"
@@ -137,7 +143,7 @@ async fn test_health_live_returns_200() {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
-/// Test: GET /health с uptime_seconds если реализован
+/// Test: GET /health uptime_seconds >= 0
 #[tokio::test]
 async fn test_health_uptime_is_positive() {
     let state = create_test_state();
@@ -153,9 +159,6 @@ async fn test_health_uptime_is_positive() {
     let body = response.into_body().collect().await.unwrap().to_bytes();
     let json: Value = serde_json::from_slice(&body).unwrap();
 
-    // uptime_seconds опционален, но если есть - должен быть >= 0
-    if let Some(uptime) = json.get("uptime_seconds") {
-        let uptime_val = uptime.as_f64().unwrap();
-        assert!(uptime_val >= 0.0, "uptime_seconds should be >= 0");
-    }
+    let uptime_val = json["uptime_seconds"].as_f64().unwrap();
+    assert!(uptime_val >= 0.0, "uptime_seconds should be >= 0");
 }
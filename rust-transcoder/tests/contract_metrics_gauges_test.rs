@@ -0,0 +1,67 @@
+//! Contract tests для gauges нагрузки в /metrics
+//!
+//! Gauges глобальные, поэтому тест живёт в отдельном бинаре: другие
+//! транскодирования не меняют значения во время проверки.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use http_body_util::BodyExt;
+use rust_transcoder::{build_router, AppState};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+mod common;
+
+/// Значение метрики без меток из вывода /metrics
+async fn gauge(app: &axum::Router, name: &str) -> i64 {
+    let request = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    body.lines()
+        .find_map(|line| line.strip_prefix(&format!("{} ", name)))
+        .unwrap_or_else(|| panic!("{} missing in /metrics", name))
+        .parse()
+        .unwrap()
+}
+
+/// Test: gauges отражают удерживаемый поток и возвращаются после его завершения
+#[tokio::test]
+async fn test_active_transcodes_gauge_tracks_held_stream() {
+    let state = Arc::new(AppState::with_config(3, common::slow_test_config()).unwrap());
+    let app = build_router(state.clone());
+
+    assert_eq!(gauge(&app, "active_transcodes").await, 0);
+    assert_eq!(gauge(&app, "transcode_permits_available").await, 3);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/v1/transcode")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"source_url": "https://example.com/audio.mp3"}"#,
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Поток удерживается: active = max - available
+    let active = gauge(&app, "active_transcodes").await;
+    assert_eq!(active, 1);
+    assert_eq!(gauge(&app, "transcode_permits_available").await, 2);
+    assert_eq!(
+        active as usize,
+        state.max_concurrent_streams - state.transcode_semaphore.available_permits()
+    );
+
+    // Клиент отключился: permit возвращается сразу
+    drop(response);
+    assert_eq!(gauge(&app, "active_transcodes").await, 0);
+    assert_eq!(gauge(&app, "transcode_permits_available").await, 3);
+}
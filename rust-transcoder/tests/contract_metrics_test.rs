@@ -9,9 +9,12 @@ use axum::{
 use http_body_util::BodyExt;
 use rust_transcoder::{build_router, AppState};
 use std::sync::Arc;
+use tower::ServiceExt;
+
+mod common;
 
 fn create_test_state() -> Arc<AppState> {
-    Arc::new(AppState::new(10))
+    Arc::new(AppState::with_config(10, common::test_config()).unwrap())
 }
 
 /// Test: GET /metrics возвращает 200
@@ -133,8 +136,6 @@ async fn test_metrics_prometheus_format() {
 /// Test: После transcode запроса должны появиться transcode метрики
 #[tokio::test]
 async fn test_metrics_after_transcode_request() {
-    use tower::ServiceExt;
-
     let state = create_test_state();
     let app = build_router(state);
 
@@ -163,3 +164,46 @@ async fn test_metrics_after_transcode_request() {
     let body = response.into_body().collect().await.unwrap().to_bytes();
     assert!(!body.is_empty());
 }
+
+/// Test: transcode запрос учитывается в transcode_requests_total
+#[tokio::test]
+async fn test_transcode_requests_total_exported() {
+    let state = create_test_state();
+    let app = build_router(state);
+
+    let transcode_request = Request::builder()
+        .method("POST")
+        .uri("/api/v1/transcode")
+        .header("Content-Type", "application/json")
+        .body(Body::from(
+            r#"{"source_url": "https://example.com/audio.mp3", "format": "mp3", "codec": "libmp3lame"}"#,
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(transcode_request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.into_body().collect().await.unwrap();
+
+    let metrics_request = Request::builder()
+        .method("GET")
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(metrics_request).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body_str = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(
+        body_str.contains(
+            r#"transcode_requests_total{codec="libmp3lame",format="mp3",status="200"}"#
+        ),
+        "transcode_requests_total missing:\n{}",
+        body_str
+    );
+    for metric in [
+        "transcode_duration_seconds",
+        "transcode_bytes_total",
+        "active_transcodes",
+    ] {
+        assert!(body_str.contains(metric), "{} missing", metric);
+    }
+}
@@ -2,10 +2,14 @@
 //!
 //! Проверяет соответствие API контракту OpenAPI.
 
+use std::sync::Arc;
+
 use axum::{
     body::Body,
     http::{Request, StatusCode},
 };
+use http_body_util::BodyExt;
+use rust_transcoder::{build_router, AppState};
 use serde_json::{json, Value};
 use tower::ServiceExt;
 
@@ -32,9 +36,9 @@ async fn test_transcode_valid_request_returns_200() {
     assert_eq!(response.status(), StatusCode::OK);
 }
 
-/// Тест: Response содержит обязательные поля (session_id, status, content_type)
+/// Тест: Body - аудио поток, метаданные сессии в заголовках
 #[tokio::test]
-async fn test_transcode_response_has_required_fields() {
+async fn test_transcode_response_streams_audio() {
     let app = common::create_test_app();
 
     let request = Request::builder()
@@ -49,13 +53,243 @@ async fn test_transcode_response_has_required_fields() {
     let response = app.oneshot(request).await.unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "audio/ogg");
+    assert!(response.headers().get("X-Transcode-Id").is_some(), "Response must contain X-Transcode-Id");
+
+    let body = axum::body::to_bytes(response.into_body(), 10240).await.unwrap();
+    assert_eq!(&body[..], b"fake-audio");
+}
+
+/// Тест: Permit держится всё время стриминга, лишний запрос получает 503
+#[tokio::test]
+async fn test_transcode_over_capacity_returns_503() {
+    let state = Arc::new(AppState::with_config(2, common::slow_test_config()).unwrap());
+    let app = build_router(state.clone());
+
+    let request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/api/v1/transcode")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({
+                "source_url": "https://example.com/audio.mp3"
+            }).to_string()))
+            .unwrap()
+    };
+
+    let mut streams = Vec::new();
+    for _ in 0..2 {
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Поток живой: первые байты уже пришли, FFmpeg продолжает работу
+        let mut body = response.into_body();
+        let chunk = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(&chunk[..], b"fake-audio");
+        streams.push(body);
+    }
+    assert_eq!(state.transcode_semaphore.available_permits(), 0);
+
+    let response = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = axum::body::to_bytes(response.into_body(), 10240).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "CONCURRENCY_LIMIT_EXCEEDED");
+
+    // Отключение клиентов освобождает permits
+    drop(streams);
+    assert_eq!(state.transcode_semaphore.available_permits(), 2);
+}
+
+fn waiting_app(acquire_wait_ms: u64) -> (Arc<AppState>, axum::Router) {
+    let config = rust_transcoder::config::Config {
+        acquire_wait_ms,
+        ..common::slow_test_config()
+    };
+    let state = Arc::new(AppState::with_config(1, config).unwrap());
+    let app = build_router(state.clone());
+    (state, app)
+}
+
+fn slow_request() -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri("/api/v1/transcode")
+        .header("content-type", "application/json")
+        .body(Body::from(json!({
+            "source_url": "https://example.com/audio.mp3"
+        }).to_string()))
+        .unwrap()
+}
+
+/// Тест: С ACQUIRE_WAIT_MS запрос дожидается освободившегося слота
+#[tokio::test]
+async fn test_transcode_waits_for_freed_permit() {
+    let (state, app) = waiting_app(2000);
+
+    let first = app.clone().oneshot(slow_request()).await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(state.transcode_semaphore.available_permits(), 0);
+
+    let waiting = tokio::spawn(app.clone().oneshot(slow_request()));
+
+    // Слот освобождается посреди ожидания
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(!waiting.is_finished());
+    drop(first);
+
+    let second = waiting.await.unwrap().unwrap();
+    assert_eq!(second.status(), StatusCode::OK);
+}
+
+/// Тест: Слот не освободился за ACQUIRE_WAIT_MS - 503 с Retry-After
+#[tokio::test]
+async fn test_transcode_wait_elapses_returns_503() {
+    let (_state, app) = waiting_app(100);
+
+    let first = app.clone().oneshot(slow_request()).await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+
+    let started = std::time::Instant::now();
+    let response = app.clone().oneshot(slow_request()).await.unwrap();
+    assert!(started.elapsed() >= std::time::Duration::from_millis(100));
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["retry-after"], "1");
 
     let body = axum::body::to_bytes(response.into_body(), 10240).await.unwrap();
     let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "CONCURRENCY_LIMIT_EXCEEDED");
+    drop(first);
+}
 
-    assert!(json.get("session_id").is_some(), "Response must contain session_id");
-    assert!(json.get("status").is_some(), "Response must contain status");
-    assert!(json.get("content_type").is_some(), "Response must contain content_type");
+/// Тест: DELETE отменяет выполняющуюся сессию и FFmpeg завершается
+#[tokio::test]
+async fn test_cancel_running_transcode_reaps_ffmpeg() {
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("ffmpeg.pid");
+    let script = format!(
+        "echo $$ > {}\nprintf 'fake-audio'\nexec sleep 30",
+        pid_file.display()
+    );
+    let config = rust_transcoder::config::Config {
+        ffmpeg_path: common::write_fake_ffmpeg(&script).to_string_lossy().into_owned(),
+        ..common::test_config()
+    };
+    let state = Arc::new(AppState::with_config(2, config).unwrap());
+    let app = build_router(state.clone());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/transcode")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({
+                    "source_url": "https://example.com/audio.mp3"
+                }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let session_id = response.headers()["X-Transcode-Id"].to_str().unwrap().to_string();
+    let mut body = response.into_body();
+    body.frame().await.unwrap().unwrap();
+
+    let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+    let proc_entry = std::path::PathBuf::from(format!("/proc/{}", pid));
+    assert!(proc_entry.exists());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri(format!("/api/v1/transcode/{}", session_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // Body обрывается без ожидания 30 секунд sleep
+    let rest = tokio::time::timeout(std::time::Duration::from_secs(5), body.collect())
+        .await
+        .expect("body must end after cancel");
+    assert!(rest.is_ok());
+
+    // Процесс убит и дождан (нет и zombie), permit возвращён
+    for _ in 0..100 {
+        if !proc_entry.exists() && state.transcode_semaphore.available_permits() == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(!proc_entry.exists(), "FFmpeg process {} was not reaped", pid);
+    assert_eq!(state.transcode_semaphore.available_permits(), 2);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/v1/transcode/{}", session_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), 10240).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["status"], "cancelled");
+}
+
+/// Тест: FFmpeg без вывода дольше таймаута убивается, клиент получает 504
+#[tokio::test]
+async fn test_hung_ffmpeg_times_out_with_504() {
+    let dir = tempfile::tempdir().unwrap();
+    let pid_file = dir.path().join("ffmpeg.pid");
+    let script = format!("echo $$ > {}\nexec sleep 30", pid_file.display());
+    let config = rust_transcoder::config::Config {
+        ffmpeg_path: common::write_fake_ffmpeg(&script).to_string_lossy().into_owned(),
+        transcode_timeout_secs: 1,
+        ..common::test_config()
+    };
+    let state = Arc::new(AppState::with_config(2, config).unwrap());
+    let app = build_router(state.clone());
+
+    let response = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        app.oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/transcode")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({
+                    "source_url": "https://example.com/audio.mp3"
+                }).to_string()))
+                .unwrap(),
+        ),
+    )
+    .await
+    .expect("request must not wait for sleep to finish")
+    .unwrap();
+
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    let body = axum::body::to_bytes(response.into_body(), 10240).await.unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "TIMEOUT");
+
+    // Процесс убит и дождан, permit возвращён
+    let pid = std::fs::read_to_string(&pid_file).unwrap().trim().to_string();
+    let proc_entry = std::path::PathBuf::from(format!("/proc/{}", pid));
+    for _ in 0..100 {
+        if !proc_entry.exists() && state.transcode_semaphore.available_permits() == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(!proc_entry.exists(), "FFmpeg process {} was not reaped", pid);
+    assert_eq!(state.transcode_semaphore.available_permits(), 2);
 }
 
 /// Тест: Пустой source_url возвращает 400 Bad Request
@@ -97,7 +331,7 @@ async fn test_transcode_invalid_bitrate_returns_400() {
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
-/// Тест: Запрос с невалидным JSON возвращает 400/422
+/// Тест: Запрос с невалидным JSON возвращает 400 в формате ErrorResponse
 #[tokio::test]
 async fn test_transcode_invalid_json_returns_error() {
     let app = common::create_test_app();
@@ -111,11 +345,12 @@ async fn test_transcode_invalid_json_returns_error() {
 
     let response = app.oneshot(request).await.unwrap();
 
-    // Axum returns 422 for JSON parse errors
-    assert!(
-        response.status() == StatusCode::BAD_REQUEST 
-        || response.status() == StatusCode::UNPROCESSABLE_ENTITY
-    );
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["code"], "VALIDATION_ERROR");
+    assert!(json["message"].is_string());
 }
 
 /// Тест: Ошибка содержит code и message
@@ -146,9 +381,10 @@ async fn test_transcode_error_response_format() {
 /// Тест: Разные форматы (opus, mp3, aac)
 #[tokio::test]
 async fn test_transcode_supports_multiple_formats() {
-    let formats = vec!["opus", "mp3", "aac"];
+    // Кодек по умолчанию (libopus) пишется только в opus
+    let formats = vec![("opus", "libopus"), ("mp3", "libmp3lame"), ("aac", "aac")];
 
-    for format in formats {
+    for (format, codec) in formats {
         let app = common::create_test_app();
 
         let request = Request::builder()
@@ -157,7 +393,8 @@ async fn test_transcode_supports_multiple_formats() {
             .header("content-type", "application/json")
             .body(Body::from(json!({
                 "source_url": "https://example.com/audio.mp3",
-                "format": format
+                "format": format,
+                "codec": codec
             }).to_string()))
             .unwrap();
 
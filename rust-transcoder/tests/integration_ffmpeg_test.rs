@@ -0,0 +1,230 @@
+//! End-to-end тесты с реальным FFmpeg
+//!
+//! Запуск: `cargo test --features integration-ffmpeg`.
+//! Бинари берутся из `FFMPEG_PATH` и `FFPROBE_PATH` (по умолчанию из PATH).
+
+#![cfg(feature = "integration-ffmpeg")]
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use rust_transcoder::config::Config;
+use rust_transcoder::models::{AudioCodec, AudioFormat};
+use rust_transcoder::transcoder::{probe, FfmpegProcess, TranscodeProfile};
+use rust_transcoder::{build_router, AppState};
+use tempfile::TempDir;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tower::ServiceExt;
+
+fn ffmpeg_path() -> String {
+    std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string())
+}
+
+fn ffprobe_path() -> String {
+    std::env::var("FFPROBE_PATH").unwrap_or_else(|_| "ffprobe".to_string())
+}
+
+/// Генерирует 1 секунду синуса 440Hz во временный WAV
+async fn synthetic_source(dir: &TempDir) -> PathBuf {
+    let path = dir.path().join("sine.wav");
+    let status = Command::new(ffmpeg_path())
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "lavfi"])
+        .args(["-i", "sine=frequency=440:duration=1:sample_rate=48000"])
+        .arg(&path)
+        .status()
+        .await
+        .expect("FFmpeg must be installed for integration tests");
+    assert!(status.success(), "failed to generate synthetic source");
+    path
+}
+
+/// Запускает транскод профиля и возвращает весь stdout
+async fn transcode(profile: TranscodeProfile) -> Vec<u8> {
+    let mut process = FfmpegProcess::spawn_with_binary(&ffmpeg_path(), profile)
+        .await
+        .unwrap();
+
+    let mut output = Vec::new();
+    process
+        .take_stdout()
+        .unwrap()
+        .read_to_end(&mut output)
+        .await
+        .unwrap();
+
+    let status = process.wait().await.unwrap();
+    assert!(status.success(), "FFmpeg exited with {}", status);
+    output
+}
+
+/// Проверяет, что результат декодируется FFmpeg без ошибок
+async fn assert_decodable(dir: &TempDir, bytes: &[u8], extension: &str) {
+    let path = dir.path().join(format!("output.{}", extension));
+    tokio::fs::write(&path, bytes).await.unwrap();
+
+    let output = Command::new(ffmpeg_path())
+        .args(["-hide_banner", "-v", "error", "-i"])
+        .arg(&path)
+        .args(["-f", "null", "-"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(output.status.success(), "output is not decodable");
+    assert!(
+        output.stderr.is_empty(),
+        "decoder reported errors: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn profile_for(source: &Path, format: AudioFormat, codec: AudioCodec) -> TranscodeProfile {
+    TranscodeProfile {
+        source_url: source.to_string_lossy().into_owned(),
+        format,
+        codec,
+        bitrate: 64,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_real_transcode_opus() {
+    let dir = TempDir::new().unwrap();
+    let source = synthetic_source(&dir).await;
+
+    let output = transcode(profile_for(&source, AudioFormat::Opus, AudioCodec::Libopus)).await;
+
+    assert!(!output.is_empty());
+    assert_eq!(&output[..4], b"OggS");
+    assert_decodable(&dir, &output, "ogg").await;
+}
+
+#[tokio::test]
+async fn test_real_transcode_mp3() {
+    let dir = TempDir::new().unwrap();
+    let source = synthetic_source(&dir).await;
+
+    let output = transcode(profile_for(
+        &source,
+        AudioFormat::Mp3,
+        AudioCodec::Libmp3lame,
+    ))
+    .await;
+
+    assert!(!output.is_empty());
+    assert_decodable(&dir, &output, "mp3").await;
+}
+
+#[tokio::test]
+async fn test_real_transcode_with_filter_chain() {
+    let dir = TempDir::new().unwrap();
+    let source = synthetic_source(&dir).await;
+
+    let profile = TranscodeProfile {
+        normalize: true,
+        fade_in: Some(0.5),
+        ..profile_for(&source, AudioFormat::Opus, AudioCodec::Libopus)
+    };
+    assert!(profile.build_ffmpeg_args().contains(&"-af".to_string()));
+
+    let output = transcode(profile).await;
+
+    assert!(!output.is_empty());
+    assert_decodable(&dir, &output, "ogg").await;
+}
+
+#[tokio::test]
+async fn test_real_probe_of_local_file() {
+    let dir = TempDir::new().unwrap();
+    let source = synthetic_source(&dir).await;
+
+    let info = probe::probe_source_with_binary(&ffprobe_path(), &source.to_string_lossy())
+        .await
+        .unwrap();
+
+    let duration = info.duration.expect("WAV source has a known duration");
+    assert!((duration - 1.0).abs() < 0.01, "duration {}", duration);
+    assert_eq!(info.format.as_deref(), Some("wav"));
+    assert_eq!(info.sample_rate, Some(48000));
+    assert_eq!(info.channels, Some(1));
+}
+
+#[tokio::test]
+async fn test_generate_silence_streams_opus() {
+    let config = Config {
+        ffmpeg_path: ffmpeg_path(),
+        ..Config::default()
+    };
+    let app = build_router(Arc::new(AppState::with_config(1, config).unwrap()));
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/api/v1/generate")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"kind": "silence", "duration": 1, "format": "opus", "codec": "libopus"}"#,
+        ))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let output = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(!output.is_empty());
+    assert_eq!(&output[..4], b"OggS");
+
+    let dir = TempDir::new().unwrap();
+    assert_decodable(&dir, &output, "ogg").await;
+}
+
+#[tokio::test]
+async fn test_real_hls_output_writes_playlist_and_segments() {
+    let dir = TempDir::new().unwrap();
+    let source = synthetic_source(&dir).await;
+    let hls_dir = dir.path().join("hls");
+    std::fs::create_dir(&hls_dir).unwrap();
+
+    let profile = TranscodeProfile {
+        hls_dir: Some(hls_dir.clone()),
+        ..profile_for(&source, AudioFormat::Aac, AudioCodec::Aac)
+    };
+    let mut process = FfmpegProcess::spawn_with_binary(&ffmpeg_path(), profile)
+        .await
+        .unwrap();
+    let status = process.wait().await.unwrap();
+    assert!(status.success(), "FFmpeg exited with {}", status);
+
+    let playlist = std::fs::read_to_string(hls_dir.join("playlist.m3u8")).unwrap();
+    assert!(playlist.starts_with("#EXTM3U"), "{}", playlist);
+    assert!(
+        playlist.contains("#EXT-X-PLAYLIST-TYPE:VOD"),
+        "{}",
+        playlist
+    );
+    assert!(playlist.contains("#EXT-X-ENDLIST"), "{}", playlist);
+    // Сегменты - относительные имена рядом с плейлистом
+    assert!(
+        playlist.lines().any(|line| line == "segment_000.ts"),
+        "{}",
+        playlist
+    );
+    assert!(hls_dir.join("segment_000.ts").exists());
+}
+
+#[tokio::test]
+async fn test_real_transcode_wav_has_streaming_riff_header() {
+    let dir = TempDir::new().unwrap();
+    let source = synthetic_source(&dir).await;
+
+    let output = transcode(profile_for(&source, AudioFormat::Wav, AudioCodec::PcmS16le)).await;
+
+    assert_eq!(&output[..4], b"RIFF");
+    assert_eq!(&output[8..12], b"WAVE");
+    // В pipe размер не дописывается: плееры читают до конца потока
+    assert_eq!(&output[4..8], &[0xFF; 4]);
+    assert_decodable(&dir, &output, "wav").await;
+}
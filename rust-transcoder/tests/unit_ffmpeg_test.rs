@@ -2,7 +2,7 @@
 //!
 //! Проверяет корректность генерации FFmpeg аргументов.
 
-use rust_transcoder::transcoder::{TranscodeProfile, FfmpegProcess};
+use rust_transcoder::transcoder::TranscodeProfile;
 use rust_transcoder::models::{AudioFormat, AudioCodec, AudioQuality};
 
 /// Тест: Профиль Opus генерирует корректные аргументы
@@ -19,6 +19,7 @@ fn test_opus_profile_args() {
         target_loudness: -16.0,
         fade_in: None,
         fade_out: None,
+        ..Default::default()
     };
 
     let args = profile.build_ffmpeg_args();
@@ -51,6 +52,7 @@ fn test_mp3_profile_args() {
         target_loudness: -16.0,
         fade_in: None,
         fade_out: None,
+        ..Default::default()
     };
 
     let args = profile.build_ffmpeg_args();
@@ -75,6 +77,7 @@ fn test_aac_profile_args() {
         target_loudness: -16.0,
         fade_in: None,
         fade_out: None,
+        ..Default::default()
     };
 
     let args = profile.build_ffmpeg_args();
@@ -97,6 +100,7 @@ fn test_normalize_adds_loudnorm_filter() {
         target_loudness: -16.0,
         fade_in: None,
         fade_out: None,
+        ..Default::default()
     };
 
     let args = profile.build_ffmpeg_args();
@@ -124,6 +128,7 @@ fn test_fade_in_adds_afade_filter() {
         target_loudness: -16.0,
         fade_in: Some(2.5),
         fade_out: None,
+        ..Default::default()
     };
 
     let args = profile.build_ffmpeg_args();
@@ -150,6 +155,7 @@ fn test_combined_filters() {
         target_loudness: -14.0,
         fade_in: Some(1.0),
         fade_out: None,
+        ..Default::default()
     };
 
     let args = profile.build_ffmpeg_args();
@@ -208,6 +214,7 @@ fn test_mono_output() {
         target_loudness: -16.0,
         fade_in: None,
         fade_out: None,
+        ..Default::default()
     };
 
     let args = profile.build_ffmpeg_args();